@@ -15,6 +15,25 @@ pub fn find_path(
     pathfinding: &PathfindingGraph,
     start_position: Vec2,
     goal_position: Vec2,
+) -> Option<Vec<PathNode>> {
+    find_path_with_hazards(pathfinding, start_position, goal_position, false)
+}
+
+/// Like `find_path`, but steers clear of hazards: nodes near a hazard cost extra and nodes
+/// inside a lethal hazard's reach are never entered
+pub fn find_path_avoiding_hazards(
+    pathfinding: &PathfindingGraph,
+    start_position: Vec2,
+    goal_position: Vec2,
+) -> Option<Vec<PathNode>> {
+    find_path_with_hazards(pathfinding, start_position, goal_position, true)
+}
+
+fn find_path_with_hazards(
+    pathfinding: &PathfindingGraph,
+    start_position: Vec2,
+    goal_position: Vec2,
+    avoid_hazards: bool,
 ) -> Option<Vec<PathNode>> {
     let goal_node_id = get_goal_node_id(pathfinding, goal_position)?;
     let start_node_id = get_start_node_id(pathfinding, start_position, goal_position)?;
@@ -89,6 +108,11 @@ pub fn find_path(
             .chain(current_graph_node.jumpable_connections.iter())
             .chain(current_graph_node.droppable_connections.iter())
         {
+            // Skip links through closed doors
+            if !pathfinding.is_connection_open(connection) {
+                continue;
+            }
+
             let connected_node_id = connection.node_id;
 
             // Skip if already in closed set
@@ -97,11 +121,19 @@ pub fn find_path(
             }
 
             let connected_graph_node = &pathfinding.nodes[connected_node_id];
+
+            // Never walk into a lethal hazard on purpose
+            if avoid_hazards && connected_graph_node.lethal {
+                continue;
+            }
+
             let mut new_node = AStarNode::new(connected_graph_node);
 
             // Set the g-cost: distance + effort (jumps are more expensive, drops are cheaper)
-            new_node.g_cost =
-                current_node.g_cost + connection.dist + EFFORT_WEIGHT * connection.effort;
+            new_node.g_cost = current_node.g_cost + connection_cost(connection);
+            if avoid_hazards {
+                new_node.g_cost += connected_graph_node.hazard_cost;
+            }
 
             // Set the h-cost using improved heuristic that accounts for vertical movement
             new_node.h_cost = calculate_heuristic(new_node.position, goal_position);
@@ -114,6 +146,52 @@ pub fn find_path(
     }
 }
 
+/// Cost of taking a connection: its distance plus the effort of the traversal
+pub fn connection_cost(connection: &PathfindingGraphConnection) -> f32 {
+    connection.dist + EFFORT_WEIGHT * connection.effort
+}
+
+/// Returns the node reachable from `start_position` that is closest to `target_position`.
+///
+/// Used when the target itself can't be reached (it is on another part of the graph or behind a
+/// closed door) to find the best spot to wait at.
+pub fn nearest_reachable_node(
+    pathfinding: &PathfindingGraph,
+    start_position: Vec2,
+    target_position: Vec2,
+) -> Option<usize> {
+    let start_node_id = get_start_node_id(pathfinding, start_position, target_position)?;
+
+    let mut visited: HashSet<usize> = HashSet::from([start_node_id]);
+    let mut stack = vec![start_node_id];
+    let mut best_node_id = start_node_id;
+    let mut best_distance_sq = f32::MAX;
+
+    // Flood fill over every open connection
+    while let Some(node_id) = stack.pop() {
+        let graph_node = &pathfinding.nodes[node_id];
+
+        let distance_sq = (target_position - graph_node.position).length_squared();
+        if distance_sq < best_distance_sq {
+            best_distance_sq = distance_sq;
+            best_node_id = node_id;
+        }
+
+        for connection in graph_node
+            .walkable_connections
+            .iter()
+            .chain(graph_node.jumpable_connections.iter())
+            .chain(graph_node.droppable_connections.iter())
+        {
+            if pathfinding.is_connection_open(connection) && visited.insert(connection.node_id) {
+                stack.push(connection.node_id);
+            }
+        }
+    }
+
+    Some(best_node_id)
+}
+
 fn get_start_node_id(
     pathfinding: &PathfindingGraph,
     start_position: Vec2,
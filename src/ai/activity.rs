@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Has, Or, With},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res},
+    },
+    math::{Vec2, Vec3Swizzles},
+    transform::components::Transform,
+};
+
+use crate::{
+    camera::GameCamera,
+    spatial::{DynamicKind, DynamicSpatialIndex},
+};
+
+use super::{flying_ai::FlyingAI, platformer_ai::PlatformerAI, tick::s_ai_tick};
+
+// Agents further than this from the player and the camera fall asleep (pixels)
+const SLEEP_DISTANCE: f32 = 1200.0;
+// Sleeping agents closer than this to the player or the camera wake up (pixels)
+// Smaller than SLEEP_DISTANCE so agents near the boundary don't keep toggling
+const WAKE_DISTANCE: f32 = 1000.0;
+
+/// Marker for agents far from the action: they skip decisions, movement and collision until
+/// the player or the camera comes near again
+#[derive(Component)]
+pub struct Asleep;
+
+pub struct AgentActivityPlugin;
+
+impl Plugin for AgentActivityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_update_agent_activity.before(s_ai_tick));
+    }
+}
+
+/// Agent activity system: Puts far-away agents to sleep and wakes them when the player or the
+/// camera approaches
+#[allow(clippy::type_complexity)]
+pub fn s_update_agent_activity(
+    mut commands: Commands,
+    agent_query: Query<(Entity, Has<Asleep>), Or<(With<PlatformerAI>, With<FlyingAI>)>>,
+    camera_query: Query<&Transform, With<GameCamera>>,
+    spatial_index: Res<DynamicSpatialIndex>,
+) {
+    let observers: Vec<Vec2> = spatial_index
+        .within_kind(DynamicKind::Player)
+        .map(|entry| entry.position)
+        .chain(camera_query.iter().map(|transform| transform.translation.xy()))
+        .collect();
+
+    // Without anyone watching there is nothing to cull against
+    if observers.is_empty() {
+        return;
+    }
+
+    // Ask the index which agents are near each observer instead of measuring every agent
+    let mut within_wake = HashSet::new();
+    let mut within_sleep = HashSet::new();
+    for observer in &observers {
+        for entry in spatial_index.within_radius(*observer, SLEEP_DISTANCE) {
+            if entry.kind != DynamicKind::Agent {
+                continue;
+            }
+
+            within_sleep.insert(entry.entity);
+            if entry.position.distance_squared(*observer) < WAKE_DISTANCE * WAKE_DISTANCE {
+                within_wake.insert(entry.entity);
+            }
+        }
+    }
+
+    for (entity, asleep) in agent_query.iter() {
+        if asleep && within_wake.contains(&entity) {
+            commands.entity(entity).remove::<Asleep>();
+        } else if !asleep && !within_sleep.contains(&entity) {
+            commands.entity(entity).insert(Asleep);
+        }
+    }
+}
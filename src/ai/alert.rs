@@ -0,0 +1,103 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        entity::Entity,
+        message::{Message, MessageReader},
+        query::Without,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res, ResMut},
+    },
+    math::{Vec2, Vec3Swizzles},
+    prelude::Resource,
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::ai::difficulty::AIDifficulty;
+
+use super::{
+    activity::Asleep,
+    platformer_ai::s_platformer_ai_movement,
+    pursue_ai::{s_pursue_ai_update, search::SearchBehavior, PursueAI, PursueAIState},
+    territory::Territory,
+};
+
+/// An agent spotted the player and shouts to nearby allies
+#[derive(Message, Clone, Copy, Debug)]
+pub struct AIAlert {
+    /// Agent that spotted the player (it is already pursuing)
+    pub shouter: Entity,
+    /// Where the shouter was when it spotted the player
+    pub origin: Vec2,
+    /// Where the player was spotted
+    pub target: Vec2,
+}
+
+/// Alerts on their way to allies, delivered once the shouting delay has passed
+#[derive(Resource, Default)]
+pub struct PendingAlerts {
+    /// Each alert with the elapsed time (seconds) at which it reaches allies
+    pub alerts: Vec<(AIAlert, f32)>,
+}
+
+pub struct AIAlertPlugin;
+
+impl Plugin for AIAlertPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingAlerts>();
+        app.add_message::<AIAlert>();
+        app.add_systems(
+            Update,
+            s_propagate_alerts
+                .after(s_pursue_ai_update)
+                .before(s_platformer_ai_movement),
+        );
+    }
+}
+
+/// Alert system: Queues alerts shouted this frame and, once their delay is up, sends every agent
+/// within the alert radius to search where the player was spotted
+pub fn s_propagate_alerts(
+    mut alerts: MessageReader<AIAlert>,
+    mut pending: ResMut<PendingAlerts>,
+    mut ai_query: Query<(Entity, &Transform, &mut PursueAI, Option<&Territory>), Without<Asleep>>,
+    difficulty: Res<AIDifficulty>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+
+    for alert in alerts.read() {
+        pending.alerts.push((*alert, now + difficulty.alert_delay));
+    }
+
+    let alert_radius_sq = difficulty.alert_radius * difficulty.alert_radius;
+
+    let mut index = 0;
+    while index < pending.alerts.len() {
+        let (alert, deliver_at) = pending.alerts[index];
+        if now < deliver_at {
+            index += 1;
+            continue;
+        }
+        pending.alerts.swap_remove(index);
+
+        for (entity, transform, mut pursue_ai, territory) in ai_query.iter_mut() {
+            // Agents don't search outside their territory
+            if entity == alert.shouter
+                || transform.translation.xy().distance_squared(alert.origin) > alert_radius_sq
+                || territory.is_some_and(|territory| !territory.contains(alert.target))
+            {
+                continue;
+            }
+
+            // Agents already chasing the player know better than a shout. Alerted agents don't
+            // shout on, so an alert only travels one hop
+            if let PursueAIState::Pursue | PursueAIState::Attack = pursue_ai.state {
+                continue;
+            }
+
+            pursue_ai.search = Some(SearchBehavior::new(alert.target, now, &difficulty));
+            pursue_ai.state = PursueAIState::Search;
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use bevy::{ecs::entity::Entity, math::Vec2};
+
+use crate::spatial::{DynamicKind, DynamicSpatialIndex};
+
+use super::pursue_ai::PURSUE_AI_AGENT_RADIUS;
+
+// Agents closer together than this push each other apart (pixels)
+const AVOIDANCE_RADIUS: f32 = PURSUE_AI_AGENT_RADIUS * 4.0;
+// Horizontal offset below which two agents count as stacked on top of each other (pixels)
+const STACKED_THRESHOLD: f32 = 0.5;
+
+/// Horizontal steering (-1 to 1) that moves `agent` at `position` away from the other agents
+/// crowding it, stronger the closer they are. Agents stacked on top of each other split up by
+/// entity order, so they always go opposite ways
+pub fn separation(spatial_index: &DynamicSpatialIndex, agent: Entity, position: Vec2) -> f32 {
+    spatial_index
+        .within_radius(position, AVOIDANCE_RADIUS)
+        .filter(|other| other.kind == DynamicKind::Agent && other.entity != agent)
+        .map(|other| {
+            let offset = position.x - other.position.x;
+            let side = if offset.abs() > STACKED_THRESHOLD {
+                offset.signum()
+            } else if agent > other.entity {
+                1.0
+            } else {
+                -1.0
+            };
+
+            side * (1.0 - position.distance(other.position) / AVOIDANCE_RADIUS)
+        })
+        .sum::<f32>()
+        .clamp(-1.0, 1.0)
+}
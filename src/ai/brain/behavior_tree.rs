@@ -0,0 +1,275 @@
+use bevy::{
+    ecs::{
+        query::Without,
+        system::{Query, Res, ResMut},
+    },
+    math::{Vec2, Vec3Swizzles},
+    prelude::Resource,
+    time::Time,
+    transform::components::Transform,
+};
+use rand::Rng;
+
+use crate::{
+    ai::{
+        activity::Asleep,
+        difficulty::AIDifficulty,
+        pathfinding::PathfindingGraph,
+        pursue_ai::{AIRng, PURSUE_AI_AGENT_RADIUS},
+        tick::AITick,
+        vision::AIVision,
+    },
+    level::Level,
+    lighting::TimeOfDay,
+    spatial::DynamicSpatialIndex,
+};
+
+use super::{AIGoal, Brain, GoalTarget};
+
+// Distance at which a goal counts as reached (pixels)
+const GOAL_REACHED_THRESHOLD: f32 = 30.0;
+// Wander goals are picked at least this far from the agent (pixels)
+const WANDER_MIN_DISTANCE: f32 = 150.0;
+
+/// Outcome of running a behavior tree node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// Behavior tree node
+pub enum BehaviorNode {
+    /// Runs its children in order until one of them doesn't fail
+    Selector(Vec<BehaviorNode>),
+    /// Runs its children in order until one of them doesn't succeed
+    Sequence(Vec<BehaviorNode>),
+    Condition(Condition),
+    Action(Action),
+}
+
+/// Behavior tree leaves that check something without acting
+#[derive(Clone, Copy, Debug)]
+pub enum Condition {
+    /// A player is within detection range
+    PlayerDetected,
+    /// The player was detected within the search duration
+    PlayerRecentlySeen,
+}
+
+/// Behavior tree leaves that set the agent's goal
+#[derive(Clone, Copy, Debug)]
+pub enum Action {
+    /// Head for the player (runs while they are detected)
+    ChasePlayer,
+    /// Head for where the player was last seen (succeeds on arrival)
+    GoToLastSeen,
+    /// Head for random ground nodes, one after another (always running)
+    Wander,
+}
+
+/// Blackboard: What a behavior tree agent remembers between decisions
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Blackboard {
+    /// Where and when (elapsed seconds) the player was last detected
+    pub last_seen: Option<(Vec2, f32)>,
+    /// Ground position the agent is wandering to
+    pub wander_goal: Option<Vec2>,
+}
+
+/// Everything a behavior tree decision can look at
+struct Context<'a, R: Rng> {
+    agent_position: Vec2,
+    detected_player: Option<Vec2>,
+    now: f32,
+    difficulty: &'a AIDifficulty,
+    pathfinding: &'a PathfindingGraph,
+    rng: &'a mut R,
+}
+
+/// The tree every behavior tree agent runs: chase a detected player, otherwise check where they
+/// were last seen, otherwise wander
+#[derive(Resource)]
+pub struct PursuerTree(pub BehaviorNode);
+
+impl Default for PursuerTree {
+    fn default() -> Self {
+        use BehaviorNode::*;
+
+        Self(Selector(vec![
+            Sequence(vec![
+                Condition(self::Condition::PlayerDetected),
+                Action(self::Action::ChasePlayer),
+            ]),
+            Sequence(vec![
+                Condition(self::Condition::PlayerRecentlySeen),
+                Action(self::Action::GoToLastSeen),
+            ]),
+            Action(self::Action::Wander),
+        ]))
+    }
+}
+
+impl BehaviorNode {
+    /// Runs the node for one decision, updating the blackboard and goal as the leaves say
+    fn tick<R: Rng>(
+        &self,
+        blackboard: &mut Blackboard,
+        goal: &mut AIGoal,
+        context: &mut Context<R>,
+    ) -> Status {
+        match self {
+            BehaviorNode::Selector(children) => children
+                .iter()
+                .map(|child| child.tick(blackboard, goal, context))
+                .find(|status| *status != Status::Failure)
+                .unwrap_or(Status::Failure),
+            BehaviorNode::Sequence(children) => children
+                .iter()
+                .map(|child| child.tick(blackboard, goal, context))
+                .find(|status| *status != Status::Success)
+                .unwrap_or(Status::Success),
+            BehaviorNode::Condition(condition) => {
+                if condition.check(blackboard, context) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            BehaviorNode::Action(action) => action.run(blackboard, goal, context),
+        }
+    }
+}
+
+impl Condition {
+    fn check<R: Rng>(self, blackboard: &Blackboard, context: &Context<R>) -> bool {
+        match self {
+            Condition::PlayerDetected => context.detected_player.is_some(),
+            Condition::PlayerRecentlySeen => blackboard.last_seen.is_some_and(|(_, seen_at)| {
+                context.now - seen_at < context.difficulty.search_duration
+            }),
+        }
+    }
+}
+
+impl Action {
+    fn run<R: Rng>(
+        self,
+        blackboard: &mut Blackboard,
+        goal: &mut AIGoal,
+        context: &mut Context<R>,
+    ) -> Status {
+        match self {
+            Action::ChasePlayer => {
+                let Some(player_position) = context.detected_player else {
+                    return Status::Failure;
+                };
+
+                blackboard.last_seen = Some((player_position, context.now));
+                blackboard.wander_goal = None;
+                *goal = AIGoal {
+                    target: GoalTarget::Player,
+                    avoid_hazards: false,
+                };
+                Status::Running
+            }
+            Action::GoToLastSeen => {
+                let Some((last_seen, _)) = blackboard.last_seen else {
+                    return Status::Failure;
+                };
+
+                if context.agent_position.distance(last_seen) < GOAL_REACHED_THRESHOLD {
+                    blackboard.last_seen = None;
+                    return Status::Success;
+                }
+
+                *goal = AIGoal {
+                    target: GoalTarget::Position(last_seen),
+                    avoid_hazards: false,
+                };
+                Status::Running
+            }
+            Action::Wander => {
+                let reached = blackboard.wander_goal.is_none_or(|wander_goal| {
+                    context.agent_position.distance(wander_goal) < GOAL_REACHED_THRESHOLD
+                });
+                if reached {
+                    blackboard.wander_goal = context
+                        .pathfinding
+                        .random_ground_positions(
+                            context.rng,
+                            1,
+                            context.agent_position,
+                            WANDER_MIN_DISTANCE,
+                            PURSUE_AI_AGENT_RADIUS,
+                        )
+                        .first()
+                        .copied();
+                }
+
+                *goal = AIGoal {
+                    target: blackboard
+                        .wander_goal
+                        .map_or(GoalTarget::Hold, GoalTarget::Position),
+                    avoid_hazards: true,
+                };
+                Status::Running
+            }
+        }
+    }
+}
+
+/// Behavior tree brain system: Runs the pursuer tree for each behavior tree agent on its AI tick
+#[allow(clippy::too_many_arguments)]
+pub fn s_behavior_tree_brains(
+    mut ai_query: Query<
+        (&Transform, &AIVision, &mut Brain, &mut AIGoal, &AITick),
+        Without<Asleep>,
+    >,
+    tree: Res<PursuerTree>,
+    pathfinding: Res<PathfindingGraph>,
+    level: Option<Res<Level>>,
+    time_of_day: Res<TimeOfDay>,
+    spatial_index: Res<DynamicSpatialIndex>,
+    difficulty: Res<AIDifficulty>,
+    mut ai_rng: ResMut<AIRng>,
+    time: Res<Time>,
+) {
+    // Vision range shrinks at night, as for the state machine
+    let detection_range = difficulty.detection_range * time_of_day.vision_multiplier();
+
+    let now = time.elapsed_secs();
+
+    for (transform, vision, mut brain, mut goal, ai_tick) in ai_query.iter_mut() {
+        let Brain::BehaviorTree(blackboard) = brain.as_mut() else {
+            continue;
+        };
+        if !ai_tick.ready {
+            continue;
+        }
+
+        // A player seen recently is tracked whichever way the agent faces
+        let tracking = blackboard
+            .last_seen
+            .is_some_and(|(_, seen_at)| now - seen_at < difficulty.lose_detection_time);
+
+        let agent_position = transform.translation.xy();
+        let mut context = Context {
+            agent_position,
+            detected_player: vision.visible_player(
+                &spatial_index,
+                level.as_deref(),
+                agent_position,
+                detection_range,
+                tracking,
+            ),
+            now,
+            difficulty: &difficulty,
+            pathfinding: &pathfinding,
+            rng: &mut ai_rng.0,
+        };
+
+        tree.0.tick(blackboard, &mut goal, &mut context);
+    }
+}
@@ -0,0 +1,137 @@
+//! Interchangeable decision makers for AI agents.
+//!
+//! Every agent has a `Brain` that decides where it goes (its `AIGoal`), and
+//! `s_platformer_ai_movement` takes it there. Brains only decide: swapping one for another keeps
+//! the agent's body and movement state, so different AI implementations can be compared live.
+
+pub mod behavior_tree;
+pub mod scripted;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        schedule::IntoScheduleConfigs,
+        system::Query,
+    },
+    math::Vec2,
+};
+
+use super::{
+    alert::s_propagate_alerts,
+    platformer_ai::s_platformer_ai_movement,
+    pursue_ai::{s_pursue_ai_update, PursueAI, PursueAIState},
+    tick::s_ai_tick,
+};
+use behavior_tree::{s_behavior_tree_brains, Blackboard, PursuerTree};
+use scripted::{s_scripted_brains, BrainScript, ScriptCursor};
+
+/// Brain component: What makes the agent's decisions, and the state only that brain uses
+#[derive(Component, Clone, Debug, Default)]
+pub enum Brain {
+    /// The Wander/Pursue/Search state machine (its state lives in `PursueAI`)
+    #[default]
+    StateMachine,
+    /// The pursuer behavior tree
+    BehaviorTree(Blackboard),
+    /// Steps through the brain script, starting again at the end
+    Scripted(ScriptCursor),
+}
+
+impl Brain {
+    /// The next brain in the debug cycle, starting from scratch
+    pub fn next(&self) -> Brain {
+        match self {
+            Brain::StateMachine => Brain::BehaviorTree(Blackboard::default()),
+            Brain::BehaviorTree(_) => Brain::Scripted(ScriptCursor::default()),
+            Brain::Scripted(_) => Brain::StateMachine,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Brain::StateMachine => "state machine",
+            Brain::BehaviorTree(_) => "behavior tree",
+            Brain::Scripted(_) => "scripted",
+        }
+    }
+}
+
+/// AI goal component: Where the agent's brain wants it to go
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct AIGoal {
+    pub target: GoalTarget,
+    /// Whether paths should keep away from hazards (for goals not worth the risk)
+    pub avoid_hazards: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GoalTarget {
+    /// Stay put
+    #[default]
+    Hold,
+    Position(Vec2),
+    /// Wherever the player is this frame
+    Player,
+}
+
+pub struct BrainPlugin;
+
+impl Plugin for BrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PursuerTree>();
+        app.insert_resource(BrainScript::load());
+
+        app.add_systems(
+            Update,
+            (
+                s_state_machine_goals
+                    .after(s_pursue_ai_update)
+                    .after(s_propagate_alerts),
+                s_behavior_tree_brains.after(s_ai_tick),
+                s_scripted_brains.after(s_ai_tick),
+            )
+                .before(s_platformer_ai_movement),
+        );
+    }
+}
+
+/// State machine brain system: Turns each state machine agent's state into its goal
+pub fn s_state_machine_goals(mut ai_query: Query<(&Brain, &PursueAI, &mut AIGoal)>) {
+    for (brain, pursue_ai, mut goal) in ai_query.iter_mut() {
+        if !matches!(brain, Brain::StateMachine) {
+            continue;
+        }
+
+        *goal = match pursue_ai.state {
+            PursueAIState::Pursue => AIGoal {
+                // Hold near an unreachable player instead of chasing them
+                target: pursue_ai
+                    .pursue_behavior
+                    .hold_target()
+                    .map_or(GoalTarget::Player, GoalTarget::Position),
+                avoid_hazards: false,
+            },
+            // Go where the wander behavior says; wandering agents have no reason to go near
+            // hazards
+            PursueAIState::Wander => AIGoal {
+                target: pursue_ai
+                    .wander_target
+                    .map_or(GoalTarget::Hold, GoalTarget::Position),
+                avoid_hazards: true,
+            },
+            // Look around where the player was last seen
+            PursueAIState::Search => AIGoal {
+                target: pursue_ai
+                    .search
+                    .map_or(GoalTarget::Hold, |search| GoalTarget::Position(search.target)),
+                avoid_hazards: false,
+            },
+            // Stand still to wind up and recover; the lunge is carried by momentum
+            PursueAIState::Attack => AIGoal {
+                target: GoalTarget::Hold,
+                avoid_hazards: false,
+            },
+        };
+    }
+}
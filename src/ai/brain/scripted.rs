@@ -0,0 +1,173 @@
+//! Scripted brain: agents step through a fixed list of goals read from `assets/brain.script`,
+//! starting again at the end. One command per line, `#` starts a comment:
+//!
+//! ```text
+//! move 96 0     # go 96 pixels right of the spawn point
+//! wait 1.5      # stay put for 1.5 seconds
+//! chase 3       # go after the player for 3 seconds
+//! ```
+
+use bevy::{
+    ecs::{
+        query::Without,
+        system::{Query, Res},
+    },
+    math::{Vec2, Vec3Swizzles},
+    prelude::Resource,
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    ai::{activity::Asleep, tick::AITick},
+    health::SpawnPoint,
+};
+
+use super::{AIGoal, Brain, GoalTarget};
+
+const BRAIN_SCRIPT_PATH: &str = "assets/brain.script";
+
+// Distance at which a `move` step counts as done (pixels)
+const MOVE_REACHED_THRESHOLD: f32 = 30.0;
+// How long a `move` step can take before the agent gives up on it (seconds)
+const MOVE_TIMEOUT: f32 = 8.0;
+
+/// One step of the brain script
+#[derive(Clone, Copy, Debug)]
+pub enum BrainStep {
+    /// Go to the spawn point plus this offset
+    Move(Vec2),
+    /// Stay put for this many seconds
+    Wait(f32),
+    /// Go after the player for this many seconds
+    Chase(f32),
+}
+
+/// Brain script resource: The steps every scripted agent loops through
+#[derive(Resource)]
+pub struct BrainScript(pub Vec<BrainStep>);
+
+impl Default for BrainScript {
+    /// Patrols either side of the spawn point, checking for the player in between
+    fn default() -> Self {
+        Self(vec![
+            BrainStep::Move(Vec2::new(96.0, 0.0)),
+            BrainStep::Wait(1.0),
+            BrainStep::Move(Vec2::new(-96.0, 0.0)),
+            BrainStep::Wait(1.0),
+            BrainStep::Chase(3.0),
+        ])
+    }
+}
+
+impl BrainScript {
+    /// Reads `assets/brain.script`, falling back to the built-in patrol if it is missing, broken
+    /// or empty
+    pub fn load() -> Self {
+        let Ok(source) = std::fs::read_to_string(BRAIN_SCRIPT_PATH) else {
+            return BrainScript::default();
+        };
+
+        match parse_brain_script(&source) {
+            Ok(steps) if !steps.is_empty() => BrainScript(steps),
+            Ok(_) => BrainScript::default(),
+            Err(error) => {
+                eprintln!(
+                    "Failed to parse {BRAIN_SCRIPT_PATH}, using the built-in patrol: {error}"
+                );
+                BrainScript::default()
+            }
+        }
+    }
+}
+
+/// Reads brain script source into steps
+fn parse_brain_script(source: &str) -> Result<Vec<BrainStep>, String> {
+    let mut steps = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(code, _)| code);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            continue;
+        };
+
+        let number = |i: usize| -> Result<f32, String> {
+            args.get(i)
+                .and_then(|arg| arg.parse::<f32>().ok())
+                .ok_or_else(|| {
+                    format!("line {}: `{command}` argument {} must be a number", index + 1, i + 1)
+                })
+        };
+
+        let step = match command {
+            "move" => BrainStep::Move(Vec2::new(number(0)?, number(1)?)),
+            "wait" => BrainStep::Wait(number(0)?),
+            "chase" => BrainStep::Chase(number(0)?),
+            _ => return Err(format!("line {}: unknown command `{command}`", index + 1)),
+        };
+        steps.push(step);
+    }
+
+    Ok(steps)
+}
+
+/// Where a scripted agent is in the brain script
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScriptCursor {
+    pub step: usize,
+    /// When (elapsed seconds) the current step ends (`None` until it starts)
+    pub until: Option<f32>,
+}
+
+/// Scripted brain system: Moves each scripted agent through the brain script on its AI tick
+pub fn s_scripted_brains(
+    mut ai_query: Query<
+        (&Transform, &SpawnPoint, &mut Brain, &mut AIGoal, &AITick),
+        Without<Asleep>,
+    >,
+    script: Res<BrainScript>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+
+    for (transform, spawn_point, mut brain, mut goal, ai_tick) in ai_query.iter_mut() {
+        let Brain::Scripted(cursor) = brain.as_mut() else {
+            continue;
+        };
+        if !ai_tick.ready {
+            continue;
+        }
+
+        let step = script.0[cursor.step % script.0.len()];
+        let until = *cursor.until.get_or_insert(
+            now + match step {
+                BrainStep::Move(_) => MOVE_TIMEOUT,
+                BrainStep::Wait(seconds) | BrainStep::Chase(seconds) => seconds,
+            },
+        );
+
+        let target = match step {
+            BrainStep::Move(offset) => GoalTarget::Position(spawn_point.0 + offset),
+            BrainStep::Wait(_) => GoalTarget::Hold,
+            BrainStep::Chase(_) => GoalTarget::Player,
+        };
+        let arrived = match target {
+            GoalTarget::Position(position) => {
+                transform.translation.xy().distance(position) < MOVE_REACHED_THRESHOLD
+            }
+            _ => false,
+        };
+
+        if arrived || now >= until {
+            cursor.step = (cursor.step + 1) % script.0.len();
+            cursor.until = None;
+        }
+
+        // Like the state machine, only chasing is worth the risk of hazards
+        *goal = AIGoal {
+            target,
+            avoid_hazards: target != GoalTarget::Player,
+        };
+    }
+}
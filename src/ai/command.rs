@@ -0,0 +1,415 @@
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    camera::{visibility::Visibility, Camera, Projection},
+    color::{Alpha, Color},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    gizmos::gizmos::Gizmos,
+    input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+    math::{Vec2, Vec3Swizzles},
+    prelude::Resource,
+    sprite::Text2d,
+    state::{condition::in_state, state_scoped::DespawnOnExit},
+    text::{TextColor, TextFont},
+    transform::components::{GlobalTransform, Transform},
+    ui::{widget::Text, Node, PositionType, Val},
+    window::{PrimaryWindow, Window},
+};
+
+use super::{
+    a_star::{connection_cost, find_path, PathNode},
+    brain::{
+        behavior_tree::s_behavior_tree_brains, s_state_machine_goals, scripted::s_scripted_brains,
+        AIGoal, GoalTarget,
+    },
+    pathfinding::{
+        connection_color, PathfindingGraph, PathfindingGraphConnection,
+        PathfindingGraphConnectionType, WALK_EDGE_COLOR,
+    },
+    patrol::s_patrol_ai_goals,
+    platformer_ai::{s_platformer_ai_movement, PlatformerAI},
+    pursue_ai::PURSUE_AI_AGENT_RADIUS,
+};
+use crate::{
+    camera::{cursor_world_position, GameCamera},
+    debug::{AI_COMMAND_KEY, SELECT_NEXT_KEY, SELECT_PREVIOUS_KEY},
+    editor::EditorState,
+    game_state::GameState,
+    pixel_perfect::CanvasCamera,
+    selection::{body_at, s_click_select, SelectedEntity},
+    GizmosVisible, KinematicBody,
+};
+
+// How close a commanded agent has to get to its target to be done (units: pixels)
+const COMMAND_ARRIVE_DISTANCE: f32 = PURSUE_AI_AGENT_RADIUS * 2.0;
+
+const COMMAND_TARGET_COLOR: Color = Color::srgb(1.0, 0.3, 0.9);
+// Gizmo sizes (units: pixels)
+const PREVIEW_NODE_RADIUS: f32 = 3.0;
+const COMMAND_TARGET_SIZE: f32 = 8.0;
+
+// Edge cost labels
+const LABEL_FONT_SIZE: f32 = 10.0;
+const LABEL_Z: f32 = 10.0;
+// Offset of a label from the middle of its edge (units: pixels)
+const LABEL_OFFSET: Vec2 = Vec2::new(0.0, 8.0);
+
+// Overlay layout (units: pixels)
+const OVERLAY_FONT_SIZE: f32 = 14.0;
+const OVERLAY_MARGIN: f32 = 16.0;
+const OVERLAY_COLOR: Color = Color::srgb(1.0, 0.8, 1.0);
+
+/// AI command component: Overrides the agent's goal with a position it was sent to, until it
+/// gets there
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AICommand {
+    pub target: Vec2,
+}
+
+/// One graph connection of a previewed path
+struct PreviewEdge {
+    from: Vec2,
+    to: Vec2,
+    connection_type: PathfindingGraphConnectionType,
+    cost: f32,
+}
+
+/// The path the selected agent would take to the cursor
+struct PathPreview {
+    start: Vec2,
+    nodes: Vec<PathNode>,
+    edges: Vec<PreviewEdge>,
+}
+
+/// AI command tool resource: Whether click-to-command is on and what the selected agent (see
+/// `SelectedEntity`) would do
+#[derive(Resource, Default)]
+pub struct AICommandTool {
+    /// Whether the tool is on (while gizmos are visible)
+    pub active: bool,
+    preview: Option<PathPreview>,
+    /// Node ids of the path the edge labels were made for
+    labelled_path: Vec<usize>,
+}
+
+/// Marker for the edge cost labels of the previewed path
+#[derive(Component)]
+struct EdgeCostLabel;
+
+/// Marker for the AI command overlay text
+#[derive(Component)]
+struct AICommandText;
+
+/// AI command plugin: Debug tool to send an agent anywhere with a click, previewing the path it
+/// will plan, what each edge costs and whether it is walked, jumped or dropped
+pub struct AICommandPlugin;
+
+impl Plugin for AICommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AICommandTool>();
+
+        app.add_systems(Startup, s_spawn_ai_command_text);
+        app.add_systems(
+            Update,
+            (
+                s_toggle_ai_command_tool,
+                s_ai_command_input
+                    .after(s_toggle_ai_command_tool)
+                    .after(s_click_select)
+                    .run_if(in_state(EditorState::Playing)),
+                s_apply_ai_commands
+                    .after(s_ai_command_input)
+                    .after(s_state_machine_goals)
+                    .after(s_behavior_tree_brains)
+                    .after(s_scripted_brains)
+                    .after(s_patrol_ai_goals)
+                    .before(s_platformer_ai_movement),
+                s_debug_ai_command.after(s_ai_command_input),
+                s_update_ai_command_labels.after(s_ai_command_input),
+            ),
+        );
+    }
+}
+
+/// Spawns the (hidden) AI command overlay in the bottom-left corner of the window
+fn s_spawn_ai_command_text(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: OVERLAY_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(OVERLAY_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(OVERLAY_MARGIN),
+            left: Val::Px(OVERLAY_MARGIN),
+            ..Default::default()
+        },
+        Visibility::Hidden,
+        AICommandText,
+    ));
+}
+
+/// AI command toggle system: Turns the tool on and off while gizmos are visible
+fn s_toggle_ai_command_tool(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut tool: ResMut<AICommandTool>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(AI_COMMAND_KEY) {
+        tool.active = !tool.active;
+    }
+
+    if !(gizmos_visible.visible && tool.active) {
+        tool.preview = None;
+    }
+}
+
+/// AI command input system: Previews the path of the selected agent to the cursor and sends it
+/// there on click (right click calls it off). Clicks on bodies are left to the selection.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn s_ai_command_input(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    canvas_query: Query<(&Projection, &CanvasCamera)>,
+    body_query: Query<(Entity, &Transform, &KinematicBody)>,
+    agent_query: Query<&Transform, With<PlatformerAI>>,
+    selected: Res<SelectedEntity>,
+    pathfinding: Res<PathfindingGraph>,
+    mut tool: ResMut<AICommandTool>,
+) {
+    if !tool.active {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.single(), camera_query.single())
+    else {
+        return;
+    };
+    let Some(cursor) =
+        cursor_world_position(window, camera, camera_transform, canvas_query.single().ok())
+    else {
+        tool.preview = None;
+        return;
+    };
+
+    // Only walking agents follow the graph
+    let Some((entity, transform)) = selected
+        .0
+        .and_then(|entity| agent_query.get(entity).ok().map(|transform| (entity, transform)))
+    else {
+        tool.preview = None;
+        return;
+    };
+
+    // Hovering over a body shows what clicking would select rather than a path
+    if body_at(body_query.iter(), cursor).is_some() {
+        tool.preview = None;
+        return;
+    }
+    tool.preview = preview_path(&pathfinding, transform.translation.xy(), cursor);
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        if let Some(preview) = &tool.preview {
+            // Aim for the node the path ends at, so the agent can tell it has arrived
+            let target = preview.nodes.last().map_or(cursor, |node| node.position);
+            commands.entity(entity).insert(AICommand { target });
+        }
+    }
+
+    if mouse_input.just_pressed(MouseButton::Right) {
+        commands.entity(entity).remove::<AICommand>();
+    }
+}
+
+/// AI command system: Points commanded agents at their target instead of what their brain wants,
+/// handing control back once they get there
+pub fn s_apply_ai_commands(
+    mut commands: Commands,
+    mut ai_query: Query<(Entity, &Transform, &AICommand, &mut AIGoal)>,
+) {
+    for (entity, transform, command, mut goal) in ai_query.iter_mut() {
+        if transform.translation.xy().distance(command.target) <= COMMAND_ARRIVE_DISTANCE {
+            commands.entity(entity).remove::<AICommand>();
+            continue;
+        }
+
+        *goal = AIGoal {
+            target: GoalTarget::Position(command.target),
+            avoid_hazards: false,
+        };
+    }
+}
+
+/// AI command debug system: Draws the previewed path colored by traversal type and the targets
+/// agents have been sent to
+fn s_debug_ai_command(
+    tool: Res<AICommandTool>,
+    command_query: Query<(&Transform, &AICommand)>,
+    mut gizmos: Gizmos,
+) {
+    if !tool.active {
+        return;
+    }
+
+    for (transform, command) in command_query.iter() {
+        gizmos.cross_2d(command.target, COMMAND_TARGET_SIZE, COMMAND_TARGET_COLOR);
+        gizmos.line_2d(
+            transform.translation.xy(),
+            command.target,
+            COMMAND_TARGET_COLOR.with_alpha(0.3),
+        );
+    }
+
+    let Some(preview) = &tool.preview else {
+        return;
+    };
+
+    // The agent first heads for the node nearest it
+    if let Some(first) = preview.nodes.first() {
+        gizmos.line_2d(preview.start, first.position, WALK_EDGE_COLOR.with_alpha(0.4));
+    }
+    for node in &preview.nodes {
+        gizmos.circle_2d(node.position, PREVIEW_NODE_RADIUS, Color::WHITE);
+    }
+    for edge in &preview.edges {
+        gizmos.arrow_2d(edge.from, edge.to, connection_color(edge.connection_type));
+    }
+}
+
+/// AI command overlay system: Labels each previewed edge with its cost and sums the path up in
+/// the overlay
+fn s_update_ai_command_labels(
+    mut commands: Commands,
+    gizmos_visible: Res<GizmosVisible>,
+    mut tool: ResMut<AICommandTool>,
+    selected: Res<SelectedEntity>,
+    label_query: Query<Entity, With<EdgeCostLabel>>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<AICommandText>>,
+) {
+    let shown = gizmos_visible.visible && tool.active;
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        *visibility = if shown {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+        if shown {
+            text.0 = ai_command_report(&tool, selected.0);
+        }
+    }
+
+    // Labels only change with the path, not every time the cursor moves
+    let path: Vec<usize> =
+        tool.preview.iter().flat_map(|preview| preview.nodes.iter().map(|node| node.id)).collect();
+    if path == tool.labelled_path {
+        return;
+    }
+
+    for entity in label_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    if let Some(preview) = &tool.preview {
+        for edge in &preview.edges {
+            let position = (edge.from + edge.to) / 2.0 + LABEL_OFFSET;
+            commands.spawn((
+                Text2d::new(format!("{:.0}", edge.cost)),
+                TextFont {
+                    font_size: LABEL_FONT_SIZE,
+                    ..Default::default()
+                },
+                TextColor(connection_color(edge.connection_type)),
+                Transform::from_translation(position.extend(LABEL_Z)),
+                EdgeCostLabel,
+                DespawnOnExit(GameState::InGame),
+            ));
+        }
+    }
+    tool.labelled_path = path;
+}
+
+/// Overlay text: the controls, the selected agent and a summary of the previewed path
+fn ai_command_report(tool: &AICommandTool, selected: Option<Entity>) -> String {
+    let mut lines = vec![format!(
+        "AI command ({AI_COMMAND_KEY:?} to leave): click an agent or press \
+         {SELECT_PREVIOUS_KEY:?}/{SELECT_NEXT_KEY:?} to select it, click anywhere to send it \
+         there, right click to call it off"
+    )];
+
+    let Some(entity) = selected else {
+        lines.push("No agent selected".to_string());
+        return lines.join("\n");
+    };
+    lines.push(format!("Agent {entity}"));
+
+    match &tool.preview {
+        Some(preview) => {
+            let count = |connection_type| {
+                preview
+                    .edges
+                    .iter()
+                    .filter(|edge| edge.connection_type == connection_type)
+                    .count()
+            };
+            let total_cost: f32 = preview.edges.iter().map(|edge| edge.cost).sum();
+            lines.push(format!(
+                "{} edges, cost {total_cost:.1}: walk {}, jump {}, drop {}",
+                preview.edges.len(),
+                count(PathfindingGraphConnectionType::Walkable),
+                count(PathfindingGraphConnectionType::Jumpable),
+                count(PathfindingGraphConnectionType::Droppable),
+            ));
+        }
+        None => lines.push("No path to the cursor".to_string()),
+    }
+
+    lines.join("\n")
+}
+
+/// Plans the path the agent would take from `start` to `target`, along with the connection each
+/// step of it uses
+fn preview_path(pathfinding: &PathfindingGraph, start: Vec2, target: Vec2) -> Option<PathPreview> {
+    let nodes = find_path(pathfinding, start, target)?;
+
+    let edges = nodes
+        .windows(2)
+        .filter_map(|pair| {
+            let connection = cheapest_connection(pathfinding, pair[0].id, pair[1].id)?;
+            Some(PreviewEdge {
+                from: pair[0].position,
+                to: pair[1].position,
+                connection_type: connection.connection_type,
+                cost: connection_cost(connection),
+            })
+        })
+        .collect();
+
+    Some(PathPreview {
+        start,
+        nodes,
+        edges,
+    })
+}
+
+/// The open connection between two nodes that the pathfinder would pick
+fn cheapest_connection(
+    pathfinding: &PathfindingGraph,
+    from: usize,
+    to: usize,
+) -> Option<&PathfindingGraphConnection> {
+    let node = &pathfinding.nodes[from];
+    node.walkable_connections
+        .iter()
+        .chain(node.jumpable_connections.iter())
+        .chain(node.droppable_connections.iter())
+        .filter(|connection| connection.node_id == to && pathfinding.is_connection_open(connection))
+        .min_by(|a, b| connection_cost(a).total_cmp(&connection_cost(b)))
+}
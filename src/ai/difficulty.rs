@@ -0,0 +1,60 @@
+use bevy::prelude::Resource;
+use serde::Deserialize;
+
+const AI_DIFFICULTY_PATH: &str = "assets/ai_difficulty.json";
+
+/// How perceptive and persistent AI agents are, loaded from `assets/ai_difficulty.json` at startup
+/// Missing files or fields fall back to the defaults
+#[derive(Resource, Deserialize, Clone)]
+#[serde(default)]
+pub struct AIDifficulty {
+    /// Distance (pixels) at which agents detect the player in full daylight
+    pub detection_range: f32,
+    /// How long (seconds) a pursuing agent can lose the player before it starts searching
+    pub lose_detection_time: f32,
+    /// How long (seconds) an agent searches around the last seen position before wandering again
+    pub search_duration: f32,
+    /// How far (pixels) from the last seen position a searching agent patrols
+    pub search_radius: f32,
+    /// How far (pixels) an agent that spots the player can alert its allies
+    pub alert_radius: f32,
+    /// How long (seconds) an alert takes to reach allies
+    pub alert_delay: f32,
+    /// How far (pixels) a wandering agent hears the loudest noises the player makes
+    pub hearing_radius: f32,
+    /// Distance (pixels) from the player within which a chasing agent attacks
+    pub attack_range: f32,
+    /// How long (seconds) an agent crouches before lunging, giving the player time to react
+    pub attack_windup: f32,
+    /// How long (seconds) an agent recovers after a lunge before it can attack again
+    pub attack_cooldown: f32,
+}
+
+impl Default for AIDifficulty {
+    fn default() -> Self {
+        Self {
+            detection_range: 500.0,
+            lose_detection_time: 0.5,
+            search_duration: 6.0,
+            search_radius: 64.0,
+            alert_radius: 300.0,
+            alert_delay: 0.75,
+            hearing_radius: 320.0,
+            attack_range: 48.0,
+            attack_windup: 0.4,
+            attack_cooldown: 1.0,
+        }
+    }
+}
+
+impl AIDifficulty {
+    pub fn load() -> Self {
+        match std::fs::read_to_string(AI_DIFFICULTY_PATH) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+                eprintln!("Failed to parse {AI_DIFFICULTY_PATH}, using defaults: {error}");
+                AIDifficulty::default()
+            }),
+            Err(_) => AIDifficulty::default(),
+        }
+    }
+}
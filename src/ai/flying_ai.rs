@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    ecs::{
+        component::Component,
+        query::Without,
+        schedule::IntoScheduleConfigs,
+        system::{ParamSet, Query, Res},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{Vec2, Vec3Swizzles},
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    characters::{nearest_player, PlayerControlled},
+    collisions::s_collision,
+    level::{Aabb, Level},
+    settings::Settings,
+    utils::line_intersect,
+    KinematicBody,
+};
+
+use super::{
+    activity::Asleep,
+    brain::{AIGoal, GoalTarget},
+    metrics::{path_length, AIMetrics},
+    platformer_ai::update_physics_and_transform,
+    profile::AIProfile,
+    pursue_ai::s_pursue_ai_update,
+    tick::AITick,
+};
+
+// Flight constants
+// Top cruising speed (units: pixels/second)
+const FLYING_MAX_SPEED: f32 = 200.0;
+// Rate at which velocity is steered towards the desired velocity (units: 1/second)
+const FLYING_RESPONSIVENESS: f32 = 6.0;
+// Strongest steering acceleration (units: pixels/second²)
+const FLYING_MAX_ACCELERATION: f32 = 1200.0;
+// Distance from the goal within which the agent slows down to stop on it (units: pixels)
+const ARRIVAL_RADIUS: f32 = 64.0;
+// Distance at which a waypoint counts as reached (units: pixels)
+const WAYPOINT_REACHED_DISTANCE: f32 = 12.0;
+// How far ahead the obstacle feelers reach, on top of the agent's radius (units: seconds of
+// travel at the current speed)
+const OBSTACLE_LOOK_AHEAD: f32 = 0.35;
+// How strongly level geometry ahead pushes the agent away (fraction of the top speed)
+const OBSTACLE_AVOIDANCE_WEIGHT: f32 = 1.5;
+// Speed below which there is nothing to look ahead for (units: pixels/second)
+const OBSTACLE_MIN_SPEED: f32 = 1.0;
+// Tile value of an empty tile in the level grid
+const EMPTY_TILE: u32 = 0;
+// Offsets (in tiles) to the eight tiles around a tile
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+// Gizmo colors for the AI debug layer
+const FLIGHT_PATH_COLOR: Color = Color::srgb(0.8, 0.4, 1.0);
+const WAYPOINT_GIZMO_RADIUS: f32 = 4.0;
+// Length of the desired velocity line per pixel/second of speed (units: seconds)
+const DESIRED_VELOCITY_GIZMO_SCALE: f32 = 0.1;
+
+/// Flying AI component: An agent that ignores gravity and flies straight through open space,
+/// planning over the level's empty tiles instead of the surface graph and steering with seek,
+/// arrival and obstacle avoidance
+#[derive(Component, Default)]
+pub struct FlyingAI {
+    /// Waypoints (world pixels) from the agent to its goal, straightened wherever the agent fits
+    /// between them
+    pub path: Vec<Vec2>,
+    /// Index of the waypoint the agent is flying towards
+    pub path_index: usize,
+    /// Time (seconds) before the agent may plan a new path
+    pub replan_timer: f32,
+    /// Velocity (pixels/second) the agent steered towards this frame
+    pub desired_velocity: Vec2,
+}
+
+pub struct FlyingAIPlugin;
+
+impl Plugin for FlyingAIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            s_flying_ai_movement
+                .after(s_pursue_ai_update)
+                .before(s_collision),
+        );
+    }
+}
+
+/// Flying AI movement system: Plans a path through open space to each flying agent's goal on its
+/// AI tick, then steers along it (arriving gently at the goal and swerving around level geometry
+/// ahead)
+#[allow(clippy::type_complexity)]
+pub fn s_flying_ai_movement(
+    mut queries: ParamSet<(
+        Query<
+            (
+                &mut Transform,
+                &mut KinematicBody,
+                &mut FlyingAI,
+                &AIGoal,
+                &AITick,
+                &AIProfile,
+                Option<&mut AIMetrics>,
+            ),
+            Without<Asleep>,
+        >,
+        Query<&Transform, PlayerControlled>,
+    )>,
+    level: Option<Res<Level>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let Some(level) = level else {
+        return;
+    };
+
+    let player_positions: Vec<Vec2> = queries
+        .p1()
+        .iter()
+        .map(|transform| transform.translation.xy())
+        .collect();
+    let dt = time.delta_secs().min(1.0 / 30.0); // Clamp delta time
+
+    for (mut transform, mut physics, mut flying_ai, goal, ai_tick, profile, metrics) in
+        queries.p0().iter_mut()
+    {
+        let position = transform.translation.xy();
+        let goal_pos = match goal.target {
+            GoalTarget::Hold => None,
+            GoalTarget::Position(position) => Some(position),
+            GoalTarget::Player => nearest_player(&player_positions, position),
+        };
+
+        flying_ai.replan_timer = (flying_ai.replan_timer - dt).max(0.0);
+        if let Some(goal_pos) = goal_pos {
+            if ai_tick.ready && flying_ai.replan_timer <= 0.0 {
+                let path = find_flight_path(&level, position, goal_pos, physics.radius);
+                if let Some(mut metrics) = metrics {
+                    let length = path.as_deref().map(|path| {
+                        path_length(std::iter::once(position).chain(path.iter().copied()))
+                    });
+                    metrics.record_plan(length);
+                }
+                // An agent squeezed into a solid tile heads straight for the goal to get out
+                flying_ai.path = path.unwrap_or_else(|| vec![goal_pos]);
+                flying_ai.path_index = 0;
+                flying_ai.replan_timer = profile.repath_interval;
+            }
+        } else {
+            flying_ai.path.clear();
+        }
+
+        // Move on to the next waypoint once the current one is reached
+        while flying_ai.path_index + 1 < flying_ai.path.len()
+            && position.distance(flying_ai.path[flying_ai.path_index]) < WAYPOINT_REACHED_DISTANCE
+        {
+            flying_ai.path_index += 1;
+        }
+
+        let max_speed = FLYING_MAX_SPEED * profile.speed_multiplier;
+        let travel = match flying_ai.path.get(flying_ai.path_index) {
+            Some(&waypoint) if flying_ai.path_index + 1 == flying_ai.path.len() => {
+                arrive(position, waypoint, max_speed)
+            }
+            Some(&waypoint) => seek(position, waypoint, max_speed),
+            // Nowhere to go: hover in place
+            None => Vec2::ZERO,
+        };
+        let avoidance = avoid_obstacles(&level, position, physics.velocity, physics.radius)
+            * max_speed
+            * OBSTACLE_AVOIDANCE_WEIGHT;
+        let desired_velocity = travel + avoidance;
+        flying_ai.desired_velocity = desired_velocity;
+
+        // No gravity: the steering acceleration is all there is
+        let acceleration_at = |velocity: Vec2| {
+            ((desired_velocity - velocity) * FLYING_RESPONSIVENESS)
+                .clamp_length_max(FLYING_MAX_ACCELERATION)
+        };
+        // The closure is handed to the integrator below as well
+        #[allow(clippy::redundant_closure_call)]
+        let acceleration = acceleration_at(physics.velocity);
+        physics.acceleration = acceleration;
+
+        update_physics_and_transform(
+            &mut physics,
+            &mut transform,
+            settings.integrator,
+            acceleration_at,
+            dt,
+        );
+    }
+}
+
+/// Seek: full speed straight at the target
+fn seek(position: Vec2, target: Vec2, max_speed: f32) -> Vec2 {
+    (target - position).normalize_or_zero() * max_speed
+}
+
+/// Arrival: like seek, but slowing down within `ARRIVAL_RADIUS` to stop on the target
+fn arrive(position: Vec2, target: Vec2, max_speed: f32) -> Vec2 {
+    let offset = target - position;
+    let speed = max_speed * (offset.length() / ARRIVAL_RADIUS).min(1.0);
+    offset.normalize_or_zero() * speed
+}
+
+/// Obstacle avoidance: Casts three feelers (from the agent's centre and both of its sides) along
+/// its velocity and returns a push (0 to 1 in length) away from the closest level edge they hit,
+/// stronger the closer the edge
+fn avoid_obstacles(level: &Level, position: Vec2, velocity: Vec2, radius: f32) -> Vec2 {
+    let speed = velocity.length();
+    if speed < OBSTACLE_MIN_SPEED {
+        return Vec2::ZERO;
+    }
+
+    let direction = velocity / speed;
+    let side = direction.perp() * radius;
+    let look_ahead = radius + speed * OBSTACLE_LOOK_AHEAD;
+    let feeler = direction * look_ahead;
+    let area = Aabb::from_points(&[position, position + feeler]).expand(radius);
+
+    let mut closest: Option<(f32, Vec2)> = None;
+    for start in [position, position + side, position - side] {
+        for edge in level.query_aabb(&area).flat_map(|polygon| polygon.points.windows(2)) {
+            let Some(hit) = line_intersect(start, start + feeler, edge[0], edge[1]) else {
+                continue;
+            };
+
+            let distance = start.distance(hit);
+            if closest.is_none_or(|(closest_distance, _)| distance < closest_distance) {
+                // Edge normal facing the agent
+                let normal = (edge[1] - edge[0]).perp().normalize_or_zero();
+                let normal = if normal.dot(direction) > 0.0 { -normal } else { normal };
+                closest = Some((distance, normal));
+            }
+        }
+    }
+
+    closest.map_or(Vec2::ZERO, |(distance, normal)| normal * (1.0 - distance / look_ahead))
+}
+
+/// Flight path from `from` to `to` for an agent of `radius`: a flood fill over the level's empty
+/// tiles (moving diagonally only where both sides are clear), with the waypoints the agent can
+/// fly straight past removed. Ends at the closest open tile if there is no route to `to`, and is
+/// `None` only if `from` is buried in solid tiles.
+pub fn find_flight_path(level: &Level, from: Vec2, to: Vec2, radius: f32) -> Option<Vec<Vec2>> {
+    let rows = level.tiles.len() as i32;
+    let columns = level.tiles.first().map_or(0, Vec::len) as i32;
+    let open = |(x, y): (i32, i32)| {
+        (0..columns).contains(&x)
+            && (0..rows).contains(&y)
+            && level.tiles[y as usize][x as usize] == EMPTY_TILE
+    };
+
+    // Tile (0, 0) sits at the top left corner of the level
+    let cell_of = |point: Vec2| {
+        (
+            ((point.x + level.half_size.x) / level.grid_size).floor() as i32,
+            ((level.half_size.y - point.y) / level.grid_size).floor() as i32,
+        )
+    };
+    let center_of = |(x, y): (i32, i32)| {
+        Vec2::new(
+            (x as f32 + 0.5) * level.grid_size - level.half_size.x,
+            level.half_size.y - (y as f32 + 0.5) * level.grid_size,
+        )
+    };
+
+    // Points on a surface (like wander goals) can sit just inside a solid tile; they start from
+    // the closest open tile around them instead
+    let open_cell_near = |point: Vec2| {
+        let (x, y) = cell_of(point);
+        NEIGHBOURS
+            .into_iter()
+            .chain([(0, 0)])
+            .map(|(dx, dy)| (x + dx, y + dy))
+            .filter(|&cell| open(cell))
+            .min_by(|&a, &b| {
+                point
+                    .distance_squared(center_of(a))
+                    .total_cmp(&point.distance_squared(center_of(b)))
+            })
+    };
+    let start = if open(cell_of(from)) { cell_of(from) } else { open_cell_near(from)? };
+    let goal = if open(cell_of(to)) { Some(cell_of(to)) } else { open_cell_near(to) };
+
+    // Flood fill from the start, remembering where each cell was reached from and which reached
+    // cell is closest to the goal
+    let index = |(x, y): (i32, i32)| (y * columns + x) as usize;
+    let mut came_from: Vec<Option<(i32, i32)>> = vec![None; (rows * columns) as usize];
+    came_from[index(start)] = Some(start);
+    let mut frontier = VecDeque::from([start]);
+    let mut closest = start;
+    while let Some(cell) = frontier.pop_front() {
+        if Some(cell) == goal {
+            closest = cell;
+            break;
+        }
+        if to.distance_squared(center_of(cell)) < to.distance_squared(center_of(closest)) {
+            closest = cell;
+        }
+
+        for (dx, dy) in NEIGHBOURS {
+            let next = (cell.0 + dx, cell.1 + dy);
+            let diagonal_clear =
+                dx == 0 || dy == 0 || (open((next.0, cell.1)) && open((cell.0, next.1)));
+            if open(next) && diagonal_clear && came_from[index(next)].is_none() {
+                came_from[index(next)] = Some(cell);
+                frontier.push_back(next);
+            }
+        }
+    }
+
+    // Walk back from the goal (the ends are the exact positions, not the cell centres). Goals
+    // that can't be reached (walled off or outside the level) are flown as close to as possible
+    let end = if Some(closest) == goal { to } else { center_of(closest) };
+    let mut cells = vec![end];
+    let mut cell = closest;
+    while cell != start {
+        cell = came_from[index(cell)]?;
+        if cell != start {
+            cells.push(center_of(cell));
+        }
+    }
+    cells.reverse();
+
+    // Skip every waypoint the agent can fly straight past
+    let mut path = Vec::new();
+    let mut anchor = from;
+    let mut next = 0;
+    while next < cells.len() {
+        let mut furthest = next;
+        while furthest + 1 < cells.len() && clear_flight(level, anchor, cells[furthest + 1], radius)
+        {
+            furthest += 1;
+        }
+        path.push(cells[furthest]);
+        anchor = cells[furthest];
+        next = furthest + 1;
+    }
+
+    Some(path)
+}
+
+/// Whether an agent of `radius` fits along the straight line between two points (checked along
+/// its centre and both of its sides)
+fn clear_flight(level: &Level, from: Vec2, to: Vec2, radius: f32) -> bool {
+    let side = (to - from).normalize_or_zero().perp() * radius;
+    [Vec2::ZERO, side, -side]
+        .into_iter()
+        .all(|offset| level.line_of_sight(from + offset, to + offset))
+}
+
+/// Draws each flying agent's flight path and the velocity it is steering towards
+pub fn s_debug_flying_ai(ai_query: Query<(&Transform, &FlyingAI)>, mut gizmos: Gizmos) {
+    for (transform, flying_ai) in ai_query.iter() {
+        let agent_position = transform.translation.xy();
+
+        let mut prev_pos = agent_position;
+        for &waypoint in flying_ai.path.iter().skip(flying_ai.path_index) {
+            gizmos.circle_2d(waypoint, WAYPOINT_GIZMO_RADIUS, FLIGHT_PATH_COLOR);
+            gizmos.line_2d(prev_pos, waypoint, FLIGHT_PATH_COLOR);
+            prev_pos = waypoint;
+        }
+
+        gizmos.line_2d(
+            agent_position,
+            agent_position + flying_ai.desired_velocity * DESIRED_VELOCITY_GIZMO_SCALE,
+            Color::srgb(1.0, 0.0, 0.0),
+        );
+    }
+}
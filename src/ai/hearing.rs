@@ -0,0 +1,149 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        message::{Message, MessageReader, MessageWriter},
+        query::Without,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    math::{Vec2, Vec3Swizzles},
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    ai::difficulty::AIDifficulty,
+    characters::PlayerControlled,
+    collisions::{s_player_contacts, CollisionStarted},
+    level::Level,
+    s_movement, ControllerAction, ControllerEvent, GROUND_NORMAL_Y_THRESHOLD,
+};
+
+use super::{
+    activity::Asleep,
+    platformer_ai::s_platformer_ai_movement,
+    pursue_ai::{s_pursue_ai_update, search::SearchBehavior, PursueAI, PursueAIState},
+    territory::Territory,
+};
+
+// Loudness of each jump (fraction of the hearing radius)
+const JUMP_LOUDNESS: f32 = 0.5;
+const AIR_JUMP_LOUDNESS: f32 = 0.3;
+const WALL_JUMP_LOUDNESS: f32 = 0.7;
+// Loudness of catching onto a wall to slide down it (fraction of the hearing radius)
+const WALL_SLIDE_LOUDNESS: f32 = 0.2;
+// Landings slower than this are silent, and landings this fast or faster are as loud as it gets
+// (units: pixels/second)
+const QUIET_LANDING_SPEED: f32 = 150.0;
+const LOUDEST_LANDING_SPEED: f32 = 1200.0;
+// How much of a noise gets through level geometry between it and the listener (fraction)
+const OCCLUDED_NOISE_ATTENUATION: f32 = 0.35;
+
+/// The player made a noise that nearby agents may hear
+#[derive(Message, Clone, Copy, Debug)]
+pub struct NoiseEvent {
+    /// Where the noise was made
+    pub origin: Vec2,
+    /// How loud it was, as a fraction of the agents' hearing radius
+    pub loudness: f32,
+}
+
+pub struct AIHearingPlugin;
+
+impl Plugin for AIHearingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<NoiseEvent>();
+        app.add_systems(
+            Update,
+            (
+                s_player_noise.after(s_movement).after(s_player_contacts),
+                // Noises are made after the agents move, so they are heard the frame after
+                s_hear_noises
+                    .after(s_pursue_ai_update)
+                    .before(s_platformer_ai_movement),
+            ),
+        );
+    }
+}
+
+/// Player noise system: Makes a noise for every jump, and for every landing, louder the harder
+/// the player hits the ground (for every player)
+fn s_player_noise(
+    player_query: Query<&Transform, PlayerControlled>,
+    mut controller_events: MessageReader<ControllerEvent>,
+    mut collisions: MessageReader<CollisionStarted>,
+    mut noises: MessageWriter<NoiseEvent>,
+) {
+    for event in controller_events.read() {
+        let Ok(player_transform) = player_query.get(event.entity) else {
+            continue;
+        };
+        let loudness = match event.action {
+            ControllerAction::Jump => JUMP_LOUDNESS,
+            ControllerAction::AirJump => AIR_JUMP_LOUDNESS,
+            ControllerAction::WallJump => WALL_JUMP_LOUDNESS,
+            ControllerAction::WallSlide => WALL_SLIDE_LOUDNESS,
+            ControllerAction::Dash => continue,
+        };
+        noises.write(NoiseEvent {
+            origin: player_transform.translation.xy(),
+            loudness,
+        });
+    }
+
+    for collision in collisions.read() {
+        let Ok(player_transform) = player_query.get(collision.entity) else {
+            continue;
+        };
+        let landed = collision.normal.y > GROUND_NORMAL_Y_THRESHOLD
+            && collision.impact_speed > QUIET_LANDING_SPEED;
+        if landed {
+            noises.write(NoiseEvent {
+                origin: player_transform.translation.xy(),
+                loudness: (collision.impact_speed / LOUDEST_LANDING_SPEED).min(1.0),
+            });
+        }
+    }
+}
+
+/// Hearing system: Sends wandering agents that hear a noise to search where it came from. Level
+/// geometry between an agent and the noise muffles it
+pub fn s_hear_noises(
+    mut noises: MessageReader<NoiseEvent>,
+    mut ai_query: Query<(&Transform, &mut PursueAI, Option<&Territory>), Without<Asleep>>,
+    level: Option<Res<Level>>,
+    difficulty: Res<AIDifficulty>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+
+    for noise in noises.read() {
+        let audible_range = difficulty.hearing_radius * noise.loudness;
+
+        for (transform, mut pursue_ai, territory) in ai_query.iter_mut() {
+            // Agents already chasing or searching have better leads than a noise, and agents don't
+            // go looking outside their territory
+            if !matches!(pursue_ai.state, PursueAIState::Wander)
+                || territory.is_some_and(|territory| !territory.contains(noise.origin))
+            {
+                continue;
+            }
+
+            let ai_pos = transform.translation.xy();
+            let distance = ai_pos.distance(noise.origin);
+            if distance > audible_range {
+                continue;
+            }
+
+            let occluded = level
+                .as_deref()
+                .is_some_and(|level| !level.line_of_sight(ai_pos, noise.origin));
+            if occluded && distance > audible_range * OCCLUDED_NOISE_ATTENUATION {
+                continue;
+            }
+
+            pursue_ai.search = Some(SearchBehavior::new(noise.origin, now, &difficulty));
+            pursue_ai.state = PursueAIState::Search;
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    camera::visibility::Visibility,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Has, With},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    math::Vec2,
+    prelude::Resource,
+    text::{TextColor, TextFont},
+    time::Time,
+    ui::{widget::Text, Node, PositionType, Val},
+};
+
+use super::{
+    flying_ai::{s_flying_ai_movement, FlyingAI},
+    platformer_ai::s_platformer_ai_movement,
+    pursue_ai::{s_pursue_ai_update, PursueAI, PursueAIState},
+};
+use crate::{debug::AI_METRICS_KEY, selection::SelectedEntity, GizmosVisible};
+
+// Command-line flag followed by the path of the telemetry CSV to write
+pub const AI_METRICS_FLAG: &str = "--ai-metrics";
+
+// Span of recent history the re-plan rate is measured over (units: seconds)
+const REPLAN_RATE_WINDOW: f32 = 5.0;
+// Time between telemetry rows for each agent (units: seconds)
+const TELEMETRY_INTERVAL: f32 = 1.0;
+
+// Overlay layout (units: pixels)
+const OVERLAY_FONT_SIZE: f32 = 14.0;
+const OVERLAY_MARGIN: f32 = 16.0;
+const OVERLAY_COLOR: Color = Color::srgb(0.6, 1.0, 0.8);
+
+// Pursue AI states in the order their times are kept
+const STATE_NAMES: [&str; 4] = ["wander", "pursue", "search", "attack"];
+
+const TELEMETRY_HEADER: &str = "time,entity,archetype,state,replans_per_second,plans,\
+                                average_path_length,cache_hit_rate,wander_time,pursue_time,\
+                                search_time,attack_time";
+
+/// AI metrics component: How often an agent plans, how long its paths are, how often its cached
+/// path is kept and where its time goes, so reports of agents acting oddly come with numbers
+#[derive(Component, Default)]
+pub struct AIMetrics {
+    /// Path searches run (including ones that found no path)
+    pub plans: u32,
+    /// Re-plan opportunities where the cached path was still good enough to keep
+    pub cache_hits: u32,
+    /// Total length (pixels) of the paths found
+    path_length_total: f32,
+    /// Searches that found a path
+    paths_found: u32,
+    /// Time (seconds) spent in each pursue AI state, in `STATE_NAMES` order
+    pub state_time: [f32; 4],
+    /// Elapsed time (seconds) and plan count at recent frames, for the re-plan rate
+    history: VecDeque<(f32, u32)>,
+}
+
+impl AIMetrics {
+    /// Counts a path search and the length (pixels) of the path it found, if any
+    pub fn record_plan(&mut self, path_length: Option<f32>) {
+        self.plans += 1;
+        if let Some(length) = path_length {
+            self.path_length_total += length;
+            self.paths_found += 1;
+        }
+    }
+
+    /// Counts a re-plan opportunity that kept the cached path
+    pub fn record_cache_hit(&mut self) {
+        self.cache_hits += 1;
+    }
+
+    /// Path searches per second over the last `REPLAN_RATE_WINDOW` seconds
+    pub fn replans_per_second(&self) -> f32 {
+        let (Some(&(start, start_plans)), Some(&(end, end_plans))) =
+            (self.history.front(), self.history.back())
+        else {
+            return 0.0;
+        };
+        if end <= start {
+            return 0.0;
+        }
+        (end_plans - start_plans) as f32 / (end - start)
+    }
+
+    /// Mean length (pixels) of the paths found (`None` before the first)
+    pub fn average_path_length(&self) -> Option<f32> {
+        (self.paths_found > 0).then(|| self.path_length_total / self.paths_found as f32)
+    }
+
+    /// Fraction of re-plan opportunities that kept the cached path (`None` before the first)
+    pub fn cache_hit_rate(&self) -> Option<f32> {
+        let lookups = self.plans + self.cache_hits;
+        (lookups > 0).then(|| self.cache_hits as f32 / lookups as f32)
+    }
+}
+
+/// Length (pixels) of a path through `points`
+pub fn path_length(points: impl IntoIterator<Item = Vec2>) -> f32 {
+    let mut points = points.into_iter();
+    let Some(mut previous) = points.next() else {
+        return 0.0;
+    };
+    points
+        .map(|point| {
+            let length = previous.distance(point);
+            previous = point;
+            length
+        })
+        .sum()
+}
+
+fn state_slot(state: &PursueAIState) -> usize {
+    match state {
+        PursueAIState::Wander => 0,
+        PursueAIState::Pursue => 1,
+        PursueAIState::Search => 2,
+        PursueAIState::Attack => 3,
+    }
+}
+
+/// AI metrics overlay resource: Whether the overlay is shown (while gizmos are visible)
+#[derive(Resource, Default)]
+pub struct AIMetricsOverlay {
+    pub visible: bool,
+}
+
+/// AI telemetry resource: The CSV metrics are sampled into, when asked for on the command line
+#[derive(Resource, Default)]
+struct AITelemetry {
+    writer: Option<BufWriter<File>>,
+    /// Elapsed time (seconds) of the next sample
+    next_sample: f32,
+}
+
+impl AITelemetry {
+    /// Opens the file given after `AI_METRICS_FLAG`, if any, and writes the CSV header
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let Some(path) = args
+            .iter()
+            .position(|arg| arg == AI_METRICS_FLAG)
+            .and_then(|index| args.get(index + 1))
+        else {
+            return Self::default();
+        };
+
+        let writer = File::create(path)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                writeln!(writer, "{TELEMETRY_HEADER}")?;
+                Ok(writer)
+            })
+            .inspect(|_| println!("Writing AI metrics to {path}"))
+            .inspect_err(|error| eprintln!("Failed to create {path}: {error}"))
+            .ok();
+
+        Self {
+            writer,
+            next_sample: 0.0,
+        }
+    }
+}
+
+/// Marker for the AI metrics overlay text
+#[derive(Component)]
+struct AIMetricsText;
+
+/// AI metrics plugin: Per-agent planning and state statistics, shown in an overlay and sampled
+/// into a telemetry CSV
+pub struct AIMetricsPlugin;
+
+impl Plugin for AIMetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AIMetricsOverlay>();
+        app.insert_resource(AITelemetry::from_args());
+
+        app.add_systems(Startup, s_spawn_ai_metrics_text);
+        app.add_systems(
+            Update,
+            (
+                s_track_ai_metrics
+                    .after(s_pursue_ai_update)
+                    .after(s_platformer_ai_movement)
+                    .after(s_flying_ai_movement),
+                s_write_ai_telemetry.after(s_track_ai_metrics),
+                s_update_ai_metrics_text.after(s_track_ai_metrics),
+            ),
+        );
+    }
+}
+
+/// Spawns the (hidden) AI metrics overlay in the bottom-right corner of the window
+fn s_spawn_ai_metrics_text(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: OVERLAY_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(OVERLAY_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(OVERLAY_MARGIN),
+            right: Val::Px(OVERLAY_MARGIN),
+            ..Default::default()
+        },
+        Visibility::Hidden,
+        AIMetricsText,
+    ));
+}
+
+/// AI metrics system: Adds the frame to each agent's time in its state and re-plan history
+fn s_track_ai_metrics(mut ai_query: Query<(&PursueAI, &mut AIMetrics)>, time: Res<Time>) {
+    let now = time.elapsed_secs();
+    let dt = time.delta_secs();
+
+    for (pursue_ai, mut metrics) in ai_query.iter_mut() {
+        metrics.state_time[state_slot(&pursue_ai.state)] += dt;
+
+        let plans = metrics.plans;
+        metrics.history.push_back((now, plans));
+        while metrics
+            .history
+            .front()
+            .is_some_and(|&(sampled_at, _)| now - sampled_at > REPLAN_RATE_WINDOW)
+        {
+            metrics.history.pop_front();
+        }
+    }
+}
+
+/// AI telemetry system: Appends a row per agent to the telemetry CSV every `TELEMETRY_INTERVAL`
+fn s_write_ai_telemetry(
+    mut telemetry: ResMut<AITelemetry>,
+    ai_query: Query<(Entity, &PursueAI, &AIMetrics, Has<FlyingAI>)>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    if now < telemetry.next_sample {
+        return;
+    }
+    telemetry.next_sample = now + TELEMETRY_INTERVAL;
+    let Some(writer) = telemetry.writer.as_mut() else {
+        return;
+    };
+
+    let mut agents: Vec<_> = ai_query.iter().collect();
+    agents.sort_unstable_by_key(|(entity, ..)| *entity);
+
+    let result = agents
+        .into_iter()
+        .try_for_each(|(entity, pursue_ai, metrics, flying)| {
+            let [wander, pursue, search, attack] = metrics.state_time;
+            writeln!(
+                writer,
+                "{now:.3},{entity},{},{},{:.3},{},{},{},{wander:.3},{pursue:.3},{search:.3},\
+                 {attack:.3}",
+                if flying { "flying" } else { "platformer" },
+                STATE_NAMES[state_slot(&pursue_ai.state)],
+                metrics.replans_per_second(),
+                metrics.plans,
+                metrics
+                    .average_path_length()
+                    .map_or(String::new(), |length| format!("{length:.1}")),
+                metrics
+                    .cache_hit_rate()
+                    .map_or(String::new(), |rate| format!("{rate:.3}")),
+            )
+        })
+        // Flushed every sample so runs that are killed still leave their numbers behind
+        .and_then(|()| writer.flush());
+
+    if let Err(error) = result {
+        eprintln!("Failed to write AI metrics, stopping: {error}");
+        telemetry.writer = None;
+    }
+}
+
+/// AI metrics overlay system: Toggles the overlay and lists every agent's metrics in it
+fn s_update_ai_metrics_text(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut overlay: ResMut<AIMetricsOverlay>,
+    selected: Res<SelectedEntity>,
+    ai_query: Query<(Entity, &PursueAI, &AIMetrics)>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<AIMetricsText>>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(AI_METRICS_KEY) {
+        overlay.visible = !overlay.visible;
+    }
+
+    let shown = gizmos_visible.visible && overlay.visible;
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        *visibility = if shown {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+        if shown {
+            text.0 = ai_metrics_report(&ai_query, selected.0);
+        }
+    }
+}
+
+/// Overlay text: one line per agent (the selected one marked), in spawn order
+fn ai_metrics_report(
+    ai_query: &Query<(Entity, &PursueAI, &AIMetrics)>,
+    selected: Option<Entity>,
+) -> String {
+    let mut agents: Vec<_> = ai_query.iter().collect();
+    agents.sort_unstable_by_key(|(entity, ..)| *entity);
+
+    let mut lines = vec![format!(
+        "AI metrics ({AI_METRICS_KEY:?} to hide): re-plans/s, avg path, cache hits, time in \
+         wander/pursue/search/attack"
+    )];
+
+    for (entity, pursue_ai, metrics) in agents {
+        let marker = if selected == Some(entity) { ">" } else { " " };
+        let average_path = metrics
+            .average_path_length()
+            .map_or("-".to_string(), |length| format!("{length:.0} px"));
+        let cache_hits = metrics
+            .cache_hit_rate()
+            .map_or("-".to_string(), |rate| format!("{:.0}%", rate * 100.0));
+        let [wander, pursue, search, attack] = metrics.state_time;
+
+        lines.push(format!(
+            "{marker} {entity} {}: {:.1}/s, {average_path}, {cache_hits}, \
+             {wander:.0}/{pursue:.0}/{search:.0}/{attack:.0} s",
+            STATE_NAMES[state_slot(&pursue_ai.state)],
+            metrics.replans_per_second(),
+        ));
+    }
+
+    lines.join("\n")
+}
@@ -1,5 +1,21 @@
 pub mod a_star;
+pub mod activity;
+pub mod alert;
+pub mod avoidance;
+pub mod brain;
+pub mod command;
+pub mod difficulty;
+pub mod flying_ai;
+pub mod hearing;
+pub mod metrics;
+pub mod path_requests;
 pub mod pathfinding;
+pub mod patrol;
 pub mod platformer_ai;
+pub mod profile;
 pub mod pursue_ai;
+pub mod spawner;
+pub mod territory;
+pub mod tick;
+pub mod vision;
 
@@ -0,0 +1,159 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        change_detection::DetectChanges,
+        entity::Entity,
+        message::{Message, MessageReader, MessageWriter},
+        schedule::IntoScheduleConfigs,
+        system::{Res, ResMut},
+    },
+    math::Vec2,
+    prelude::Resource,
+    tasks::{futures::check_ready, AsyncComputeTaskPool, Task},
+};
+
+use super::{
+    a_star::{find_path, find_path_avoiding_hazards, PathNode},
+    pathfinding::PathfindingGraph,
+    platformer_ai::s_platformer_ai_movement,
+};
+
+/// Whether agents plan their paths on background tasks (off in headless runs, which must plan
+/// the same paths on the same frames however fast the machine is)
+#[derive(Resource)]
+pub struct AsyncPathfinding {
+    pub enabled: bool,
+}
+
+impl Default for AsyncPathfinding {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl AsyncPathfinding {
+    /// Off: paths are found on the spot, inside the AI update
+    pub fn disabled() -> Self {
+        Self { enabled: false }
+    }
+}
+
+/// An agent asks for a new path; it keeps following its old one until the answer arrives
+#[derive(Message, Clone, Copy, Debug)]
+pub struct PathRequest {
+    pub agent: Entity,
+    pub start: Vec2,
+    pub goal: Vec2,
+    /// Steer clear of hazards (see `find_path_avoiding_hazards`)
+    pub avoid_hazards: bool,
+}
+
+/// The path found for a `PathRequest` (`None` if the goal can't be reached)
+#[derive(Message, Clone)]
+pub struct PathResponse {
+    pub agent: Entity,
+    /// Goal the path was planned to
+    pub goal: Vec2,
+    pub path: Option<Vec<PathNode>>,
+}
+
+/// A path being planned on the async compute task pool
+struct PathTask {
+    goal: Vec2,
+    task: Task<Option<Vec<PathNode>>>,
+}
+
+/// Path searches in flight, at most one per agent
+#[derive(Resource, Default)]
+pub struct PathTasks {
+    /// Copy of the pathfinding graph the searches read, replaced whenever the graph changes
+    graph: Arc<PathfindingGraph>,
+    tasks: HashMap<Entity, PathTask>,
+}
+
+impl PathTasks {
+    /// Whether a search for `agent` is still running
+    pub fn is_pending(&self, agent: Entity) -> bool {
+        self.tasks.contains_key(&agent)
+    }
+}
+
+pub struct PathRequestPlugin;
+
+impl Plugin for PathRequestPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AsyncPathfinding>();
+        app.init_resource::<PathTasks>();
+        app.add_message::<PathRequest>();
+        app.add_message::<PathResponse>();
+
+        app.add_systems(
+            Update,
+            (
+                s_finish_path_requests.before(s_platformer_ai_movement),
+                s_start_path_requests.after(s_platformer_ai_movement),
+            ),
+        );
+    }
+}
+
+/// Path from `start` to `goal`, kept clear of hazards if asked to
+pub fn plan_path(
+    pathfinding: &PathfindingGraph,
+    start: Vec2,
+    goal: Vec2,
+    avoid_hazards: bool,
+) -> Option<Vec<PathNode>> {
+    if avoid_hazards {
+        find_path_avoiding_hazards(pathfinding, start, goal)
+    } else {
+        find_path(pathfinding, start, goal)
+    }
+}
+
+/// Path request system: Starts a background search for each path requested this frame (a newer
+/// request from the same agent replaces, and cancels, its older one)
+pub fn s_start_path_requests(
+    mut requests: MessageReader<PathRequest>,
+    pathfinding: Res<PathfindingGraph>,
+    mut path_tasks: ResMut<PathTasks>,
+) {
+    // Searches on the old graph would hand out paths through closed doors or a previous level
+    if pathfinding.is_changed() {
+        path_tasks.graph = Arc::new(pathfinding.clone());
+        path_tasks.tasks.clear();
+    }
+
+    let pool = AsyncComputeTaskPool::get();
+    for request in requests.read() {
+        let graph = Arc::clone(&path_tasks.graph);
+        let PathRequest {
+            start,
+            goal,
+            avoid_hazards,
+            ..
+        } = *request;
+        let task = pool.spawn(async move { plan_path(&graph, start, goal, avoid_hazards) });
+        path_tasks.tasks.insert(request.agent, PathTask { goal, task });
+    }
+}
+
+/// Path response system: Hands the paths of finished searches to their agents
+pub fn s_finish_path_requests(
+    mut path_tasks: ResMut<PathTasks>,
+    mut responses: MessageWriter<PathResponse>,
+) {
+    path_tasks.tasks.retain(|&agent, path_task| {
+        let Some(path) = check_ready(&mut path_task.task) else {
+            return true;
+        };
+        responses.write(PathResponse {
+            agent,
+            goal: path_task.goal,
+            path,
+        });
+        false
+    });
+}
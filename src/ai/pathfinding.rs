@@ -1,70 +1,300 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Range,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use bevy::{
     app::{App, Plugin},
-    ecs::system::ResMut,
+    color::{Alpha, Color},
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        message::Message,
+        query::With,
+        system::{Commands, Query, Res, ResMut},
+    },
+    camera::visibility::Visibility,
+    gizmos::gizmos::Gizmos,
+    input::{keyboard::KeyCode, ButtonInput},
     math::Vec2,
     prelude::Resource,
+    sprite::Text2d,
+    state::state_scoped::DespawnOnExit,
+    tasks::{ComputeTaskPool, ParallelSlice, TaskPool},
+    text::{TextColor, TextFont},
+    transform::components::Transform,
+    ui::{widget::Text, JustifyContent, Node, PositionType, Val},
+};
+
+use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    debug::{DebugLayer, DebugLayers, PATHFINDING_COST_LABELS_KEY},
+    game_state::GameState,
+    level::{Aabb, Level},
+    movement_config::MovementConfig,
+    utils::{closest_point_on_segment, line_intersect},
+    GizmosVisible, GROUND_NORMAL_Y_THRESHOLD,
 };
 
-use crate::{level::Level, utils::line_intersect, GRAVITY_STRENGTH};
+use super::{
+    a_star::{connection_cost, PathNode},
+    pursue_ai::PURSUE_AI_AGENT_RADIUS,
+};
 
-use super::{platformer_ai::PLATFORMER_AI_JUMP_FORCE, pursue_ai::PURSUE_AI_AGENT_RADIUS};
+// Debug overlay warning shown when the graph can't be used (units: pixels)
+const WARNING_FONT_SIZE: f32 = 16.0;
+const WARNING_MARGIN: f32 = 16.0;
+const WARNING_COLOR: Color = Color::srgb(1.0, 0.45, 0.3);
 
 // Pathfinding constants
 const PATHFINDING_NODE_SPACING: f32 = 20.0;
 const PATHFINDING_NODE_DIRECTION_THRESHOLD: f32 = -0.1;
 const JUMPABILITY_CHECK_TIMESTEP_DIVISIONS: i32 = 10;
+// Flight times tried for each jump, as multiples of the lowest energy flight time (shorter ones fly
+// flatter to pass under ceilings, longer ones higher to clear lips)
+const JUMP_ARC_TIME_SCALES: [f32; 5] = [1.0, 0.85, 0.7, 1.15, 1.3];
 const SPATIAL_CELL_SIZE: f32 = 50.0; // ~2.5x node spacing
+// Nodes closer together than this are the same node (units: pixels squared)
+const DUPLICATE_NODE_DISTANCE_SQ: f32 = 1.0;
+// Allow small horizontal offset for drops (1.5x node spacing)
+const MAX_HORIZONTAL_DROP_OFFSET: f32 = PATHFINDING_NODE_SPACING * 1.5;
+// Geometry this close to a changed region counts as changed too (units: pixels)
+const CHANGED_REGION_MARGIN: f32 = 1.0;
+const DEBUG_NODE_GIZMO_RADIUS: f32 = 2.0;
+// Debug colors, one per traversal type
+pub const WALK_EDGE_COLOR: Color = Color::srgb(0.3, 1.0, 0.4);
+pub const JUMP_EDGE_COLOR: Color = Color::srgb(1.0, 0.6, 0.1);
+pub const DROP_EDGE_COLOR: Color = Color::srgb(0.3, 0.7, 1.0);
+// Opacity of the graph's edges, kept faint so agents' paths stand out on top of them
+const DEBUG_EDGE_ALPHA: f32 = 0.3;
+// Edge cost labels
+const COST_LABEL_FONT_SIZE: f32 = 8.0;
+const COST_LABEL_Z: f32 = 10.0;
+// Fraction of the way along a one-way edge its label sits at, so the labels of a jump and the
+// jump back don't overlap
+const COST_LABEL_EDGE_FRACTION: f32 = 0.35;
+// Cosine of the sharpest bend (~25°) between two lines that is still smooth ground rather than a
+// corner (the segments of quarter circle tiles meet at 22.5° at most)
+const SMOOTH_JOINT_MIN_DOT: f32 = 0.9;
+// Cosine of the largest difference between surface normals that string-pulling still treats as
+// the same flat run of ground
+const STRAIGHT_RUN_MIN_DOT: f32 = 0.999;
+// Gap (pixels) between an agent and a hazard's reach within which hazard-averse paths pay extra
+const HAZARD_AVOIDANCE_MARGIN: f32 = 32.0;
+// Extra path cost (pixels) for a node right at a hazard's reach, fading out across the margin
+const HAZARD_PATH_PENALTY: f32 = 200.0;
+// Graph build work is split into this many chunks per compute thread, so threads that finish
+// early can pick up more
+const BUILD_CHUNKS_PER_THREAD: usize = 4;
+// Graph build stages over at least this many items print their progress (smaller levels build
+// too quickly for it to matter)
+const BUILD_PROGRESS_MIN_ITEMS: usize = 1000;
+// Percentage between progress messages
+const BUILD_PROGRESS_STEP_PERCENT: usize = 10;
+
+// Parallel graph build stages, with the share of the whole build each one stands for (jump links
+// take by far the longest on big levels)
+const NODES_STAGE: BuildStage = BuildStage {
+    name: "nodes",
+    progress: 0.0..0.1,
+};
+const JUMPS_STAGE: BuildStage = BuildStage {
+    name: "jump links",
+    progress: 0.1..0.8,
+};
+const DROPS_STAGE: BuildStage = BuildStage {
+    name: "drop links",
+    progress: 0.8..1.0,
+};
 
 pub struct PathfindingPlugin;
 
 impl Plugin for PathfindingPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(PathfindingGraph {
-            nodes: Vec::new(),
-            spatial_grid: HashMap::new(),
-            grid_bounds: (Vec2::ZERO, Vec2::ZERO),
-        });
+        app.init_resource::<PathfindingGraph>();
+        app.add_message::<PathfindingGraphChanged>();
     }
 }
 
-pub fn init_pathfinding_graph(level: &Level, mut pathfinding: ResMut<PathfindingGraph>) {
-    place_nodes(&mut pathfinding, level);
+/// Sent after the pathfinding graph changed under the agents, so paths planned on it before can be
+/// checked (node ids may have changed too, see `PathfindingGraph::node_at`)
+#[derive(Message, Clone, Copy)]
+pub struct PathfindingGraphChanged {
+    /// Area whose nodes and links changed (`None` for changes that can affect any path, like a
+    /// door opening or closing)
+    pub region: Option<Aabb>,
+}
+
+/// Builds the level's pathfinding graph, returning what it came out as (a level can end up with a
+/// graph agents can't use, see `GraphDiagnostics::problem`)
+pub fn init_pathfinding_graph(
+    level: &Level,
+    pathfinding: &mut PathfindingGraph,
+    movement: &MovementConfig,
+) -> GraphDiagnostics {
+    init_pathfinding_graph_with_progress(
+        level,
+        pathfinding,
+        movement,
+        &GraphBuildProgress::default(),
+    )
+}
+
+/// Builds the pathfinding graph like `init_pathfinding_graph`, reporting how far along it is to
+/// `progress` (for builds running in the background)
+pub fn init_pathfinding_graph_with_progress(
+    level: &Level,
+    pathfinding: &mut PathfindingGraph,
+    movement: &MovementConfig,
+    progress: &GraphBuildProgress,
+) -> GraphDiagnostics {
+    // Start from scratch when the level is rebuilt
+    pathfinding.nodes.clear();
+
+    place_nodes(pathfinding, level, progress);
+
+    make_walkable_connections_2_way(pathfinding);
+
+    remove_duplicate_nodes(pathfinding);
+
+    make_node_ids_indices(pathfinding);
+
+    // Jump arcs start and end where agents stand, a radius out along the normals
+    calculate_normals(pathfinding, level);
+
+    make_jumpable_connections(pathfinding, level, PURSUE_AI_AGENT_RADIUS, movement, progress);
 
-    make_walkable_connections_2_way(&mut pathfinding);
+    make_droppable_connections(pathfinding, level, PURSUE_AI_AGENT_RADIUS, movement, progress);
 
-    remove_duplicate_nodes(&mut pathfinding);
+    setup_corners(pathfinding, level);
 
-    make_node_ids_indices(&mut pathfinding);
+    build_spatial_index(pathfinding);
 
-    make_jumpable_connections(&mut pathfinding, level, PURSUE_AI_AGENT_RADIUS);
+    mark_door_connections(pathfinding, level);
 
-    make_droppable_connections(&mut pathfinding, level, PURSUE_AI_AGENT_RADIUS);
+    mark_hazard_nodes(pathfinding, level);
 
-    calculate_normals(&mut pathfinding, level);
+    progress.advance_to(1.0);
 
-    setup_corners(&mut pathfinding);
+    GraphDiagnostics::of(pathfinding)
+}
+
+/// What a pathfinding graph is made of, to tell whether agents can find their way on it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GraphDiagnostics {
+    pub nodes: usize,
+    /// Walk, jump and drop links between nodes
+    pub connections: usize,
+    /// Nodes clear of lethal hazards with a link out of them
+    pub usable_nodes: usize,
+}
 
-    build_spatial_index(&mut pathfinding);
+/// Why agents can't plan paths on a pathfinding graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphProblem {
+    /// No surface in the level has room for a node
+    Empty,
+    /// No node can be walked, jumped or dropped to from another
+    Unconnected,
+    /// Every linked node is next to something lethal
+    AllLethal,
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for GraphProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphProblem::Empty => write!(f, "no nodes"),
+            GraphProblem::Unconnected => write!(f, "no links between nodes"),
+            GraphProblem::AllLethal => write!(f, "every linked node is next to a lethal hazard"),
+        }
+    }
+}
+
+impl GraphDiagnostics {
+    pub fn of(pathfinding: &PathfindingGraph) -> Self {
+        Self {
+            nodes: pathfinding.nodes.len(),
+            connections: pathfinding.nodes.iter().map(node_connection_count).sum(),
+            usable_nodes: pathfinding.nodes.iter().filter(|node| is_usable_node(node)).count(),
+        }
+    }
+
+    /// Why agents can't use the graph, if they can't (they steer straight at their goals instead)
+    pub fn problem(&self) -> Option<GraphProblem> {
+        if self.nodes == 0 {
+            Some(GraphProblem::Empty)
+        } else if self.connections == 0 {
+            Some(GraphProblem::Unconnected)
+        } else if self.usable_nodes == 0 {
+            Some(GraphProblem::AllLethal)
+        } else {
+            None
+        }
+    }
+}
+
+fn node_connection_count(node: &PathfindingGraphNode) -> usize {
+    node.walkable_connections.len()
+        + node.jumpable_connections.len()
+        + node.droppable_connections.len()
+}
+
+fn is_usable_node(node: &PathfindingGraphNode) -> bool {
+    !node.lethal && node_connection_count(node) > 0
+}
+
+/// How far along a pathfinding graph build is (0-1), readable from other threads while it runs
+#[derive(Clone, Default)]
+pub struct GraphBuildProgress(Arc<AtomicU32>);
+
+impl GraphBuildProgress {
+    pub fn fraction(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Moves the progress forward (never back, as chunks finish out of order)
+    fn advance_to(&self, fraction: f32) {
+        // Non-negative floats order the same as their bits
+        self.0.fetch_max(fraction.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// A parallel stage of the graph build: its name in progress messages and the part of the whole
+/// build it covers
+struct BuildStage {
+    name: &'static str,
+    progress: Range<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PathfindingGraphConnectionType {
     Walkable,
     Jumpable,
     Droppable,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathfindingGraphConnection {
     pub node_id: usize,
     pub dist: f32,
     pub connection_type: PathfindingGraphConnectionType,
     pub effort: f32,
+    /// Door this connection passes through (only usable while that door is open)
+    pub door: Option<usize>,
+    /// Velocity (pixels/second) to leave the start node with to land on the other node, along
+    /// the arc checked against the level (jumps only)
+    pub launch_velocity: Option<Vec2>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathfindingGraphNode {
     pub id: usize,
     pub position: Vec2,
@@ -76,16 +306,27 @@ pub struct PathfindingGraphNode {
     pub normal: Vec2,
     pub is_corner: bool,
     pub is_external_corner: Option<bool>,
+    /// Extra cost (pixels) for hazard-averse paths that pass near a hazard
+    pub hazard_cost: f32,
+    /// Whether an agent standing here can be killed by a hazard (hazard-averse paths never use it)
+    pub lethal: bool,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
 pub struct PathfindingGraph {
     pub nodes: Vec<PathfindingGraphNode>,
     pub spatial_grid: HashMap<(i32, i32), Vec<usize>>,
     pub grid_bounds: (Vec2, Vec2), // (min, max) for bounds checking
+    /// Doors that are currently closed (connections through them are skipped)
+    pub closed_doors: HashSet<usize>,
 }
 
 impl PathfindingGraph {
+    /// Whether agents can plan paths on the graph (see `GraphDiagnostics::problem`)
+    pub fn is_usable(&self) -> bool {
+        self.nodes.iter().any(is_usable_node)
+    }
+
     /// Convert a world position to a grid cell coordinate
     pub fn position_to_cell(&self, pos: Vec2) -> (i32, i32) {
         let x = ((pos.x - self.grid_bounds.0.x) / SPATIAL_CELL_SIZE).floor() as i32;
@@ -93,6 +334,250 @@ impl PathfindingGraph {
         (x, y)
     }
 
+    /// Whether a connection can currently be used (it doesn't pass through a closed door)
+    pub fn is_connection_open(&self, connection: &PathfindingGraphConnection) -> bool {
+        connection
+            .door
+            .is_none_or(|door| !self.closed_doors.contains(&door))
+    }
+
+    /// Picks up to `count` distinct ground nodes at least `min_distance` from `avoid`, returning
+    /// positions a body of the given radius can stand at
+    pub fn random_ground_positions(
+        &self,
+        rng: &mut impl Rng,
+        count: usize,
+        avoid: Vec2,
+        min_distance: f32,
+        radius: f32,
+    ) -> Vec<Vec2> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                node.normal.y > GROUND_NORMAL_Y_THRESHOLD
+                    && node.position.distance(avoid) >= min_distance
+            })
+            .choose_multiple(rng, count)
+            .into_iter()
+            // Just above the surface
+            .map(|node| node.position + node.normal * radius)
+            .collect()
+    }
+
+    /// Picks a ground node within `max_distance` of `center` and at least `min_distance` from
+    /// `avoid`, returning a position a body of the given radius can stand at
+    pub fn random_ground_position_near(
+        &self,
+        rng: &mut impl Rng,
+        center: Vec2,
+        max_distance: f32,
+        avoid: Vec2,
+        min_distance: f32,
+        radius: f32,
+    ) -> Option<Vec2> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                node.normal.y > GROUND_NORMAL_Y_THRESHOLD
+                    && node.position.distance(center) <= max_distance
+                    && node.position.distance(avoid) >= min_distance
+            })
+            .choose(rng)
+            .map(|node| node.position + node.normal * radius)
+    }
+
+    /// Whether the node closest to `pos` is near a hazard
+    pub fn is_near_hazard(&self, pos: Vec2) -> bool {
+        self.get_nearby_node_indices(pos)
+            .into_iter()
+            .map(|index| &self.nodes[index])
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(pos)
+                    .total_cmp(&b.position.distance_squared(pos))
+            })
+            .is_some_and(|node| node.hazard_cost > 0.0)
+    }
+
+    /// Index of the node at `position`, if there is one
+    pub fn node_at(&self, position: Vec2) -> Option<usize> {
+        self.get_nearby_node_indices(position)
+            .into_iter()
+            .find(|&index| {
+                self.nodes[index].position.distance_squared(position) < DUPLICATE_NODE_DISTANCE_SQ
+            })
+    }
+
+    /// Rebuilds the part of the graph around `region` after the level geometry there changed,
+    /// leaving the jump and drop links elsewhere (the slow part of a full build) as they are.
+    ///
+    /// Nodes on polygons touching the region are placed again and joined to the nodes they meet,
+    /// and nodes that could jump or drop into or through the region get their links worked out
+    /// again. Node ids change, so paths planned before need their nodes looked up again by
+    /// position (see `node_at`). Jumps are planned with the AI jump velocity and gravity in
+    /// `movement`.
+    pub fn update_region(&mut self, level: &Level, region: Aabb, movement: &MovementConfig) {
+        let changed_area = region.expand(CHANGED_REGION_MARGIN);
+        let polygon_indices = graph_polygon_indices(level);
+        let changed_polygons: Vec<usize> = polygon_indices
+            .iter()
+            .copied()
+            .filter(|&polygon_index| {
+                level.polygons[polygon_index]
+                    .points
+                    .windows(2)
+                    .any(|line| changed_area.intersects_segment(line[0], line[1]))
+            })
+            .collect();
+        let unchanged_polygons: HashSet<usize> = polygon_indices
+            .into_iter()
+            .filter(|polygon_index| !changed_polygons.contains(polygon_index))
+            .collect();
+
+        // Nodes away from the region stay, pointed at their polygon (tile edits renumber them)
+        let old_nodes = std::mem::take(&mut self.nodes);
+        let mut old_to_new: Vec<Option<usize>> = vec![None; old_nodes.len()];
+        let mut dropped: Vec<(usize, Vec2)> = Vec::new();
+        for (old_id, mut node) in old_nodes.into_iter().enumerate() {
+            let surface = (!changed_area.contains(node.position))
+                .then(|| walkable_surface_at(level, &unchanged_polygons, node.position))
+                .flatten();
+            let Some((polygon_index, line_indicies)) = surface else {
+                dropped.push((old_id, node.position));
+                continue;
+            };
+
+            node.id = self.nodes.len();
+            node.polygon_index = polygon_index;
+            node.line_indicies = line_indicies;
+            old_to_new[old_id] = Some(node.id);
+            self.nodes.push(node);
+        }
+        let kept_count = self.nodes.len();
+
+        // Place the changed polygons' nodes like a full build does, on their own
+        let mut placed = PathfindingGraph::default();
+        for &polygon_index in &changed_polygons {
+            append_polygon_nodes(&mut placed, polygon_nodes(level, polygon_index));
+        }
+        make_walkable_connections_2_way(&mut placed);
+        remove_duplicate_nodes(&mut placed);
+        make_node_ids_indices(&mut placed);
+
+        // Kept nodes where the changed polygons meet the rest of the level are shared with them
+        let seam_areas: Vec<Aabb> = changed_polygons
+            .iter()
+            .map(|&polygon_index| level.polygons[polygon_index].aabb.expand(CHANGED_REGION_MARGIN))
+            .collect();
+        let seam_nodes: Vec<usize> = (0..kept_count)
+            .filter(|&index| {
+                seam_areas
+                    .iter()
+                    .any(|area| area.contains(self.nodes[index].position))
+            })
+            .collect();
+
+        let mut relink = vec![false; kept_count];
+        let mut placed_ids = Vec::with_capacity(placed.nodes.len());
+        for node in &placed.nodes {
+            let shared = seam_nodes.iter().copied().find(|&index| {
+                self.nodes[index].position.distance_squared(node.position)
+                    < DUPLICATE_NODE_DISTANCE_SQ
+            });
+
+            if let Some(index) = shared {
+                let kept_node = &mut self.nodes[index];
+                if kept_node.polygon_index == node.polygon_index {
+                    kept_node.line_indicies.extend(&node.line_indicies);
+                }
+                relink[index] = true;
+                placed_ids.push(index);
+            } else {
+                placed_ids.push(self.nodes.len());
+                self.nodes.push(PathfindingGraphNode {
+                    id: self.nodes.len(),
+                    walkable_connections: Vec::new(),
+                    ..node.clone()
+                });
+            }
+        }
+
+        // Links to nodes that were placed again follow them to their new ids
+        for (old_id, position) in dropped {
+            old_to_new[old_id] = placed_ids
+                .iter()
+                .copied()
+                .find(|&index| {
+                    self.nodes[index].position.distance_squared(position)
+                        < DUPLICATE_NODE_DISTANCE_SQ
+                });
+        }
+        for node in &mut self.nodes[..kept_count] {
+            for connections in [
+                &mut node.walkable_connections,
+                &mut node.jumpable_connections,
+                &mut node.droppable_connections,
+            ] {
+                connections.retain_mut(|connection| {
+                    old_to_new[connection.node_id]
+                        .inspect(|&node_id| connection.node_id = node_id)
+                        .is_some()
+                });
+            }
+        }
+
+        for (placed_index, node) in placed.nodes.iter().enumerate() {
+            let index = placed_ids[placed_index];
+            for connection in &node.walkable_connections {
+                let node_id = placed_ids[connection.node_id];
+                let walkable_connections = &mut self.nodes[index].walkable_connections;
+                if node_id != index && walkable_connections.iter().all(|c| c.node_id != node_id) {
+                    walkable_connections.push(PathfindingGraphConnection {
+                        node_id,
+                        ..connection.clone()
+                    });
+                }
+            }
+        }
+
+        calculate_normals(self, level);
+
+        // Jumps and drops that could land in or pass through the region are worked out again
+        let reach_area = changed_area.expand(PURSUE_AI_AGENT_RADIUS);
+        let relinked: Vec<usize> = (0..self.nodes.len())
+            .filter(|&index| {
+                index >= kept_count
+                    || relink[index]
+                    || can_reach(&reach_area, self.nodes[index].position, movement)
+            })
+            .collect();
+        let progress = GraphBuildProgress::default();
+        let graph = &*self;
+        let jumps = map_in_parallel(&relinked, &JUMPS_STAGE, &progress, |&index| {
+            jumpable_connections_from(graph, level, PURSUE_AI_AGENT_RADIUS, movement, index)
+        });
+        let drops = map_in_parallel(&relinked, &DROPS_STAGE, &progress, |&index| {
+            droppable_connections_from(graph, level, PURSUE_AI_AGENT_RADIUS, movement, index)
+        });
+        for ((index, jumpable_connections), droppable_connections) in
+            relinked.into_iter().zip(jumps).zip(drops)
+        {
+            self.nodes[index].jumpable_connections = jumpable_connections;
+            self.nodes[index].droppable_connections = droppable_connections;
+        }
+
+        setup_corners(self, level);
+
+        build_spatial_index(self);
+
+        // Doors keep whatever state they are in
+        let closed_doors = std::mem::take(&mut self.closed_doors);
+        mark_door_connections(self, level);
+        self.closed_doors = closed_doors;
+
+        mark_hazard_nodes(self, level);
+    }
+
     /// Get node indices in cells near the given position (3x3 grid search)
     pub fn get_nearby_node_indices(&self, pos: Vec2) -> Vec<usize> {
         let (cx, cy) = self.position_to_cell(pos);
@@ -110,85 +595,231 @@ impl PathfindingGraph {
     }
 }
 
-pub fn place_nodes(pathfinding: &mut PathfindingGraph, level: &Level) {
+pub fn place_nodes(
+    pathfinding: &mut PathfindingGraph,
+    level: &Level,
+    progress: &GraphBuildProgress,
+) {
+    let polygon_indices = graph_polygon_indices(level);
+
+    // Place each polygon's nodes in parallel, with ids and connections local to the polygon
+    let polygon_nodes =
+        map_in_parallel(&polygon_indices, &NODES_STAGE, progress, |&polygon_index| {
+            polygon_nodes(level, polygon_index)
+        });
+
+    for nodes in polygon_nodes {
+        append_polygon_nodes(pathfinding, nodes);
+    }
+}
+
+/// Indices of the polygons that get nodes: all of them but the outer container, which is walked
+/// from the inside
+fn graph_polygon_indices(level: &Level) -> Vec<usize> {
     let mut outer_container_seen = false;
 
-    // Place nodes
-    for polygon_index in 0..level.polygons.len() {
-        let polygon = &level.polygons[polygon_index];
-        if polygon.is_container {
-            outer_container_seen = !outer_container_seen;
-        }
+    (0..level.polygons.len())
+        .filter(|&polygon_index| {
+            let polygon = &level.polygons[polygon_index];
+            if polygon.is_container {
+                outer_container_seen = !outer_container_seen;
+            }
 
-        if outer_container_seen && polygon.is_container {
-            continue;
+            !(outer_container_seen && polygon.is_container)
+        })
+        .collect()
+}
+
+/// Adds a polygon's nodes (ids starting at 0) to the end of the graph
+fn append_polygon_nodes(pathfinding: &mut PathfindingGraph, mut nodes: Vec<PathfindingGraphNode>) {
+    let offset = pathfinding.nodes.len();
+    for node in &mut nodes {
+        node.id += offset;
+        for connection in &mut node.walkable_connections {
+            connection.node_id += offset;
         }
+    }
+    pathfinding.nodes.append(&mut nodes);
+}
 
-        for line_index in 1..polygon.points.len() {
-            let start = polygon.points[line_index - 1];
-            let end = polygon.points[line_index];
+/// Whether agents walk along the line from `start` to `end` (the ground side faces up or sideways)
+fn is_walkable_line(start: Vec2, end: Vec2) -> bool {
+    (end - start).normalize().dot(Vec2::X) > PATHFINDING_NODE_DIRECTION_THRESHOLD
+}
 
-            let mut start_to_end = end - start;
+/// Polygon (out of `polygon_indices`) and walkable lines of it that `position` lies on
+fn walkable_surface_at(
+    level: &Level,
+    polygon_indices: &HashSet<usize>,
+    position: Vec2,
+) -> Option<(usize, Vec<usize>)> {
+    let point_aabb = Aabb::from_point_radius(position, CHANGED_REGION_MARGIN);
+    level
+        .query_aabb_indices(&point_aabb)
+        .into_iter()
+        .filter(|polygon_index| polygon_indices.contains(polygon_index))
+        .find_map(|polygon_index| {
+            let points = &level.polygons[polygon_index].points;
+            let line_indicies: Vec<usize> = (1..points.len())
+                .filter(|&line_index| {
+                    let (start, end) = (points[line_index - 1], points[line_index]);
+                    is_walkable_line(start, end)
+                        && closest_point_on_segment(start, end, position)
+                            .distance_squared(position)
+                            < DUPLICATE_NODE_DISTANCE_SQ
+                })
+                .map(|line_index| line_index - 1)
+                .collect();
 
-            let length = start_to_end.length();
+            (!line_indicies.is_empty()).then_some((polygon_index, line_indicies))
+        })
+}
 
-            let nodes_on_line_count = (length.abs() / PATHFINDING_NODE_SPACING).ceil();
-            let dist_between_nodes_on_line = length / nodes_on_line_count;
+/// Whether an agent jumping or dropping from `position` could land in or pass through `area`
+fn can_reach(area: &Aabb, position: Vec2, movement: &MovementConfig) -> bool {
+    let dx = (area.min.x - position.x)
+        .max(position.x - area.max.x)
+        .max(0.0);
+    let dy = area.min.y - position.y;
+
+    // Jump arcs stay inside the parabola of safety: |offset| + rise <= the furthest an agent can
+    // jump on level ground
+    let jump_reach = movement.ai_jump_velocity * movement.ai_jump_velocity / movement.gravity;
+    let in_jump_reach = Vec2::new(dx, dy).length() + dy <= jump_reach;
+    // Drops fall (almost) straight down
+    let in_drop_reach = dx <= MAX_HORIZONTAL_DROP_OFFSET && dy <= 0.0;
+
+    in_jump_reach || in_drop_reach
+}
 
-            start_to_end = start_to_end.normalize();
+/// Nodes along the ground-facing lines of a polygon (ids start at 0)
+fn polygon_nodes(level: &Level, polygon_index: usize) -> Vec<PathfindingGraphNode> {
+    let polygon = &level.polygons[polygon_index];
+    let mut nodes: Vec<PathfindingGraphNode> = Vec::new();
 
-            if start_to_end.dot(Vec2::X) > PATHFINDING_NODE_DIRECTION_THRESHOLD {
-                for j in 0..(nodes_on_line_count as i32) {
-                    let node_pos = start + start_to_end * (j as f32 * dist_between_nodes_on_line);
+    for line_index in 1..polygon.points.len() {
+        let start = polygon.points[line_index - 1];
+        let end = polygon.points[line_index];
 
-                    let mut new_node = PathfindingGraphNode {
-                        id: pathfinding.nodes.len(),
-                        position: node_pos,
-                        polygon_index,
-                        line_indicies: vec![(line_index - 1)],
-                        walkable_connections: Vec::new(),
-                        jumpable_connections: Vec::new(),
-                        droppable_connections: Vec::new(),
-                        normal: Vec2::ZERO,
-                        is_corner: false,
-                        is_external_corner: None,
-                    };
+        let mut start_to_end = end - start;
 
-                    if j > 0 {
-                        new_node
-                            .walkable_connections
-                            .push(PathfindingGraphConnection {
-                                node_id: pathfinding.nodes.len() - 1,
-                                dist: dist_between_nodes_on_line,
-                                connection_type: PathfindingGraphConnectionType::Walkable,
-                                effort: 0.0,
-                            });
-                    }
+        let length = start_to_end.length();
 
-                    pathfinding.nodes.push(new_node);
-                }
-                let new_node = PathfindingGraphNode {
-                    id: pathfinding.nodes.len(),
-                    position: end,
+        let nodes_on_line_count = (length.abs() / PATHFINDING_NODE_SPACING).ceil();
+        let dist_between_nodes_on_line = length / nodes_on_line_count;
+
+        start_to_end = start_to_end.normalize();
+
+        if is_walkable_line(start, end) {
+            for j in 0..(nodes_on_line_count as i32) {
+                let node_pos = start + start_to_end * (j as f32 * dist_between_nodes_on_line);
+
+                let mut new_node = PathfindingGraphNode {
+                    id: nodes.len(),
+                    position: node_pos,
                     polygon_index,
                     line_indicies: vec![(line_index - 1)],
-                    walkable_connections: vec![PathfindingGraphConnection {
-                        node_id: pathfinding.nodes.len() - 1,
-                        dist: dist_between_nodes_on_line,
-                        connection_type: PathfindingGraphConnectionType::Walkable,
-                        effort: 0.0,
-                    }],
+                    walkable_connections: Vec::new(),
                     jumpable_connections: Vec::new(),
                     droppable_connections: Vec::new(),
                     normal: Vec2::ZERO,
                     is_corner: false,
                     is_external_corner: None,
+                    hazard_cost: 0.0,
+                    lethal: false,
                 };
 
-                pathfinding.nodes.push(new_node);
+                if j > 0 {
+                    new_node
+                        .walkable_connections
+                        .push(PathfindingGraphConnection {
+                            node_id: nodes.len() - 1,
+                            dist: dist_between_nodes_on_line,
+                            connection_type: PathfindingGraphConnectionType::Walkable,
+                            effort: 0.0,
+                            door: None,
+                            launch_velocity: None,
+                        });
+                }
+
+                nodes.push(new_node);
             }
+            let new_node = PathfindingGraphNode {
+                id: nodes.len(),
+                position: end,
+                polygon_index,
+                line_indicies: vec![(line_index - 1)],
+                walkable_connections: vec![PathfindingGraphConnection {
+                    node_id: nodes.len() - 1,
+                    dist: dist_between_nodes_on_line,
+                    connection_type: PathfindingGraphConnectionType::Walkable,
+                    effort: 0.0,
+                    door: None,
+                    launch_velocity: None,
+                }],
+                jumpable_connections: Vec::new(),
+                droppable_connections: Vec::new(),
+                normal: Vec2::ZERO,
+                is_corner: false,
+                is_external_corner: None,
+                hazard_cost: 0.0,
+                lethal: false,
+            };
+
+            nodes.push(new_node);
         }
     }
+
+    nodes
+}
+
+/// Maps every item on the compute task pool (results stay in the items' order), reporting the
+/// stage's progress (and printing it for big levels)
+fn map_in_parallel<T: Sync, R: Send + 'static>(
+    items: &[T],
+    stage: &BuildStage,
+    progress: &GraphBuildProgress,
+    map: impl Fn(&T) -> R + Send + Sync,
+) -> Vec<R> {
+    // Level bakes run without the app, so the pool may not exist yet
+    let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+    let chunk_size = items
+        .len()
+        .div_ceil(task_pool.thread_num().max(1) * BUILD_CHUNKS_PER_THREAD)
+        .max(1);
+
+    let done = AtomicUsize::new(0);
+    let reported_percent = AtomicUsize::new(0);
+    let report_progress = items.len() >= BUILD_PROGRESS_MIN_ITEMS;
+
+    items
+        .par_chunk_map(task_pool, chunk_size, |_, chunk| {
+            chunk
+                .iter()
+                .map(|item| {
+                    let result = map(item);
+
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    let stage_span = stage.progress.end - stage.progress.start;
+                    progress.advance_to(
+                        stage.progress.start + stage_span * done as f32 / items.len() as f32,
+                    );
+
+                    if report_progress {
+                        let percent = done * 100 / items.len() / BUILD_PROGRESS_STEP_PERCENT
+                            * BUILD_PROGRESS_STEP_PERCENT;
+                        if reported_percent.fetch_max(percent, Ordering::Relaxed) < percent {
+                            println!("Building pathfinding graph: {} {percent}%", stage.name);
+                        }
+                    }
+
+                    result
+                })
+                .collect::<Vec<R>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
 }
 
 /// Makes all of the connections between nodes 2-way
@@ -207,6 +838,8 @@ pub fn make_walkable_connections_2_way(pathfinding: &mut PathfindingGraph) {
                     dist: connection.dist,
                     connection_type: PathfindingGraphConnectionType::Walkable,
                     effort: 0.0,
+                    door: None,
+                    launch_velocity: None,
                 });
         }
     }
@@ -219,7 +852,7 @@ pub fn remove_duplicate_nodes(pathfinding: &mut PathfindingGraph) {
         let mut j = i + 1;
         while j < pathfinding.nodes.len() {
             if (pathfinding.nodes[i].position - pathfinding.nodes[j].position).length_squared()
-                < 1.0
+                < DUPLICATE_NODE_DISTANCE_SQ
             {
                 // Append the connections to the first node
                 let mut j_connections = pathfinding.nodes[j].walkable_connections.clone();
@@ -283,273 +916,296 @@ pub fn make_node_ids_indices(pathfinding: &mut PathfindingGraph) {
     }
 }
 
-pub fn make_jumpable_connections(pathfinding: &mut PathfindingGraph, level: &Level, radius: f32) {
-    for i in 0..pathfinding.nodes.len() {
-        let main_node = &pathfinding.nodes[i];
-
-        let mut jumpable_connections: Vec<PathfindingGraphConnection> = Vec::new();
+pub fn make_jumpable_connections(
+    pathfinding: &mut PathfindingGraph,
+    level: &Level,
+    radius: f32,
+    movement: &MovementConfig,
+    progress: &GraphBuildProgress,
+) {
+    // Each node's jumps only depend on the nodes and the level, so nodes are linked in parallel
+    let node_indices: Vec<usize> = (0..pathfinding.nodes.len()).collect();
+    let connections = map_in_parallel(&node_indices, &JUMPS_STAGE, progress, |&i| {
+        jumpable_connections_from(pathfinding, level, radius, movement, i)
+    });
+
+    for (node, jumpable_connections) in pathfinding.nodes.iter_mut().zip(connections) {
+        node.jumpable_connections = jumpable_connections;
+    }
+}
 
-        'other_nodes: for j in 0..pathfinding.nodes.len() {
-            // Make sure we're not comparing the same node
-            if i == j {
-                continue;
-            }
+/// Jumps an agent of the given radius, moving as `movement` says, can make from node `i`
+fn jumpable_connections_from(
+    pathfinding: &PathfindingGraph,
+    level: &Level,
+    radius: f32,
+    movement: &MovementConfig,
+    i: usize,
+) -> Vec<PathfindingGraphConnection> {
+    let main_node = &pathfinding.nodes[i];
 
-            let other_node = &pathfinding.nodes[j];
+    let mut jumpable_connections: Vec<PathfindingGraphConnection> = Vec::new();
 
-            // Make sure the nodes are not on the same polygon
-            if main_node.polygon_index == other_node.polygon_index {
-                continue;
-            }
+    'other_nodes: for j in 0..pathfinding.nodes.len() {
+        // Make sure we're not comparing the same node
+        if i == j {
+            continue;
+        }
 
-            for polygon_index in 0..level.polygons.len() {
-                let polygon = &level.polygons[polygon_index];
+        let other_node = &pathfinding.nodes[j];
 
-                'polygon_lines: for line_index in 1..polygon.points.len() {
-                    if main_node.polygon_index == polygon_index
-                        && main_node.line_indicies.contains(&(line_index - 1))
-                        || other_node.polygon_index == polygon_index
-                            && other_node.line_indicies.contains(&(line_index - 1))
-                    {
-                        continue 'polygon_lines;
-                    }
+        // Make sure the nodes are not on the same polygon
+        if main_node.polygon_index == other_node.polygon_index {
+            continue;
+        }
 
-                    let start = polygon.points[line_index - 1];
-                    let end = polygon.points[line_index];
+        // Agents land on ground and ledges: jumping at the face of a wall they slip down it, or
+        // off the bottom of it, before they can climb
+        if other_node.normal.y <= 0.0 {
+            continue;
+        }
 
-                    let intersection =
-                        line_intersect(start, end, main_node.position, other_node.position);
+        // Only polygons near the line between the nodes can block it
+        let line_aabb = Aabb::from_points(&[main_node.position, other_node.position]);
+        for polygon_index in level.query_aabb_indices(&line_aabb) {
+            let polygon = &level.polygons[polygon_index];
 
-                    if intersection.is_some() {
-                        continue 'other_nodes;
-                    }
+            'polygon_lines: for line_index in 1..polygon.points.len() {
+                if main_node.polygon_index == polygon_index
+                    && main_node.line_indicies.contains(&(line_index - 1))
+                    || other_node.polygon_index == polygon_index
+                        && other_node.line_indicies.contains(&(line_index - 1))
+                {
+                    continue 'polygon_lines;
                 }
-            }
 
-            let jumpable_velocity = jumpability_check(main_node, other_node, level, radius);
+                let start = polygon.points[line_index - 1];
+                let end = polygon.points[line_index];
 
-            if jumpable_velocity.is_none() {
-                continue 'other_nodes;
-            }
+                let intersection =
+                    line_intersect(start, end, main_node.position, other_node.position);
 
-            jumpable_connections.push(PathfindingGraphConnection {
-                node_id: j,
-                dist: (main_node.position - other_node.position).length(),
-                connection_type: PathfindingGraphConnectionType::Jumpable,
-                effort: jumpable_velocity.unwrap(),
-            });
+                if intersection.is_some() {
+                    continue 'other_nodes;
+                }
+            }
         }
 
-        pathfinding.nodes[i].jumpable_connections = jumpable_connections;
+        let Some(launch_velocity) =
+            jumpability_check(main_node, other_node, level, radius, movement)
+        else {
+            continue 'other_nodes;
+        };
+
+        jumpable_connections.push(PathfindingGraphConnection {
+            node_id: j,
+            dist: (main_node.position - other_node.position).length(),
+            connection_type: PathfindingGraphConnectionType::Jumpable,
+            effort: launch_velocity.length(),
+            door: None,
+            launch_velocity: Some(launch_velocity),
+        });
     }
-}
 
-pub fn make_droppable_connections(pathfinding: &mut PathfindingGraph, level: &Level, radius: f32) {
-    const DROP_EFFORT_MULTIPLIER: f32 = 0.5; // Falling is cheaper than jumping
-    const MAX_HORIZONTAL_DROP_OFFSET: f32 = PATHFINDING_NODE_SPACING * 1.5; // Allow small horizontal offset (1.5x node spacing)
+    jumpable_connections
+}
 
-    for i in 0..pathfinding.nodes.len() {
-        let main_node = &pathfinding.nodes[i];
+pub fn make_droppable_connections(
+    pathfinding: &mut PathfindingGraph,
+    level: &Level,
+    radius: f32,
+    movement: &MovementConfig,
+    progress: &GraphBuildProgress,
+) {
+    // Like jumps, each node's drops are found in parallel
+    let node_indices: Vec<usize> = (0..pathfinding.nodes.len()).collect();
+    let connections = map_in_parallel(&node_indices, &DROPS_STAGE, progress, |&i| {
+        droppable_connections_from(pathfinding, level, radius, movement, i)
+    });
+
+    for (node, droppable_connections) in pathfinding.nodes.iter_mut().zip(connections) {
+        node.droppable_connections = droppable_connections;
+    }
+}
 
-        let mut droppable_connections: Vec<PathfindingGraphConnection> = Vec::new();
+/// Drops an agent of the given radius, falling with the gravity in `movement`, can make from
+/// node `i`
+fn droppable_connections_from(
+    pathfinding: &PathfindingGraph,
+    level: &Level,
+    radius: f32,
+    movement: &MovementConfig,
+    i: usize,
+) -> Vec<PathfindingGraphConnection> {
+    const DROP_EFFORT_MULTIPLIER: f32 = 0.5; // Falling is cheaper than jumping
 
-        'other_nodes: for j in 0..pathfinding.nodes.len() {
-            // Make sure we're not comparing the same node
-            if i == j {
-                continue;
-            }
+    let main_node = &pathfinding.nodes[i];
 
-            let other_node = &pathfinding.nodes[j];
+    let mut droppable_connections: Vec<PathfindingGraphConnection> = Vec::new();
 
-            // Make sure the nodes are not on the same polygon
-            if main_node.polygon_index == other_node.polygon_index {
-                continue;
-            }
+    'other_nodes: for j in 0..pathfinding.nodes.len() {
+        // Make sure we're not comparing the same node
+        if i == j {
+            continue;
+        }
 
-            // Check that target is below source (droppable connections are one-way downward)
-            if other_node.position.y >= main_node.position.y {
-                continue;
-            }
+        let other_node = &pathfinding.nodes[j];
 
-            // Check that target is almost directly below (limit horizontal offset)
-            let horizontal_distance = (other_node.position.x - main_node.position.x).abs();
-            if horizontal_distance > MAX_HORIZONTAL_DROP_OFFSET {
-                continue;
-            }
+        // Make sure the nodes are not on the same polygon
+        if main_node.polygon_index == other_node.polygon_index {
+            continue;
+        }
 
-            // Check line-of-sight: ensure no geometry blocks the direct path
-            for polygon_index in 0..level.polygons.len() {
-                let polygon = &level.polygons[polygon_index];
-
-                'polygon_lines: for line_index in 1..polygon.points.len() {
-                    // Skip lines that belong to the source or target nodes
-                    if main_node.polygon_index == polygon_index
-                        && main_node.line_indicies.contains(&(line_index - 1))
-                        || other_node.polygon_index == polygon_index
-                            && other_node.line_indicies.contains(&(line_index - 1))
-                    {
-                        continue 'polygon_lines;
-                    }
+        // Check that target is below source (droppable connections are one-way downward)
+        if other_node.position.y >= main_node.position.y {
+            continue;
+        }
 
-                    let start = polygon.points[line_index - 1];
-                    let end = polygon.points[line_index];
+        // Check that target is almost directly below (limit horizontal offset)
+        let horizontal_distance = (other_node.position.x - main_node.position.x).abs();
+        if horizontal_distance > MAX_HORIZONTAL_DROP_OFFSET {
+            continue;
+        }
 
-                    let intersection =
-                        line_intersect(start, end, main_node.position, other_node.position);
+        // Check line-of-sight: ensure no geometry blocks the direct path
+        let line_aabb = Aabb::from_points(&[main_node.position, other_node.position]);
+        for polygon_index in level.query_aabb_indices(&line_aabb) {
+            let polygon = &level.polygons[polygon_index];
 
-                    if intersection.is_some() {
-                        continue 'other_nodes;
-                    }
+            'polygon_lines: for line_index in 1..polygon.points.len() {
+                // Skip lines that belong to the source or target nodes
+                if main_node.polygon_index == polygon_index
+                    && main_node.line_indicies.contains(&(line_index - 1))
+                    || other_node.polygon_index == polygon_index
+                        && other_node.line_indicies.contains(&(line_index - 1))
+                {
+                    continue 'polygon_lines;
                 }
-            }
 
-            // Check if the falling trajectory is valid
-            let drop_effort = droppability_check(main_node, other_node, level, radius);
+                let start = polygon.points[line_index - 1];
+                let end = polygon.points[line_index];
+
+                let intersection =
+                    line_intersect(start, end, main_node.position, other_node.position);
 
-            if drop_effort.is_none() {
-                continue 'other_nodes;
+                if intersection.is_some() {
+                    continue 'other_nodes;
+                }
             }
+        }
 
-            let drop_distance = (main_node.position - other_node.position).length();
-            let effort = drop_distance * DROP_EFFORT_MULTIPLIER;
+        // Check if the falling trajectory is valid
+        let drop_effort =
+            droppability_check(main_node, other_node, level, radius, movement.gravity);
 
-            droppable_connections.push(PathfindingGraphConnection {
-                node_id: j,
-                dist: drop_distance,
-                connection_type: PathfindingGraphConnectionType::Droppable,
-                effort,
-            });
+        if drop_effort.is_none() {
+            continue 'other_nodes;
         }
 
-        pathfinding.nodes[i].droppable_connections = droppable_connections;
+        let drop_distance = (main_node.position - other_node.position).length();
+        let effort = drop_distance * DROP_EFFORT_MULTIPLIER;
+
+        droppable_connections.push(PathfindingGraphConnection {
+            node_id: j,
+            dist: drop_distance,
+            connection_type: PathfindingGraphConnectionType::Droppable,
+            effort,
+            door: None,
+            launch_velocity: None,
+        });
     }
+
+    droppable_connections
 }
 
+/// Launch velocity for a jump between two nodes by an agent of the given radius, if it can make
+/// it: the jump has to be within the AI jump velocity and the agent's body has to clear the
+/// level along the whole arc (which runs between where the agent stands on each node). The lowest
+/// energy arc is tried first, then flatter and higher ones, so a low ceiling or a lip in the way
+/// doesn't rule the jump out.
 pub fn jumpability_check(
     start_graph_node: &PathfindingGraphNode,
     goal_graph_node: &PathfindingGraphNode,
     level: &Level,
     radius: f32,
-) -> Option<f32> {
-    let start_node = start_graph_node;
-    let start_pos = start_node.position;
-
-    let goal_node = goal_graph_node;
-    let goal_pos = goal_node.position;
+    movement: &MovementConfig,
+) -> Option<Vec2> {
+    let start_pos = start_graph_node.position + start_graph_node.normal * radius;
+    let goal_pos = goal_graph_node.position + goal_graph_node.normal * radius;
 
     let delta_p = goal_pos - start_pos;
-    let acceleration = Vec2::new(0.0, -GRAVITY_STRENGTH);
-    let v_max = PLATFORMER_AI_JUMP_FORCE;
-    let b1 = delta_p.dot(acceleration) + v_max * v_max;
-    let discriminant = b1 * b1 - acceleration.dot(acceleration) * delta_p.dot(delta_p);
-
-    let mut jump_possible = discriminant >= 0.0;
+    let acceleration = Vec2::new(0.0, -movement.gravity);
 
     let t_low_energy = (4.0 * delta_p.dot(delta_p) / acceleration.dot(acceleration))
         .sqrt()
         .sqrt();
-    let launch_velocity = delta_p / t_low_energy - acceleration * t_low_energy / 2.0;
-    let timestep = t_low_energy / JUMPABILITY_CHECK_TIMESTEP_DIVISIONS as f32;
 
-    if jump_possible {
-        'polygon: for polygon_index in 0..level.polygons.len() {
+    JUMP_ARC_TIME_SCALES.iter().find_map(|&time_scale| {
+        let flight_time = t_low_energy * time_scale;
+        let launch_velocity = delta_p / flight_time - acceleration * flight_time / 2.0;
+        if launch_velocity.length() > movement.ai_jump_velocity {
+            return None;
+        }
+
+        let timestep = flight_time / JUMPABILITY_CHECK_TIMESTEP_DIVISIONS as f32;
+        let arc: Vec<Vec2> = (0..=JUMPABILITY_CHECK_TIMESTEP_DIVISIONS)
+            .map(|i| {
+                let t = timestep * i as f32;
+                start_pos + launch_velocity * t + acceleration * t * t / 2.0
+            })
+            .chain([goal_pos])
+            .collect();
+
+        jump_arc_is_clear(start_graph_node, goal_graph_node, &arc, level, radius)
+            .then_some(launch_velocity)
+    })
+}
+
+/// Whether an agent of the given radius following `arc` (points along it, in order) from one node
+/// to another clears the level, ignoring the lines the two nodes are on
+fn jump_arc_is_clear(
+    start_node: &PathfindingGraphNode,
+    goal_node: &PathfindingGraphNode,
+    arc: &[Vec2],
+    level: &Level,
+    radius: f32,
+) -> bool {
+    // Only polygons near the jump arc can block it
+    let arc_aabb = Aabb::from_points(arc).expand(radius);
+
+    level
+        .query_aabb_indices(&arc_aabb)
+        .into_iter()
+        .all(|polygon_index| {
             let polygon = &level.polygons[polygon_index];
-            'line: for line_index in 1..polygon.points.len() {
-                let start_node_on_line = start_node.polygon_index == polygon_index
-                    && start_node.line_indicies.contains(&(line_index - 1));
-                let goal_node_on_line = goal_node.polygon_index == polygon_index
-                    && goal_node.line_indicies.contains(&(line_index - 1));
-
-                if start_node_on_line || goal_node_on_line {
-                    continue 'line;
+            let on_node_line = |node: &PathfindingGraphNode, line_index: usize| {
+                node.polygon_index == polygon_index && node.line_indicies.contains(&line_index)
+            };
+
+            (1..polygon.points.len()).all(|line_index| {
+                if on_node_line(start_node, line_index - 1)
+                    || on_node_line(goal_node, line_index - 1)
+                {
+                    return true;
                 }
 
                 let line_start = polygon.points[line_index - 1];
                 let line_end = polygon.points[line_index];
 
-                let mut prev_pos = start_pos;
-
-                for i in 1..=JUMPABILITY_CHECK_TIMESTEP_DIVISIONS {
-                    let t = timestep * i as f32;
-                    let pos = start_pos + launch_velocity * t + acceleration * t * t / 2.0;
-
-                    let line_dir = (pos - prev_pos).normalize();
-
-                    let line_normal = Vec2::new(-line_dir.y, line_dir.x);
-
-                    let line_beginning_offset_1 = prev_pos + line_normal * radius;
-                    let line_beginning_offset_2 = prev_pos - line_normal * radius;
-                    let line_end_offset_1 = pos + line_normal * radius;
-                    let line_end_offset_2 = pos - line_normal * radius;
-
-                    let offset_1_intersection = line_intersect(
-                        line_beginning_offset_1,
-                        line_end_offset_1,
-                        line_start,
-                        line_end,
-                    );
-
-                    if offset_1_intersection.is_some() {
-                        jump_possible = false;
-                        break 'polygon;
-                    }
-
-                    let offset_2_intersection = line_intersect(
-                        line_beginning_offset_2,
-                        line_end_offset_2,
-                        line_start,
-                        line_end,
-                    );
-
-                    if offset_2_intersection.is_some() {
-                        jump_possible = false;
-                        break 'polygon;
-                    }
-
-                    prev_pos = pos;
-                }
-
-                let line_dir = (goal_pos - prev_pos).normalize();
-
-                let line_normal = Vec2::new(-line_dir.y, line_dir.x);
-
-                let line_beginning_offset_1 = prev_pos + line_normal * radius;
-                let line_beginning_offset_2 = prev_pos - line_normal * radius;
-                let line_end_offset_1 = goal_pos + line_normal * radius;
-                let line_end_offset_2 = goal_pos - line_normal * radius;
-
-                let offset_1_intersection = line_intersect(
-                    line_beginning_offset_1,
-                    line_end_offset_1,
-                    line_start,
-                    line_end,
-                );
-
-                if offset_1_intersection.is_some() {
-                    jump_possible = false;
-                    break 'polygon;
-                }
-
-                let offset_2_intersection = line_intersect(
-                    line_beginning_offset_2,
-                    line_end_offset_2,
-                    line_start,
-                    line_end,
-                );
-
-                if offset_2_intersection.is_some() {
-                    jump_possible = false;
-                    break 'polygon;
-                }
-            }
-        }
-    }
+                // Sweep both sides of the agent's body along each step of the arc
+                arc.windows(2).all(|step| {
+                    let Some(direction) = (step[1] - step[0]).try_normalize() else {
+                        return true;
+                    };
+                    let side = Vec2::new(-direction.y, direction.x) * radius;
 
-    if jump_possible {
-        Some(launch_velocity.length())
-    } else {
-        None
-    }
+                    [side, -side].into_iter().all(|offset| {
+                        line_intersect(step[0] + offset, step[1] + offset, line_start, line_end)
+                            .is_none()
+                    })
+                })
+            })
+        })
 }
 
 pub fn droppability_check(
@@ -557,6 +1213,7 @@ pub fn droppability_check(
     goal_graph_node: &PathfindingGraphNode,
     level: &Level,
     radius: f32,
+    gravity: f32,
 ) -> Option<f32> {
     let start_pos = start_graph_node.position;
     let goal_pos = goal_graph_node.position;
@@ -569,7 +1226,7 @@ pub fn droppability_check(
     // Calculate falling time: t = sqrt(2 * distance / gravity)
     let delta_y = start_pos.y - goal_pos.y;
     let delta_x = goal_pos.x - start_pos.x;
-    let fall_time = (2.0 * delta_y / GRAVITY_STRENGTH).sqrt();
+    let fall_time = (2.0 * delta_y / gravity).sqrt();
 
     // Calculate horizontal velocity needed (if any)
     let horizontal_velocity = if fall_time > 0.0 {
@@ -580,11 +1237,12 @@ pub fn droppability_check(
 
     // Simulate falling trajectory in discrete steps
     let timestep = fall_time / JUMPABILITY_CHECK_TIMESTEP_DIVISIONS as f32;
-    let acceleration = Vec2::new(0.0, -GRAVITY_STRENGTH);
+    let acceleration = Vec2::new(0.0, -gravity);
     let initial_velocity = Vec2::new(horizontal_velocity, 0.0);
 
-    // Check for collisions along the falling path
-    'polygon: for polygon_index in 0..level.polygons.len() {
+    // Check for collisions along the falling path (it stays between the two nodes)
+    let fall_aabb = Aabb::from_points(&[start_pos, goal_pos]).expand(radius);
+    'polygon: for polygon_index in level.query_aabb_indices(&fall_aabb) {
         let polygon = &level.polygons[polygon_index];
         'line: for line_index in 1..polygon.points.len() {
             // Skip lines that belong to the source or target nodes
@@ -703,12 +1361,23 @@ pub fn calculate_normals(pathfinding: &mut PathfindingGraph, level: &Level) {
     }
 }
 
-pub fn setup_corners(pathfinding: &mut PathfindingGraph) {
+/// Marks the nodes where the surface bends as corners. The joints between the short segments of
+/// curved ground bend so little that agents walk over them like flat ground.
+pub fn setup_corners(pathfinding: &mut PathfindingGraph, level: &Level) {
     for node_index in 0..pathfinding.nodes.len() {
-        // let node = &mut pathfinding.nodes[node_index];
-
-        pathfinding.nodes[node_index].is_corner =
-            pathfinding.nodes[node_index].line_indicies.len() > 1;
+        let node = &pathfinding.nodes[node_index];
+        let points = &level.polygons[node.polygon_index].points;
+        let line_directions: Vec<Vec2> = node
+            .line_indicies
+            .iter()
+            .map(|&line_index| (points[line_index + 1] - points[line_index]).normalize_or_zero())
+            .collect();
+
+        pathfinding.nodes[node_index].is_corner = line_directions.iter().any(|direction| {
+            line_directions
+                .iter()
+                .any(|other| direction.dot(*other) < SMOOTH_JOINT_MIN_DOT)
+        });
 
         if pathfinding.nodes[node_index].is_corner {
             let mut line_dir = Vec2::ZERO;
@@ -751,3 +1420,290 @@ fn build_spatial_index(pathfinding: &mut PathfindingGraph) {
     );
 }
 
+/// Tags every connection that passes through a door so it is only used while the door is open.
+/// All doors start closed. Jump arcs are approximated by the straight line between their nodes.
+fn mark_door_connections(pathfinding: &mut PathfindingGraph, level: &Level) {
+    pathfinding.closed_doors.clear();
+
+    for (door_id, door) in level.metadata.doors.iter().enumerate() {
+        pathfinding.closed_doors.insert(door_id);
+
+        // Agents pass through the door with their whole body, not just their center
+        let area = Aabb {
+            min: Vec2::from(door.min),
+            max: Vec2::from(door.max),
+        }
+        .expand(PURSUE_AI_AGENT_RADIUS);
+
+        let positions: Vec<Vec2> = pathfinding.nodes.iter().map(|node| node.position).collect();
+
+        for node in pathfinding.nodes.iter_mut() {
+            for connection in node
+                .walkable_connections
+                .iter_mut()
+                .chain(node.jumpable_connections.iter_mut())
+                .chain(node.droppable_connections.iter_mut())
+            {
+                if area.intersects_segment(node.position, positions[connection.node_id]) {
+                    connection.door = Some(door_id);
+                }
+            }
+        }
+    }
+}
+
+/// Annotates nodes near hazards: nodes inside a lethal hazard's reach are marked lethal, the
+/// rest pay a penalty that grows the closer the hazard gets
+fn mark_hazard_nodes(pathfinding: &mut PathfindingGraph, level: &Level) {
+    for node in pathfinding.nodes.iter_mut() {
+        node.hazard_cost = 0.0;
+        node.lethal = false;
+
+        // Agents stand on the node, so measure from where their center is
+        let agent_position = node.position + node.normal * PURSUE_AI_AGENT_RADIUS;
+
+        for hazard in &level.metadata.hazards {
+            let gap = hazard.distance_to(agent_position) - PURSUE_AI_AGENT_RADIUS;
+            if gap >= HAZARD_AVOIDANCE_MARGIN {
+                continue;
+            }
+
+            if gap <= 0.0 && hazard.is_lethal() {
+                node.lethal = true;
+            }
+
+            let closeness = 1.0 - gap.max(0.0) / HAZARD_AVOIDANCE_MARGIN;
+            node.hazard_cost = node.hazard_cost.max(HAZARD_PATH_PENALTY * closeness);
+        }
+    }
+}
+
+/// String-pulls a path found over the graph: drops the nodes in the middle of straight runs of
+/// walkable ground, so an agent of the given radius walks straight across flat ground instead of
+/// from node center to node center.
+///
+/// A node is only dropped if the agent can see past it from the last node kept (along the line it
+/// walks, a radius out from the surface). Corners and the nodes jumps and drops start and end at
+/// are always kept, so path following still finds every jump.
+pub fn smooth_path(
+    pathfinding: &PathfindingGraph,
+    level: &Level,
+    path: &[PathNode],
+    radius: f32,
+) -> Vec<PathNode> {
+    let Some((last, middle)) = path.split_last() else {
+        return Vec::new();
+    };
+    let Some((first, middle)) = middle.split_first() else {
+        return path.to_vec();
+    };
+
+    let walks_to = |from: &PathNode, to: &PathNode| {
+        pathfinding.nodes[from.id]
+            .walkable_connections
+            .iter()
+            .any(|connection| connection.node_id == to.id)
+    };
+    let walking_position =
+        |node: &PathNode| node.position + pathfinding.nodes[node.id].normal * radius;
+
+    let mut smoothed = vec![first.clone()];
+    for (index, node) in middle.iter().enumerate() {
+        // `middle[index]` is `path[index + 1]`
+        let previous = &path[index];
+        let next = &path[index + 2];
+        let anchor = smoothed.last().unwrap_or(first);
+
+        let graph_node = &pathfinding.nodes[node.id];
+        let same_run = graph_node
+            .normal
+            .dot(pathfinding.nodes[anchor.id].normal)
+            >= STRAIGHT_RUN_MIN_DOT;
+        let redundant = same_run
+            && !graph_node.is_corner
+            && walks_to(previous, node)
+            && walks_to(node, next)
+            && level.line_of_sight(walking_position(anchor), walking_position(next));
+
+        if !redundant {
+            smoothed.push(node.clone());
+        }
+    }
+    smoothed.push(last.clone());
+
+    smoothed
+}
+
+/// Color an edge of the given traversal type is drawn in
+pub fn connection_color(connection_type: PathfindingGraphConnectionType) -> Color {
+    match connection_type {
+        PathfindingGraphConnectionType::Walkable => WALK_EDGE_COLOR,
+        PathfindingGraphConnectionType::Jumpable => JUMP_EDGE_COLOR,
+        PathfindingGraphConnectionType::Droppable => DROP_EDGE_COLOR,
+    }
+}
+
+/// Pathfinding debug layer: Draws every graph node and edge (walks as lines, jumps and drops as
+/// arrows, each in their own color)
+pub fn s_debug_pathfinding_graph(pathfinding: Res<PathfindingGraph>, mut gizmos: Gizmos) {
+    for node in &pathfinding.nodes {
+        for connection in &node.walkable_connections {
+            // Walks go both ways, so each is drawn once
+            if connection.node_id > node.id {
+                let other = pathfinding.nodes[connection.node_id].position;
+                gizmos.line_2d(
+                    node.position,
+                    other,
+                    WALK_EDGE_COLOR.with_alpha(DEBUG_EDGE_ALPHA),
+                );
+            }
+        }
+        for connection in node.jumpable_connections.iter().chain(&node.droppable_connections) {
+            let other = pathfinding.nodes[connection.node_id].position;
+            gizmos.arrow_2d(
+                node.position,
+                other,
+                connection_color(connection.connection_type).with_alpha(DEBUG_EDGE_ALPHA),
+            );
+        }
+    }
+
+    for node in &pathfinding.nodes {
+        let color = if node.lethal {
+            Color::srgb(1.0, 0.0, 0.0)
+        } else if node.hazard_cost > 0.0 {
+            Color::srgb(1.0, 0.6, 0.0)
+        } else {
+            Color::srgb(0.0, 0.5, 1.0)
+        };
+
+        gizmos.circle_2d(node.position, DEBUG_NODE_GIZMO_RADIUS, color);
+    }
+}
+
+/// Pathfinding cost labels resource: Whether the pathfinding debug layer labels every edge with
+/// its cost, for tuning the graph builder
+#[derive(Resource, Default)]
+pub struct PathfindingCostLabels {
+    pub visible: bool,
+}
+
+/// Marker for an edge cost label of the pathfinding debug layer
+#[derive(Component)]
+pub struct PathfindingCostLabel;
+
+/// Pathfinding cost label system: Toggles the edge cost labels and (re)spawns them while they're
+/// shown along with the pathfinding debug layer, whenever the graph changes
+pub fn s_update_pathfinding_cost_labels(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    debug_layers: Res<DebugLayers>,
+    mut cost_labels: ResMut<PathfindingCostLabels>,
+    pathfinding: Res<PathfindingGraph>,
+    label_query: Query<Entity, With<PathfindingCostLabel>>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(PATHFINDING_COST_LABELS_KEY) {
+        cost_labels.visible = !cost_labels.visible;
+    }
+
+    let shown = gizmos_visible.visible
+        && debug_layers.is_enabled(DebugLayer::Pathfinding)
+        && cost_labels.visible;
+    let spawned = !label_query.is_empty();
+    if shown == spawned && !(shown && pathfinding.is_changed()) {
+        return;
+    }
+
+    for entity in label_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !shown {
+        return;
+    }
+
+    for node in &pathfinding.nodes {
+        let connections = node
+            .walkable_connections
+            .iter()
+            .chain(&node.jumpable_connections)
+            .chain(&node.droppable_connections);
+        for connection in connections {
+            let other = pathfinding.nodes[connection.node_id].position;
+            // Walks cost the same both ways, so each gets one label in its middle
+            let fraction = match connection.connection_type {
+                PathfindingGraphConnectionType::Walkable if connection.node_id < node.id => {
+                    continue;
+                }
+                PathfindingGraphConnectionType::Walkable => 0.5,
+                _ => COST_LABEL_EDGE_FRACTION,
+            };
+            let position = node.position.lerp(other, fraction);
+
+            commands.spawn((
+                Text2d::new(format!("{:.0}", connection_cost(connection))),
+                TextFont {
+                    font_size: COST_LABEL_FONT_SIZE,
+                    ..Default::default()
+                },
+                TextColor(connection_color(connection.connection_type)),
+                Transform::from_translation(position.extend(COST_LABEL_Z)),
+                PathfindingCostLabel,
+                DespawnOnExit(GameState::InGame),
+            ));
+        }
+    }
+}
+
+/// Marker for the debug overlay line warning that the pathfinding graph can't be used
+#[derive(Component)]
+pub struct PathfindingWarningText;
+
+/// Spawns the (hidden) pathfinding warning along the top of the window
+pub fn s_spawn_pathfinding_warning_text(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: WARNING_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(WARNING_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(WARNING_MARGIN),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        Visibility::Hidden,
+        PathfindingWarningText,
+        DespawnOnExit(GameState::InGame),
+    ));
+}
+
+/// Pathfinding warning system: Shows why agents are steering without paths while gizmos are
+/// visible, if the level's graph can't be used
+pub fn s_update_pathfinding_warning(
+    gizmos_visible: Res<GizmosVisible>,
+    pathfinding: Res<PathfindingGraph>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<PathfindingWarningText>>,
+) {
+    let problem = gizmos_visible
+        .visible
+        .then(|| GraphDiagnostics::of(&pathfinding))
+        .and_then(|diagnostics| Some((diagnostics, diagnostics.problem()?)));
+
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        match problem {
+            Some((diagnostics, problem)) => {
+                *visibility = Visibility::Visible;
+                text.0 = format!(
+                    "Pathfinding graph unusable: {problem} ({} nodes, {} links), agents steer \
+                     straight at their goals",
+                    diagnostics.nodes, diagnostics.connections
+                );
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
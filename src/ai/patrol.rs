@@ -0,0 +1,222 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    color::{Alpha, Color},
+    ecs::{
+        component::Component,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{Vec2, Vec3Swizzles},
+    time::Time,
+    transform::components::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    brain::{s_state_machine_goals, AIGoal, Brain, GoalTarget},
+    flying_ai::s_flying_ai_movement,
+    platformer_ai::s_platformer_ai_movement,
+    pursue_ai::{PursueAI, PursueAIState},
+};
+
+// How close an agent has to get to a waypoint to count as there (units: pixels)
+const WAYPOINT_REACHED_DISTANCE: f32 = 32.0;
+// Gizmo colors and sizes for the AI debug layer
+const PATROL_ROUTE_COLOR: Color = Color::srgb(1.0, 0.8, 0.2);
+const PATROL_WAYPOINT_GIZMO_RADIUS: f32 = 6.0;
+
+/// A patrol route read from level metadata (positions in world pixels)
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct PatrolRouteSetting {
+    /// Points visited in order; the route's agent starts at the first
+    pub waypoints: Vec<[f32; 2]>,
+    /// Walk the route back and forth instead of looping from the last point to the first
+    pub ping_pong: bool,
+    /// Time spent at each waypoint before moving on (seconds)
+    pub wait: f32,
+}
+
+/// Patrol AI component: Walks an agent along a route of waypoints while it has nothing better to
+/// do. A player it spots is left to the state machine (`PursueAI`); once that gives up, the agent
+/// picks the route back up at the closest waypoint.
+///
+/// ```ignore
+/// PatrolAI::new(vec![Vec2::new(-200.0, -280.0), Vec2::new(200.0, -280.0)])
+///     .ping_pong()
+///     .with_wait(1.5)
+/// ```
+#[derive(Component, Clone, Debug)]
+pub struct PatrolAI {
+    /// Points visited in order (world pixels)
+    pub waypoints: Vec<Vec2>,
+    /// Walk the route back and forth instead of looping
+    pub ping_pong: bool,
+    /// Time spent at each waypoint (seconds)
+    pub wait: f32,
+    /// Index of the waypoint the agent is heading for
+    pub next_waypoint: usize,
+    /// Whether a ping-pong route is being walked backwards
+    reversed: bool,
+    /// When (elapsed seconds) the agent moves on from the waypoint it has reached
+    waiting_until: Option<f32>,
+    /// Whether the agent has left the route (chasing or searching for the player)
+    off_route: bool,
+}
+
+impl PatrolAI {
+    /// Loops through `waypoints` without stopping
+    pub fn new(waypoints: Vec<Vec2>) -> Self {
+        Self {
+            waypoints,
+            ping_pong: false,
+            wait: 0.0,
+            next_waypoint: 0,
+            reversed: false,
+            waiting_until: None,
+            // Agents spawn chasing, so the route starts from the closest waypoint
+            off_route: true,
+        }
+    }
+
+    /// Walks the route back and forth instead of looping
+    pub fn ping_pong(mut self) -> Self {
+        self.ping_pong = true;
+        self
+    }
+
+    /// Waits `seconds` at each waypoint
+    pub fn with_wait(mut self, seconds: f32) -> Self {
+        self.wait = seconds;
+        self
+    }
+
+    pub fn from_setting(setting: &PatrolRouteSetting) -> Self {
+        let patrol = Self::new(setting.waypoints.iter().copied().map(Vec2::from).collect());
+        let patrol = if setting.ping_pong { patrol.ping_pong() } else { patrol };
+        patrol.with_wait(setting.wait)
+    }
+
+    /// Index of the waypoint closest to `position`
+    pub fn nearest_waypoint(&self, position: Vec2) -> usize {
+        self.waypoints
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+            .map_or(0, |(index, _)| index)
+    }
+
+    /// Moves on to the following waypoint, turning around at the ends of ping-pong routes
+    fn advance(&mut self) {
+        let last = self.waypoints.len().saturating_sub(1);
+        if !self.ping_pong || last == 0 {
+            self.next_waypoint = (self.next_waypoint + 1) % self.waypoints.len().max(1);
+            return;
+        }
+
+        let at_end = if self.reversed {
+            self.next_waypoint == 0
+        } else {
+            self.next_waypoint >= last
+        };
+        if at_end {
+            self.reversed = !self.reversed;
+        }
+        self.next_waypoint = if self.reversed {
+            self.next_waypoint - 1
+        } else {
+            self.next_waypoint + 1
+        };
+    }
+}
+
+pub struct PatrolAIPlugin;
+
+impl Plugin for PatrolAIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            s_patrol_ai_goals
+                .after(s_state_machine_goals)
+                .before(s_platformer_ai_movement)
+                .before(s_flying_ai_movement),
+        );
+    }
+}
+
+/// Patrol AI system: Points wandering state machine agents at the next waypoint of their route
+/// instead of somewhere random, waiting at each waypoint for the route's wait time
+pub fn s_patrol_ai_goals(
+    mut ai_query: Query<(&Transform, &Brain, &PursueAI, &mut PatrolAI, &mut AIGoal)>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+
+    for (transform, brain, pursue_ai, mut patrol, mut goal) in ai_query.iter_mut() {
+        if !matches!(brain, Brain::StateMachine) || patrol.waypoints.is_empty() {
+            continue;
+        }
+
+        // Chasing, searching and attacking are left to the state machine
+        if !matches!(pursue_ai.state, PursueAIState::Wander) {
+            patrol.off_route = true;
+            patrol.waiting_until = None;
+            continue;
+        }
+
+        let position = transform.translation.xy();
+        if patrol.off_route {
+            patrol.off_route = false;
+            patrol.next_waypoint = patrol.nearest_waypoint(position);
+        }
+
+        if position.distance(patrol.waypoints[patrol.next_waypoint]) <= WAYPOINT_REACHED_DISTANCE {
+            match patrol.waiting_until {
+                None => patrol.waiting_until = Some(now + patrol.wait),
+                Some(until) if now >= until => {
+                    patrol.waiting_until = None;
+                    patrol.advance();
+                }
+                Some(_) => {}
+            }
+        }
+
+        // Patrolling isn't worth getting hurt for
+        *goal = AIGoal {
+            target: GoalTarget::Position(patrol.waypoints[patrol.next_waypoint]),
+            avoid_hazards: true,
+        };
+    }
+}
+
+/// Draws each patrol route and the waypoint its agent is heading for
+pub fn s_debug_patrol_ai(ai_query: Query<(&Transform, &PatrolAI)>, mut gizmos: Gizmos) {
+    for (transform, patrol) in ai_query.iter() {
+        let Some(&next_waypoint) = patrol.waypoints.get(patrol.next_waypoint) else {
+            continue;
+        };
+
+        gizmos.linestrip_2d(patrol.waypoints.iter().copied(), PATROL_ROUTE_COLOR);
+        if !patrol.ping_pong {
+            if let (Some(&first), Some(&last)) = (patrol.waypoints.first(), patrol.waypoints.last())
+            {
+                gizmos.line_2d(last, first, PATROL_ROUTE_COLOR);
+            }
+        }
+        for &waypoint in &patrol.waypoints {
+            gizmos.circle_2d(waypoint, PATROL_WAYPOINT_GIZMO_RADIUS, PATROL_ROUTE_COLOR);
+        }
+
+        if !patrol.off_route {
+            gizmos.line_2d(
+                transform.translation.xy(),
+                next_waypoint,
+                PATROL_ROUTE_COLOR.with_alpha(0.4),
+            );
+        }
+    }
+}
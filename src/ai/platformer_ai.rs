@@ -1,24 +1,48 @@
+use std::collections::HashMap;
+
 use bevy::{
     app::{App, Plugin, Update},
     color::Color,
     ecs::{
+        change_detection::DetectChanges,
         component::Component,
-        query::With,
+        entity::Entity,
+        message::{MessageReader, MessageWriter},
+        query::Without,
         schedule::IntoScheduleConfigs,
-        system::{ParamSet, Query, Res},
+        system::{ParamSet, Query, Res, ResMut},
     },
     gizmos::gizmos::Gizmos,
     math::{Vec2, Vec3Swizzles},
     transform::components::Transform,
     time::Time,
 };
-
-use crate::GRAVITY_STRENGTH;
+use rand::Rng;
+
+use crate::{
+    characters::{nearest_player, PlayerControlled},
+    collisions::sweep_circle,
+    integrators::Integrator,
+    level::{s_apply_level_changes, Aabb, Level},
+    movement_config::MovementConfig,
+    settings::Settings,
+    spatial::DynamicSpatialIndex,
+    utils::closest_point_on_segment,
+    weather::Weather,
+    KinematicBody,
+};
 
 use super::{
-    a_star::{find_path, PathNode},
-    pathfinding::PathfindingGraph,
-    pursue_ai::s_pursue_ai_update,
+    a_star::PathNode,
+    activity::Asleep,
+    avoidance::separation,
+    brain::{AIGoal, GoalTarget},
+    metrics::{path_length, AIMetrics},
+    path_requests::{plan_path, AsyncPathfinding, PathRequest, PathResponse, PathTasks},
+    pathfinding::{smooth_path, PathfindingGraph, PathfindingGraphChanged},
+    profile::AIProfile,
+    pursue_ai::{s_pursue_ai_update, AIRng, PursueAI, PURSUE_AI_AGENT_RADIUS},
+    tick::AITick,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -38,25 +62,35 @@ pub enum PathFollowingStrategy {
 // Converted from frame-based: multiply by 60 (assuming 60fps target)
 const WANDER_MAX_SPEED: f32 = 180.0; // 3.0 * 60
 
-pub const PLATFORMER_AI_JUMP_FORCE: f32 = 480.0; // 8.0 * 60
-
-// Acceleration scalers (units: 1/second)
-// Converted from frame-based: 0.2 per frame at 60fps = 12.0 per second
-pub const ACCELERATION_SCALERS: (f32, f32) = (12.0, 24.0);
-
 // Platformer AI movement constants
 const GIZMO_LINE_LENGTH: f32 = 15.0;
 const VELOCITY_MAGNITUDE_THRESHOLD: f32 = 0.1;
-const JUMP_TIME_MULTIPLIER: f32 = 1.0;
 const PATHFINDING_NODE_GIZMO_RADIUS: f32 = 5.0;
+const TARGET_NODE_GIZMO_SIZE: f32 = 12.0;
 
 
 // Path caching constants (using squared distances to avoid sqrt)
 const GOAL_CHANGE_THRESHOLD_SQ: f32 = 25.0; // 5.0 squared
 const PATH_DEVIATION_THRESHOLD_SQ: f32 = 100.0; // 10.0 squared
 const NODE_REACHED_THRESHOLD_SQ: f32 = 64.0; // 8.0 squared (agent radius squared)
+// Horizontal distance from a jump's landing spot within which air control is left alone (pixels)
+const AIR_CONTROL_DEADZONE: f32 = 2.0;
+// How far an agent may stray from the path segment it is walking before steering is constrained
+const PATH_CORRIDOR_CLEARANCE: f32 = 16.0;
+const PATH_CORRIDOR_CLEARANCE_SQ: f32 = PATH_CORRIDOR_CLEARANCE * PATH_CORRIDOR_CLEARANCE;
 // Threshold for final goal node (matches wander goal threshold)
 const FINAL_GOAL_REACHED_THRESHOLD_SQ: f32 = 900.0; // 30.0 squared
+// How much of the agent's steering goes to spreading out from crowding agents (fraction)
+const AVOIDANCE_WEIGHT: f32 = 0.8;
+// Largest error in jump speed for an agent with no jump precision (fraction of the jump speed)
+pub const MAX_JUMP_ERROR: f32 = 0.25;
+// Steering without a pathfinding graph: how close (horizontally) counts as at the goal, how far
+// ahead walls are looked for, how high above a goal has to be to jump for it (units: pixels), and
+// how upright a surface has to be to count as a wall (normal x component)
+const STEERING_ARRIVE_DISTANCE: f32 = 8.0;
+const STEERING_WALL_LOOKAHEAD: f32 = 24.0;
+const STEERING_CLIMB_HEIGHT: f32 = 48.0;
+const STEERING_WALL_NORMAL_X: f32 = 0.7;
 
 #[allow(dead_code)]
 pub struct PlatformerAIPlugin;
@@ -65,7 +99,12 @@ impl Plugin for PlatformerAIPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            s_platformer_ai_movement.after(s_pursue_ai_update),
+            (
+                s_invalidate_changed_paths.after(s_apply_level_changes),
+                s_platformer_ai_movement
+                    .after(s_pursue_ai_update)
+                    .after(s_invalidate_changed_paths),
+            ),
         );
     }
 }
@@ -80,100 +119,311 @@ pub struct PlatformerAI {
     pub cached_path: Option<Vec<PathNode>>,
     pub last_goal_position: Option<Vec2>,
     pub current_path_index: usize,
-}
-
-/// AI Physics component: Similar to Physics but for AI entities
-#[derive(Component)]
-pub struct AIPhysics {
-    pub prev_position: Vec2,
-    pub velocity: Vec2,
-    pub acceleration: Vec2,
-    pub radius: f32,
-    pub normal: Vec2,
+    /// Direction the agent steered in this frame (drawn by the AI debug layer)
+    pub move_dir: Vec2,
+    /// Path segment the agent is walking along this frame (`None` while jumping or falling)
+    pub corridor: Option<(Vec2, Vec2)>,
+    /// Whether the agent has landed since its last jump (set by `s_ai_contacts`)
     pub grounded: bool,
+    /// Side of the wall the agent is touching (-1 left, 1 right, 0 none)
     pub walled: i8,
     pub has_wall_jumped: bool,
+    /// Fraction of the ground acceleration available for horizontal steering in the air
+    pub air_control: f32,
+    /// Time remaining (seconds) before the agent may re-plan its path (see
+    /// `AIProfile::repath_interval`)
+    pub replan_timer: f32,
+}
+
+/// Path invalidation system: Drops cached paths that pass through a changed part of the
+/// pathfinding graph and looks up the nodes of the rest (and of wander goals) in the new graph
+pub fn s_invalidate_changed_paths(
+    mut graph_changes: MessageReader<PathfindingGraphChanged>,
+    pathfinding: Res<PathfindingGraph>,
+    mut platformer_ai_query: Query<&mut PlatformerAI>,
+    mut pursue_ai_query: Query<&mut PursueAI>,
+) {
+    let regions: Vec<Option<Aabb>> = graph_changes.read().map(|change| change.region).collect();
+    if regions.is_empty() {
+        return;
+    }
+
+    // Agents walk a radius out from the nodes of their path
+    let changed_areas: Option<Vec<Aabb>> = regions
+        .into_iter()
+        .map(|region| region.map(|region| region.expand(PURSUE_AI_AGENT_RADIUS)))
+        .collect();
+    let crosses_change = |path: &[PathNode]| {
+        changed_areas.as_ref().is_none_or(|areas| {
+            areas.iter().any(|area| {
+                path.iter().any(|node| area.contains(node.position))
+                    || path
+                        .windows(2)
+                        .any(|step| area.intersects_segment(step[0].position, step[1].position))
+            })
+        })
+    };
+
+    for mut platformer_ai in platformer_ai_query.iter_mut() {
+        platformer_ai.cached_path = platformer_ai
+            .cached_path
+            .take()
+            .filter(|path| !crosses_change(path))
+            .and_then(|path| {
+                path.into_iter()
+                    .map(|node| {
+                        let id = pathfinding.node_at(node.position)?;
+                        Some(PathNode { id, ..node })
+                    })
+                    .collect()
+            });
+    }
+
+    for mut pursue_ai in pursue_ai_query.iter_mut() {
+        if pursue_ai.current_wander_goal.is_some() {
+            pursue_ai.current_wander_goal = pursue_ai
+                .wander_target
+                .and_then(|target| pathfinding.node_at(target));
+        }
+    }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn s_platformer_ai_movement(
     mut queries: ParamSet<(
-        Query<(
-            &mut Transform,
-            &mut AIPhysics,
-            &mut PlatformerAI,
-            &crate::ai::pursue_ai::PursueAI,
-        )>,
-        Query<&Transform, With<crate::Player>>,
+        Query<
+            (
+                Entity,
+                &mut Transform,
+                &mut KinematicBody,
+                &mut PlatformerAI,
+                &AIGoal,
+                &AITick,
+                &AIProfile,
+                Option<&mut AIMetrics>,
+            ),
+            Without<Asleep>,
+        >,
+        Query<&Transform, PlayerControlled>,
     )>,
     pathfinding: Res<PathfindingGraph>,
-    gizmos_visible: Res<crate::GizmosVisible>,
+    level: Option<Res<Level>>,
+    async_pathfinding: Res<AsyncPathfinding>,
+    path_tasks: Res<PathTasks>,
+    mut path_requests: MessageWriter<PathRequest>,
+    mut path_responses: MessageReader<PathResponse>,
+    spatial_index: Res<DynamicSpatialIndex>,
+    mut ai_rng: ResMut<AIRng>,
+    weather: Res<Weather>,
+    settings: Res<Settings>,
+    movement_config: Res<MovementConfig>,
     time: Res<Time>,
-    mut gizmos: Gizmos,
 ) {
-    // Get player position for Pursue state (read-only query)
-    let player_pos = queries.p1().single().map(|t| t.translation.xy()).ok();
+    // Get player positions for goals that follow the nearest player (read-only query)
+    let player_positions: Vec<Vec2> = queries
+        .p1()
+        .iter()
+        .map(|transform| transform.translation.xy())
+        .collect();
 
     // Process AI entities (mutable query)
-    for (mut transform, mut physics, mut platformer_ai, pursue_ai) in queries.p0().iter_mut()
+    let dt = time.delta_secs().min(1.0 / 30.0); // Clamp delta time
+    let gravity = movement_config.gravity;
+
+    // Levels whose graph came out empty or unusable still get chasing agents, just dumber ones
+    let graph_usable = pathfinding.is_usable();
+
+    // Paths planned in the background since last frame
+    let mut responses: HashMap<Entity, PathResponse> = path_responses
+        .read()
+        .map(|response| (response.agent, response.clone()))
+        .collect();
+
+    for (
+        entity,
+        mut transform,
+        mut physics,
+        mut platformer_ai,
+        goal,
+        ai_tick,
+        profile,
+        mut metrics,
+    ) in queries.p0().iter_mut()
     {
-        // Get goal position based on AI state
-        let goal_pos = match pursue_ai.state {
-            crate::ai::pursue_ai::PursueAIState::Pursue => {
-                // In Pursue state, use player position as goal
-                // If player doesn't exist, skip this AI entity
-                match player_pos {
-                    Some(pos) => pos,
+        // Get goal position from the agent's brain (`None` holds position)
+        let goal_pos = match goal.target {
+            GoalTarget::Hold => None,
+            GoalTarget::Position(position) => Some(position),
+            // If no player exists, skip this AI entity
+            GoalTarget::Player => {
+                match nearest_player(&player_positions, transform.translation.xy()) {
+                    Some(pos) => Some(pos),
                     None => continue,
                 }
             }
-            crate::ai::pursue_ai::PursueAIState::Wander => {
-                // In Wander state, use wander goal node
-                if let Some(wander_node_id) = pursue_ai.current_wander_goal {
-                    if let Some(wander_node) = pathfinding.nodes.get(wander_node_id) {
-                        wander_node.position
+        };
+
+        // Paths planned on the graph from before a change may use stale node ids (cached paths
+        // are taken care of by `s_invalidate_changed_paths`)
+        if pathfinding.is_changed() {
+            responses.remove(&entity);
+        } else if let Some(response) = responses.remove(&entity) {
+            adopt_path(
+                &mut platformer_ai,
+                &pathfinding,
+                level.as_deref(),
+                physics.radius,
+                response.goal,
+                response.path,
+                metrics.as_deref_mut(),
+            );
+        }
+
+        // Path following sets the corridor again if the agent is walking a path segment
+        platformer_ai.corridor = None;
+
+        let falling = physics.normal.length_squared() == 0.0;
+
+        // A jump is over once the agent touches a surface again
+        if !falling {
+            platformer_ai.jump_from_pos = None;
+            platformer_ai.jump_to_pos = None;
+        }
+
+        let avoid_hazards = goal.avoid_hazards;
+
+        // Paths are re-planned on the agent's AI tick, at most once per re-path interval
+        platformer_ai.replan_timer = (platformer_ai.replan_timer - dt).max(0.0);
+        let can_replan = ai_tick.ready && platformer_ai.replan_timer <= 0.0;
+
+        let (mut move_dir, jump_velocity, jump_from_node, jump_to_node) =
+            match (platformer_ai.jump_to_pos, goal_pos) {
+                // Mid-jump the agent is committed to its landing spot: no re-planning until it
+                // lands, and air control only if it is drifting away from where it was heading
+                (Some(jump_to_pos), _) => {
+                    let to_landing = jump_to_pos.x - transform.translation.x;
+                    let drifting = to_landing * physics.velocity.x <= 0.0
+                        && to_landing.abs() > AIR_CONTROL_DEADZONE;
+                    let air_dir = if drifting { to_landing.signum() } else { 0.0 };
+
+                    (Vec2::new(air_dir, 0.0), Vec2::ZERO, None, None)
+                }
+                // Without a usable graph there are no paths to plan, so head straight for the goal
+                (None, Some(goal_pos)) if !graph_usable => steer_without_graph(
+                    level.as_deref(),
+                    transform.translation.xy(),
+                    &physics,
+                    goal_pos,
+                    movement_config.ai_jump_velocity,
+                ),
+                (None, Some(goal_pos)) => {
+                    let position = transform.translation.xy();
+                    let needs_path = should_recalculate_path(
+                        &platformer_ai,
+                        position,
+                        goal_pos,
+                        &pathfinding,
+                        can_replan,
+                    );
+
+                    if !needs_path {
+                        if let Some(metrics) = metrics.as_deref_mut().filter(|_| can_replan) {
+                            metrics.record_cache_hit();
+                        }
+                    } else if async_pathfinding.enabled {
+                        // Keep following the old path until the new one arrives
+                        if !path_tasks.is_pending(entity) {
+                            path_requests.write(PathRequest {
+                                agent: entity,
+                                start: position,
+                                goal: goal_pos,
+                                avoid_hazards,
+                            });
+                        }
                     } else {
-                        Vec2::ZERO
+                        let path = plan_path(&pathfinding, position, goal_pos, avoid_hazards);
+                        adopt_path(
+                            &mut platformer_ai,
+                            &pathfinding,
+                            level.as_deref(),
+                            physics.radius,
+                            goal_pos,
+                            path,
+                            metrics.as_deref_mut(),
+                        );
                     }
-                } else {
-                    Vec2::ZERO
+
+                    get_move_inputs(
+                        pathfinding.as_ref(),
+                        position,
+                        &physics,
+                        &mut platformer_ai,
+                        goal_pos,
+                    )
                 }
-            }
-            _ => Vec2::ZERO, // Other states not implemented yet
-        };
+                (None, None) => (Vec2::ZERO, Vec2::ZERO, None, None),
+            };
 
-        let (move_dir, jump_velocity, jump_from_node, jump_to_node) = get_move_inputs(
-            pathfinding.as_ref(),
-            transform.translation.xy(),
-            &physics,
-            &mut platformer_ai,
-            &mut gizmos,
-            gizmos_visible.visible,
-            goal_pos,
-        );
+        if can_replan {
+            platformer_ai.replan_timer = profile.repath_interval;
+        }
 
-        // Draw move direction line
-        if gizmos_visible.visible {
-            gizmos.line_2d(
-                transform.translation.xy(),
-                transform.translation.xy() + move_dir * GIZMO_LINE_LENGTH,
-                Color::srgb(1.0, 0.0, 0.0),
-            );
+        // Less precise agents over- or undershoot their jumps
+        let jump_velocity = if jump_velocity != Vec2::ZERO && profile.jump_precision < 1.0 {
+            let error = (1.0 - profile.jump_precision.max(0.0)) * MAX_JUMP_ERROR;
+            jump_velocity * (1.0 + ai_rng.0.random_range(-error..=error))
+        } else {
+            jump_velocity
+        };
+
+        // Spread out from other agents on the same stretch of ground instead of stacking up on
+        // the same path (jumps stay committed to their landing spot)
+        if !falling && jump_velocity == Vec2::ZERO {
+            let push = separation(&spatial_index, entity, transform.translation.xy());
+            move_dir = (move_dir + Vec2::X * push * AVOIDANCE_WEIGHT).clamp_length_max(1.0);
         }
 
-        let dt = time.delta_secs().min(1.0 / 30.0); // Clamp delta time
+        // Remember the move direction for the AI debug layer
+        platformer_ai.move_dir = move_dir;
 
-        let falling = physics.normal.length_squared() == 0.0;
         let no_move_dir = move_dir.length_squared() == 0.0;
 
-        apply_movement_acceleration(&mut physics, &move_dir, falling, no_move_dir, dt);
+        let surface_friction = weather.surface_friction();
+        let air_control = platformer_ai.air_control;
+        let acceleration_scalers = movement_config.ai_acceleration_scalers;
+        let max_speed = WANDER_MAX_SPEED * profile.speed_multiplier;
+        let acceleration_at = |velocity: Vec2| {
+            movement_acceleration(
+                velocity,
+                move_dir,
+                max_speed,
+                falling,
+                no_move_dir,
+                surface_friction,
+                air_control,
+                acceleration_scalers,
+            )
+        };
+        // The closure is handed to the integrator below as well
+        #[allow(clippy::redundant_closure_call)]
+        let acceleration = acceleration_at(physics.velocity);
+        physics.acceleration = acceleration;
+
+        // Don't let momentum carry the agent further out of its path corridor
+        if let Some(outward) = platformer_ai
+            .corridor
+            .and_then(|corridor| corridor_escape_direction(transform.translation.xy(), corridor))
+        {
+            physics.velocity = reject_outward(physics.velocity, outward);
+        }
 
         // Apply gravity
         if falling {
             // Apply gravity directly to velocity when falling
-            physics.velocity.y -= GRAVITY_STRENGTH * dt;
+            physics.velocity.y -= gravity * dt;
         } else {
             // Apply gravity toward normal when on a surface
-            let gravity_normal_dir = physics.normal * GRAVITY_STRENGTH * dt;
+            let gravity_normal_dir = physics.normal * gravity * dt;
             physics.velocity += gravity_normal_dir;
         }
 
@@ -182,44 +432,111 @@ pub fn s_platformer_ai_movement(
             // If the player is trying to jump
             if jump_velocity.length_squared() > 0.0 && !falling {
                 // If on the ground
-                if physics.grounded {
+                if platformer_ai.grounded {
                     // Jump
                     physics.velocity = jump_velocity;
                     physics.acceleration.x = 0.0;
-                    physics.acceleration.y = -GRAVITY_STRENGTH;
-                    physics.grounded = false;
-                    physics.has_wall_jumped = false;
-                    physics.walled = 0;
+                    physics.acceleration.y = -gravity;
+                    platformer_ai.grounded = false;
+                    platformer_ai.has_wall_jumped = false;
+                    platformer_ai.walled = 0;
 
                     platformer_ai.jump_from_pos = jump_from_node;
                     platformer_ai.jump_to_pos = jump_to_node;
                 }
                 // If on a wall
-                else if physics.walled != 0 {
+                else if platformer_ai.walled != 0 {
                     // Wall jump
                     physics.velocity = jump_velocity;
                     physics.acceleration.x = 0.0;
-                    physics.acceleration.y = -GRAVITY_STRENGTH;
-                    physics.walled = 0;
-                    physics.grounded = false;
-                    physics.has_wall_jumped = true;
+                    physics.acceleration.y = -gravity;
+                    platformer_ai.walled = 0;
+                    platformer_ai.grounded = false;
+                    platformer_ai.has_wall_jumped = true;
                     platformer_ai.jump_from_pos = jump_from_node;
                     platformer_ai.jump_to_pos = jump_to_node;
                 }
             }
         }
 
-        update_physics_and_transform(&mut physics, &mut transform, dt);
+        update_physics_and_transform(
+            &mut physics,
+            &mut transform,
+            settings.integrator,
+            acceleration_at,
+            dt,
+        );
+    }
+}
+
+/// Steers straight at the goal without a path: runs toward it and jumps at walls in the way (and
+/// to climb toward a goal overhead while standing right under it)
+fn steer_without_graph(
+    level: Option<&Level>,
+    position: Vec2,
+    physics: &KinematicBody,
+    goal_pos: Vec2,
+    jump_velocity: f32,
+) -> (Vec2, Vec2, Option<Vec2>, Option<Vec2>) {
+    let to_goal = goal_pos - position;
+    if to_goal.x.abs() <= STEERING_ARRIVE_DISTANCE {
+        let climb = to_goal.y > STEERING_CLIMB_HEIGHT;
+        let jump_velocity = if climb {
+            Vec2::Y * jump_velocity
+        } else {
+            Vec2::ZERO
+        };
+        return (Vec2::ZERO, jump_velocity, None, None);
+    }
+
+    let direction = to_goal.x.signum();
+    let lookahead = position + Vec2::X * direction * STEERING_WALL_LOOKAHEAD;
+    let wall_ahead = level.is_some_and(|level| {
+        sweep_circle(level, position, lookahead, physics.radius)
+            .is_some_and(|(_, normal)| normal.x * direction < -STEERING_WALL_NORMAL_X)
+    });
+    let jump_velocity = if wall_ahead {
+        Vec2::new(direction * WANDER_MAX_SPEED, jump_velocity)
+    } else {
+        Vec2::ZERO
+    };
+
+    (Vec2::X * direction, jump_velocity, None, None)
+}
+
+/// Makes a freshly planned path the agent's own, walking straight across flat ground rather than
+/// node to node
+fn adopt_path(
+    platformer_ai: &mut PlatformerAI,
+    pathfinding: &PathfindingGraph,
+    level: Option<&Level>,
+    radius: f32,
+    goal_position: Vec2,
+    path: Option<Vec<PathNode>>,
+    metrics: Option<&mut AIMetrics>,
+) {
+    let path = match (path, level) {
+        (Some(path), Some(level)) => Some(smooth_path(pathfinding, level, &path, radius)),
+        (path, _) => path,
+    };
+    if let Some(metrics) = metrics {
+        let length = path
+            .as_ref()
+            .map(|path| path_length(path.iter().map(|node| node.position)));
+        metrics.record_plan(length);
     }
+
+    platformer_ai.cached_path = path;
+    platformer_ai.last_goal_position = Some(goal_position);
+    platformer_ai.current_path_index = 0;
 }
 
+/// Steering and jumps that follow the agent's cached path
 fn get_move_inputs(
     pathfinding: &PathfindingGraph,
     agent_position: Vec2,
-    agent_physics: &AIPhysics,
+    agent_physics: &KinematicBody,
     platformer_ai: &mut PlatformerAI,
-    gizmos: &mut Gizmos,
-    gizmos_visible: bool,
     goal_position: Vec2,
 ) -> (Vec2, Vec2, Option<Vec2>, Option<Vec2>) {
     let mut move_dir = Vec2::ZERO;
@@ -227,41 +544,9 @@ fn get_move_inputs(
     let mut jump_from_node = None;
     let mut jump_to_node = None;
 
-    // Check if cached path is still valid
-    let path_needs_recalculation =
-        should_recalculate_path(platformer_ai, agent_position, goal_position, pathfinding);
-
-    let path = if path_needs_recalculation {
-        // Recalculate path
-        let new_path = find_path(pathfinding, agent_position, goal_position);
-        if let Some(ref path_vec) = new_path {
-            platformer_ai.cached_path = Some(path_vec.clone());
-        } else {
-            platformer_ai.cached_path = None;
-        }
-        platformer_ai.last_goal_position = Some(goal_position);
-        platformer_ai.current_path_index = 0;
-        new_path
-    } else {
-        // Use cached path
-        platformer_ai.cached_path.clone()
-    };
+    let path = platformer_ai.cached_path.clone();
 
     if let Some(path) = &path {
-        // Draw path gizmos
-        if gizmos_visible {
-            let mut prev_pos = agent_position;
-            for node in path {
-                gizmos.circle_2d(
-                    node.position,
-                    PATHFINDING_NODE_GIZMO_RADIUS,
-                    Color::srgb(0.0, 1.0, 0.0),
-                );
-                gizmos.line_2d(prev_pos, node.position, Color::srgb(0.0, 1.0, 0.0));
-                prev_pos = node.position;
-            }
-        }
-
         // Use current_path_index to get the current and next nodes
         let current_idx = platformer_ai.current_path_index;
         
@@ -287,10 +572,12 @@ fn get_move_inputs(
 
                 let current_node_is_corner = corner_is_external.is_some();
 
-                let is_jumpable_connection = pathfinding.nodes[path[current_idx].id]
+                let jump_connection = pathfinding.nodes[path[current_idx].id]
                     .jumpable_connections
                     .iter()
-                    .any(|jumpable_connection| jumpable_connection.node_id == path[current_idx + 1].id);
+                    .find(|connection| connection.node_id == path[current_idx + 1].id);
+
+                let is_jumpable_connection = jump_connection.is_some();
 
                 let falling = agent_physics.normal.length_squared() <= 0.0;
 
@@ -319,7 +606,15 @@ fn get_move_inputs(
                     } else {
                         // Non-jumping corner
                         if current_node_is_corner {
-                            path_following_strategy = PathFollowingStrategy::AgentToNextNode;
+                            // Hug the corner on the way down, but aim above the lip when climbing
+                            // over it, or the agent pushes straight into the wall below it
+                            let climbing_over =
+                                path[current_idx + 1].position.y >= path[current_idx].position.y;
+                            path_following_strategy = if climbing_over {
+                                PathFollowingStrategy::AgentToNextNodeOffset
+                            } else {
+                                PathFollowingStrategy::AgentToNextNode
+                            };
                         }
                         // Non-jumping flat surface
                         else {
@@ -364,21 +659,32 @@ fn get_move_inputs(
                 }
                 .normalize_or_zero();
 
+                // Keep walking agents inside a corridor around the current path segment so
+                // steering can't drag them into wall pockets off the path
+                if !falling && !is_jumpable_connection {
+                    let corridor = (offset_current_node, offset_next_node);
+                    platformer_ai.corridor = Some(corridor);
+
+                    if let Some(outward) = corridor_escape_direction(agent_position, corridor) {
+                        let constrained = reject_outward(move_dir, outward);
+                        move_dir = if constrained.length_squared() > VELOCITY_MAGNITUDE_THRESHOLD {
+                            constrained.normalize()
+                        } else {
+                            // Nothing useful left, so head back towards the path
+                            -outward
+                        };
+                    }
+                }
+
                 // Jumping
                 if (path_following_strategy == PathFollowingStrategy::AgentToNextNodeOffset
                     || path_following_strategy == PathFollowingStrategy::AgentToNextNode)
                     && is_jumpable_connection
                 {
-                    let node_position_delta =
-                        path[current_idx + 1].position - path[current_idx].position;
-                    let gravity_acceleration = Vec2::new(0.0, -GRAVITY_STRENGTH);
-                    let jump_time = JUMP_TIME_MULTIPLIER
-                        * (4.0 * node_position_delta.dot(node_position_delta)
-                            / gravity_acceleration.dot(gravity_acceleration))
-                        .sqrt()
-                        .sqrt();
-                    jump_velocity =
-                        node_position_delta / jump_time - gravity_acceleration * jump_time / 2.0;
+                    // The arc the graph checked against the level, from where the agent stands
+                    jump_velocity = jump_connection
+                        .and_then(|connection| connection.launch_velocity)
+                        .unwrap_or_default();
 
                     jump_from_node = Some(offset_current_node);
                     jump_to_node = Some(offset_next_node);
@@ -392,7 +698,7 @@ fn get_move_inputs(
 
     // Advance path index if agent reached current node
     if let Some(ref path) = path {
-        advance_path_index(platformer_ai, agent_position, path);
+        advance_path_index(platformer_ai, pathfinding, agent_position, agent_physics.radius, path);
     }
 
     (move_dir, jump_velocity, jump_from_node, jump_to_node)
@@ -403,6 +709,7 @@ fn should_recalculate_path(
     agent_position: Vec2,
     goal_position: Vec2,
     _pathfinding: &PathfindingGraph,
+    can_replan: bool,
 ) -> bool {
     // If no cached path, recalculate
     let Some(ref cached_path) = platformer_ai.cached_path else {
@@ -414,6 +721,11 @@ fn should_recalculate_path(
         return true;
     }
 
+    // Otherwise the path is only re-planned on the agent's AI tick
+    if !can_replan {
+        return false;
+    }
+
     // If goal moved beyond threshold, recalculate
     if let Some(last_goal) = platformer_ai.last_goal_position {
         let goal_delta_sq = (goal_position - last_goal).length_squared();
@@ -424,9 +736,17 @@ fn should_recalculate_path(
         return true;
     }
 
-    // If agent deviated significantly from path, recalculate
-    if let Some(current_node) = cached_path.get(platformer_ai.current_path_index) {
-        let deviation_sq = (agent_position - current_node.position).length_squared();
+    // If agent deviated significantly from path, recalculate (measured from the segment being
+    // walked, as smoothed paths cross flat ground in one long step)
+    let index = platformer_ai.current_path_index;
+    if let Some(current_node) = cached_path.get(index) {
+        let segment_start = index
+            .checked_sub(1)
+            .and_then(|previous| cached_path.get(previous))
+            .map_or(current_node.position, |previous| previous.position);
+        let closest =
+            closest_point_on_segment(segment_start, current_node.position, agent_position);
+        let deviation_sq = (agent_position - closest).length_squared();
         if deviation_sq > PATH_DEVIATION_THRESHOLD_SQ {
             return true;
         }
@@ -435,7 +755,13 @@ fn should_recalculate_path(
     false
 }
 
-fn advance_path_index(platformer_ai: &mut PlatformerAI, agent_position: Vec2, path: &[PathNode]) {
+fn advance_path_index(
+    platformer_ai: &mut PlatformerAI,
+    pathfinding: &PathfindingGraph,
+    agent_position: Vec2,
+    agent_radius: f32,
+    path: &[PathNode],
+) {
     // Early return if path is empty
     if path.is_empty() {
         return;
@@ -443,8 +769,11 @@ fn advance_path_index(platformer_ai: &mut PlatformerAI, agent_position: Vec2, pa
     
     // Advance index if agent reached current node
     while platformer_ai.current_path_index < path.len() {
+        // Agents walk a radius out from the surface, so nodes are reached at that offset
         let current_node = &path[platformer_ai.current_path_index];
-        let distance_sq = (agent_position - current_node.position).length_squared();
+        let node_offset = current_node.position
+            + pathfinding.nodes[current_node.id].normal * agent_radius;
+        let distance_sq = (agent_position - node_offset).length_squared();
 
         // Use larger threshold for final node to match wander goal threshold
         let is_final_node = platformer_ai.current_path_index >= path.len().saturating_sub(1);
@@ -462,42 +791,91 @@ fn advance_path_index(platformer_ai: &mut PlatformerAI, agent_position: Vec2, pa
     }
 }
 
-fn apply_movement_acceleration(
-    physics: &mut AIPhysics,
-    move_dir: &Vec2,
+/// Direction pointing away from a path segment when the agent is further than
+/// `PATH_CORRIDOR_CLEARANCE` from it, or `None` while it is inside the corridor
+fn corridor_escape_direction(agent_position: Vec2, corridor: (Vec2, Vec2)) -> Option<Vec2> {
+    let (start, end) = corridor;
+    let segment = end - start;
+    let along_segment = ((agent_position - start).dot(segment)
+        / segment.length_squared().max(f32::EPSILON))
+    .clamp(0.0, 1.0);
+    let offset = agent_position - (start + segment * along_segment);
+
+    if offset.length_squared() <= PATH_CORRIDOR_CLEARANCE_SQ {
+        return None;
+    }
+
+    Some(offset.normalize())
+}
+
+/// Removes the part of a vector that points in the `outward` direction
+fn reject_outward(vector: Vec2, outward: Vec2) -> Vec2 {
+    let outward_amount = vector.dot(outward);
+    if outward_amount > 0.0 {
+        vector - outward * outward_amount
+    } else {
+        vector
+    }
+}
+
+/// Acceleration (pixels/second²) of an agent moving at `velocity` that steers along `move_dir`
+#[allow(clippy::too_many_arguments)]
+fn movement_acceleration(
+    velocity: Vec2,
+    move_dir: Vec2,
+    max_speed: f32,
     falling: bool,
     no_move_dir: bool,
-    _dt: f32,
-) {
-    // If the player is falling
+    surface_friction: f32,
+    air_control: f32,
+    acceleration_scalers: (f32, f32),
+) -> Vec2 {
+    // In the air the agent keeps its momentum and can only nudge its horizontal speed
     if falling {
-        physics.acceleration = Vec2::ZERO;
-        return;
+        if no_move_dir {
+            return Vec2::ZERO;
+        }
+
+        return Vec2::new(
+            (move_dir.x * max_speed - velocity.x) * acceleration_scalers.0 * air_control,
+            0.0,
+        );
     }
 
     // Apply acceleration (frame-rate independent)
-    physics.acceleration = (*move_dir * WANDER_MAX_SPEED - physics.velocity)
+    (move_dir * max_speed - velocity)
         * if no_move_dir {
             // Deacceleration
-            ACCELERATION_SCALERS.1
+            acceleration_scalers.1
         } else {
             // Acceleration
-            ACCELERATION_SCALERS.0
-        };
+            acceleration_scalers.0
+        }
+        * surface_friction
 }
 
 
-fn update_physics_and_transform(physics: &mut AIPhysics, transform: &mut Transform, dt: f32) {
+pub fn update_physics_and_transform(
+    physics: &mut KinematicBody,
+    transform: &mut Transform,
+    integrator: Integrator,
+    acceleration_at: impl Fn(Vec2) -> Vec2,
+    dt: f32,
+) {
     // Update previous position
     physics.prev_position = transform.translation.xy();
 
-    // Update velocity using semi-implicit Euler integration
-    physics.velocity += physics.acceleration * dt;
-
-    // Update position using new velocity (scaled by delta time)
-    let velocity_dt = physics.velocity * dt;
-    transform.translation.x += velocity_dt.x;
-    transform.translation.y += velocity_dt.y;
+    // Update velocity and position with the configured integrator
+    let (position, velocity) = integrator.step(
+        physics.prev_position,
+        physics.velocity,
+        physics.acceleration,
+        acceleration_at,
+        dt,
+    );
+    physics.velocity = velocity;
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
 }
 
 pub fn agent_on_other_side_next_frame(
@@ -519,3 +897,42 @@ pub fn agent_on_other_side_next_frame(
     agent_side_of_corner_current != agent_side_of_corner_next_frame
 }
 
+
+/// AI debug layer: Draws each agent's cached path, the node of it the agent is heading for and
+/// its steering direction
+pub fn s_debug_platformer_ai(
+    ai_query: Query<(&Transform, &PlatformerAI)>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, platformer_ai) in ai_query.iter() {
+        let agent_position = transform.translation.xy();
+
+        if let Some(path) = &platformer_ai.cached_path {
+            let mut prev_pos = agent_position;
+            for node in path {
+                gizmos.circle_2d(
+                    node.position,
+                    PATHFINDING_NODE_GIZMO_RADIUS,
+                    Color::srgb(0.0, 1.0, 0.0),
+                );
+                gizmos.line_2d(prev_pos, node.position, Color::srgb(0.0, 1.0, 0.0));
+                prev_pos = node.position;
+            }
+
+            if let Some(target) = path.get(platformer_ai.current_path_index) {
+                gizmos.cross_2d(
+                    target.position,
+                    TARGET_NODE_GIZMO_SIZE,
+                    Color::srgb(1.0, 1.0, 0.0),
+                );
+            }
+        }
+
+        // Draw move direction line
+        gizmos.line_2d(
+            agent_position,
+            agent_position + platformer_ai.move_dir * GIZMO_LINE_LENGTH,
+            Color::srgb(1.0, 0.0, 0.0),
+        );
+    }
+}
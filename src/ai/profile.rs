@@ -0,0 +1,108 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        query::Changed,
+        schedule::IntoScheduleConfigs,
+        system::Query,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    brain::behavior_tree::s_behavior_tree_brains, pursue_ai::s_pursue_ai_update,
+    vision::AIVision,
+};
+
+/// AI profile component: How skilled a single agent is, so some enemies can be easy and others
+/// ruthless in the same level. Works on top of the level-wide `AIDifficulty`
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct AIProfile {
+    /// How long (seconds) the agent takes to react to spotting the player
+    pub reaction_delay: f32,
+    /// Multiplier for the agent's top movement speed
+    pub speed_multiplier: f32,
+    /// Multiplier for the distance at which the agent detects the player
+    pub detection_range: f32,
+    /// How accurately the agent times its jumps (1 lands exactly where it aims, 0 misses by up to
+    /// `MAX_JUMP_ERROR` of the jump
+    /// speed)
+    pub jump_precision: f32,
+    /// Shortest time (seconds) between path re-plans (0 re-plans on every AI tick)
+    pub repath_interval: f32,
+}
+
+impl AIProfile {
+    /// Slow to react, slow, short-sighted and clumsy
+    pub const EASY: Self = Self {
+        reaction_delay: 0.6,
+        speed_multiplier: 0.75,
+        detection_range: 0.6,
+        jump_precision: 0.5,
+        repath_interval: 0.5,
+    };
+
+    /// The baseline every other profile is measured against
+    pub const NORMAL: Self = Self {
+        reaction_delay: 0.0,
+        speed_multiplier: 1.0,
+        detection_range: 1.0,
+        jump_precision: 1.0,
+        repath_interval: 0.0,
+    };
+
+    /// Quick, far-sighted and precise
+    pub const RUTHLESS: Self = Self {
+        reaction_delay: 0.0,
+        speed_multiplier: 1.25,
+        detection_range: 1.4,
+        jump_precision: 1.0,
+        repath_interval: 0.0,
+    };
+}
+
+impl Default for AIProfile {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+/// Preset profiles that levels, encounters and scenarios can pick by name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AIProfilePreset {
+    Easy,
+    #[default]
+    Normal,
+    Ruthless,
+}
+
+impl AIProfilePreset {
+    pub fn profile(self) -> AIProfile {
+        match self {
+            AIProfilePreset::Easy => AIProfile::EASY,
+            AIProfilePreset::Normal => AIProfile::NORMAL,
+            AIProfilePreset::Ruthless => AIProfile::RUTHLESS,
+        }
+    }
+}
+
+pub struct AIProfilePlugin;
+
+impl Plugin for AIProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            s_apply_ai_profiles
+                .before(s_pursue_ai_update)
+                .before(s_behavior_tree_brains),
+        );
+    }
+}
+
+/// AI profile system: Scales the vision range of agents whose profile was set or changed
+pub fn s_apply_ai_profiles(mut ai_query: Query<(&AIProfile, &mut AIVision), Changed<AIProfile>>) {
+    for (profile, mut vision) in ai_query.iter_mut() {
+        vision.range_scale = profile.detection_range;
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::{
+    ecs::{
+        message::MessageWriter,
+        query::{With, Without},
+        system::Query,
+    },
+    math::{Vec2, Vec3, Vec3Swizzles},
+    transform::components::Transform,
+};
+
+use crate::{
+    ai::{activity::Asleep, brain::Brain, difficulty::AIDifficulty},
+    characters::PlayerControlled,
+    health::Health,
+    knockback::{apply_knockback, Mass},
+    screen_shake::ScreenShake,
+    KinematicBody, Player,
+};
+
+use super::{PursueAI, PursueAIState};
+
+// Lunge: a hop at the player, carried by momentum (units: pixels/second, seconds)
+const ATTACK_LUNGE_SPEED: f32 = 420.0;
+const ATTACK_LUNGE_LIFT: f32 = 180.0;
+const ATTACK_LUNGE_DURATION: f32 = 0.35;
+// Damage a lunge deals to the player it touches
+const ATTACK_DAMAGE: f32 = 1.0;
+// Knockback dealt with the damage (pixels/second for a unit mass body)
+const ATTACK_KNOCKBACK: f32 = 360.0;
+// Gap (pixels) between the bodies within which a lunge still connects
+const ATTACK_HIT_MARGIN: f32 = 4.0;
+// Screen shake trauma from a lunge that connects (0 to 1)
+const ATTACK_SHAKE_TRAUMA: f32 = 0.6;
+// Telegraph: agents crouch while winding up
+const ATTACK_WINDUP_SCALE: Vec3 = Vec3::new(1.25, 0.75, 1.0);
+
+/// Stages of an attack, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttackPhase {
+    /// Standing still and crouching (the telegraph)
+    Windup,
+    /// Hopping at the player; touching them deals damage
+    Lunge,
+    /// Recovering before the next attack
+    Cooldown,
+}
+
+/// What an attacking agent is doing
+#[derive(Clone, Copy, Debug)]
+pub struct AttackBehavior {
+    pub phase: AttackPhase,
+    /// When the current phase ends (elapsed seconds)
+    pub until: f32,
+    /// Whether the lunge has hit the player (each lunge hits at most once)
+    pub hit: bool,
+}
+
+impl AttackBehavior {
+    /// Starts winding up an attack
+    pub fn new(now: f32, difficulty: &AIDifficulty) -> Self {
+        Self {
+            phase: AttackPhase::Windup,
+            until: now + difficulty.attack_windup,
+            hit: false,
+        }
+    }
+}
+
+/// Runs the attack state for one AI decision: wind up, lunge at the player, cool down, then attack
+/// again if they are still in range or go back to chasing them
+pub fn attack_update(
+    transform: &Transform,
+    physics: &mut KinematicBody,
+    attack: &mut AttackBehavior,
+    difficulty: &AIDifficulty,
+    now: f32,
+    player_position: Vec2,
+) -> Option<PursueAIState> {
+    if now < attack.until {
+        return None;
+    }
+
+    let agent_position = transform.translation.xy();
+
+    match attack.phase {
+        AttackPhase::Windup => {
+            let direction = (player_position - agent_position).normalize_or(Vec2::X);
+            physics.velocity = direction * ATTACK_LUNGE_SPEED + Vec2::Y * ATTACK_LUNGE_LIFT;
+            attack.phase = AttackPhase::Lunge;
+            attack.until = now + ATTACK_LUNGE_DURATION;
+            None
+        }
+        AttackPhase::Lunge => {
+            attack.phase = AttackPhase::Cooldown;
+            attack.until = now + difficulty.attack_cooldown;
+            None
+        }
+        AttackPhase::Cooldown => {
+            if agent_position.distance(player_position) <= difficulty.attack_range {
+                *attack = AttackBehavior::new(now, difficulty);
+                None
+            } else {
+                Some(PursueAIState::Pursue)
+            }
+        }
+    }
+}
+
+/// Attack hit system: Damages and knocks back the nearest player a lunging agent touches, and
+/// shakes the screen
+#[allow(clippy::type_complexity)]
+pub fn s_attack_hits(
+    mut ai_query: Query<
+        (&Transform, &KinematicBody, &mut PursueAI, &Brain),
+        (Without<Player>, Without<Asleep>),
+    >,
+    mut player_query: Query<
+        (&Transform, &mut KinematicBody, &mut Health, Option<&Mass>),
+        (With<Player>, PlayerControlled),
+    >,
+    mut screen_shake: MessageWriter<ScreenShake>,
+) {
+    for (transform, physics, mut pursue_ai, brain) in ai_query.iter_mut() {
+        if !matches!(brain, Brain::StateMachine) {
+            continue;
+        }
+        let Some(attack) = pursue_ai.attack.as_mut() else {
+            continue;
+        };
+        if attack.phase != AttackPhase::Lunge || attack.hit {
+            continue;
+        }
+
+        let agent_position = transform.translation.xy();
+        let Some((player_position, mut player_physics, mut health, mass)) = player_query
+            .iter_mut()
+            .map(|(player_transform, player_physics, health, mass)| {
+                (player_transform.translation.xy(), player_physics, health, mass)
+            })
+            .filter(|(player_position, player_physics, ..)| {
+                let reach = physics.radius + player_physics.radius + ATTACK_HIT_MARGIN;
+                agent_position.distance_squared(*player_position) <= reach * reach
+            })
+            .min_by(|(a, ..), (b, ..)| {
+                a.distance_squared(agent_position)
+                    .total_cmp(&b.distance_squared(agent_position))
+            })
+        else {
+            continue;
+        };
+
+        attack.hit = true;
+        if health.damage(ATTACK_DAMAGE) {
+            let direction = (player_position - agent_position).normalize_or(Vec2::Y);
+            apply_knockback(&mut player_physics.velocity, direction * ATTACK_KNOCKBACK, mass);
+            screen_shake.write(ScreenShake(ATTACK_SHAKE_TRAUMA));
+        }
+    }
+}
+
+/// Attack telegraph system: Agents crouch while winding up an attack
+pub fn s_attack_telegraph(mut ai_query: Query<(&mut Transform, &PursueAI, &Brain)>) {
+    for (mut transform, pursue_ai, brain) in ai_query.iter_mut() {
+        let winding_up = matches!(brain, Brain::StateMachine)
+            && pursue_ai
+                .attack
+                .is_some_and(|attack| attack.phase == AttackPhase::Windup);
+        let scale = if winding_up { ATTACK_WINDUP_SCALE } else { Vec3::ONE };
+
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+    }
+}
@@ -1,22 +1,55 @@
+pub mod attack;
 pub mod movement;
+pub mod pursue;
+pub mod search;
 pub mod wander;
 
 use bevy::{
     app::{App, Plugin, Update},
     ecs::{
         component::Component,
-        query::With,
-        system::{ParamSet, Query, Res},
+        entity::Entity,
+        message::MessageWriter,
+        query::Without,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res, ResMut},
     },
-    math::Vec3Swizzles,
+    math::{Vec2, Vec3Swizzles},
+    prelude::Resource,
+    time::Time,
     transform::components::Transform,
 };
+use rand::{rngs::StdRng, SeedableRng};
 
+use crate::{
+    ai::difficulty::AIDifficulty,
+    health::s_respawn,
+    level::Level,
+    lighting::TimeOfDay,
+    spatial::DynamicSpatialIndex,
+    KinematicBody,
+};
+
+use super::activity::Asleep;
+use super::alert::AIAlert;
+use super::brain::Brain;
 use super::pathfinding::PathfindingGraph;
-use super::platformer_ai::AIPhysics;
+use super::platformer_ai::s_platformer_ai_movement;
+use super::profile::AIProfile;
+use super::territory::Territory;
+use super::tick::AITick;
+use super::vision::AIVision;
+use attack::{s_attack_hits, s_attack_telegraph, AttackBehavior};
+use pursue::PursueBehavior;
+use search::SearchBehavior;
+use wander::WanderBehavior;
 
 pub const PURSUE_AI_AGENT_RADIUS: f32 = 8.0;
 
+// How far beyond the detection range wandering agents can still see (and taunt) the player
+const SIGHT_RANGE_MULTIPLIER: f32 = 1.5;
+
+#[derive(Debug)]
 pub enum PursueAIState {
     Wander,
     Pursue,
@@ -28,7 +61,26 @@ pub struct PursueAIPlugin;
 
 impl Plugin for PursueAIPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<AIRng>();
+        app.insert_resource(AIDifficulty::load());
         app.add_systems(Update, s_pursue_ai_update);
+        app.add_systems(
+            Update,
+            (
+                s_attack_hits.after(s_platformer_ai_movement).before(s_respawn),
+                s_attack_telegraph.after(s_platformer_ai_movement),
+            ),
+        );
+    }
+}
+
+/// Random number generator shared by AI decisions (seeded by `s_init`, so daily runs replay)
+#[derive(Resource)]
+pub struct AIRng(pub StdRng);
+
+impl Default for AIRng {
+    fn default() -> Self {
+        Self(StdRng::from_os_rng())
     }
 }
 
@@ -36,62 +88,248 @@ impl Plugin for PursueAIPlugin {
 pub struct PursueAI {
     pub state: PursueAIState,
     pub current_wander_goal: Option<usize>,
+    /// Idle flourish (or plain travel) while wandering
+    pub wander_behavior: WanderBehavior,
+    /// Where the agent moves to while wandering (`None` holds position)
+    pub wander_target: Option<Vec2>,
+    /// Chasing the player, or holding near them while they are unreachable
+    pub pursue_behavior: PursueBehavior,
+    /// Where and when (elapsed seconds) the player was last detected
+    pub last_seen: Option<(Vec2, f32)>,
+    /// When (elapsed seconds) the agent spotted the player it can currently see, for its
+    /// reaction delay
+    pub spotted_at: Option<f32>,
+    /// Where the agent is looking for a player it lost track of (only used while searching)
+    pub search: Option<SearchBehavior>,
+    /// How far through its attack the agent is (only used while attacking)
+    pub attack: Option<AttackBehavior>,
 }
 
+impl PursueAI {
+    pub fn new(state: PursueAIState) -> Self {
+        Self {
+            state,
+            current_wander_goal: None,
+            wander_behavior: WanderBehavior::default(),
+            wander_target: None,
+            pursue_behavior: PursueBehavior::default(),
+            last_seen: None,
+            spotted_at: None,
+            search: None,
+            attack: None,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn s_pursue_ai_update(
-    mut queries: ParamSet<(
-        Query<(&mut Transform, &mut AIPhysics, &mut PursueAI)>,
-        Query<&Transform, With<crate::Player>>,
-    )>,
+    mut ai_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut KinematicBody,
+            &mut PursueAI,
+            &AIVision,
+            &Brain,
+            &AITick,
+            &AIProfile,
+            Option<&Territory>,
+        ),
+        Without<Asleep>,
+    >,
     pathfinding: Res<PathfindingGraph>,
+    level: Option<Res<Level>>,
+    time_of_day: Res<TimeOfDay>,
+    spatial_index: Res<DynamicSpatialIndex>,
+    difficulty: Res<AIDifficulty>,
+    mut ai_rng: ResMut<AIRng>,
+    mut alerts: MessageWriter<AIAlert>,
+    time: Res<Time>,
 ) {
-    // Get player position for detection (read-only query)
-    let player_pos = queries.p1().single().map(|t| t.translation.xy()).ok();
+    // Vision range shrinks at night
+    let detection_range = difficulty.detection_range * time_of_day.vision_multiplier();
+    let now = time.elapsed_secs();
+
+    for (
+        entity,
+        mut transform,
+        mut physics,
+        mut pursue_ai,
+        vision,
+        brain,
+        ai_tick,
+        profile,
+        territory,
+    ) in ai_query.iter_mut()
+    {
+        // Decisions only run on the agent's AI tick, and only for agents this state machine drives
+        if !ai_tick.ready || !matches!(brain, Brain::StateMachine) {
+            continue;
+        }
 
-    // Process AI entities (mutable query)
-    for (mut transform, mut physics, mut pursue_ai) in queries.p0().iter_mut() {
         let ai_pos = transform.translation.xy();
-        
-        // Simple distance-based detection: if player is within range, pursue
-        const DETECTION_RANGE_SQ: f32 = 500.0 * 500.0; // 500 pixels detection range
-        
-        let should_pursue = if let Some(player_position) = player_pos {
-            let distance_sq = (ai_pos - player_position).length_squared();
-            distance_sq <= DETECTION_RANGE_SQ
-        } else {
-            false
-        };
+
+        // Pursue a player in sight (agents already chasing keep track of them whichever way they
+        // face, until they duck behind a wall)
+        let tracking = matches!(pursue_ai.state, PursueAIState::Pursue | PursueAIState::Attack);
+        let detected_player = vision.visible_player(
+            &spatial_index,
+            level.as_deref(),
+            ai_pos,
+            detection_range,
+            tracking,
+        );
+
+        // Agents with a territory only go after players inside it, and give up on one that leaves
+        let out_of_bounds = |position: Vec2| territory.is_some_and(|t| !t.contains(position));
+        let pursued_player = detected_player.filter(|&position| !out_of_bounds(position));
+        let leave_chase = !matches!(pursue_ai.state, PursueAIState::Wander)
+            && detected_player.is_some_and(out_of_bounds);
+
+        // A player that has only just come into view takes the agent's reaction delay to act on
+        pursue_ai.spotted_at = pursued_player.map(|_| pursue_ai.spotted_at.unwrap_or(now));
+        let should_pursue = pursue_ai
+            .spotted_at
+            .is_some_and(|spotted_at| tracking || now - spotted_at >= profile.reaction_delay);
 
         let next_state: Option<PursueAIState> = match pursue_ai.state {
+            _ if leave_chase => Some(PursueAIState::Wander),
             PursueAIState::Wander => {
                 if should_pursue {
                     // Transition to Pursue when player detected
                     Some(PursueAIState::Pursue)
                 } else {
-                    // Continue wandering
+                    // Continue wandering (a player further away may still be in sight)
+                    let visible_player = vision.visible_player(
+                        &spatial_index,
+                        level.as_deref(),
+                        ai_pos,
+                        detection_range * SIGHT_RANGE_MULTIPLIER,
+                        false,
+                    );
+
                     wander::wander_update(
                         &mut transform,
                         &mut physics,
                         &mut pursue_ai,
                         pathfinding.as_ref(),
+                        territory,
+                        &mut ai_rng.0,
+                        now,
+                        visible_player,
                     )
                 }
             }
-            PursueAIState::Pursue => {
-                if !should_pursue {
-                    // Transition back to Wander if player is out of range
-                    Some(PursueAIState::Wander)
+            PursueAIState::Pursue => match pursued_player {
+                // Continue pursuing (or holding, if the player can't be reached)
+                Some(player_position) => {
+                    pursue_ai.last_seen = Some((player_position, now));
+                    pursue::pursue_update(
+                        &transform,
+                        &mut pursue_ai,
+                        pathfinding.as_ref(),
+                        now,
+                        player_position,
+                    );
+
+                    // Attack a player that is close enough (and can be reached)
+                    let in_range = ai_pos.distance(player_position) <= difficulty.attack_range;
+                    if in_range && pursue_ai.pursue_behavior.hold_target().is_none() {
+                        pursue_ai.attack = Some(AttackBehavior::new(now, &difficulty));
+                        Some(PursueAIState::Attack)
+                    } else {
+                        None
+                    }
+                }
+                // Search where the player was last seen once they have been lost for long enough
+                None => match pursue_ai.last_seen {
+                    Some((last_seen, seen_at)) => {
+                        if now - seen_at >= difficulty.lose_detection_time {
+                            pursue_ai.search =
+                                Some(SearchBehavior::new(last_seen, now, &difficulty));
+                            Some(PursueAIState::Search)
+                        } else {
+                            None
+                        }
+                    }
+                    None => Some(PursueAIState::Wander),
+                },
+            },
+            PursueAIState::Search => {
+                if should_pursue {
+                    // Transition back to Pursue when the player is found
+                    Some(PursueAIState::Pursue)
                 } else {
-                    // Continue pursuing
-                    None
+                    match pursue_ai.search.as_mut() {
+                        // Only running out of search time returns to Wander
+                        Some(search) => search::search_update(
+                            &transform,
+                            search,
+                            pathfinding.as_ref(),
+                            &mut ai_rng.0,
+                            &difficulty,
+                            now,
+                        ),
+                        None => Some(PursueAIState::Wander),
+                    }
                 }
             }
-            // PursueAIState::Search => {}
-            // PursueAIState::Attack => {}
-            _ => None,
+            PursueAIState::Attack => match (pursued_player, pursue_ai.attack.as_mut()) {
+                (Some(player_position), Some(attack)) => {
+                    let next_state = attack::attack_update(
+                        &transform,
+                        &mut physics,
+                        attack,
+                        &difficulty,
+                        now,
+                        player_position,
+                    );
+                    pursue_ai.last_seen = Some((player_position, now));
+                    next_state
+                }
+                // Chase (and eventually search for) a player that got away
+                _ => Some(PursueAIState::Pursue),
+            },
         };
 
         if let Some(new_state) = next_state {
+            // Start wandering afresh rather than resuming an old flourish
+            if let PursueAIState::Wander = new_state {
+                pursue_ai.wander_behavior = WanderBehavior::default();
+                pursue_ai.current_wander_goal = None;
+                // Head home first after giving up on a player who left the territory
+                if leave_chase {
+                    pursue_ai.current_wander_goal =
+                        territory.and_then(|territory| territory.home_node(&pathfinding));
+                    pursue_ai.wander_target = pursue_ai
+                        .current_wander_goal
+                        .and_then(|goal_node_id| pathfinding.nodes.get(goal_node_id))
+                        .map(|goal_node| goal_node.position);
+                }
+            }
+            // Check reachability straight away when a chase starts
+            if let PursueAIState::Pursue = new_state {
+                pursue_ai.pursue_behavior = PursueBehavior::default();
+                // A player that got away from an attack is still remembered
+                if let Some(position) = pursued_player {
+                    pursue_ai.last_seen = Some((position, now));
+                }
+
+                // Shout to nearby allies
+                if let Some(target) = pursued_player {
+                    alerts.write(AIAlert {
+                        shouter: entity,
+                        origin: ai_pos,
+                        target,
+                    });
+                }
+            }
+            if !matches!(new_state, PursueAIState::Search) {
+                pursue_ai.search = None;
+            }
+            if !matches!(new_state, PursueAIState::Attack) {
+                pursue_ai.attack = None;
+            }
             pursue_ai.state = new_state;
         }
     }
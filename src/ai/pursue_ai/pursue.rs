@@ -0,0 +1,159 @@
+use bevy::{
+    math::{Vec2, Vec3Swizzles},
+    transform::components::Transform,
+};
+
+use crate::ai::{
+    a_star::{find_path, nearest_reachable_node},
+    pathfinding::PathfindingGraph,
+};
+
+use super::PursueAI;
+
+// How often a chasing agent checks that the player can still be reached (seconds)
+const REACHABILITY_CHECK_INTERVAL: f32 = 0.5;
+// How often a holding agent checks whether the player has become reachable again (seconds)
+const REACHABILITY_RECHECK_INTERVAL: f32 = 1.5;
+
+// Hold: pace beside the closest reachable node, stopping to glare at the player at the end
+// nearest them (units: pixels, seconds)
+const HOLD_PACE_DISTANCE: f32 = 24.0;
+const HOLD_TURN_THRESHOLD: f32 = 6.0;
+const HOLD_GLARE_DURATION: f32 = 1.2;
+
+/// What a pursuing agent is currently up to
+#[derive(Clone, Copy, Debug)]
+pub enum PursueBehavior {
+    /// Following a path to the player
+    Chase { next_reachability_check: f32 },
+    /// The player can't be reached: wait at the closest reachable spot and glare at them
+    Hold {
+        anchor: Vec2,
+        side: f32,
+        glare_until: f32,
+        next_recheck: f32,
+        target: Vec2,
+    },
+}
+
+impl Default for PursueBehavior {
+    fn default() -> Self {
+        PursueBehavior::Chase {
+            next_reachability_check: 0.0,
+        }
+    }
+}
+
+impl PursueBehavior {
+    /// Where a holding agent should move to (`None` while chasing the player)
+    pub fn hold_target(&self) -> Option<Vec2> {
+        match self {
+            PursueBehavior::Chase { .. } => None,
+            PursueBehavior::Hold { target, .. } => Some(*target),
+        }
+    }
+}
+
+/// Runs the pursue state for one AI decision.
+///
+/// Switches between chasing the player and holding near them when the path planner reports them
+/// unreachable, so the agent stays in Pursue instead of flip-flopping with Wander.
+pub fn pursue_update(
+    transform: &Transform,
+    pursue_ai: &mut PursueAI,
+    pathfinding: &PathfindingGraph,
+    now: f32,
+    player_position: Vec2,
+) {
+    let agent_position = transform.translation.xy();
+
+    // Without a usable graph there's no telling whether the player can be reached, so the agent
+    // keeps chasing (steering straight at them)
+    if !pathfinding.is_usable() {
+        pursue_ai.pursue_behavior = PursueBehavior::default();
+        return;
+    }
+
+    match pursue_ai.pursue_behavior {
+        PursueBehavior::Chase {
+            next_reachability_check,
+        } => {
+            if now < next_reachability_check {
+                return;
+            }
+
+            if find_path(pathfinding, agent_position, player_position).is_some() {
+                pursue_ai.pursue_behavior = PursueBehavior::Chase {
+                    next_reachability_check: now + REACHABILITY_CHECK_INTERVAL,
+                };
+            } else {
+                start_hold(pursue_ai, pathfinding, agent_position, player_position, now);
+            }
+        }
+        PursueBehavior::Hold {
+            anchor,
+            mut side,
+            mut glare_until,
+            next_recheck,
+            ..
+        } => {
+            // Periodically see whether the player has come within reach
+            if now >= next_recheck {
+                if find_path(pathfinding, agent_position, player_position).is_some() {
+                    pursue_ai.pursue_behavior = PursueBehavior::default();
+                } else {
+                    // The player may have moved, so find the best spot again
+                    start_hold(pursue_ai, pathfinding, agent_position, player_position, now);
+                }
+                return;
+            }
+
+            let player_side = if player_position.x < anchor.x { -1.0 } else { 1.0 };
+
+            let target = if now < glare_until {
+                // Stand still at the end nearest the player
+                anchor + Vec2::X * player_side * HOLD_PACE_DISTANCE
+            } else {
+                // Turn around at each end, glaring when reaching the player's side
+                let pace_end = anchor + Vec2::X * side * HOLD_PACE_DISTANCE;
+                if (agent_position.x - pace_end.x).abs() < HOLD_TURN_THRESHOLD {
+                    if side == player_side {
+                        glare_until = now + HOLD_GLARE_DURATION;
+                    }
+                    side = -side;
+                }
+                anchor + Vec2::X * side * HOLD_PACE_DISTANCE
+            };
+
+            pursue_ai.pursue_behavior = PursueBehavior::Hold {
+                anchor,
+                side,
+                glare_until,
+                next_recheck,
+                target,
+            };
+        }
+    }
+}
+
+/// Starts holding at the reachable node closest to the player
+fn start_hold(
+    pursue_ai: &mut PursueAI,
+    pathfinding: &PathfindingGraph,
+    agent_position: Vec2,
+    player_position: Vec2,
+    now: f32,
+) {
+    let anchor = nearest_reachable_node(pathfinding, agent_position, player_position)
+        .map(|node_id| pathfinding.nodes[node_id].position)
+        .unwrap_or(agent_position);
+    let side = if player_position.x < anchor.x { -1.0 } else { 1.0 };
+
+    pursue_ai.pursue_behavior = PursueBehavior::Hold {
+        anchor,
+        side,
+        glare_until: 0.0,
+        next_recheck: now + REACHABILITY_RECHECK_INTERVAL,
+        target: anchor,
+    };
+}
@@ -0,0 +1,78 @@
+use bevy::{
+    math::{Vec2, Vec3Swizzles},
+    transform::components::Transform,
+};
+use rand::Rng;
+
+use crate::ai::{difficulty::AIDifficulty, pathfinding::PathfindingGraph};
+
+use super::{PursueAIState, PURSUE_AI_AGENT_RADIUS};
+
+// Distance at which the last seen position or a patrol point counts as reached (pixels)
+const SEARCH_POINT_REACHED_THRESHOLD: f32 = 12.0;
+// Patrol points are picked at least this far from the agent, so it doesn't stand still (pixels)
+const SEARCH_MIN_HOP: f32 = 24.0;
+
+/// Where a searching agent is looking for a player it lost track of
+#[derive(Clone, Copy, Debug)]
+pub struct SearchBehavior {
+    /// Where the player was last detected
+    pub last_seen: Vec2,
+    /// When the agent gives up and goes back to wandering (seconds)
+    pub until: f32,
+    /// Whether the agent has reached `last_seen` and is patrolling around it
+    pub sweeping: bool,
+    /// Which side of `last_seen` the agent sweeps towards when there are no nodes to patrol
+    pub side: f32,
+    /// Where the agent is moving to
+    pub target: Vec2,
+}
+
+impl SearchBehavior {
+    pub fn new(last_seen: Vec2, now: f32, difficulty: &AIDifficulty) -> Self {
+        Self {
+            last_seen,
+            until: now + difficulty.search_duration,
+            sweeping: false,
+            side: 1.0,
+            target: last_seen,
+        }
+    }
+}
+
+/// Runs the search state for one AI decision: head to the last seen position, patrol the ground
+/// nodes around it one after another, and go back to wandering once the search times out
+pub fn search_update(
+    transform: &Transform,
+    search: &mut SearchBehavior,
+    pathfinding: &PathfindingGraph,
+    rng: &mut impl Rng,
+    difficulty: &AIDifficulty,
+    now: f32,
+) -> Option<PursueAIState> {
+    if now >= search.until {
+        return Some(PursueAIState::Wander);
+    }
+
+    let agent_position = transform.translation.xy();
+
+    if (agent_position.x - search.target.x).abs() < SEARCH_POINT_REACHED_THRESHOLD {
+        search.sweeping = true;
+        search.target = pathfinding
+            .random_ground_position_near(
+                rng,
+                search.last_seen,
+                difficulty.search_radius,
+                agent_position,
+                SEARCH_MIN_HOP,
+                PURSUE_AI_AGENT_RADIUS,
+            )
+            .unwrap_or_else(|| {
+                // Sweep back and forth where there is nothing to patrol
+                search.side = -search.side;
+                search.last_seen + Vec2::X * search.side * difficulty.search_radius
+            });
+    }
+
+    None
+}
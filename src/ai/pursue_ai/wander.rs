@@ -4,9 +4,13 @@ use bevy::{
 };
 use rand::prelude::*;
 
-use crate::ai::{
-    pathfinding::{PathfindingGraph, PathfindingGraphNode},
-    platformer_ai::AIPhysics,
+use crate::{
+    ai::{
+        a_star::find_path,
+        pathfinding::{PathfindingGraph, PathfindingGraphNode},
+        territory::Territory,
+    },
+    KinematicBody,
 };
 
 use super::PursueAI;
@@ -18,23 +22,213 @@ const WANDER_GOAL_REACHED_THRESHOLD: f32 = 30.0; // Distance threshold for consi
 const WANDER_GOAL_REACHED_THRESHOLD_SQ: f32 =
     WANDER_GOAL_REACHED_THRESHOLD * WANDER_GOAL_REACHED_THRESHOLD; // 900.0 squared
 
+// Chances of each idle flourish when a wander goal is reached (the rest travel straight on)
+const LOOK_AROUND_CHANCE: f64 = 0.35;
+const PACE_CHANCE: f64 = 0.25;
+
+// Look around: shuffle a few pixels one way then the other (units: pixels, seconds)
+const LOOK_AROUND_STEP: f32 = 4.0;
+const LOOK_AROUND_TURN_INTERVAL: f32 = 0.7;
+const LOOK_AROUND_DURATION: (f32, f32) = (1.5, 3.5);
+
+// Pace: walk back and forth around the node that was reached (units: pixels, seconds)
+const PACE_DISTANCE: f32 = 40.0;
+const PACE_TURN_THRESHOLD: f32 = 8.0;
+const PACE_DURATION: (f32, f32) = (3.0, 6.0);
+
+// Taunt: hop on the spot at a player who is in sight but out of reach (units: seconds)
+const TAUNT_DURATION: (f32, f32) = (1.5, 3.0);
+const TAUNT_HOP_INTERVAL: f32 = 0.5;
+const TAUNT_HOP_VELOCITY: f32 = 250.0; // pixels/second
+// How often a travelling agent checks whether a player it can see is reachable (seconds)
+const TAUNT_CHECK_INTERVAL: f32 = 1.0;
+
+/// What a wandering agent is currently up to
+#[derive(Clone, Copy, Debug)]
+pub enum WanderBehavior {
+    /// Walking to the current wander goal
+    Travel { next_taunt_check: f32 },
+    /// Pausing and looking one way then the other
+    LookAround {
+        anchor: Vec2,
+        side: f32,
+        next_turn: f32,
+        until: f32,
+    },
+    /// Patrolling back and forth near a node
+    Pace { anchor: Vec2, side: f32, until: f32 },
+    /// Hopping at a player who is in sight but can't be reached
+    Taunt { next_hop: f32, until: f32 },
+}
+
+impl Default for WanderBehavior {
+    fn default() -> Self {
+        WanderBehavior::Travel {
+            next_taunt_check: 0.0,
+        }
+    }
+}
+
+/// Runs the wander state for one AI decision.
+///
+/// `now` is the elapsed time in seconds and `visible_player` is the position of a player the
+/// agent can see but isn't pursuing. Agents with a `territory` only pick wander goals inside it.
+#[allow(clippy::too_many_arguments)]
 pub fn wander_update(
     transform: &mut Transform,
-    _physics: &mut AIPhysics,
+    physics: &mut KinematicBody,
     pursue_ai: &mut PursueAI,
     pathfinding: &PathfindingGraph,
+    territory: Option<&Territory>,
+    rng: &mut impl Rng,
+    now: f32,
+    visible_player: Option<Vec2>,
 ) -> Option<PursueAIState> {
-    wander_movement(transform, pursue_ai, pathfinding);
+    let agent_position = transform.translation.xy();
+
+    match pursue_ai.wander_behavior {
+        WanderBehavior::Travel { next_taunt_check } => {
+            // Taunt a player in sight if there is no way to get to them
+            if let Some(player_position) = visible_player {
+                if now >= next_taunt_check {
+                    pursue_ai.wander_behavior = WanderBehavior::Travel {
+                        next_taunt_check: now + TAUNT_CHECK_INTERVAL,
+                    };
+
+                    if find_path(pathfinding, agent_position, player_position).is_none() {
+                        pursue_ai.wander_behavior = WanderBehavior::Taunt {
+                            next_hop: now,
+                            until: now + rng.random_range(TAUNT_DURATION.0..TAUNT_DURATION.1),
+                        };
+                        pursue_ai.wander_target = None;
+                        return None;
+                    }
+                }
+            }
+
+            let reached_goal = wander_movement(transform, pursue_ai, pathfinding, territory, rng);
+            if reached_goal {
+                choose_flourish(pursue_ai, agent_position, rng, now);
+            }
+        }
+        WanderBehavior::LookAround {
+            anchor,
+            mut side,
+            mut next_turn,
+            until,
+        } => {
+            if now >= until {
+                return end_flourish(transform, pursue_ai, pathfinding, territory, rng);
+            }
+
+            if now >= next_turn {
+                side = -side;
+                next_turn = now + LOOK_AROUND_TURN_INTERVAL;
+            }
+
+            pursue_ai.wander_behavior = WanderBehavior::LookAround {
+                anchor,
+                side,
+                next_turn,
+                until,
+            };
+            pursue_ai.wander_target = Some(anchor + Vec2::X * side * LOOK_AROUND_STEP);
+        }
+        WanderBehavior::Pace {
+            anchor,
+            mut side,
+            until,
+        } => {
+            if now >= until {
+                return end_flourish(transform, pursue_ai, pathfinding, territory, rng);
+            }
+
+            // Turn around at each end, or early rather than pace up to a hazard
+            let target = anchor + Vec2::X * side * PACE_DISTANCE;
+            if (agent_position.x - target.x).abs() < PACE_TURN_THRESHOLD
+                || pathfinding.is_near_hazard(target)
+            {
+                side = -side;
+            }
+
+            pursue_ai.wander_behavior = WanderBehavior::Pace {
+                anchor,
+                side,
+                until,
+            };
+            pursue_ai.wander_target = Some(anchor + Vec2::X * side * PACE_DISTANCE);
+        }
+        WanderBehavior::Taunt {
+            mut next_hop,
+            until,
+        } => {
+            if now >= until || visible_player.is_none() {
+                return end_flourish(transform, pursue_ai, pathfinding, territory, rng);
+            }
+
+            if physics.on_ground() && now >= next_hop {
+                physics.velocity.y = TAUNT_HOP_VELOCITY;
+                next_hop = now + TAUNT_HOP_INTERVAL;
+            }
+
+            pursue_ai.wander_behavior = WanderBehavior::Taunt { next_hop, until };
+            pursue_ai.wander_target = None;
+        }
+    }
 
     None
 }
 
+/// Picks what to do after reaching a wander goal: look around, pace, or keep travelling
+fn choose_flourish(pursue_ai: &mut PursueAI, anchor: Vec2, rng: &mut impl Rng, now: f32) {
+    let side = if rng.random_bool(0.5) { 1.0 } else { -1.0 };
+    let roll = rng.random::<f64>();
+
+    if roll < LOOK_AROUND_CHANCE {
+        pursue_ai.wander_behavior = WanderBehavior::LookAround {
+            anchor,
+            side,
+            next_turn: now + LOOK_AROUND_TURN_INTERVAL,
+            until: now + rng.random_range(LOOK_AROUND_DURATION.0..LOOK_AROUND_DURATION.1),
+        };
+        pursue_ai.wander_target = Some(anchor);
+    } else if roll < LOOK_AROUND_CHANCE + PACE_CHANCE {
+        pursue_ai.wander_behavior = WanderBehavior::Pace {
+            anchor,
+            side,
+            until: now + rng.random_range(PACE_DURATION.0..PACE_DURATION.1),
+        };
+        pursue_ai.wander_target = Some(anchor);
+    }
+}
+
+/// Goes back to travelling to a new wander goal
+fn end_flourish(
+    transform: &mut Transform,
+    pursue_ai: &mut PursueAI,
+    pathfinding: &PathfindingGraph,
+    territory: Option<&Territory>,
+    rng: &mut impl Rng,
+) -> Option<PursueAIState> {
+    pursue_ai.wander_behavior = WanderBehavior::default();
+    pursue_ai.current_wander_goal = None;
+    wander_movement(transform, pursue_ai, pathfinding, territory, rng);
+
+    None
+}
+
+/// Keeps the agent heading to a wander goal, picking a new one when needed.
+///
+/// Returns whether the previous goal was just reached.
 pub fn wander_movement(
     transform: &mut Transform,
     pursue_ai: &mut PursueAI,
     pathfinding: &PathfindingGraph,
-) {
+    territory: Option<&Territory>,
+    rng: &mut impl Rng,
+) -> bool {
     let agent_position = transform.translation.xy();
+    let mut reached_goal = false;
 
     // Check if we have a current wander goal
     if let Some(goal_node_id) = pursue_ai.current_wander_goal {
@@ -42,8 +236,9 @@ pub fn wander_movement(
         if let Some(goal_node) = pathfinding.nodes.get(goal_node_id) {
             let distance_sq = (agent_position - goal_node.position).length_squared();
             if distance_sq <= WANDER_GOAL_REACHED_THRESHOLD_SQ {
-                // Goal reached, clear it so a new one is selected next frame
+                // Goal reached, clear it so a new one is selected
                 pursue_ai.current_wander_goal = None;
+                reached_goal = true;
             }
         } else {
             // Invalid node ID, clear it
@@ -51,27 +246,49 @@ pub fn wander_movement(
         }
     }
 
-    // If no goal is set, pick a new random distant node
+    // If no goal is set, pick a new random distant node (with no node out of harm's way the agent
+    // stays put)
     if pursue_ai.current_wander_goal.is_none() {
-        let goal_node = get_random_goal_node(agent_position, pathfinding);
+        let goal_node = get_random_goal_node(agent_position, pathfinding, territory, rng);
         // Use the node's ID directly
-        pursue_ai.current_wander_goal = Some(goal_node.id);
+        pursue_ai.current_wander_goal = goal_node.map(|goal_node| goal_node.id);
     }
+
+    pursue_ai.wander_target = pursue_ai
+        .current_wander_goal
+        .and_then(|goal_node_id| pathfinding.nodes.get(goal_node_id))
+        .map(|goal_node| goal_node.position);
+
+    reached_goal
 }
 
 pub fn get_random_goal_node(
     agent_position: Vec2,
     pathfinding: &PathfindingGraph,
-) -> PathfindingGraphNode {
+    territory: Option<&Territory>,
+    rng: &mut impl Rng,
+) -> Option<PathfindingGraphNode> {
     let pathfinding_node_count = pathfinding.nodes.len();
+    if pathfinding_node_count == 0 {
+        return None;
+    }
+    let in_territory = |node: &PathfindingGraphNode| {
+        territory.is_none_or(|territory| territory.contains(node.position))
+    };
 
     let mut furthest_node: Option<PathfindingGraphNode> = None;
     let mut furthest_node_distance_sq: f32 = 0.0; // Changed to 0.0 to find furthest, not closest
 
     for _ in 0..WANDER_SAMPLE_COUNT {
-        let random_node_index = rand::rng().random_range(0..pathfinding_node_count);
+        let random_node_index = rng.random_range(0..pathfinding_node_count);
         let random_node = &pathfinding.nodes[random_node_index];
 
+        // Don't pick somewhere next to a hazard to hang around at, or outside the agent's
+        // territory
+        if random_node.hazard_cost > 0.0 || !in_territory(random_node) {
+            continue;
+        }
+
         let distance_sq = (agent_position - random_node.position).length_squared();
 
         if distance_sq > furthest_node_distance_sq {
@@ -80,6 +297,23 @@ pub fn get_random_goal_node(
         }
     }
 
-    furthest_node.expect("Pathfinding graph should have at least one node")
+    // Every sample was near a hazard (or out of bounds), so settle for any node out of harm's way,
+    // in the territory if there is one there
+    furthest_node
+        .or_else(|| {
+            pathfinding
+                .nodes
+                .iter()
+                .filter(|node| !node.lethal && in_territory(node))
+                .choose(rng)
+                .cloned()
+        })
+        .or_else(|| {
+            pathfinding
+                .nodes
+                .iter()
+                .filter(|node| !node.lethal)
+                .choose(rng)
+                .cloned()
+        })
 }
-
@@ -0,0 +1,87 @@
+use bevy::{
+    asset::Assets,
+    ecs::{entity::Entity, system::Commands},
+    math::Vec2,
+    mesh::Mesh,
+    sprite_render::ColorMaterial,
+};
+
+use crate::{level::LevelMetadata, spawn_ai_agent, AIVariant};
+
+use super::{profile::AIProfile, pursue_ai::PURSUE_AI_AGENT_RADIUS};
+
+// Horizontal gap between agents spawned at the same point (pixels)
+const AGENT_SPAWN_SPACING: f32 = PURSUE_AI_AGENT_RADIUS * 3.0;
+
+/// AI spawner: Spawns a number of agents spread over a set of spawn points. Points are used in
+/// turn, and agents sharing a point stand side by side rather than on top of each other
+#[derive(Clone, Debug, Default)]
+pub struct AISpawner {
+    /// Where agents are spawned (world pixels)
+    pub points: Vec<Vec2>,
+    /// How many agents to spawn in total
+    pub count: usize,
+    /// How skilled the agents are
+    pub profile: AIProfile,
+}
+
+impl AISpawner {
+    /// One agent per point
+    pub fn at_points(points: Vec<Vec2>) -> Self {
+        Self {
+            count: points.len(),
+            points,
+            ..Default::default()
+        }
+    }
+
+    /// The agents a level starts with: `ai_count` agents (one per spawn point by default) with the
+    /// level's AI profile over its AI spawn points
+    pub fn from_metadata(metadata: &LevelMetadata) -> Self {
+        let points = metadata.ai_spawn_positions();
+        Self {
+            count: metadata.ai_count.map_or(points.len(), |count| count as usize),
+            points,
+            profile: metadata.ai_profile.profile(),
+        }
+    }
+
+    /// Where each agent starts
+    pub fn positions(&self) -> Vec<Vec2> {
+        if self.points.is_empty() {
+            return Vec::new();
+        }
+
+        (0..self.points.len())
+            .flat_map(|point| {
+                // Agents left over after an even split go to the first points
+                let agents = self.count / self.points.len()
+                    + usize::from(point < self.count % self.points.len());
+                let first_offset = agents.saturating_sub(1) as f32 / 2.0;
+
+                (0..agents).map(move |agent| {
+                    let offset = (agent as f32 - first_offset) * AGENT_SPAWN_SPACING;
+                    self.points[point] + Vec2::X * offset
+                })
+            })
+            .collect()
+    }
+
+    /// Spawns the agents, each with the variant `variant` picks for it
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+        mut variant: impl FnMut() -> AIVariant,
+    ) -> Vec<Entity> {
+        self.positions()
+            .into_iter()
+            .map(|position| {
+                let agent = spawn_ai_agent(commands, meshes, materials, position, variant());
+                commands.entity(agent).insert(self.profile);
+                agent
+            })
+            .collect()
+    }
+}
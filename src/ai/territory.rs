@@ -0,0 +1,139 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Added, Without},
+        system::{Commands, Query, Res},
+    },
+    gizmos::gizmos::Gizmos,
+    math::Vec2,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{pathfinding::PathfindingGraph, pursue_ai::PursueAI};
+use crate::{health::SpawnPoint, level::Level, utils::polygon_contains};
+
+// Gizmo colors and sizes for the AI debug layer
+const TERRITORY_COLOR: Color = Color::srgb(0.4, 0.9, 0.6);
+const TERRITORY_HOME_GIZMO_SIZE: f32 = 10.0;
+
+/// A territory read from level metadata (positions in world pixels)
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TerritorySetting {
+    /// Where agents of the territory return to after giving up a chase
+    pub home: [f32; 2],
+    /// Size of a circular territory around `home` (pixels)
+    pub radius: f32,
+    /// Outline of the territory; takes the place of `radius` when it has three or more points
+    pub polygon: Vec<[f32; 2]>,
+}
+
+/// The shape of a territory
+#[derive(Clone, Debug)]
+pub enum TerritoryArea {
+    /// Everything within this distance of home (pixels)
+    Radius(f32),
+    /// Everything inside this closed outline
+    Polygon(Vec<Vec2>),
+}
+
+/// Territory component: The part of the level an agent keeps to. A player who leaves it is no
+/// longer chased, and the agent heads back home and only wanders inside it.
+///
+/// Agents are given the first territory from the level metadata that contains their spawn point.
+#[derive(Component, Clone, Debug)]
+pub struct Territory {
+    /// Where the agent returns to after giving up a chase (world pixels)
+    pub home: Vec2,
+    pub area: TerritoryArea,
+}
+
+impl Territory {
+    pub fn from_setting(setting: &TerritorySetting) -> Self {
+        let area = if setting.polygon.len() >= 3 {
+            TerritoryArea::Polygon(setting.polygon.iter().copied().map(Vec2::from).collect())
+        } else {
+            TerritoryArea::Radius(setting.radius)
+        };
+
+        Self {
+            home: Vec2::from(setting.home),
+            area,
+        }
+    }
+
+    /// Whether `point` is inside the territory
+    pub fn contains(&self, point: Vec2) -> bool {
+        match &self.area {
+            TerritoryArea::Radius(radius) => self.home.distance(point) <= *radius,
+            TerritoryArea::Polygon(points) => polygon_contains(points, point),
+        }
+    }
+
+    /// Index of the pathfinding node closest to home, for an agent to walk back to
+    pub fn home_node(&self, pathfinding: &PathfindingGraph) -> Option<usize> {
+        pathfinding
+            .nodes
+            .iter()
+            .filter(|node| !node.lethal)
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(self.home)
+                    .total_cmp(&b.position.distance_squared(self.home))
+            })
+            .map(|node| node.id)
+    }
+}
+
+pub struct TerritoryPlugin;
+
+impl Plugin for TerritoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_assign_territories);
+    }
+}
+
+/// Territory system: Gives newly spawned agents the level territory their spawn point is in
+#[allow(clippy::type_complexity)]
+pub fn s_assign_territories(
+    mut commands: Commands,
+    ai_query: Query<(Entity, &SpawnPoint), (Added<PursueAI>, Without<Territory>)>,
+    level: Option<Res<Level>>,
+) {
+    let Some(level) = level else {
+        return;
+    };
+
+    for (entity, spawn_point) in ai_query.iter() {
+        let territory = level
+            .metadata
+            .ai_territories
+            .iter()
+            .map(Territory::from_setting)
+            .find(|territory| territory.contains(spawn_point.0));
+        if let Some(territory) = territory {
+            commands.entity(entity).insert(territory);
+        }
+    }
+}
+
+/// Draws each agent's territory and its home
+pub fn s_debug_territories(ai_query: Query<&Territory>, mut gizmos: Gizmos) {
+    for territory in ai_query.iter() {
+        match &territory.area {
+            TerritoryArea::Radius(radius) => {
+                gizmos.circle_2d(territory.home, *radius, TERRITORY_COLOR);
+            }
+            TerritoryArea::Polygon(points) => {
+                if let (Some(&first), Some(&last)) = (points.first(), points.last()) {
+                    gizmos.linestrip_2d(points.iter().copied(), TERRITORY_COLOR);
+                    gizmos.line_2d(last, first, TERRITORY_COLOR);
+                }
+            }
+        }
+        gizmos.cross_2d(territory.home, TERRITORY_HOME_GIZMO_SIZE, TERRITORY_COLOR);
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    time::Time,
+};
+
+use crate::{frame_budget::FrameBudget, settings::Settings};
+
+use super::pursue_ai::s_pursue_ai_update;
+
+// Spreads agents' decision ticks evenly over the tick interval (golden ratio conjugate)
+const TICK_PHASE_STEP: f64 = 0.618_033_988_749_895;
+
+/// AI tick component: Whether the agent makes decisions (state changes, path re-planning) this
+/// frame. Decisions run at the `ai_tick_rate` setting, staggered across agents; movement still
+/// runs every frame.
+#[derive(Component, Default)]
+pub struct AITick {
+    /// Index of the last decision tick
+    last_tick: Option<u64>,
+    /// Whether the agent makes decisions this frame
+    pub ready: bool,
+}
+
+pub struct AITickPlugin;
+
+impl Plugin for AITickPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_ai_tick.before(s_pursue_ai_update));
+    }
+}
+
+/// AI tick system: Marks the agents whose staggered decision tick falls on this frame (at half
+/// the rate while the frame budget is degraded)
+pub fn s_ai_tick(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    frame_budget: Res<FrameBudget>,
+    mut tick_query: Query<(Entity, &mut AITick)>,
+) {
+    // A rate of zero (or less) disables throttling
+    if settings.ai_tick_rate <= 0.0 {
+        for (_, mut tick) in tick_query.iter_mut() {
+            tick.ready = true;
+        }
+        return;
+    }
+
+    let tick_rate = settings.ai_tick_rate * frame_budget.ai_tick_scale();
+    let ticks_elapsed = time.elapsed_secs_f64() * tick_rate as f64;
+
+    for (entity, mut tick) in tick_query.iter_mut() {
+        let phase = (entity.index() as f64 * TICK_PHASE_STEP).fract();
+        let current_tick = (ticks_elapsed + phase) as u64;
+
+        tick.ready = tick.last_tick != Some(current_tick);
+        tick.last_tick = Some(current_tick);
+    }
+}
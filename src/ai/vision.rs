@@ -0,0 +1,119 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{component::Component, schedule::IntoScheduleConfigs, system::Query},
+    math::{ops, Vec2},
+};
+
+use crate::{
+    level::Level,
+    spatial::{DynamicKind, DynamicSpatialIndex},
+    KinematicBody,
+};
+
+use super::{brain::behavior_tree::s_behavior_tree_brains, pursue_ai::s_pursue_ai_update};
+
+// Full angle of the cone in front of an agent that it can spot the player in (units: radians)
+const DEFAULT_VISION_CONE_ANGLE: f32 = 120.0 * std::f32::consts::PI / 180.0;
+// Distance within which agents notice the player whichever way they face (units: pixels)
+const DEFAULT_PERIPHERAL_RANGE: f32 = 40.0;
+// Horizontal speed above which an agent turns to face the way it moves (units: pixels/second)
+const FACING_MIN_SPEED: f32 = 10.0;
+
+/// AI vision component: What an agent can see of the player.
+///
+/// The player is only spotted inside the vision cone (or within the peripheral range) with no
+/// level geometry in the way. Agents already tracking the player follow them whichever way they
+/// face, but still lose them behind walls.
+#[derive(Component, Clone)]
+pub struct AIVision {
+    /// Multiplier for the detection range set by the difficulty (kept in sync with the agent's
+    /// `AIProfile`)
+    pub range_scale: f32,
+    /// Full angle (radians) of the vision cone
+    pub cone_angle: f32,
+    /// Distance (pixels) within which the player is noticed outside the cone
+    pub peripheral_range: f32,
+    /// Direction the vision cone points in (follows the agent's movement)
+    pub facing: Vec2,
+}
+
+impl Default for AIVision {
+    fn default() -> Self {
+        Self {
+            range_scale: 1.0,
+            cone_angle: DEFAULT_VISION_CONE_ANGLE,
+            peripheral_range: DEFAULT_PERIPHERAL_RANGE,
+            facing: Vec2::X,
+        }
+    }
+}
+
+impl AIVision {
+    /// Whether an agent at `position` can see `target`, `range` pixels away at most (before
+    /// `range_scale`)
+    pub fn can_see(
+        &self,
+        level: Option<&Level>,
+        position: Vec2,
+        target: Vec2,
+        range: f32,
+        tracking: bool,
+    ) -> bool {
+        let to_target = target - position;
+        let distance = to_target.length();
+        if distance > range * self.range_scale {
+            return false;
+        }
+
+        let in_cone = tracking
+            || distance <= self.peripheral_range
+            || to_target.dot(self.facing) >= distance * ops::cos(self.cone_angle * 0.5);
+
+        // Without a level (while loading) there is nothing to hide behind
+        in_cone && level.is_none_or(|level| level.line_of_sight(position, target))
+    }
+
+    /// Closest player an agent at `position` can see, `range` pixels away at most (before
+    /// `range_scale`); `tracking` agents are already following the player and ignore the cone
+    pub fn visible_player(
+        &self,
+        spatial_index: &DynamicSpatialIndex,
+        level: Option<&Level>,
+        position: Vec2,
+        range: f32,
+        tracking: bool,
+    ) -> Option<Vec2> {
+        spatial_index
+            .within_radius(position, range * self.range_scale)
+            .filter(|entry| entry.kind == DynamicKind::Player)
+            .map(|entry| entry.position)
+            .filter(|&player| self.can_see(level, position, player, range, tracking))
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+    }
+}
+
+pub struct AIVisionPlugin;
+
+impl Plugin for AIVisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            s_update_vision_facing
+                .before(s_pursue_ai_update)
+                .before(s_behavior_tree_brains),
+        );
+    }
+}
+
+/// Vision facing system: Turns each agent's vision cone the way it is moving (agents standing
+/// still keep looking where they last went)
+pub fn s_update_vision_facing(mut ai_query: Query<(&KinematicBody, &mut AIVision)>) {
+    for (physics, mut vision) in ai_query.iter_mut() {
+        if physics.velocity.x.abs() > FACING_MIN_SPEED {
+            vision.facing = Vec2::new(physics.velocity.x.signum(), 0.0);
+        }
+    }
+}
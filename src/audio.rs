@@ -0,0 +1,448 @@
+//! Sound effects: footsteps, jumps, landings and wall slides of the player, and a stinger when an
+//! agent spots them.
+//!
+//! The sounds are synthesized (`SynthSound`, a tone swept between two pitches mixed with filtered
+//! noise) rather than loaded from files. Each effect has a bank of a few variations, one picked at
+//! random and played at a slightly random pitch every time so repeats don't sound mechanical, and
+//! footsteps, landings and wall slides have a bank per surface material (see
+//! `LevelMetadata::surface_materials`): a dull thud on stone, a ring on metal, a crackle on ice.
+
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::{Asset, Assets, Handle},
+    audio::{AddAudioSource, AudioPlayer, Decodable, PlaybackSettings, Source, Volume},
+    ecs::{
+        entity::Entity,
+        message::MessageReader,
+        query::With,
+        schedule::{common_conditions::resource_exists, IntoScheduleConfigs},
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    math::ops,
+    prelude::Resource,
+    reflect::TypePath,
+    time::Time,
+};
+use rand::Rng;
+
+use crate::{
+    ai::alert::AIAlert,
+    characters::ActiveCharacter,
+    collisions::{s_player_contacts, s_wall_slides, CollisionStarted, SurfaceContact, WallSliding},
+    level::materials::SurfaceMaterial,
+    settings::Settings,
+    ControllerAction, ControllerEvent, KinematicBody,
+};
+
+// Output format of synthesized sounds (units: samples/second)
+const SAMPLE_RATE: u32 = 44_100;
+// Fade-in at the start of every sound, so it doesn't click (units: seconds)
+const ATTACK_TIME: f32 = 0.003;
+
+// Variations in each bank, and how far apart their pitches are (fraction of the base pitch)
+const BANK_VARIATIONS: u32 = 3;
+const VARIATION_PITCH_STEP: f32 = 0.06;
+
+// Ground travelled between footsteps, and the slowest run that makes any (units: pixels,
+// pixels/second)
+const FOOTSTEP_STRIDE: f32 = 56.0;
+const FOOTSTEP_MIN_SPEED: f32 = 40.0;
+// Landings softer than this are silent, and landings this hard are at full volume (units:
+// pixels/second)
+const LANDING_MIN_SPEED: f32 = 150.0;
+const LANDING_LOUD_SPEED: f32 = 900.0;
+// Time between scrapes while sliding down a wall (units: seconds)
+const WALL_SLIDE_INTERVAL: f32 = 0.11;
+// Shortest time between detection stingers, however many agents spot the player (units: seconds)
+const STINGER_COOLDOWN: f32 = 2.0;
+
+/// A synthesized sound effect: a tone sweeping from one pitch to another, mixed with low-pass
+/// filtered noise, fading in quickly and out over its length
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct SynthSound {
+    /// Pitch of the tone at the start and end (Hz)
+    pub start_frequency: f32,
+    pub end_frequency: f32,
+    /// Share of noise in the mix (0 is a pure tone, 1 pure noise)
+    pub noise: f32,
+    /// How much of the noise's high end is kept (0 to 1, higher is hissier)
+    pub brightness: f32,
+    /// Length (seconds)
+    pub duration: f32,
+    /// Peak amplitude (0 to 1)
+    pub volume: f32,
+    /// Seed for the noise, so variations of a sound differ
+    pub seed: u32,
+}
+
+impl SynthSound {
+    const fn new(
+        start_frequency: f32,
+        end_frequency: f32,
+        noise: f32,
+        brightness: f32,
+        duration: f32,
+        volume: f32,
+    ) -> Self {
+        Self {
+            start_frequency,
+            end_frequency,
+            noise,
+            brightness,
+            duration,
+            volume,
+            seed: 1,
+        }
+    }
+}
+
+// Sound presets
+const STONE_STEP: SynthSound = SynthSound::new(140.0, 90.0, 0.85, 0.25, 0.07, 0.35);
+const METAL_STEP: SynthSound = SynthSound::new(880.0, 840.0, 0.35, 0.6, 0.12, 0.25);
+const ICE_STEP: SynthSound = SynthSound::new(2200.0, 1800.0, 0.9, 0.9, 0.05, 0.2);
+const STONE_LANDING: SynthSound = SynthSound::new(90.0, 50.0, 0.7, 0.2, 0.16, 0.6);
+const METAL_LANDING: SynthSound = SynthSound::new(520.0, 480.0, 0.4, 0.5, 0.3, 0.45);
+const ICE_LANDING: SynthSound = SynthSound::new(1600.0, 900.0, 0.85, 0.85, 0.14, 0.4);
+const STONE_SCRAPE: SynthSound = SynthSound::new(200.0, 180.0, 0.95, 0.4, 0.1, 0.15);
+const METAL_SCRAPE: SynthSound = SynthSound::new(1400.0, 1300.0, 0.6, 0.7, 0.1, 0.12);
+const ICE_SCRAPE: SynthSound = SynthSound::new(2600.0, 2400.0, 0.95, 0.95, 0.1, 0.1);
+const JUMP: SynthSound = SynthSound::new(300.0, 620.0, 0.1, 0.3, 0.12, 0.3);
+const STINGER: SynthSound = SynthSound::new(660.0, 990.0, 0.05, 0.2, 0.35, 0.35);
+
+impl Decodable for SynthSound {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            sound: self.clone(),
+            sample: 0,
+            samples: (self.duration * SAMPLE_RATE as f32) as u32,
+            phase: 0.0,
+            noise_state: self.seed.max(1),
+            filtered_noise: 0.0,
+        }
+    }
+}
+
+/// Sample generator for a `SynthSound`
+pub struct SynthDecoder {
+    sound: SynthSound,
+    sample: u32,
+    /// Length (samples)
+    samples: u32,
+    /// Phase of the tone (radians)
+    phase: f32,
+    /// Xorshift state of the noise
+    noise_state: u32,
+    filtered_noise: f32,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample >= self.samples {
+            return None;
+        }
+        let time = self.sample as f32 / SAMPLE_RATE as f32;
+        let progress = self.sample as f32 / self.samples as f32;
+        self.sample += 1;
+
+        let sound = &self.sound;
+        let frequency =
+            sound.start_frequency + (sound.end_frequency - sound.start_frequency) * progress;
+        self.phase = (self.phase + TAU * frequency / SAMPLE_RATE as f32) % TAU;
+        let tone = ops::sin(self.phase);
+
+        self.noise_state ^= self.noise_state << 13;
+        self.noise_state ^= self.noise_state >> 17;
+        self.noise_state ^= self.noise_state << 5;
+        let white = self.noise_state as f32 / u32::MAX as f32 * 2.0 - 1.0;
+        self.filtered_noise += (white - self.filtered_noise) * sound.brightness;
+
+        let envelope = (time / ATTACK_TIME).min(1.0) * (1.0 - progress) * (1.0 - progress);
+        let mix = tone * (1.0 - sound.noise) + self.filtered_noise * sound.noise;
+        Some(mix * envelope * sound.volume)
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.sound.duration))
+    }
+}
+
+/// Variations of one sound effect, played at a random pitch
+pub struct SoundBank {
+    sounds: Vec<Handle<SynthSound>>,
+    /// Largest random change in playback speed (fraction, so the pitch changes with it)
+    pitch_jitter: f32,
+}
+
+impl SoundBank {
+    fn new(sounds: &mut Assets<SynthSound>, sound: SynthSound, pitch_jitter: f32) -> Self {
+        let sounds = (0..BANK_VARIATIONS)
+            .map(|variation| {
+                let pitch = 1.0 + (variation as f32 - 1.0) * VARIATION_PITCH_STEP;
+                sounds.add(SynthSound {
+                    start_frequency: sound.start_frequency * pitch,
+                    end_frequency: sound.end_frequency * pitch,
+                    seed: sound.seed + variation * 7919,
+                    ..sound
+                })
+            })
+            .collect();
+
+        Self {
+            sounds,
+            pitch_jitter,
+        }
+    }
+
+    /// Plays a random variation at a random pitch, `speed` times faster (and higher) than normal
+    fn play(&self, commands: &mut Commands, volume: f32, speed: f32) {
+        let mut rng = rand::rng();
+        let Some(sound) = self.sounds.get(rng.random_range(0..self.sounds.len())) else {
+            return;
+        };
+        let jitter = rng.random_range(-self.pitch_jitter..=self.pitch_jitter);
+
+        commands.spawn((
+            AudioPlayer(sound.clone()),
+            PlaybackSettings::DESPAWN
+                .with_volume(Volume::Linear(volume))
+                .with_speed(speed * (1.0 + jitter)),
+        ));
+    }
+}
+
+/// A bank for each surface material
+pub struct MaterialBanks {
+    stone: SoundBank,
+    metal: SoundBank,
+    ice: SoundBank,
+}
+
+impl MaterialBanks {
+    fn new(
+        sounds: &mut Assets<SynthSound>,
+        [stone, metal, ice]: [SynthSound; 3],
+        pitch_jitter: f32,
+    ) -> Self {
+        Self {
+            stone: SoundBank::new(sounds, stone, pitch_jitter),
+            metal: SoundBank::new(sounds, metal, pitch_jitter),
+            ice: SoundBank::new(sounds, ice, pitch_jitter),
+        }
+    }
+
+    fn get(&self, material: SurfaceMaterial) -> &SoundBank {
+        match material {
+            SurfaceMaterial::Stone => &self.stone,
+            SurfaceMaterial::Metal => &self.metal,
+            SurfaceMaterial::Ice => &self.ice,
+        }
+    }
+}
+
+/// Sound banks resource: Every sound effect, with the volume from the settings. Only inserted
+/// when there is an audio output and the volume isn't zero.
+#[derive(Resource)]
+pub struct SoundBanks {
+    pub volume: f32,
+    pub footsteps: MaterialBanks,
+    pub landings: MaterialBanks,
+    pub wall_slides: MaterialBanks,
+    pub jump: SoundBank,
+    pub stinger: SoundBank,
+}
+
+/// Audio plugin: Plays sound effects for the player's movement and for agents spotting them
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        // Headless runs have no audio output to play anything on
+        if !app.is_plugin_added::<bevy::audio::AudioPlugin>() {
+            return;
+        }
+
+        app.add_audio_source::<SynthSound>();
+        app.add_systems(Startup, s_build_sound_banks);
+        app.add_systems(
+            Update,
+            (
+                s_footstep_sounds,
+                s_landing_sounds,
+                s_wall_slide_sounds,
+                s_jump_sounds,
+                s_detection_stingers,
+            )
+                .after(s_player_contacts)
+                .after(s_wall_slides)
+                .run_if(resource_exists::<SoundBanks>),
+        );
+    }
+}
+
+/// Sound bank system: Synthesizes every sound effect, unless the volume is turned all the way down
+fn s_build_sound_banks(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut sounds: ResMut<Assets<SynthSound>>,
+) {
+    if settings.sound_volume <= 0.0 {
+        return;
+    }
+
+    commands.insert_resource(SoundBanks {
+        volume: settings.sound_volume,
+        footsteps: MaterialBanks::new(&mut sounds, [STONE_STEP, METAL_STEP, ICE_STEP], 0.08),
+        landings: MaterialBanks::new(
+            &mut sounds,
+            [STONE_LANDING, METAL_LANDING, ICE_LANDING],
+            0.05,
+        ),
+        wall_slides: MaterialBanks::new(
+            &mut sounds,
+            [STONE_SCRAPE, METAL_SCRAPE, ICE_SCRAPE],
+            0.1,
+        ),
+        jump: SoundBank::new(&mut sounds, JUMP, 0.04),
+        stinger: SoundBank::new(&mut sounds, STINGER, 0.0),
+    });
+}
+
+/// Footstep system: Plays a footstep on the ground's material every stride the player runs
+fn s_footstep_sounds(
+    mut commands: Commands,
+    banks: Res<SoundBanks>,
+    time: Res<Time>,
+    mut contacts: MessageReader<SurfaceContact>,
+    player_query: Query<(Entity, &KinematicBody), With<ActiveCharacter>>,
+    // Ground covered since the last footstep (pixels)
+    mut travelled: Local<f32>,
+) {
+    let Ok((player, physics)) = player_query.single() else {
+        return;
+    };
+    let ground = contacts.read().find(|contact| {
+        contact.entity == player && KinematicBody::is_ground_normal(contact.normal)
+    });
+
+    let speed = physics.velocity.x.abs();
+    let Some(ground) = ground.filter(|_| speed >= FOOTSTEP_MIN_SPEED) else {
+        // The first step after landing or starting off comes half a stride in
+        *travelled = FOOTSTEP_STRIDE / 2.0;
+        return;
+    };
+
+    *travelled += speed * time.delta_secs();
+    if *travelled >= FOOTSTEP_STRIDE {
+        *travelled -= FOOTSTEP_STRIDE;
+        banks.footsteps.get(ground.material).play(&mut commands, banks.volume, 1.0);
+    }
+}
+
+/// Landing system: Plays a landing on the ground's material when the player hits the ground,
+/// louder the harder they land
+fn s_landing_sounds(
+    mut commands: Commands,
+    banks: Res<SoundBanks>,
+    mut collisions: MessageReader<CollisionStarted>,
+    player_query: Query<Entity, With<ActiveCharacter>>,
+) {
+    let Ok(player) = player_query.single() else {
+        return;
+    };
+
+    let landing = collisions.read().find(|collision| {
+        collision.entity == player
+            && KinematicBody::is_ground_normal(collision.normal)
+            && collision.impact_speed >= LANDING_MIN_SPEED
+    });
+    if let Some(landing) = landing {
+        let loudness = (landing.impact_speed / LANDING_LOUD_SPEED).clamp(0.3, 1.0);
+        banks
+            .landings
+            .get(landing.material)
+            .play(&mut commands, banks.volume * loudness, 1.0);
+    }
+}
+
+/// Wall slide system: Plays scrapes on the wall's material while the player slides down it
+fn s_wall_slide_sounds(
+    mut commands: Commands,
+    banks: Res<SoundBanks>,
+    time: Res<Time>,
+    mut wall_slides: MessageReader<WallSliding>,
+    player_query: Query<Entity, With<ActiveCharacter>>,
+    // Seconds until the next scrape
+    mut scrape_timer: Local<f32>,
+) {
+    let Ok(player) = player_query.single() else {
+        return;
+    };
+    let Some(wall) = wall_slides.read().find(|slide| slide.entity == player) else {
+        *scrape_timer = 0.0;
+        return;
+    };
+
+    *scrape_timer -= time.delta_secs();
+    if *scrape_timer <= 0.0 {
+        *scrape_timer = WALL_SLIDE_INTERVAL;
+        banks.wall_slides.get(wall.material).play(&mut commands, banks.volume, 1.0);
+    }
+}
+
+/// Jump system: Plays a jump for every kind of jump, higher for air jumps
+fn s_jump_sounds(
+    mut commands: Commands,
+    banks: Res<SoundBanks>,
+    mut controller_events: MessageReader<ControllerEvent>,
+) {
+    for event in controller_events.read() {
+        let speed = match event.action {
+            ControllerAction::Jump => 1.0,
+            ControllerAction::AirJump => 1.25,
+            ControllerAction::WallJump => 0.9,
+            ControllerAction::Dash | ControllerAction::WallSlide => continue,
+        };
+        banks.jump.play(&mut commands, banks.volume, speed);
+    }
+}
+
+/// Stinger system: Plays a stinger when an agent spots the player (at most one per cooldown)
+fn s_detection_stingers(
+    mut commands: Commands,
+    banks: Res<SoundBanks>,
+    time: Res<Time>,
+    mut alerts: MessageReader<AIAlert>,
+    // Elapsed time (seconds) of the last stinger
+    mut last_stinger: Local<Option<f32>>,
+) {
+    if alerts.read().last().is_none() {
+        return;
+    }
+
+    let now = time.elapsed_secs();
+    if last_stinger.is_none_or(|last| now - last >= STINGER_COOLDOWN) {
+        *last_stinger = Some(now);
+        banks.stinger.play(&mut commands, banks.volume, 1.0);
+    }
+}
@@ -0,0 +1,275 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    app::App,
+    asset::{AssetApp, AssetPlugin, Assets},
+    ecs::{
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    gizmos::GizmoPlugin,
+    image::Image,
+    input::InputPlugin,
+    math::{Vec2, Vec3Swizzles},
+    mesh::Mesh,
+    prelude::{MinimalPlugins, Resource},
+    sprite_render::ColorMaterial,
+    state::{app::StatesPlugin, state::OnEnter},
+    time::TimeUpdateStrategy,
+    transform::components::Transform,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::Serialize;
+
+use crate::{
+    ai::{
+        path_requests::AsyncPathfinding, pathfinding::PathfindingGraph,
+        pursue_ai::PURSUE_AI_AGENT_RADIUS,
+    },
+    characters::ActiveCharacter,
+    frame_budget::FrameBudget,
+    game_state::GameState,
+    level::{
+        procgen::{generate_level, ProcgenStyle, DEFAULT_HEIGHT, DEFAULT_WIDTH},
+        LevelSource,
+    },
+    loading::BackgroundLoading,
+    progress::LevelProgressTracker,
+    replay::ReplayRecorder,
+    s_enter_game, spawn_ai_agent, AIVariant, GamePlugin,
+};
+
+// Command-line flags
+pub const BENCH_FLAG: &str = "--bench";
+const BENCH_OUTPUT_FLAG: &str = "--bench-output";
+const DEFAULT_BENCH_OUTPUT: &str = "bench_report.json";
+
+// Every benchmark frame advances the simulation by exactly this much (units: seconds)
+pub const BENCH_FRAME_DT: f64 = 1.0 / 60.0;
+// Seed for agent placement and generated levels so every run benchmarks the same layout
+const BENCH_SEED: u64 = 0;
+// Minimum distance (pixels) between extra agents and the player
+const BENCH_MIN_SPAWN_DISTANCE: f32 = 100.0;
+
+/// A predefined headless run
+struct BenchScenario {
+    name: &'static str,
+    /// Frames simulated after startup
+    frames: u32,
+    /// AI agents spawned on top of the level's usual one
+    extra_agents: usize,
+    level: BenchLevel,
+}
+
+/// Level a benchmark scenario is played on
+enum BenchLevel {
+    /// The bundled level
+    Bundled,
+    /// A random level generated from `BENCH_SEED` (size in tiles)
+    Generated { width: usize, height: usize },
+}
+
+impl BenchLevel {
+    fn source(&self) -> LevelSource {
+        match *self {
+            BenchLevel::Bundled => LevelSource::default(),
+            BenchLevel::Generated { width, height } => {
+                generate_level(BENCH_SEED, ProcgenStyle::Rooms, width, height).source
+            }
+        }
+    }
+}
+
+const SCENARIOS: [BenchScenario; 3] = [
+    BenchScenario {
+        name: "idle",
+        frames: 600,
+        extra_agents: 0,
+        level: BenchLevel::Bundled,
+    },
+    BenchScenario {
+        name: "100_agents",
+        frames: 600,
+        extra_agents: 100,
+        level: BenchLevel::Bundled,
+    },
+    BenchScenario {
+        name: "big_level",
+        frames: 300,
+        extra_agents: 0,
+        level: BenchLevel::Generated {
+            width: DEFAULT_WIDTH * 2,
+            height: DEFAULT_HEIGHT * 2,
+        },
+    },
+];
+
+/// Timing results for one scenario (all times in milliseconds)
+#[derive(Serialize)]
+struct ScenarioReport {
+    name: &'static str,
+    frames: u32,
+    /// Startup systems plus the first frame (level and pathfinding graph build)
+    startup_ms: f64,
+    total_ms: f64,
+    mean_frame_ms: f64,
+    p50_frame_ms: f64,
+    p95_frame_ms: f64,
+    max_frame_ms: f64,
+}
+
+#[derive(Serialize)]
+struct BenchReport {
+    version: &'static str,
+    frame_dt: f64,
+    scenarios: Vec<ScenarioReport>,
+}
+
+/// Extra agents to spawn once the level is built
+#[derive(Resource)]
+struct BenchAgents(usize);
+
+/// Runs every scenario headlessly and writes the JSON report
+pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+    let output_path = args
+        .iter()
+        .position(|arg| arg == BENCH_OUTPUT_FLAG)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_BENCH_OUTPUT);
+
+    let scenarios = SCENARIOS
+        .iter()
+        .map(|scenario| {
+            let report = run_scenario(scenario);
+            println!(
+                "{}: {} frames, mean {:.3} ms, p95 {:.3} ms",
+                report.name, report.frames, report.mean_frame_ms, report.p95_frame_ms
+            );
+            report
+        })
+        .collect();
+
+    let report = BenchReport {
+        version: env!("CARGO_PKG_VERSION"),
+        frame_dt: BENCH_FRAME_DT,
+        scenarios,
+    };
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(contents) => match std::fs::write(output_path, contents) {
+            Ok(()) => println!("Benchmark report written to {output_path}"),
+            Err(error) => eprintln!("Failed to write {output_path}: {error}"),
+        },
+        Err(error) => eprintln!("Failed to serialize benchmark report: {error}"),
+    }
+}
+
+/// The game without a window or renderer, playing `level_source` and advancing by exactly
+/// `BENCH_FRAME_DT` every update
+pub fn headless_app(level_source: LevelSource) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((AssetPlugin::default(), InputPlugin, GizmoPlugin, StatesPlugin))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .init_asset::<Image>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            BENCH_FRAME_DT,
+        )))
+        .insert_resource(level_source)
+        .insert_resource(FrameBudget::disabled())
+        .insert_resource(BackgroundLoading::disabled())
+        .insert_resource(AsyncPathfinding::disabled())
+        .insert_resource(LevelProgressTracker::disabled())
+        .insert_resource(ReplayRecorder::disabled())
+        .add_plugins(GamePlugin);
+    app
+}
+
+fn run_scenario(scenario: &BenchScenario) -> ScenarioReport {
+    let mut app = headless_app(scenario.level.source());
+    app.insert_resource(BenchAgents(scenario.extra_agents))
+        .add_systems(OnEnter(GameState::InGame), s_spawn_bench_agents.after(s_enter_game));
+
+    let startup = Instant::now();
+    app.update();
+    let startup_ms = startup.elapsed().as_secs_f64() * 1000.0;
+
+    let mut frame_times: Vec<f64> = (0..scenario.frames)
+        .map(|_| {
+            let frame = Instant::now();
+            app.update();
+            frame.elapsed().as_secs_f64() * 1000.0
+        })
+        .collect();
+
+    let total_ms: f64 = frame_times.iter().sum();
+    frame_times.sort_by(f64::total_cmp);
+
+    ScenarioReport {
+        name: scenario.name,
+        frames: scenario.frames,
+        startup_ms,
+        total_ms,
+        mean_frame_ms: total_ms / frame_times.len().max(1) as f64,
+        p50_frame_ms: percentile(&frame_times, 0.5),
+        p95_frame_ms: percentile(&frame_times, 0.95),
+        max_frame_ms: frame_times.last().copied().unwrap_or(0.0),
+    }
+}
+
+/// Value at the given fraction of a sorted list
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let index = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+    sorted[index]
+}
+
+/// Spawns the scenario's extra agents on random ground nodes
+fn s_spawn_bench_agents(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    pathfinding: Res<PathfindingGraph>,
+    bench_agents: Res<BenchAgents>,
+    player_query: Query<&Transform, With<ActiveCharacter>>,
+) {
+    let player_position = player_query
+        .single()
+        .map(|transform| transform.translation.xy())
+        .unwrap_or(Vec2::ZERO);
+
+    let mut rng = StdRng::seed_from_u64(BENCH_SEED);
+
+    // Agents share nodes when there are more agents than ground nodes
+    let mut positions = Vec::new();
+    while positions.len() < bench_agents.0 {
+        let batch = pathfinding.random_ground_positions(
+            &mut rng,
+            bench_agents.0 - positions.len(),
+            player_position,
+            BENCH_MIN_SPAWN_DISTANCE,
+            PURSUE_AI_AGENT_RADIUS,
+        );
+        if batch.is_empty() {
+            break;
+        }
+        positions.extend(batch);
+    }
+
+    for position in positions {
+        spawn_ai_agent(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            position,
+            AIVariant::Normal,
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashSet;
+
+use bevy::{
+    app::{App, Plugin, PostUpdate, Update},
+    asset::Assets,
+    camera::{Camera, Camera2d, Projection},
+    ecs::{
+        component::Component,
+        query::{With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    image::Image,
+    input::{
+        keyboard::KeyCode,
+        mouse::{AccumulatedMouseScroll, MouseScrollUnit},
+        ButtonInput,
+    },
+    math::{ops, UVec2, Vec2, Vec3, Vec3Swizzles},
+    prelude::Resource,
+    state::state::OnEnter,
+    time::{Real, Time},
+    transform::{
+        components::{GlobalTransform, Transform},
+        TransformSystems,
+    },
+    window::Window,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    characters::ActiveCharacter,
+    game_state::GameState,
+    level::{Aabb, Level},
+    pixel_perfect::{spawn_pixel_perfect_cameras, CanvasCamera, PixelPerfectPlugin},
+    settings::Settings,
+    KinematicBody,
+};
+
+// Zoom constants (orthographic scale, larger = further out)
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+// Scale change per scroll line (unitless multiplier)
+const ZOOM_STEP: f32 = 1.1;
+// Pixel-based scroll deltas (touchpads) per scroll line
+const PIXELS_PER_SCROLL_LINE: f32 = 100.0;
+
+// Rate at which the zoom eases to a new level, e.g. on entering a camera zone (units: 1/second)
+const ZOOM_DAMPING: f32 = 8.0;
+// Scale difference below which the zoom snaps to its target (orthographic scale)
+const ZOOM_SNAP: f32 = 0.001;
+
+// Free-fly speed at zoom 1.0 (units: pixels/second)
+const FREE_FLY_SPEED: f32 = 600.0;
+
+// Rate at which the look-ahead swings over when the player turns (units: 1/second)
+const LOOK_AHEAD_DAMPING: f32 = 3.0;
+
+/// Camera that renders the world (zoom and free-fly apply to it)
+#[derive(Component)]
+pub struct GameCamera;
+
+/// Camera zoom level and debug free-fly state
+#[derive(Resource)]
+pub struct CameraControls {
+    /// Orthographic projection scale, clamped to [MIN_ZOOM, MAX_ZOOM]
+    pub zoom: f32,
+    /// Whether the camera is detached and moved with WASD
+    pub free_fly: bool,
+    /// Where the camera was when free-fly started (restored when it ends)
+    pub detached_from: Option<Vec3>,
+}
+
+/// Camera follow resource: Where the following camera is aiming, on top of the player's position
+#[derive(Resource, Default)]
+pub struct CameraFollow {
+    /// Current horizontal lead ahead of the player (pixels)
+    pub look_ahead: f32,
+    /// Height the camera frames, held while the player is in the air and inside the vertical
+    /// deadzone (`None` until the camera has found the player in the level)
+    pub focus_y: Option<f32>,
+}
+
+/// Camera override resource: Where a script has pointed the camera, which stays there instead of
+/// following the player until the script finishes
+#[derive(Resource, Default)]
+pub struct CameraOverride(pub Option<Vec2>);
+
+/// A camera zone read from level metadata (positions in world pixels): while the player is in its
+/// region the camera takes the zone's zoom, and a locked zone keeps the view inside the region
+/// instead of the level, like a boss room that fills the screen
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct CameraZoneSetting {
+    /// Bottom-left corner of the region
+    pub min: [f32; 2],
+    /// Top-right corner of the region
+    pub max: [f32; 2],
+    /// Zoom inside the zone (orthographic scale), or the player's own zoom
+    pub zoom: Option<f32>,
+    /// Keep the view inside the region
+    pub lock: bool,
+}
+
+impl CameraZoneSetting {
+    pub fn region(&self) -> Aabb {
+        Aabb {
+            min: Vec2::from(self.min),
+            max: Vec2::from(self.max),
+        }
+    }
+}
+
+/// Camera zone resource: Index of the level's camera zone the player is in (the first, where
+/// zones overlap)
+#[derive(Resource, Default)]
+pub struct CameraZone(pub Option<usize>);
+
+impl CameraZone {
+    /// Settings of the zone the player is in
+    pub fn setting<'a>(&self, level: Option<&'a Level>) -> Option<&'a CameraZoneSetting> {
+        level?.metadata.camera_zones.get(self.0?)
+    }
+}
+
+pub struct CameraControlsPlugin;
+
+impl Plugin for CameraControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CameraControls {
+            zoom: 1.0,
+            free_fly: false,
+            detached_from: None,
+        });
+        app.init_resource::<CameraZone>();
+        app.add_plugins(PixelPerfectPlugin);
+        app.add_systems(Update, s_camera_zones);
+        app.add_systems(Update, s_camera_zoom.after(s_camera_zones));
+        app.add_systems(Update, s_free_fly_camera.after(s_camera_zoom));
+        // After everything that moves the camera, before the move reaches the renderer
+        app.add_systems(PostUpdate, s_camera_collision.before(TransformSystems::Propagate));
+    }
+}
+
+/// Camera follow plugin: Keeps the camera on the player with the damping, look-ahead and vertical
+/// deadzone from the settings, without showing anything past the level bounds
+pub struct CameraFollowPlugin;
+
+impl Plugin for CameraFollowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraFollow>();
+        app.init_resource::<CameraOverride>();
+        app.add_systems(OnEnter(GameState::InGame), s_reset_camera_follow);
+        // After the player has moved, before collision confines the view
+        app.add_systems(PostUpdate, s_camera_follow.before(s_camera_collision));
+    }
+}
+
+/// Spawns the world camera, rendering through a low-res canvas when pixel-perfect mode is on
+pub fn spawn_game_camera(commands: &mut Commands, images: &mut Assets<Image>, settings: &Settings) {
+    if settings.pixel_perfect {
+        spawn_pixel_perfect_cameras(commands, images, UVec2::from(settings.virtual_resolution));
+    } else {
+        commands.spawn((Camera2d, GameCamera));
+    }
+}
+
+/// World position under the mouse cursor, if it is over the window. In pixel-perfect mode the
+/// cursor is mapped onto the low-res canvas first.
+pub fn cursor_world_position(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    canvas: Option<(&Projection, &CanvasCamera)>,
+) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+
+    let viewport_position = match canvas {
+        Some((Projection::Orthographic(orthographic), canvas_camera)) => {
+            // The canvas is drawn centred in the window, scaled up by the inverse of its scale
+            let from_center = cursor - window.size() / 2.0;
+            from_center * orthographic.scale + canvas_camera.resolution.as_vec2() / 2.0
+        }
+        _ => cursor,
+    };
+
+    camera.viewport_to_world_2d(camera_transform, viewport_position).ok()
+}
+
+/// Camera zone system: Finds the camera zone the player is in
+pub fn s_camera_zones(
+    level: Option<Res<Level>>,
+    player_query: Query<&Transform, With<ActiveCharacter>>,
+    mut camera_zone: ResMut<CameraZone>,
+) {
+    let zone = level.zip(player_query.single().ok()).and_then(|(level, player_transform)| {
+        let player = player_transform.translation.xy();
+        level
+            .metadata
+            .camera_zones
+            .iter()
+            .position(|zone| zone.region().contains(player))
+    });
+    if camera_zone.0 != zone {
+        camera_zone.0 = zone;
+    }
+}
+
+/// Zoom system: Mouse wheel zooms the camera in and out. Inside a camera zone with a zoom of its
+/// own the camera eases to that instead (except in free-fly).
+pub fn s_camera_zoom(
+    scroll: Res<AccumulatedMouseScroll>,
+    time: Res<Time<Real>>,
+    mut camera_controls: ResMut<CameraControls>,
+    camera_zone: Res<CameraZone>,
+    level: Option<Res<Level>>,
+    mut projection_query: Query<&mut Projection, With<GameCamera>>,
+) {
+    if scroll.delta.y != 0.0 {
+        let lines = match scroll.unit {
+            MouseScrollUnit::Line => scroll.delta.y,
+            MouseScrollUnit::Pixel => scroll.delta.y / PIXELS_PER_SCROLL_LINE,
+        };
+
+        // Scrolling up zooms in
+        camera_controls.zoom =
+            (camera_controls.zoom * ops::powf(ZOOM_STEP, -lines)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    let zone_zoom = camera_zone
+        .setting(level.as_deref())
+        .and_then(|zone| zone.zoom)
+        .filter(|_| !camera_controls.free_fly);
+    let zoom = zone_zoom.unwrap_or(camera_controls.zoom);
+    // Fraction of the remaining zoom change covered this frame (framerate independent)
+    let blend = 1.0 - ops::exp(-ZOOM_DAMPING * time.delta_secs());
+
+    for mut projection in projection_query.iter_mut() {
+        if let Projection::Orthographic(orthographic) = projection.as_mut() {
+            if orthographic.scale != zoom {
+                orthographic.scale = if (orthographic.scale - zoom).abs() < ZOOM_SNAP {
+                    zoom
+                } else {
+                    orthographic.scale + (zoom - orthographic.scale) * blend
+                };
+            }
+        }
+    }
+}
+
+/// Free-fly system: While free-fly is on, WASD moves the camera independently of the player (the
+/// second player's input is held back meanwhile, see `s_read_input_actions`). Uses real time so
+/// the camera still moves if the simulation is paused or slowed.
+pub fn s_free_fly_camera(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    mut camera_controls: ResMut<CameraControls>,
+    mut camera_query: Query<&mut Transform, With<GameCamera>>,
+) {
+    let Ok(mut camera_transform) = camera_query.single_mut() else {
+        return;
+    };
+
+    if !camera_controls.free_fly {
+        // Snap back to where the camera was when free-fly started
+        if let Some(position) = camera_controls.detached_from.take() {
+            camera_transform.translation = position;
+        }
+        return;
+    }
+
+    if camera_controls.detached_from.is_none() {
+        camera_controls.detached_from = Some(camera_transform.translation);
+    }
+
+    let mut direction = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::KeyW) {
+        direction.y += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::KeyS) {
+        direction.y -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::KeyA) {
+        direction.x -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::KeyD) {
+        direction.x += 1.0;
+    }
+
+    // Move faster when zoomed out so crossing the level takes the same time on screen
+    let velocity_dt =
+        direction.normalize_or_zero() * FREE_FLY_SPEED * camera_controls.zoom * time.delta_secs();
+    camera_transform.translation += velocity_dt.extend(0.0);
+}
+
+/// Camera follow reset system: Makes the camera jump straight to the player in a new level
+pub fn s_reset_camera_follow(
+    mut follow: ResMut<CameraFollow>,
+    mut camera_override: ResMut<CameraOverride>,
+) {
+    *follow = CameraFollow::default();
+    camera_override.0 = None;
+}
+
+/// Camera follow system: Eases the camera towards the player, leading them in the direction they
+/// are running. While the player is in the air the camera only moves up or down once they leave
+/// the vertical deadzone, so a jump doesn't bob the view. The view stays inside the level, or the
+/// locked camera zone the player is in. Free-fly and cameras a script has moved are left alone.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn s_camera_follow(
+    settings: Res<Settings>,
+    time: Res<Time>,
+    camera_controls: Res<CameraControls>,
+    camera_override: Res<CameraOverride>,
+    mut follow: ResMut<CameraFollow>,
+    camera_zone: Res<CameraZone>,
+    level: Option<Res<Level>>,
+    player_query: Query<(&Transform, &KinematicBody), (With<ActiveCharacter>, Without<GameCamera>)>,
+    mut camera_query: Query<(&mut Transform, &Projection), With<GameCamera>>,
+) {
+    if !settings.camera_follow || camera_controls.free_fly || camera_override.0.is_some() {
+        return;
+    }
+    let Ok((player_transform, physics)) = player_query.single() else {
+        return;
+    };
+    let Ok((mut camera_transform, projection)) = camera_query.single_mut() else {
+        return;
+    };
+    let player = player_transform.translation.xy();
+    let dt = time.delta_secs();
+
+    // Ease the lead over rather than flipping it when the player turns
+    let max_look_ahead = settings.camera_max_look_ahead;
+    let look_ahead_goal =
+        (physics.velocity.x * settings.camera_look_ahead).clamp(-max_look_ahead, max_look_ahead);
+    follow.look_ahead += (look_ahead_goal - follow.look_ahead)
+        * (1.0 - ops::exp(-LOOK_AHEAD_DAMPING * dt));
+
+    let snap = follow.focus_y.is_none();
+    let deadzone = settings.camera_vertical_deadzone;
+    let focus_y = match follow.focus_y {
+        Some(focus_y) if !physics.on_ground() => {
+            focus_y.clamp(player.y - deadzone, player.y + deadzone)
+        }
+        _ => player.y,
+    };
+    follow.focus_y = Some(focus_y);
+
+    let mut goal = Vec2::new(player.x + follow.look_ahead, focus_y);
+    if let (Some(level), Projection::Orthographic(orthographic)) = (&level, projection) {
+        let bounds = match camera_zone.setting(Some(level)) {
+            Some(zone) if zone.lock => zone.region(),
+            _ => Aabb::from_half_size(level.half_size),
+        };
+        goal = clamp_view(goal, orthographic.area.half_size(), &bounds);
+    }
+
+    let center = camera_transform.translation.xy();
+    let center = if snap {
+        goal
+    } else {
+        // Fraction of the remaining distance covered this frame (framerate independent)
+        center.lerp(goal, 1.0 - ops::exp(-settings.camera_damping * dt))
+    };
+    camera_transform.translation = center.extend(camera_transform.translation.z);
+}
+
+/// Camera collision system: Keeps the view inside the level bounds and off the level's curtain
+/// polygons (see `LevelMetadata::camera_curtains`), unless turned off in the settings. Free-fly
+/// is left alone, as it is for looking around the level.
+pub fn s_camera_collision(
+    settings: Res<Settings>,
+    camera_controls: Res<CameraControls>,
+    level: Option<Res<Level>>,
+    mut camera_query: Query<(&mut Transform, &Projection), With<GameCamera>>,
+) {
+    if !settings.camera_collision || camera_controls.free_fly {
+        return;
+    }
+    let Some(level) = level else {
+        return;
+    };
+
+    for (mut transform, projection) in camera_query.iter_mut() {
+        let Projection::Orthographic(orthographic) = projection else {
+            continue;
+        };
+        let half_view = orthographic.area.half_size();
+        let center = transform.translation.truncate();
+
+        let confined = confine_view(&level, center, half_view);
+        if confined != center {
+            transform.translation = confined.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Center of a view of the given half size, moved as little as possible from `center` so that
+/// it stays inside the level and doesn't overlap any curtain polygon
+pub fn confine_view(level: &Level, center: Vec2, half_view: Vec2) -> Vec2 {
+    let bounds = Aabb::from_half_size(level.half_size);
+    let mut center = clamp_view(center, half_view, &bounds);
+
+    let curtains: HashSet<usize> = level
+        .metadata
+        .camera_curtains
+        .iter()
+        .filter_map(|&point| level.polygon_at(Vec2::from(point)))
+        .collect();
+    if curtains.is_empty() {
+        return center;
+    }
+
+    let view = Aabb {
+        min: center - half_view,
+        max: center + half_view,
+    };
+    for polygon in level
+        .query_aabb(&view)
+        .filter(|polygon| curtains.contains(&polygon.id))
+    {
+        let view = Aabb {
+            min: center - half_view,
+            max: center + half_view,
+        };
+        if !view.overlaps(&polygon.aabb) {
+            continue;
+        }
+
+        // Back away from the curtain on the side the view is already on, along the axis that
+        // needs the smaller move
+        let curtain_center = (polygon.aabb.min + polygon.aabb.max) / 2.0;
+        let push = Vec2::new(
+            if center.x < curtain_center.x {
+                polygon.aabb.min.x - view.max.x
+            } else {
+                polygon.aabb.max.x - view.min.x
+            },
+            if center.y < curtain_center.y {
+                polygon.aabb.min.y - view.max.y
+            } else {
+                polygon.aabb.max.y - view.min.y
+            },
+        );
+        if push.x.abs() < push.y.abs() {
+            center.x += push.x;
+        } else {
+            center.y += push.y;
+        }
+    }
+
+    // The level edge wins over a curtain that can't be cleared
+    clamp_view(center, half_view, &bounds)
+}
+
+/// Clamps a view's center so the view stays inside `bounds`, centering it on an axis where the
+/// view is larger than the bounds
+fn clamp_view(center: Vec2, half_view: Vec2, bounds: &Aabb) -> Vec2 {
+    let clamp_axis = |center: f32, half_view: f32, min: f32, max: f32| {
+        if max - min <= half_view * 2.0 {
+            (min + max) / 2.0
+        } else {
+            center.clamp(min + half_view, max - half_view)
+        }
+    };
+
+    Vec2::new(
+        clamp_axis(center.x, half_view.x, bounds.min.x, bounds.max.x),
+        clamp_axis(center.y, half_view.y, bounds.min.y, bounds.max.y),
+    )
+}
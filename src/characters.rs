@@ -0,0 +1,211 @@
+//! Controllable characters: besides the player's own character, a level can place characters
+//! with other movement profiles (heavy, floaty, speedster), and the switch key hands control from
+//! one to the next.
+//!
+//! Every character is a `Player` entity with its own controller state, but only the one with
+//! `ActiveCharacter` takes input (the others stand where they were left). Systems that follow
+//! "the player" (camera, feedback) look at the active character.
+//!
+//! With `Settings::two_player` on, a second local player controls a character of their own
+//! (marked `PlayerTwo`) at the same time. AI agents go after whichever player is nearest.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::{Color, Luminance},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Has, Or, With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::Vec2,
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    state::condition::in_state,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_state::GameState, input::InputAction, movement_config::MovementConfig, s_input, Player,
+};
+
+// How much darker characters are drawn while nobody is controlling them (fraction of brightness)
+const INACTIVE_DARKEN_AMOUNT: f32 = 0.5;
+// Colour of the second player's character
+const PLAYER_TWO_COLOR: Color = Color::srgb(0.5, 1.0, 0.6);
+
+/// How a character moves, as changes to the base `MovementConfig`
+#[derive(Component, Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CharacterProfile {
+    /// Moves exactly as the movement config says
+    #[default]
+    Standard,
+    /// Slow, falls fast and shrugs off knockback, with no air jumps
+    Heavy,
+    /// Drifts through the air under low gravity, with an extra air jump
+    Floaty,
+    /// Runs and dashes fast, with quick turns
+    Speedster,
+}
+
+impl CharacterProfile {
+    /// The base movement config adjusted for this profile
+    pub fn movement_config(self, base: &MovementConfig) -> MovementConfig {
+        let mut config = *base;
+        match self {
+            CharacterProfile::Standard => {}
+            CharacterProfile::Heavy => {
+                config.max_speed *= 0.75;
+                config.gravity *= 1.4;
+                config.jump_velocity *= 1.1;
+                config.dash_velocity *= 0.8;
+                config.max_air_jumps = 0;
+            }
+            CharacterProfile::Floaty => {
+                config.gravity *= 0.55;
+                config.jump_velocity *= 0.8;
+                config.acceleration_scalers.0 *= 0.6;
+                config.acceleration_scalers.1 *= 0.6;
+                config.max_air_jumps += 1;
+            }
+            CharacterProfile::Speedster => {
+                config.max_speed *= 1.5;
+                config.acceleration_scalers.0 *= 1.5;
+                config.acceleration_scalers.1 *= 1.5;
+                config.dash_velocity *= 1.25;
+                config.dash_cooldown *= 0.5;
+            }
+        }
+        config
+    }
+
+    /// Knockback resistance (see `Mass`)
+    pub fn mass(self) -> f32 {
+        match self {
+            CharacterProfile::Heavy => 2.5,
+            CharacterProfile::Floaty => 0.7,
+            CharacterProfile::Standard | CharacterProfile::Speedster => 1.0,
+        }
+    }
+
+    /// Colour the character is drawn in while in control
+    pub fn color(self) -> Color {
+        match self {
+            CharacterProfile::Standard => Color::WHITE,
+            CharacterProfile::Heavy => Color::srgb(1.0, 0.6, 0.4),
+            CharacterProfile::Floaty => Color::srgb(0.7, 0.7, 1.0),
+            CharacterProfile::Speedster => Color::srgb(1.0, 1.0, 0.4),
+        }
+    }
+}
+
+/// An extra character placed in the level (read from level metadata)
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct CharacterSetting {
+    /// World pixels
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub profile: CharacterProfile,
+}
+
+/// Marker for the character the (first) player is controlling
+#[derive(Component)]
+pub struct ActiveCharacter;
+
+/// Marker for the character the second local player controls
+#[derive(Component)]
+pub struct PlayerTwo;
+
+/// Query filter for the characters someone is controlling
+pub type PlayerControlled = Or<(With<ActiveCharacter>, With<PlayerTwo>)>;
+
+/// Of the `players` positions, the one nearest `position`
+pub fn nearest_player(players: &[Vec2], position: Vec2) -> Option<Vec2> {
+    players.iter().copied().min_by(|a, b| {
+        a.distance_squared(position)
+            .total_cmp(&b.distance_squared(position))
+    })
+}
+
+pub struct CharacterPlugin;
+
+impl Plugin for CharacterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (s_switch_character, s_tint_characters)
+                .chain()
+                .before(s_input)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Character switch system: Hands control to the next character (in spawn order) when the switch
+/// action is pressed, skipping the second player's
+#[allow(clippy::type_complexity)]
+fn s_switch_character(
+    mut commands: Commands,
+    input_action: Res<InputAction>,
+    character_query: Query<
+        (Entity, Option<&ActiveCharacter>),
+        (With<Player>, Without<PlayerTwo>),
+    >,
+) {
+    if !input_action.switch_character_pressed {
+        return;
+    }
+
+    let mut characters: Vec<_> = character_query.iter().collect();
+    if characters.len() < 2 {
+        return;
+    }
+    characters.sort_unstable_by_key(|(entity, _)| *entity);
+
+    let active = characters
+        .iter()
+        .position(|(_, active)| active.is_some())
+        .unwrap_or(characters.len() - 1);
+    let next = characters[(active + 1) % characters.len()].0;
+
+    for (entity, active) in characters {
+        if active.is_some() {
+            commands.entity(entity).remove::<ActiveCharacter>();
+        }
+    }
+    commands.entity(next).insert(ActiveCharacter);
+}
+
+/// Character tint system: Draws the characters in control in their profile's colour (the second
+/// player's in theirs) and the others darker
+#[allow(clippy::type_complexity)]
+fn s_tint_characters(
+    character_query: Query<
+        (
+            &CharacterProfile,
+            &MeshMaterial2d<ColorMaterial>,
+            Has<ActiveCharacter>,
+            Has<PlayerTwo>,
+        ),
+        With<Player>,
+    >,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (profile, material, active, player_two) in character_query.iter() {
+        let color = if player_two {
+            PLAYER_TWO_COLOR
+        } else if active {
+            profile.color()
+        } else {
+            profile.color().darker(INACTIVE_DARKEN_AMOUNT)
+        };
+        // Only touch materials that change, so unchanged ones aren't re-uploaded every frame
+        if materials.get(&material.0).is_some_and(|current| current.color != color) {
+            if let Some(current) = materials.get_mut(&material.0) {
+                current.color = color;
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        message::{Message, MessageWriter},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query},
+    },
+    math::{primitives::RegularPolygon, Vec2},
+    mesh::{Mesh, Mesh2d},
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    transform::components::Transform,
+};
+
+use crate::{
+    characters::PlayerControlled,
+    collisions::{s_sensors, Sensor},
+    level::Level,
+};
+
+// Collectible gem constants
+const GEM_RADIUS: f32 = 8.0;
+const GEM_COLOR: Color = Color::srgb(0.3, 0.8, 1.0);
+const GEM_Z: f32 = 0.5;
+
+/// Collectible component: A gem placed by the level metadata, picked up when the player touches it
+#[derive(Component)]
+pub struct Collectible {
+    /// Index of the gem in the level metadata (identifies it in the save file)
+    pub index: usize,
+}
+
+/// Sent when the player picks up a collectible
+#[derive(Message, Clone, Copy)]
+pub struct Collected {
+    pub index: usize,
+}
+
+pub struct CollectiblePlugin;
+
+impl Plugin for CollectiblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<Collected>();
+        app.add_systems(Update, s_collect.after(s_sensors));
+    }
+}
+
+/// Spawns a gem for every collectible position in the level metadata
+pub fn spawn_collectibles(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    level: &Level,
+) {
+    for (index, position) in level.metadata.collectibles.iter().enumerate() {
+        commands.spawn((
+            Transform::from_translation(Vec2::from(*position).extend(GEM_Z)),
+            Mesh2d(meshes.add(RegularPolygon::new(GEM_RADIUS, 4))),
+            MeshMaterial2d(materials.add(GEM_COLOR)),
+            Sensor::new(GEM_RADIUS),
+            Collectible { index },
+        ));
+    }
+}
+
+/// Collect system: Picks up the gems either player touches
+pub fn s_collect(
+    mut commands: Commands,
+    collectible_query: Query<(Entity, &Sensor, &Collectible)>,
+    player_query: Query<(), PlayerControlled>,
+    mut collected: MessageWriter<Collected>,
+) {
+    for (entity, sensor, collectible) in collectible_query.iter() {
+        let touched = sensor
+            .overlapping_entities
+            .iter()
+            .any(|&overlapping| player_query.contains(overlapping));
+        if touched {
+            commands.entity(entity).despawn();
+            collected.write(Collected {
+                index: collectible.index,
+            });
+        }
+    }
+}
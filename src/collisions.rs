@@ -2,216 +2,942 @@ use bevy::{
     app::{App, Plugin, Update},
     color::Color,
     ecs::{
+        component::Component,
+        entity::Entity,
+        message::{Message, MessageReader, MessageWriter},
+        query::Without,
         schedule::IntoScheduleConfigs,
         system::{Query, Res},
     },
     gizmos::gizmos::Gizmos,
-    math::{Vec2, Vec3Swizzles},
+    math::{ops::FloatPow, Vec2, Vec3Swizzles},
+    state::condition::in_state,
+    time::Time,
     transform::components::Transform,
 };
 
 use crate::{
-    ai::platformer_ai::{AIPhysics, s_platformer_ai_movement},
-    level::{Aabb, Level},
-    s_movement, Physics, Player, CEILING_NORMAL_Y_THRESHOLD,
-    GROUND_NORMAL_Y_THRESHOLD, MAX_GROUNDED_TIMER, MAX_WALLED_TIMER, NORMAL_DOT_THRESHOLD,
+    ai::{
+        activity::Asleep,
+        platformer_ai::{s_platformer_ai_movement, PlatformerAI},
+    },
+    game_state::GameState,
+    knockback::Mass,
+    level::{materials::SurfaceMaterial, Aabb, Level, Polygon},
+    movement_config::MovementConfig,
+    s_movement, KinematicBody, Player, CEILING_NORMAL_Y_THRESHOLD,
+    GROUND_NORMAL_Y_THRESHOLD, NORMAL_DOT_THRESHOLD,
 };
 
 // Collision detection constants
 const RAYCAST_DIRECTION_SCALE: f32 = 10000.0;
 const RAYCAST_DIRECTION: Vec2 = Vec2::new(2.0, 1.0);
-const TOUCH_THRESHOLD: f32 = 0.5;
+pub const TOUCH_THRESHOLD: f32 = 0.5;
 const DEBUG_NORMAL_LINE_LENGTH: f32 = 12.0;
+const DEBUG_AABB_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.25);
 const DISTANCE_CALCULATION_RADIUS_MULTIPLIER: f32 = 2.0;
+// Upper bound on deepest-contact resolution passes per body per frame
+const MAX_PENETRATION_ITERATIONS: usize = 4;
+// Bisection steps used to find the last non-penetrating point along a tunneling motion
+const TUNNEL_RESTORE_SEARCH_STEPS: usize = 8;
+// Upper bound on sweep-and-slide passes per body per frame
+const MAX_SWEEP_ITERATIONS: usize = 3;
+// Gap left between a swept circle and the surface it hit (pixels)
+const SWEEP_SKIN: f32 = 0.01;
+// How far past an edge's end a body still stands on it, bridging the float error between the
+// vertices of neighbouring tiles (pixels)
+const SEAM_TOLERANCE: f32 = 0.05;
+// Contacts whose normals agree this closely lie on collinear edges and count as one
+const COLLINEAR_CONTACT_DOT: f32 = 0.9999;
 
 pub struct CollisionPlugin;
 
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, s_collision.after(s_movement));
-        app.add_systems(Update, s_ai_collision.after(s_platformer_ai_movement));
+        app.add_message::<CollisionStarted>();
+        app.add_message::<CollisionEnded>();
+        app.add_message::<SurfaceContact>();
+        app.add_message::<WallSliding>();
+
+        app.add_systems(
+            Update,
+            s_collision
+                .after(s_movement)
+                .after(s_platformer_ai_movement)
+                .run_if(in_state(GameState::InGame)),
+        );
+        app.add_systems(Update, s_player_contacts.after(s_collision));
+        app.add_systems(Update, s_ai_contacts.after(s_collision));
+        app.add_systems(Update, s_body_collision.after(s_collision));
+        app.add_systems(Update, s_wall_slides.after(s_collision));
+        app.add_systems(
+            Update,
+            s_sensors
+                .after(s_collision)
+                .after(s_body_collision)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Sensor collider: Detects overlaps with bodies and level geometry but never receives positional
+/// correction. Used for trigger volumes such as detection zones, attack hitboxes and pickups.
+#[derive(Component)]
+pub struct Sensor {
+    /// Overlap radius (pixels)
+    pub radius: f32,
+    /// Bodies overlapping the sensor this frame (filled in by `s_sensors`)
+    pub overlapping_entities: Vec<Entity>,
+    /// Whether the sensor overlaps any level polygon this frame (filled in by `s_sensors`)
+    pub overlapping_level: bool,
+}
+
+impl Sensor {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            overlapping_entities: Vec::new(),
+            overlapping_level: false,
+        }
+    }
+}
+
+/// Collision layers of a dynamic body. Two bodies push each other apart only if each one's
+/// `mask` includes a layer of the other's `membership`
+#[derive(Component, Clone, Copy)]
+pub struct CollisionLayers {
+    /// Layers the body belongs to
+    pub membership: u32,
+    /// Layers the body collides with
+    pub mask: u32,
+}
+
+impl CollisionLayers {
+    pub const PLAYER: u32 = 1 << 0;
+    pub const AGENT: u32 = 1 << 1;
+
+    pub fn new(membership: u32, mask: u32) -> Self {
+        Self { membership, mask }
+    }
+
+    /// Whether two bodies with these layers collide with each other
+    pub fn interacts_with(&self, other: &CollisionLayers) -> bool {
+        self.mask & other.membership != 0 && other.mask & self.membership != 0
     }
 }
 
+/// A body started touching a level polygon
+#[derive(Message, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct CollisionStarted {
+    pub entity: Entity,
+    pub polygon_id: usize,
+    /// Direction away from the surface that was hit
+    pub normal: Vec2,
+    /// Speed (pixels/second) the body was moving into the surface when it hit
+    pub impact_speed: f32,
+    /// What the surface that was hit is made of
+    pub material: SurfaceMaterial,
+}
+
+/// A body stopped touching a level polygon
+#[derive(Message, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct CollisionEnded {
+    pub entity: Entity,
+    pub polygon_id: usize,
+}
+
+/// A body is touching a surface of a level polygon this frame (one per surface)
+#[derive(Message, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct SurfaceContact {
+    pub entity: Entity,
+    /// Direction away from the surface
+    pub normal: Vec2,
+    pub polygon_id: usize,
+    /// What the surface is made of where the body touches it
+    pub material: SurfaceMaterial,
+}
+
+/// A character is sliding down a wall it presses into this frame (one per character, see
+/// `Player::wall_sliding`)
+#[derive(Message, Clone, Copy, Debug)]
+pub struct WallSliding {
+    pub entity: Entity,
+    /// Direction away from the wall
+    pub normal: Vec2,
+    /// Speed (pixels/second) the body is sliding down at
+    pub speed: f32,
+    /// What the wall is made of where the body touches it
+    pub material: SurfaceMaterial,
+}
+
+/// Level collision system: Resolves every entity with `KinematicBody` against the level, records
+/// its contacts and reports them as collision messages (sleeping AI agents are skipped)
+#[allow(clippy::type_complexity)]
 pub fn s_collision(
-    mut player_query: Query<(&mut Transform, &mut Physics, &mut Player)>,
+    mut physics_query: Query<
+        (Entity, &mut Transform, &mut KinematicBody),
+        (Without<Sensor>, Without<Asleep>),
+    >,
     level: Res<Level>,
+    movement_config: Res<MovementConfig>,
+    time: Res<Time>,
+    mut collisions_started: MessageWriter<CollisionStarted>,
+    mut collisions_ended: MessageWriter<CollisionEnded>,
+    mut surface_contacts: MessageWriter<SurfaceContact>,
 ) {
-    if let Ok((mut player_transform, mut player_physics, mut player_data)) =
-        player_query.single_mut()
-    {
-        let mut adjustment = Vec2::ZERO;
-        let mut new_player_normal = Vec2::ZERO;
-
-        // Pre-compute player AABB for broad-phase collision detection
-        let player_pos = player_transform.translation.xy();
-        let player_aabb = Aabb::from_point_radius(player_pos, player_physics.radius);
-        // Expand AABB slightly to account for movement
-        let expanded_player_aabb = player_aabb.expand(player_physics.radius * 0.5);
-
-        // Pre-compute radius squared to avoid repeated calculations
-        let radius_sq = player_physics.radius.powi(2);
-        let touch_threshold_sq = (player_physics.radius + TOUCH_THRESHOLD).powi(2);
-
-        for polygon in &level.polygons {
-            // Broad-phase: AABB pre-check to skip polygons far from player
-            if !expanded_player_aabb.overlaps(&polygon.aabb) {
+    let dt = time.delta_secs().min(1.0 / 30.0);
+
+    for (entity, mut transform, mut physics) in physics_query.iter_mut() {
+        // Bodies standing on a moving platform travel with it
+        let carried = physics.ground_velocity * dt;
+        physics.prev_position += carried;
+        transform.translation += carried.extend(0.0);
+
+        let prev_position = physics.prev_position;
+        let radius = physics.radius;
+        let incoming_velocity = physics.velocity;
+
+        let position = resolve_level_penetration(
+            &level,
+            transform.translation.xy(),
+            prev_position,
+            radius,
+            &mut physics.velocity,
+        );
+
+        let contacts = find_contacts(&level, position, prev_position, radius);
+        physics.contacts = contacts.iter().map(|(normal_dir, _)| *normal_dir).collect();
+
+        // The body touches each surface one radius from its centre, against the normal
+        let material_at = |normal: Vec2| level.material_at(position - normal * radius);
+        for &(normal, polygon_id) in &contacts {
+            surface_contacts.write(SurfaceContact {
+                entity,
+                normal,
+                polygon_id,
+                material: material_at(normal),
+            });
+        }
+
+        // Compare against last frame's polygons to find the collisions that started and ended
+        let mut touching_polygons: Vec<usize> =
+            contacts.iter().map(|(_, polygon_id)| *polygon_id).collect();
+        touching_polygons.sort_unstable();
+        touching_polygons.dedup();
+
+        let previous_polygons = std::mem::take(&mut physics.touching_polygons);
+        for &polygon_id in &touching_polygons {
+            if previous_polygons.contains(&polygon_id) {
                 continue;
             }
+            // Report the first surface of the polygon that was hit
+            if let Some(&(normal, _)) = contacts.iter().find(|(_, id)| *id == polygon_id) {
+                collisions_started.write(CollisionStarted {
+                    entity,
+                    polygon_id,
+                    normal,
+                    impact_speed: (-incoming_velocity.dot(normal)).max(0.0),
+                    material: material_at(normal),
+                });
+            }
+        }
+        for polygon_id in previous_polygons {
+            if touching_polygons.binary_search(&polygon_id).is_err() {
+                collisions_ended.write(CollisionEnded { entity, polygon_id });
+            }
+        }
+        physics.touching_polygons = touching_polygons;
 
-            let mut intersect_counter = 0;
-            let mut colliding_with_polygon = false;
+        // Keep the platform's momentum when stepping or jumping off it
+        let ground_velocity = find_ground_velocity(&level, position, prev_position, radius);
+        if ground_velocity == Vec2::ZERO {
+            let platform_velocity = physics.ground_velocity;
+            physics.velocity += platform_velocity;
+        }
+        physics.ground_velocity = ground_velocity;
 
-            // Raycast intersection check for point-in-polygon test
-            for i in 1..polygon.points.len() {
-                let start = polygon.points[i - 1];
-                let end = polygon.points[i];
+        // Update the body's normal
+        let mut new_normal = Vec2::ZERO;
+        for normal_dir in &physics.contacts {
+            new_normal -= *normal_dir;
+        }
+        new_normal = new_normal.normalize_or_zero();
+        physics.normal = new_normal;
+
+        // Remove the body's velocity into the surface
+        physics.velocity = clamp_velocity_into_surface(
+            physics.velocity,
+            new_normal,
+            movement_config.landing_restitution_threshold,
+        );
+
+        // Update the body's position
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
 
-                let intersection = line_intersect(
-                    start,
-                    end,
-                    player_pos,
-                    player_pos + RAYCAST_DIRECTION * RAYCAST_DIRECTION_SCALE,
-                );
+/// Wall slide system: Reports characters that are wall sliding and moving down the wall (not
+/// every body falling past a wall, which is only brushing it)
+pub fn s_wall_slides(
+    mut surface_contacts: MessageReader<SurfaceContact>,
+    body_query: Query<(&KinematicBody, &Player)>,
+    mut wall_slides: MessageWriter<WallSliding>,
+) {
+    let mut sliding: Vec<Entity> = Vec::new();
 
-                if intersection.is_some() {
-                    intersect_counter += 1;
-                }
+    for contact in surface_contacts.read() {
+        if contact.normal.x.abs() < NORMAL_DOT_THRESHOLD || sliding.contains(&contact.entity) {
+            continue;
+        }
+        let Ok((body, player)) = body_query.get(contact.entity) else {
+            continue;
+        };
+        if !player.wall_sliding() || body.velocity.y >= 0.0 {
+            continue;
+        }
+
+        sliding.push(contact.entity);
+        wall_slides.write(WallSliding {
+            entity: contact.entity,
+            normal: contact.normal,
+            speed: -body.velocity.y,
+            material: contact.material,
+        });
+    }
+}
+
+/// Body collision system: Pushes overlapping dynamic bodies apart and cancels the velocity they
+/// close in with, splitting both by mass so heavy bodies shove light ones aside
+#[allow(clippy::type_complexity)]
+pub fn s_body_collision(
+    mut body_query: Query<
+        (&mut Transform, &mut KinematicBody, &CollisionLayers, Option<&Mass>),
+        (Without<Sensor>, Without<Asleep>),
+    >,
+) {
+    let mut pairs = body_query.iter_combinations_mut();
+
+    while let Some([body_a, body_b]) = pairs.fetch_next() {
+        let (mut transform_a, mut body_a, layers_a, mass_a) = body_a;
+        let (mut transform_b, mut body_b, layers_b, mass_b) = body_b;
+
+        if !layers_a.interacts_with(layers_b) {
+            continue;
+        }
+
+        let position_a = transform_a.translation.xy();
+        let position_b = transform_b.translation.xy();
+        let radii = body_a.radius + body_b.radius;
+
+        let offset = position_b - position_a;
+        let distance_sq = offset.length_squared();
+        if distance_sq >= radii * radii {
+            continue;
+        }
+
+        // Bodies exactly on top of each other are split sideways
+        let distance = distance_sq.sqrt();
+        let normal = if distance > f32::EPSILON {
+            offset / distance
+        } else {
+            Vec2::X
+        };
+        let penetration = radii - distance;
+
+        let inverse_mass_a = 1.0 / Mass::of(mass_a);
+        let inverse_mass_b = 1.0 / Mass::of(mass_b);
+        let inverse_mass_sum = inverse_mass_a + inverse_mass_b;
+
+        let push = normal * penetration / inverse_mass_sum;
+        transform_a.translation -= (push * inverse_mass_a).extend(0.0);
+        transform_b.translation += (push * inverse_mass_b).extend(0.0);
+
+        // Inelastic: remove the velocity the bodies approach each other with
+        let closing_speed = (body_b.velocity - body_a.velocity).dot(normal);
+        if closing_speed < 0.0 {
+            let impulse = normal * closing_speed / inverse_mass_sum;
+            body_a.velocity += impulse * inverse_mass_a;
+            body_b.velocity -= impulse * inverse_mass_b;
+        }
+    }
+}
+
+/// Player contact system: Turns the contacts recorded by `s_collision` into wall and ground timers
+pub fn s_player_contacts(
+    movement_config: Res<MovementConfig>,
+    mut player_query: Query<(&KinematicBody, &mut Player)>,
+) {
+    for (player_physics, mut player_data) in player_query.iter_mut() {
+        for normal_dir in &player_physics.contacts {
+            // If the player is on a wall
+            if normal_dir.x.abs() >= NORMAL_DOT_THRESHOLD {
+                player_data.wall_timer = movement_config.wall_coyote_time;
+                player_data.wall_direction = normal_dir.x.signum();
+                player_data.last_wall_normal = Some(*normal_dir);
+                player_data.has_wall_jumped = false;
+                player_data.air_jumps_used = 0;
             }
 
-            // Narrow-phase: detailed collision detection with polygon edges
+            // If the player is on the ground
+            if normal_dir.y > GROUND_NORMAL_Y_THRESHOLD {
+                player_data.grounded_timer = movement_config.coyote_time;
+                player_data.is_grounded = true;
+                player_data.wall_timer = 0.0;
+                player_data.wall_direction = 0.0;
+                player_data.has_wall_jumped = false;
+                player_data.has_air_dashed = false;
+                player_data.air_jumps_used = 0;
+            }
+        }
+    }
+}
+
+/// AI contact system: Turns the contacts recorded by `s_collision` into each agent's wall and
+/// ground state
+pub fn s_ai_contacts(mut ai_query: Query<(&KinematicBody, &mut PlatformerAI), Without<Asleep>>) {
+    for (body, mut platformer_ai) in ai_query.iter_mut() {
+        for normal_dir in &body.contacts {
+            // If the AI is on a wall
+            if normal_dir.x.abs() >= NORMAL_DOT_THRESHOLD {
+                platformer_ai.walled = normal_dir.x.signum() as i8;
+                platformer_ai.has_wall_jumped = false;
+            }
+
+            // If the AI is on the ground
+            if normal_dir.y > GROUND_NORMAL_Y_THRESHOLD {
+                platformer_ai.grounded = true;
+                platformer_ai.walled = 0;
+                platformer_ai.has_wall_jumped = false;
+            }
+        }
+    }
+}
+
+/// Cancels the part of a velocity that drives into a surface, leaving the tangential part intact.
+///
+/// `normal` points into the surface. Speeds into the surface at or below `restitution_threshold`
+/// (see `MovementConfig::landing_restitution_threshold`) and velocity away from the surface are
+/// left untouched.
+pub fn clamp_velocity_into_surface(
+    velocity: Vec2,
+    normal: Vec2,
+    restitution_threshold: f32,
+) -> Vec2 {
+    let into_surface = velocity.dot(normal);
+
+    if into_surface > restitution_threshold {
+        velocity - normal * into_surface
+    } else {
+        velocity
+    }
+}
+
+/// Moves a circle from its previous position without passing through the level, then pushes it out
+/// of the level geometry one contact at a time.
+///
+/// The motion is swept first so fast bodies stop at the first surface they reach and slide along it
+/// rather than tunneling through thin polygons. Each penetration iteration re-evaluates every
+/// nearby edge from the current position and resolves only the deepest penetration along its own
+/// normal, so overlapping contacts in acute corners settle into a consistent position instead of
+/// being combined axis by axis. Returns the corrected position.
+pub fn resolve_level_penetration(
+    level: &Level,
+    position: Vec2,
+    prev_position: Vec2,
+    radius: f32,
+    velocity: &mut Vec2,
+) -> Vec2 {
+    // Continuous collision: stop at the first surface the circle sweeps into, then slide along it
+    let mut sweep_start = prev_position;
+    let mut position = position;
+
+    for _ in 0..MAX_SWEEP_ITERATIONS {
+        let Some((time_of_impact, normal_dir)) =
+            sweep_circle(level, sweep_start, position, radius)
+        else {
+            break;
+        };
+
+        if normal_dir.y < CEILING_NORMAL_Y_THRESHOLD {
+            velocity.y = 0.0;
+        }
+
+        let motion = position - sweep_start;
+        let contact = sweep_start + motion * time_of_impact + normal_dir * SWEEP_SKIN;
+        let remaining = motion * (1.0 - time_of_impact);
+
+        sweep_start = contact;
+        position = contact + remaining - normal_dir * remaining.dot(normal_dir).min(0.0);
+    }
+
+    // Pre-compute AABB for broad-phase collision detection
+    let aabb = Aabb::from_point_radius(position, radius);
+    // Expand AABB slightly to account for movement
+    let expanded_aabb = aabb.expand(radius * 0.5);
+
+    let nearby_polygons: Vec<&Polygon> = level.query_aabb(&expanded_aabb).collect();
+
+    // Point-in-polygon check: if inside a polygon and the raycast intersects an odd number of times
+    for polygon in &nearby_polygons {
+        if is_colliding_with_polygon(polygon, position, prev_position, radius)
+            && is_inside_polygon(polygon, position)
+        {
+            position = restore_tunneled_position(polygon, prev_position, position, velocity);
+        }
+    }
+
+    // Pre-compute radius squared to avoid repeated calculations
+    let radius_sq = radius.squared();
+
+    for _ in 0..MAX_PENETRATION_ITERATIONS {
+        let mut deepest_contact: Option<(f32, Vec2)> = None;
+
+        for polygon in &nearby_polygons {
             for i in 1..polygon.points.len() {
                 let start = polygon.points[i - 1];
                 let end = polygon.points[i];
 
-                let previous_side_of_line =
-                    side_of_line_detection(start, end, player_physics.prev_position);
+                if side_of_line_detection(start, end, prev_position) != polygon.collision_side {
+                    continue;
+                }
+
+                let (distance_sq, projection) = find_projection(start, end, position, radius);
 
-                if previous_side_of_line != polygon.collision_side {
+                if distance_sq >= radius_sq {
                     continue;
                 }
 
-                let (distance_sq, projection) =
-                    find_projection(start, end, player_pos, player_physics.radius);
-
-                let colliding_with_line = distance_sq <= radius_sq;
-                colliding_with_polygon = colliding_with_polygon || colliding_with_line;
-
-                let touching_line = distance_sq <= touch_threshold_sq;
-
-                if touching_line {
-                    let normal_dir = (player_pos - projection).normalize_or_zero();
-
-                    // If the line is not above the player
-                    if normal_dir.y >= CEILING_NORMAL_Y_THRESHOLD {
-                        // Add the normal dir to the players new normal
-                        new_player_normal -= normal_dir;
-
-                        // If the player is on a wall
-                        if normal_dir.x.abs() >= NORMAL_DOT_THRESHOLD {
-                            player_data.wall_timer = MAX_WALLED_TIMER;
-                            player_data.wall_direction = normal_dir.x.signum();
-                            player_data.last_wall_normal = Some(normal_dir);
-                            player_data.has_wall_jumped = false;
-                        }
-
-                        // If the player is on the ground
-                        if normal_dir.y > GROUND_NORMAL_Y_THRESHOLD {
-                            player_data.grounded_timer = MAX_GROUNDED_TIMER;
-                            player_data.is_grounded = true;
-                            player_data.wall_timer = 0.0;
-                            player_data.wall_direction = 0.0;
-                            player_data.has_wall_jumped = false;
-                        }
-                    }
+                // Use squared distance calculation, only compute sqrt when needed
+                let penetration = radius - distance_sq.sqrt();
+
+                if deepest_contact.is_none_or(|(deepest, _)| penetration > deepest) {
+                    let normal_dir = (position - projection).normalize_or_zero();
+                    deepest_contact = Some((penetration, normal_dir));
                 }
+            }
+        }
 
-                if colliding_with_line {
-                    let mut delta = (player_pos - projection).normalize_or_zero();
+        let Some((penetration, normal_dir)) = deepest_contact else {
+            break;
+        };
 
-                    if delta.y < CEILING_NORMAL_Y_THRESHOLD {
-                        player_physics.velocity.y = 0.0;
-                    }
+        if normal_dir.y < CEILING_NORMAL_Y_THRESHOLD {
+            velocity.y = 0.0;
+        }
 
-                    // Use squared distance calculation, only compute sqrt when needed
-                    let distance = distance_sq.sqrt();
-                    delta *= player_physics.radius - distance;
+        position += normal_dir * penetration;
+    }
 
-                    if delta.x.abs() > adjustment.x.abs() {
-                        adjustment.x = delta.x;
-                    }
-                    if delta.y.abs() > adjustment.y.abs() {
-                        adjustment.y = delta.y;
-                    }
-                }
+    position
+}
+
+/// Sweeps a circle from `start` to `end` against the level geometry.
+///
+/// Returns the time of impact (the fraction of the motion travelled before touching) and the normal
+/// pointing away from the first surface hit. Edges the circle already overlaps at `start` are left
+/// to the penetration solver.
+pub fn sweep_circle(level: &Level, start: Vec2, end: Vec2, radius: f32) -> Option<(f32, Vec2)> {
+    let motion = end - start;
+    if motion.length_squared() <= f32::EPSILON {
+        return None;
+    }
+
+    // Broad-phase: the box covering the whole swept path
+    let sweep_aabb = Aabb {
+        min: start.min(end) - Vec2::splat(radius),
+        max: start.max(end) + Vec2::splat(radius),
+    };
+
+    let mut first_hit: Option<(f32, Vec2)> = None;
+
+    for polygon in level.query_aabb(&sweep_aabb) {
+        for i in 1..polygon.points.len() {
+            let line_start = polygon.points[i - 1];
+            let line_end = polygon.points[i];
+
+            if side_of_line_detection(line_start, line_end, start) != polygon.collision_side {
+                continue;
             }
 
-            // Point-in-polygon check: if inside polygon and raycast intersects odd number of times
-            if colliding_with_polygon && intersect_counter % 2 == 1 {
-                player_transform.translation = player_physics.prev_position.extend(0.0);
+            let Some(hit) = sweep_circle_segment(start, motion, radius, line_start, line_end) else {
+                continue;
+            };
+
+            if first_hit.is_none_or(|(first_time, _)| hit.0 < first_time) {
+                first_hit = Some(hit);
             }
         }
+    }
+
+    first_hit
+}
+
+/// Time of impact of a circle moving by `motion` against a single segment, tested against the
+/// segment's face first and then its end points
+fn sweep_circle_segment(
+    start: Vec2,
+    motion: Vec2,
+    radius: f32,
+    line_start: Vec2,
+    line_end: Vec2,
+) -> Option<(f32, Vec2)> {
+    let edge = line_end - line_start;
+    let edge_length_sq = edge.length_squared();
+    if edge_length_sq <= f32::EPSILON {
+        return None;
+    }
+
+    // Face normal pointing towards the side the circle starts on
+    let mut normal = Vec2::new(-edge.y, edge.x).normalize();
+    if normal.dot(start - line_start) < 0.0 {
+        normal = -normal;
+    }
 
-        // Update the players normal
-        new_player_normal = new_player_normal.normalize_or_zero();
-        player_physics.normal = new_player_normal;
+    let start_distance = (start - line_start).dot(normal);
+    let approach_speed = motion.dot(normal);
 
-        // Remove the players velocity in the direction of the normal
-        let velocity_adjustment =
-            player_physics.velocity.dot(new_player_normal) * new_player_normal;
+    // Already touching this edge
+    if start_distance < radius {
+        return None;
+    }
 
-        player_physics.velocity -= velocity_adjustment;
+    if approach_speed < 0.0 {
+        let time_of_impact = (start_distance - radius) / -approach_speed;
+        if time_of_impact > 1.0 {
+            return None;
+        }
 
-        // Update the players position
-        player_transform.translation += adjustment.extend(0.0);
+        // The circle reaches the line's face within the segment
+        let center = start + motion * time_of_impact;
+        let along_edge = (center - line_start).dot(edge) / edge_length_sq;
+        if (0.0..=1.0).contains(&along_edge) {
+            return Some((time_of_impact, normal));
+        }
     }
+
+    // Otherwise it can only catch one of the end points
+    [line_start, line_end]
+        .into_iter()
+        .filter_map(|point| sweep_circle_point(start, motion, radius, point))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
 }
 
-/// Debug rendering system for collision visualization (optional, runs after collision)
-pub fn s_debug_collision(
-    player_query: Query<(&Transform, &Physics, &Player)>,
-    level: Res<Level>,
-    mut gizmos: Gizmos,
-) {
-    if let Ok((player_transform, player_physics, _player_data)) = player_query.single() {
-        let player_pos = player_transform.translation.xy();
-        let touch_threshold_sq = (player_physics.radius + TOUCH_THRESHOLD).powi(2);
+/// Time of impact of a circle moving by `motion` against a single point
+fn sweep_circle_point(start: Vec2, motion: Vec2, radius: f32, point: Vec2) -> Option<(f32, Vec2)> {
+    let offset = start - point;
+
+    // Solve |offset + motion * t| = radius for the first t
+    let a = motion.length_squared();
+    let b = 2.0 * offset.dot(motion);
+    let c = offset.length_squared() - radius.squared();
+
+    if c < 0.0 || a <= f32::EPSILON {
+        return None;
+    }
 
-        // Pre-compute player AABB for broad-phase
-        let player_aabb = Aabb::from_point_radius(player_pos, player_physics.radius);
-        let expanded_player_aabb = player_aabb.expand(player_physics.radius * 0.5);
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let time_of_impact = (-b - discriminant.sqrt()) / (2.0 * a);
+    if !(0.0..=1.0).contains(&time_of_impact) {
+        return None;
+    }
+
+    let normal = (offset + motion * time_of_impact).normalize_or_zero();
+    Some((time_of_impact, normal))
+}
 
-        for polygon in &level.polygons {
-            // Skip polygons far from player
-            if !expanded_player_aabb.overlaps(&polygon.aabb) {
+/// Returns the directions away from every surface the circle is touching that is not above it,
+/// each with the id of the polygon the surface belongs to (collinear edges, such as the seams
+/// between tiles, count as one surface)
+pub fn find_contacts(
+    level: &Level,
+    position: Vec2,
+    prev_position: Vec2,
+    radius: f32,
+) -> Vec<(Vec2, usize)> {
+    let aabb = Aabb::from_point_radius(position, radius).expand(radius * 0.5);
+    let touch_threshold_sq = (radius + TOUCH_THRESHOLD).squared();
+
+    // Normal, polygon and distance (squared) of each contact
+    let mut contacts: Vec<(Vec2, usize, f32)> = Vec::new();
+
+    // Broad-phase: only polygons whose bounding boxes reach the circle
+    for polygon in level.query_aabb(&aabb) {
+        for i in 1..polygon.points.len() {
+            let start = polygon.points[i - 1];
+            let end = polygon.points[i];
+
+            if side_of_line_detection(start, end, prev_position) != polygon.collision_side {
                 continue;
             }
 
-            // Draw collision normals for touching surfaces
-            for i in 1..polygon.points.len() {
-                let start = polygon.points[i - 1];
-                let end = polygon.points[i];
+            let (mut distance_sq, projection) = find_projection(start, end, position, radius);
 
-                let (distance_sq, projection) =
-                    find_projection(start, end, player_pos, player_physics.radius);
+            // Just past the end of an edge is still on it, so a seam can't open a gap underfoot
+            if distance_sq > touch_threshold_sq
+                && distance_past_segment(start, end, projection) <= SEAM_TOLERANCE
+            {
+                distance_sq = position.distance_squared(projection);
+            }
 
-                let touching_line = distance_sq <= touch_threshold_sq;
+            if distance_sq > touch_threshold_sq {
+                continue;
+            }
 
-                if touching_line {
-                    let normal_dir = (player_pos - projection).normalize_or_zero();
+            let normal_dir = (position - projection).normalize_or_zero();
+
+            // If the line is above the circle
+            if normal_dir.y < CEILING_NORMAL_Y_THRESHOLD {
+                continue;
+            }
 
-                    // If the line is not above the player
-                    if normal_dir.y >= CEILING_NORMAL_Y_THRESHOLD {
-                        gizmos.line_2d(
-                            player_pos,
-                            player_pos - normal_dir * DEBUG_NORMAL_LINE_LENGTH,
-                            Color::WHITE,
-                        );
-                    }
+            // Collinear edges (tile seams, neighbouring polygons) are one surface: keep the
+            // contact with the edge the body is over
+            if let Some(contact) = contacts
+                .iter_mut()
+                .find(|(normal, ..)| normal.dot(normal_dir) > COLLINEAR_CONTACT_DOT)
+            {
+                if distance_sq < contact.2 {
+                    *contact = (normal_dir, polygon.id, distance_sq);
                 }
+                continue;
             }
+
+            contacts.push((normal_dir, polygon.id, distance_sq));
         }
     }
+
+    contacts
+        .into_iter()
+        .map(|(normal_dir, polygon_id, _)| (normal_dir, polygon_id))
+        .collect()
+}
+
+/// How far a point on the line through `start` and `end` lies beyond the segment between them
+fn distance_past_segment(start: Vec2, end: Vec2, point: Vec2) -> f32 {
+    let line_vec = end - start;
+    let t = (point - start).dot(line_vec) / line_vec.length_squared();
+    (t - t.clamp(0.0, 1.0)).abs() * line_vec.length()
+}
+
+/// Returns the velocity of the moving polygon the circle is standing on, or zero if it is on
+/// static geometry or in the air
+pub fn find_ground_velocity(
+    level: &Level,
+    position: Vec2,
+    prev_position: Vec2,
+    radius: f32,
+) -> Vec2 {
+    let aabb = Aabb::from_point_radius(position, radius).expand(radius * 0.5);
+    let touch_threshold_sq = (radius + TOUCH_THRESHOLD).squared();
+
+    for polygon in level.query_aabb(&aabb) {
+        if polygon.velocity == Vec2::ZERO {
+            continue;
+        }
+
+        for i in 1..polygon.points.len() {
+            let start = polygon.points[i - 1];
+            let end = polygon.points[i];
+
+            if side_of_line_detection(start, end, prev_position) != polygon.collision_side {
+                continue;
+            }
+
+            let (distance_sq, projection) = find_projection(start, end, position, radius);
+
+            if distance_sq <= touch_threshold_sq
+                && (position - projection).normalize_or_zero().y > GROUND_NORMAL_Y_THRESHOLD
+            {
+                return polygon.velocity;
+            }
+        }
+    }
+
+    Vec2::ZERO
+}
+
+/// Moves a body that tunneled into a polygon back to the last point on its motion path outside it.
+///
+/// The point is found with a binary search between the previous and current positions, and only the
+/// velocity component driving into the crossed edge is removed so tangential motion carries on.
+fn restore_tunneled_position(
+    polygon: &Polygon,
+    prev_position: Vec2,
+    position: Vec2,
+    velocity: &mut Vec2,
+) -> Vec2 {
+    if is_inside_polygon(polygon, prev_position) {
+        return prev_position;
+    }
+
+    let motion = position - prev_position;
+
+    let mut outside_t = 0.0;
+    let mut inside_t = 1.0;
+
+    for _ in 0..TUNNEL_RESTORE_SEARCH_STEPS {
+        let t = (outside_t + inside_t) / 2.0;
+
+        if is_inside_polygon(polygon, prev_position + motion * t) {
+            inside_t = t;
+        } else {
+            outside_t = t;
+        }
+    }
+
+    // Find the edge that was crossed first to get the surface normal
+    let mut hit_normal = None;
+    let mut closest_hit_distance_sq = f32::MAX;
+
+    for i in 1..polygon.points.len() {
+        let start = polygon.points[i - 1];
+        let end = polygon.points[i];
+
+        let Some(intersection) = line_intersect(start, end, prev_position, position) else {
+            continue;
+        };
+
+        let hit_distance_sq = (intersection - prev_position).length_squared();
+
+        if hit_distance_sq < closest_hit_distance_sq {
+            closest_hit_distance_sq = hit_distance_sq;
+
+            let edge_dir = (end - start).normalize_or_zero();
+            let mut normal = Vec2::new(-edge_dir.y, edge_dir.x);
+            if normal.dot(prev_position - start) < 0.0 {
+                normal = -normal;
+            }
+            hit_normal = Some(normal);
+        }
+    }
+
+    // Preserve the velocity tangential to the hit surface
+    if let Some(normal) = hit_normal {
+        let into_surface = velocity.dot(normal);
+        if into_surface < 0.0 {
+            *velocity -= normal * into_surface;
+        }
+    }
+
+    prev_position + motion * outside_t
+}
+
+fn is_colliding_with_polygon(
+    polygon: &Polygon,
+    position: Vec2,
+    prev_position: Vec2,
+    radius: f32,
+) -> bool {
+    let radius_sq = radius.squared();
+
+    (1..polygon.points.len()).any(|i| {
+        let start = polygon.points[i - 1];
+        let end = polygon.points[i];
+
+        side_of_line_detection(start, end, prev_position) == polygon.collision_side
+            && find_projection(start, end, position, radius).0 <= radius_sq
+    })
+}
+
+fn is_inside_polygon(polygon: &Polygon, position: Vec2) -> bool {
+    // Raycast intersection check for point-in-polygon test
+    let intersect_counter = (1..polygon.points.len())
+        .filter(|&i| {
+            line_intersect(
+                polygon.points[i - 1],
+                polygon.points[i],
+                position,
+                position + RAYCAST_DIRECTION * RAYCAST_DIRECTION_SCALE,
+            )
+            .is_some()
+        })
+        .count();
+
+    intersect_counter % 2 == 1
+}
+
+/// Trigger system: Records which bodies and level polygons overlap each sensor
+pub fn s_sensors(
+    mut sensor_query: Query<(Entity, &Transform, &mut Sensor)>,
+    physics_query: Query<(Entity, &Transform, &KinematicBody)>,
+    level: Res<Level>,
+) {
+    let bodies: Vec<(Entity, Vec2, f32)> = physics_query
+        .iter()
+        .map(|(entity, transform, physics)| (entity, transform.translation.xy(), physics.radius))
+        .collect();
+
+    for (sensor_entity, sensor_transform, mut sensor) in sensor_query.iter_mut() {
+        let sensor_pos = sensor_transform.translation.xy();
+        // Same broad-phase as solid collision
+        let sensor_aabb = Aabb::from_point_radius(sensor_pos, sensor.radius);
+
+        sensor.overlapping_entities.clear();
+
+        for (entity, body_pos, body_radius) in &bodies {
+            if *entity == sensor_entity
+                || !sensor_aabb.overlaps(&Aabb::from_point_radius(*body_pos, *body_radius))
+            {
+                continue;
+            }
+
+            if (*body_pos - sensor_pos).length_squared()
+                <= (sensor.radius + body_radius).squared()
+            {
+                sensor.overlapping_entities.push(*entity);
+            }
+        }
+
+        let radius_sq = sensor.radius.squared();
+
+        sensor.overlapping_level = level.query_aabb(&sensor_aabb).any(|polygon| {
+            (1..polygon.points.len()).any(|i| {
+                find_projection(polygon.points[i - 1], polygon.points[i], sensor_pos, 0.0).0
+                    <= radius_sq
+            }) || (!polygon.is_container && is_inside_polygon(polygon, sensor_pos))
+        });
+    }
+}
+
+/// Collision debug layer: Draws contact normals and body bounding boxes
+pub fn s_debug_collision(
+    physics_query: Query<(&Transform, &KinematicBody)>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, physics) in physics_query.iter() {
+        let position = transform.translation.xy();
+
+        // Draw collision normals for touching surfaces
+        for normal_dir in &physics.contacts {
+            gizmos.line_2d(
+                position,
+                position - *normal_dir * DEBUG_NORMAL_LINE_LENGTH,
+                Color::WHITE,
+            );
+        }
+
+        gizmos.rect_2d(position, Vec2::splat(physics.radius * 2.0), DEBUG_AABB_COLOR);
+    }
+}
+
+/// Trigger debug layer: Draws sensor volumes, highlighted while something overlaps them
+pub fn s_debug_sensors(sensor_query: Query<(&Transform, &Sensor)>, mut gizmos: Gizmos) {
+    for (transform, sensor) in sensor_query.iter() {
+        let color = if sensor.overlapping_entities.is_empty() && !sensor.overlapping_level {
+            Color::srgb(0.0, 0.6, 1.0)
+        } else {
+            Color::srgb(1.0, 1.0, 0.0)
+        };
+
+        gizmos.circle_2d(transform.translation.xy(), sensor.radius, color);
+    }
 }
 
 pub fn find_projection(start: Vec2, end: Vec2, point: Vec2, radius: f32) -> (f32, Vec2) {
@@ -231,7 +957,7 @@ pub fn find_projection(start: Vec2, end: Vec2, point: Vec2, radius: f32) -> (f32
         );
     }
 
-    if dot.powi(2) > (end - start).length_squared() {
+    if dot.squared() > (end - start).length_squared() {
         return (
             (point - end).length_squared() + radius * DISTANCE_CALCULATION_RADIUS_MULTIPLIER,
             projection_point,
@@ -276,133 +1002,3 @@ pub fn line_intersect(
 pub fn cross_product(a: Vec2, b: Vec2) -> f32 {
     a.x * b.y - a.y * b.x
 }
-
-/// AI collision system: Similar to s_collision but for AI entities with AIPhysics
-pub fn s_ai_collision(
-    mut ai_query: Query<(&mut Transform, &mut AIPhysics)>,
-    level: Res<Level>,
-) {
-    for (mut ai_transform, mut ai_physics) in ai_query.iter_mut() {
-        let mut adjustment = Vec2::ZERO;
-        let mut new_ai_normal = Vec2::ZERO;
-
-        // Pre-compute AI AABB for broad-phase collision detection
-        let ai_pos = ai_transform.translation.xy();
-        let ai_aabb = Aabb::from_point_radius(ai_pos, ai_physics.radius);
-        // Expand AABB slightly to account for movement
-        let expanded_ai_aabb = ai_aabb.expand(ai_physics.radius * 0.5);
-
-        // Pre-compute radius squared to avoid repeated calculations
-        let radius_sq = ai_physics.radius.powi(2);
-        let touch_threshold_sq = (ai_physics.radius + TOUCH_THRESHOLD).powi(2);
-
-        for polygon in &level.polygons {
-            // Broad-phase: AABB pre-check to skip polygons far from AI
-            if !expanded_ai_aabb.overlaps(&polygon.aabb) {
-                continue;
-            }
-
-            let mut intersect_counter = 0;
-            let mut colliding_with_polygon = false;
-
-            // Raycast intersection check for point-in-polygon test
-            for i in 1..polygon.points.len() {
-                let start = polygon.points[i - 1];
-                let end = polygon.points[i];
-
-                let intersection = line_intersect(
-                    start,
-                    end,
-                    ai_pos,
-                    ai_pos + RAYCAST_DIRECTION * RAYCAST_DIRECTION_SCALE,
-                );
-
-                if intersection.is_some() {
-                    intersect_counter += 1;
-                }
-            }
-
-            // Narrow-phase: detailed collision detection with polygon edges
-            for i in 1..polygon.points.len() {
-                let start = polygon.points[i - 1];
-                let end = polygon.points[i];
-
-                let previous_side_of_line =
-                    side_of_line_detection(start, end, ai_physics.prev_position);
-
-                if previous_side_of_line != polygon.collision_side {
-                    continue;
-                }
-
-                let (distance_sq, projection) =
-                    find_projection(start, end, ai_pos, ai_physics.radius);
-
-                let colliding_with_line = distance_sq <= radius_sq;
-                colliding_with_polygon = colliding_with_polygon || colliding_with_line;
-
-                let touching_line = distance_sq <= touch_threshold_sq;
-
-                if touching_line {
-                    let normal_dir = (ai_pos - projection).normalize_or_zero();
-
-                    // If the line is not above the AI
-                    if normal_dir.y >= CEILING_NORMAL_Y_THRESHOLD {
-                        // Add the normal dir to the AI's new normal
-                        new_ai_normal -= normal_dir;
-
-                        // If the AI is on a wall
-                        if normal_dir.x.abs() >= NORMAL_DOT_THRESHOLD {
-                            ai_physics.walled = normal_dir.x.signum() as i8;
-                            ai_physics.has_wall_jumped = false;
-                        }
-
-                        // If the AI is on the ground
-                        if normal_dir.y > GROUND_NORMAL_Y_THRESHOLD {
-                            ai_physics.grounded = true;
-                            ai_physics.walled = 0;
-                            ai_physics.has_wall_jumped = false;
-                        }
-                    }
-                }
-
-                if colliding_with_line {
-                    let mut delta = (ai_pos - projection).normalize_or_zero();
-
-                    if delta.y < CEILING_NORMAL_Y_THRESHOLD {
-                        ai_physics.velocity.y = 0.0;
-                    }
-
-                    // Use squared distance calculation, only compute sqrt when needed
-                    let distance = distance_sq.sqrt();
-                    delta *= ai_physics.radius - distance;
-
-                    if delta.x.abs() > adjustment.x.abs() {
-                        adjustment.x = delta.x;
-                    }
-                    if delta.y.abs() > adjustment.y.abs() {
-                        adjustment.y = delta.y;
-                    }
-                }
-            }
-
-            // Point-in-polygon check: if inside polygon and raycast intersects odd number of times
-            if colliding_with_polygon && intersect_counter % 2 == 1 {
-                ai_transform.translation = ai_physics.prev_position.extend(0.0);
-            }
-        }
-
-        // Update the AI's normal
-        new_ai_normal = new_ai_normal.normalize_or_zero();
-        ai_physics.normal = new_ai_normal;
-
-        // Remove the AI's velocity in the direction of the normal
-        let velocity_adjustment =
-            ai_physics.velocity.dot(new_ai_normal) * new_ai_normal;
-
-        ai_physics.velocity -= velocity_adjustment;
-
-        // Update the AI's position
-        ai_transform.translation += adjustment.extend(0.0);
-    }
-}
-
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    color::Color,
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        message::{Message, MessageReader, MessageWriter},
+        query::{With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::Vec3Swizzles,
+    prelude::Resource,
+    text::{TextColor, TextFont},
+    time::Time,
+    transform::components::Transform,
+    ui::{widget::Text, Node, PositionType, Val},
+};
+
+use crate::{
+    ai::platformer_ai::PlatformerAI,
+    characters::ActiveCharacter,
+    collisions::{s_sensors, Sensor},
+    hazards::{s_hazard_contacts, Hazard},
+    health::{s_respawn, Health},
+    ControllerAction, ControllerEvent, KinematicBody,
+};
+
+// Meter gained per action (a kill is worth several)
+const COMBO_GAIN: f32 = 1.0;
+const COMBO_KILL_GAIN: f32 = 3.0;
+// Meter needed for each step of the multiplier
+const COMBO_PER_MULTIPLIER: f32 = 4.0;
+const COMBO_MAX_MULTIPLIER: u32 = 5;
+// Time without an action before the meter starts to decay (seconds)
+const COMBO_IDLE_TIME: f32 = 2.0;
+// Meter lost per second while idle
+const COMBO_DECAY_RATE: f32 = 2.0;
+
+// Base score for each action (before the multiplier)
+const WALL_JUMP_SCORE: u32 = 10;
+const DASH_SCORE: u32 = 5;
+const NEAR_MISS_SCORE: u32 = 25;
+const KILL_SCORE: u32 = 100;
+
+// Gap between the player and a hazard (pixels) that counts as a close call
+const NEAR_MISS_DISTANCE: f32 = 12.0;
+
+const HUD_FONT_SIZE: f32 = 20.0;
+const HUD_MARGIN: f32 = 12.0;
+const HUD_COLOR: Color = Color::srgb(1.0, 0.9, 0.2);
+
+/// Stylish actions that build the combo meter
+#[derive(Message, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComboAction {
+    WallJump,
+    Dash,
+    /// The player passed close to a hazard without touching it
+    NearMiss,
+    /// An agent died
+    Kill,
+}
+
+impl ComboAction {
+    fn base_score(self) -> u32 {
+        match self {
+            ComboAction::WallJump => WALL_JUMP_SCORE,
+            ComboAction::Dash => DASH_SCORE,
+            ComboAction::NearMiss => NEAR_MISS_SCORE,
+            ComboAction::Kill => KILL_SCORE,
+        }
+    }
+
+    fn meter_gain(self) -> f32 {
+        match self {
+            ComboAction::Kill => COMBO_KILL_GAIN,
+            _ => COMBO_GAIN,
+        }
+    }
+}
+
+/// Style meter built up by chaining actions and drained by standing around
+#[derive(Resource, Default)]
+pub struct ComboMeter {
+    pub meter: f32,
+    /// Time (seconds) since the last action
+    pub idle_time: f32,
+}
+
+impl ComboMeter {
+    pub fn multiplier(&self) -> u32 {
+        (1 + (self.meter / COMBO_PER_MULTIPLIER) as u32).min(COMBO_MAX_MULTIPLIER)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Score {
+    pub points: u32,
+}
+
+/// Hazards the player is currently close to, and whether they touched it on this pass
+#[derive(Resource, Default)]
+struct NearMissTracker {
+    close_hazards: HashMap<Entity, bool>,
+}
+
+/// Marker for the score and combo HUD text
+#[derive(Component)]
+pub struct ComboHud;
+
+pub struct ComboPlugin;
+
+impl Plugin for ComboPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ComboMeter>();
+        app.init_resource::<Score>();
+        app.init_resource::<NearMissTracker>();
+        app.add_message::<ComboAction>();
+
+        app.add_systems(Startup, s_spawn_combo_hud);
+        app.add_systems(Update, s_controller_combo_actions);
+        app.add_systems(
+            Update,
+            s_detect_near_misses
+                .after(s_sensors)
+                .after(s_hazard_contacts),
+        );
+        app.add_systems(Update, s_detect_kills.before(s_respawn));
+        app.add_systems(
+            Update,
+            s_update_combo
+                .after(s_controller_combo_actions)
+                .after(s_detect_near_misses)
+                .after(s_detect_kills),
+        );
+        app.add_systems(Update, s_update_combo_hud.after(s_update_combo));
+    }
+}
+
+/// Spawns the score and combo text in the top-right corner of the window
+fn s_spawn_combo_hud(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: HUD_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(HUD_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(HUD_MARGIN),
+            right: Val::Px(HUD_MARGIN),
+            ..Default::default()
+        },
+        ComboHud,
+    ));
+}
+
+/// Controller combo system: Turns the active character's stylish movement from the controller
+/// event stream into combo actions
+pub fn s_controller_combo_actions(
+    mut controller_events: MessageReader<ControllerEvent>,
+    active_query: Query<(), With<ActiveCharacter>>,
+    mut combo_actions: MessageWriter<ComboAction>,
+) {
+    for event in controller_events.read() {
+        if !active_query.contains(event.entity) {
+            continue;
+        }
+        match event.action {
+            ControllerAction::WallJump => {
+                combo_actions.write(ComboAction::WallJump);
+            }
+            ControllerAction::Dash => {
+                combo_actions.write(ComboAction::Dash);
+            }
+            ControllerAction::Jump | ControllerAction::AirJump | ControllerAction::WallSlide => {}
+        }
+    }
+}
+
+/// Near-miss system: Counts a near miss when the player leaves a hazard's vicinity without
+/// having touched it
+#[allow(clippy::type_complexity)]
+fn s_detect_near_misses(
+    hazard_query: Query<(Entity, &Transform, &Sensor), With<Hazard>>,
+    player_query: Query<(&Transform, &KinematicBody), (With<ActiveCharacter>, Without<Sensor>)>,
+    mut tracker: ResMut<NearMissTracker>,
+    mut combo_actions: MessageWriter<ComboAction>,
+) {
+    let Ok((player_transform, player_physics)) = player_query.single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.xy();
+
+    for (entity, transform, sensor) in hazard_query.iter() {
+        let gap = transform.translation.xy().distance(player_pos)
+            - sensor.radius
+            - player_physics.radius;
+
+        if gap <= 0.0 {
+            tracker.close_hazards.insert(entity, true);
+        } else if gap <= NEAR_MISS_DISTANCE {
+            tracker.close_hazards.entry(entity).or_insert(false);
+        } else if tracker.close_hazards.remove(&entity) == Some(false) {
+            combo_actions.write(ComboAction::NearMiss);
+        }
+    }
+}
+
+/// Kill system: Counts every agent that died this frame (before it respawns)
+pub fn s_detect_kills(
+    agent_query: Query<&Health, With<PlatformerAI>>,
+    mut combo_actions: MessageWriter<ComboAction>,
+) {
+    for health in agent_query.iter() {
+        if health.is_dead() {
+            combo_actions.write(ComboAction::Kill);
+        }
+    }
+}
+
+/// Combo system: Builds the meter from combo actions, awards multiplied score and decays the
+/// meter while the player is idle
+pub fn s_update_combo(
+    time: Res<Time>,
+    mut combo_actions: MessageReader<ComboAction>,
+    mut combo: ResMut<ComboMeter>,
+    mut score: ResMut<Score>,
+) {
+    for action in combo_actions.read() {
+        combo.meter += action.meter_gain();
+        combo.idle_time = 0.0;
+        score.points += action.base_score() * combo.multiplier();
+    }
+
+    combo.idle_time += time.delta_secs();
+    if combo.idle_time > COMBO_IDLE_TIME && combo.meter > 0.0 {
+        combo.meter = (combo.meter - COMBO_DECAY_RATE * time.delta_secs()).max(0.0);
+    }
+
+    let max_meter = COMBO_PER_MULTIPLIER * COMBO_MAX_MULTIPLIER as f32;
+    combo.meter = combo.meter.min(max_meter);
+}
+
+/// Combo HUD system: Shows the score and the current multiplier
+fn s_update_combo_hud(
+    combo: Res<ComboMeter>,
+    score: Res<Score>,
+    mut hud_query: Query<&mut Text, With<ComboHud>>,
+) {
+    if !combo.is_changed() && !score.is_changed() {
+        return;
+    }
+
+    for mut text in hud_query.iter_mut() {
+        text.0 = format!("Score {}  x{}", score.points, combo.multiplier());
+    }
+}
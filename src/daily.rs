@@ -0,0 +1,123 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{
+    app::{App, Plugin, PostStartup},
+    asset::Assets,
+    ecs::system::{Res, ResMut},
+    math::Vec2,
+    prelude::Resource,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    ai::pathfinding::PathfindingGraph,
+    level::{
+        procgen::{generate_level, ProcgenStyle, DEFAULT_HEIGHT, DEFAULT_WIDTH},
+        LevelSource,
+    },
+    level_loader::{LevelAsset, LevelManager},
+};
+
+// Command-line flag that starts today's daily challenge
+const DAILY_FLAG: &str = "--daily";
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+// Number of AI agents spawned in a daily run
+const DAILY_MIN_AI_SPAWNS: usize = 1;
+const DAILY_MAX_AI_SPAWNS: usize = 3;
+// Minimum distance (pixels) between an AI spawn and the player spawn
+const DAILY_MIN_SPAWN_DISTANCE: f32 = 200.0;
+
+/// Daily challenge: A random level generated from the current date (UTC), and every random choice
+/// in the run seeded from it too, so everyone playing the same build on the same day gets the same
+/// run. The level is played like any other (see `LevelManager`), so its progress and best time are
+/// saved under `level_name`.
+#[derive(Resource)]
+pub struct DailyChallenge {
+    /// Days since the Unix epoch
+    pub day: u64,
+}
+
+impl DailyChallenge {
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            day: seconds / SECONDS_PER_DAY,
+        }
+    }
+
+    /// Random number generator seeded from the day
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.day)
+    }
+
+    /// Name the day's level goes by (in the save file and replays)
+    pub fn level_name(&self) -> String {
+        format!("daily-{}", self.day)
+    }
+
+    /// Generates the day's level, completed at the far end from the player spawn
+    pub fn level(&self) -> LevelSource {
+        let style = if self.rng().random_bool(0.5) {
+            ProcgenStyle::Caves
+        } else {
+            ProcgenStyle::Rooms
+        };
+        let generated = generate_level(self.day, style, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+
+        let mut source = generated.source;
+        source.metadata.exit = Some(generated.exit.to_array());
+        source
+    }
+
+    /// Picks distinct ground positions on the pathfinding graph to spawn AI agents on
+    pub fn ai_spawn_positions(
+        &self,
+        pathfinding: &PathfindingGraph,
+        rng: &mut impl Rng,
+        player_position: Vec2,
+        agent_radius: f32,
+    ) -> Vec<Vec2> {
+        let count = rng.random_range(DAILY_MIN_AI_SPAWNS..=DAILY_MAX_AI_SPAWNS);
+
+        pathfinding.random_ground_positions(
+            rng,
+            count,
+            player_position,
+            DAILY_MIN_SPAWN_DISTANCE,
+            agent_radius,
+        )
+    }
+}
+
+pub struct DailyChallengePlugin;
+
+impl Plugin for DailyChallengePlugin {
+    fn build(&self, app: &mut App) {
+        if std::env::args().any(|arg| arg == DAILY_FLAG) {
+            let daily = DailyChallenge::today();
+            println!("Daily challenge: day {}", daily.day);
+            app.insert_resource(daily.level());
+            app.insert_resource(daily);
+
+            app.add_systems(PostStartup, s_add_daily_level);
+        }
+    }
+}
+
+/// Daily level system: Adds the day's level to the level manager as the current level
+fn s_add_daily_level(
+    daily: Res<DailyChallenge>,
+    level_manager: Option<ResMut<LevelManager>>,
+    mut level_assets: ResMut<Assets<LevelAsset>>,
+) {
+    let Some(mut level_manager) = level_manager else {
+        return;
+    };
+    level_manager.add_generated_level(&daily.level_name(), daily.level(), &mut level_assets);
+}
@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    camera::Projection,
+    color::Alpha,
+    ecs::{
+        entity::Entity,
+        query::{With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res, ResMut},
+    },
+    gizmos::gizmos::Gizmos,
+    input::{keyboard::KeyCode, ButtonInput},
+    math::Vec3Swizzles,
+    prelude::Resource,
+    state::{condition::in_state, state::OnEnter},
+    transform::components::Transform,
+};
+
+use crate::{
+    ai::{
+        brain::Brain,
+        flying_ai::{s_debug_flying_ai, s_flying_ai_movement},
+        pathfinding::{
+            s_debug_pathfinding_graph, s_spawn_pathfinding_warning_text,
+            s_update_pathfinding_cost_labels, s_update_pathfinding_warning, PathfindingCostLabels,
+            PathfindingGraph,
+        },
+        patrol::{s_debug_patrol_ai, s_patrol_ai_goals},
+        platformer_ai::{s_debug_platformer_ai, s_platformer_ai_movement, PlatformerAI},
+        pursue_ai::{s_pursue_ai_update, PursueAI, PursueAIState},
+        territory::s_debug_territories,
+    },
+    camera::{CameraControls, GameCamera},
+    characters::ActiveCharacter,
+    collisions::{s_collision, s_debug_collision, s_debug_sensors, s_sensors},
+    frame_budget::FrameBudget,
+    game_state::GameState,
+    level::{Aabb, Level, Polygon},
+    memory::MemoryReport,
+    movement_config::MovementConfig,
+    GizmosVisible, Player,
+};
+
+/// Individually toggleable groups of debug gizmos
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DebugLayer {
+    /// Level polygon outlines and bounding boxes
+    Level,
+    /// Contact normals and body bounding boxes
+    Collision,
+    /// Pathfinding graph nodes and edges (and their costs, see `PATHFINDING_COST_LABELS_KEY`)
+    Pathfinding,
+    /// Agent paths, the nodes they're heading for and steering
+    AI,
+    /// Sensor volumes and their overlaps
+    Triggers,
+}
+
+impl DebugLayer {
+    pub const ALL: [DebugLayer; 5] = [
+        DebugLayer::Level,
+        DebugLayer::Collision,
+        DebugLayer::Pathfinding,
+        DebugLayer::AI,
+        DebugLayer::Triggers,
+    ];
+
+    /// Key that toggles this layer while gizmos are visible
+    pub fn toggle_key(self) -> KeyCode {
+        match self {
+            DebugLayer::Level => KeyCode::F1,
+            DebugLayer::Collision => KeyCode::F2,
+            DebugLayer::Pathfinding => KeyCode::F3,
+            DebugLayer::AI => KeyCode::F4,
+            DebugLayer::Triggers => KeyCode::F5,
+        }
+    }
+}
+
+// Key that toggles the free-fly camera while gizmos are visible
+pub const FREE_FLY_TOGGLE_KEY: KeyCode = KeyCode::F6;
+// Key that prints the memory report to the console while gizmos are visible
+pub const MEMORY_REPORT_KEY: KeyCode = KeyCode::F7;
+// Key that cycles between single, double and triple jump while gizmos are visible
+pub const AIR_JUMPS_CYCLE_KEY: KeyCode = KeyCode::F8;
+// Highest number of air jumps the cycle key goes up to
+const MAX_CYCLED_AIR_JUMPS: u32 = 2;
+// Key that swaps the brain of the agent nearest the player while gizmos are visible
+pub const BRAIN_CYCLE_KEY: KeyCode = KeyCode::F9;
+// Key that shows the jump timing overlay while gizmos are visible (see `JumpTimingPlugin`)
+pub const JUMP_TIMING_KEY: KeyCode = KeyCode::F10;
+// Key that turns on click-to-command for agents while gizmos are visible (see `AICommandPlugin`)
+pub const AI_COMMAND_KEY: KeyCode = KeyCode::F11;
+// Key that shows the per-agent AI metrics overlay while gizmos are visible (see `AIMetricsPlugin`)
+pub const AI_METRICS_KEY: KeyCode = KeyCode::F12;
+// Keys that step the selection (see `SelectionPlugin`) through the agents while gizmos are visible
+pub const SELECT_PREVIOUS_KEY: KeyCode = KeyCode::Comma;
+pub const SELECT_NEXT_KEY: KeyCode = KeyCode::Period;
+// Key that labels the pathfinding graph's edges with their costs while gizmos are visible
+pub const PATHFINDING_COST_LABELS_KEY: KeyCode = KeyCode::Slash;
+// Key that shows the stats and movement tunables panel while gizmos are visible (see
+// `DebugPanelPlugin`)
+pub const DEBUG_PANEL_KEY: KeyCode = KeyCode::Backquote;
+
+/// Debug layers currently enabled (only drawn while `GizmosVisible` is set)
+#[derive(Resource)]
+pub struct DebugLayers {
+    pub enabled: HashSet<DebugLayer>,
+}
+
+impl DebugLayers {
+    pub fn is_enabled(&self, layer: DebugLayer) -> bool {
+        self.enabled.contains(&layer)
+    }
+}
+
+/// Run condition: true when gizmos are visible and the given layer is enabled (and the frame
+/// budget isn't degraded)
+pub fn debug_layer_visible(
+    layer: DebugLayer,
+) -> impl FnMut(Res<GizmosVisible>, Res<DebugLayers>, Res<FrameBudget>) -> bool + Clone {
+    move |gizmos_visible: Res<GizmosVisible>,
+          debug_layers: Res<DebugLayers>,
+          frame_budget: Res<FrameBudget>| {
+        gizmos_visible.visible
+            && debug_layers.is_enabled(layer)
+            && frame_budget.debug_draws_enabled()
+    }
+}
+
+/// Debug plugin: The single place where debug gizmo systems are registered against their layer
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DebugLayers {
+            enabled: DebugLayer::ALL.into_iter().collect(),
+        });
+        app.init_resource::<PathfindingCostLabels>();
+
+        app.add_systems(Update, s_toggle_debug_layers);
+        app.add_systems(Update, s_toggle_free_fly_camera);
+        app.add_systems(Update, s_print_memory_report.run_if(in_state(GameState::InGame)));
+        app.add_systems(Update, s_cycle_air_jumps);
+        app.add_systems(Update, s_cycle_agent_brain.before(s_pursue_ai_update));
+        app.add_systems(
+            Update,
+            s_update_pathfinding_cost_labels
+                .after(s_toggle_debug_layers)
+                .run_if(in_state(GameState::InGame)),
+        );
+        app.add_systems(OnEnter(GameState::InGame), s_spawn_pathfinding_warning_text);
+        app.add_systems(
+            Update,
+            s_update_pathfinding_warning.run_if(in_state(GameState::InGame)),
+        );
+
+        app.add_systems(
+            Update,
+            (
+                s_debug_level
+                    .run_if(debug_layer_visible(DebugLayer::Level))
+                    .run_if(in_state(GameState::InGame)),
+                s_debug_collision
+                    .after(s_collision)
+                    .run_if(debug_layer_visible(DebugLayer::Collision)),
+                s_debug_pathfinding_graph.run_if(debug_layer_visible(DebugLayer::Pathfinding)),
+                s_debug_platformer_ai
+                    .after(s_platformer_ai_movement)
+                    .run_if(debug_layer_visible(DebugLayer::AI)),
+                s_debug_flying_ai
+                    .after(s_flying_ai_movement)
+                    .run_if(debug_layer_visible(DebugLayer::AI)),
+                s_debug_patrol_ai
+                    .after(s_patrol_ai_goals)
+                    .run_if(debug_layer_visible(DebugLayer::AI)),
+                s_debug_territories.run_if(debug_layer_visible(DebugLayer::AI)),
+                s_debug_sensors
+                    .after(s_sensors)
+                    .run_if(debug_layer_visible(DebugLayer::Triggers)),
+            ),
+        );
+    }
+}
+
+/// Debug layer toggle system: Function keys toggle individual layers while gizmos are visible
+pub fn s_toggle_debug_layers(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut debug_layers: ResMut<DebugLayers>,
+) {
+    if !gizmos_visible.visible {
+        return;
+    }
+
+    for layer in DebugLayer::ALL {
+        if keyboard_input.just_pressed(layer.toggle_key()) && !debug_layers.enabled.remove(&layer)
+        {
+            debug_layers.enabled.insert(layer);
+        }
+    }
+}
+
+/// Free-fly toggle system: Detaches the camera for inspecting the level while gizmos are visible
+pub fn s_toggle_free_fly_camera(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut camera_controls: ResMut<CameraControls>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(FREE_FLY_TOGGLE_KEY) {
+        camera_controls.free_fly = !camera_controls.free_fly;
+    }
+}
+
+/// Memory report system: Prints memory used by the level and pathfinding data on request
+pub fn s_print_memory_report(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    level: Res<Level>,
+    pathfinding: Res<PathfindingGraph>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(MEMORY_REPORT_KEY) {
+        println!("{}", MemoryReport::collect(&level, &pathfinding));
+    }
+}
+
+/// Air jump cycle system: Switches between single, double and triple jump builds
+pub fn s_cycle_air_jumps(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut movement_config: ResMut<MovementConfig>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(AIR_JUMPS_CYCLE_KEY) {
+        movement_config.max_air_jumps =
+            (movement_config.max_air_jumps + 1) % (MAX_CYCLED_AIR_JUMPS + 1);
+        println!("Air jumps: {}", movement_config.max_air_jumps);
+    }
+}
+
+/// Brain cycle system: Swaps the brain of the agent nearest the player for the next one, so AI
+/// implementations can be compared in the same situation.
+///
+/// The agent keeps its body and position; only what it was planning is forgotten.
+#[allow(clippy::type_complexity)]
+pub fn s_cycle_agent_brain(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut ai_query: Query<
+        (Entity, &Transform, &mut Brain, &mut PursueAI, &mut PlatformerAI),
+        Without<Player>,
+    >,
+    player_query: Query<&Transform, With<ActiveCharacter>>,
+) {
+    if !gizmos_visible.visible || !keyboard_input.just_pressed(BRAIN_CYCLE_KEY) {
+        return;
+    }
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+    let player_position = player_transform.translation.xy();
+
+    let Some((entity, _, mut brain, mut pursue_ai, mut platformer_ai)) =
+        ai_query.iter_mut().min_by(|(_, a, ..), (_, b, ..)| {
+            let distance_a = a.translation.xy().distance_squared(player_position);
+            let distance_b = b.translation.xy().distance_squared(player_position);
+            distance_a.total_cmp(&distance_b)
+        })
+    else {
+        return;
+    };
+
+    *brain = brain.next();
+    // The state machine starts over from wandering
+    if matches!(*brain, Brain::StateMachine) {
+        *pursue_ai = PursueAI::new(PursueAIState::Wander);
+    }
+    // Plan a path to the new brain's goal
+    platformer_ai.cached_path = None;
+    platformer_ai.last_goal_position = None;
+
+    println!("Agent {entity}: {} brain", brain.name());
+}
+
+/// Level debug layer: Draws outlines and bounding boxes for the polygons in view
+pub fn s_debug_level(
+    level: Res<Level>,
+    camera_query: Query<(&Transform, &Projection), With<GameCamera>>,
+    mut gizmos: Gizmos,
+) {
+    let view = camera_query
+        .single()
+        .ok()
+        .and_then(|(transform, projection)| match projection {
+            Projection::Orthographic(orthographic) => Some(Aabb {
+                min: transform.translation.xy() + orthographic.area.min,
+                max: transform.translation.xy() + orthographic.area.max,
+            }),
+            _ => None,
+        });
+
+    // Fall back to every polygon if the view can't be worked out
+    let polygons: Vec<&Polygon> = match view {
+        Some(view) => level.query_aabb(&view).collect(),
+        None => level.polygons.iter().collect(),
+    };
+
+    for polygon in polygons {
+        gizmos.linestrip_2d(polygon.points.iter().copied(), polygon.color);
+        gizmos.rect_2d(
+            (polygon.aabb.min + polygon.aabb.max) / 2.0,
+            polygon.aabb.max - polygon.aabb.min,
+            polygon.color.with_alpha(0.25),
+        );
+    }
+}
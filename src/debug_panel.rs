@@ -0,0 +1,367 @@
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    camera::visibility::Visibility,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        hierarchy::ChildOf,
+        query::{With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    prelude::Resource,
+    text::{TextColor, TextFont},
+    time::{Real, Time},
+    ui::{
+        widget::Text, AlignItems, BackgroundColor, FlexDirection, Interaction, Node,
+        PositionType, RelativeCursorPosition, UiRect, Val,
+    },
+};
+
+use crate::{
+    ai::pursue_ai::PursueAI,
+    characters::{ActiveCharacter, CharacterProfile},
+    debug::DEBUG_PANEL_KEY,
+    movement_config::MovementConfig,
+    s_movement,
+    selection::SelectedEntity,
+    GizmosVisible, KinematicBody, Player,
+};
+
+// Panel layout (units: pixels)
+const PANEL_FONT_SIZE: f32 = 14.0;
+const PANEL_MARGIN: f32 = 16.0;
+const PANEL_PADDING: f32 = 8.0;
+const PANEL_ROW_GAP: f32 = 4.0;
+const SLIDER_WIDTH: f32 = 160.0;
+const SLIDER_HEIGHT: f32 = 10.0;
+const SLIDER_LABEL_WIDTH: f32 = 190.0;
+const PANEL_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+const TEXT_COLOR: Color = Color::srgb(0.6, 1.0, 0.8);
+const TRACK_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+const FILL_COLOR: Color = Color::srgb(0.6, 1.0, 0.8);
+
+// How quickly the shown frame rate follows the real one (units: 1/second)
+const FPS_SMOOTHING: f32 = 4.0;
+// Agents listed by state before the rest are summarized
+const MAX_LISTED_AGENTS: usize = 6;
+
+/// A movement constant the panel has a slider for
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Tunable {
+    MaxSpeed,
+    Acceleration,
+    Deceleration,
+    JumpVelocity,
+    Gravity,
+}
+
+impl Tunable {
+    const ALL: [Tunable; 5] = [
+        Tunable::MaxSpeed,
+        Tunable::Acceleration,
+        Tunable::Deceleration,
+        Tunable::JumpVelocity,
+        Tunable::Gravity,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Tunable::MaxSpeed => "Max speed",
+            Tunable::Acceleration => "Acceleration",
+            Tunable::Deceleration => "Deceleration",
+            Tunable::JumpVelocity => "Jump velocity",
+            Tunable::Gravity => "Gravity",
+        }
+    }
+
+    /// Lowest and highest value the slider reaches
+    fn range(self) -> (f32, f32) {
+        match self {
+            Tunable::MaxSpeed => (50.0, 900.0),
+            Tunable::Acceleration | Tunable::Deceleration => (1.0, 60.0),
+            Tunable::JumpVelocity => (100.0, 1500.0),
+            Tunable::Gravity => (200.0, 6000.0),
+        }
+    }
+
+    fn value_mut(self, config: &mut MovementConfig) -> &mut f32 {
+        match self {
+            Tunable::MaxSpeed => &mut config.max_speed,
+            Tunable::Acceleration => &mut config.acceleration_scalers.0,
+            Tunable::Deceleration => &mut config.acceleration_scalers.1,
+            Tunable::JumpVelocity => &mut config.jump_velocity,
+            Tunable::Gravity => &mut config.gravity,
+        }
+    }
+
+    fn value(self, config: &MovementConfig) -> f32 {
+        match self {
+            Tunable::MaxSpeed => config.max_speed,
+            Tunable::Acceleration => config.acceleration_scalers.0,
+            Tunable::Deceleration => config.acceleration_scalers.1,
+            Tunable::JumpVelocity => config.jump_velocity,
+            Tunable::Gravity => config.gravity,
+        }
+    }
+}
+
+/// Debug panel resource: Whether the panel is shown (while gizmos are visible)
+#[derive(Resource, Default)]
+pub struct DebugPanel {
+    pub visible: bool,
+}
+
+/// Marker for the panel's root node
+#[derive(Component)]
+struct DebugPanelRoot;
+
+/// Marker for the panel's stats text
+#[derive(Component)]
+struct DebugPanelStats;
+
+/// Slider track for a tunable (pressing or dragging on it sets the value)
+#[derive(Component)]
+struct TunableSlider(Tunable);
+
+/// Filled part of a slider's track
+#[derive(Component)]
+struct TunableFill(Tunable);
+
+/// Name and value shown next to a slider
+#[derive(Component)]
+struct TunableLabel(Tunable);
+
+/// Debug panel plugin: Debug tool showing the frame rate, the player's motion and timers and the
+/// agents' states, with sliders to change the player's movement constants while playing
+pub struct DebugPanelPlugin;
+
+impl Plugin for DebugPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugPanel>();
+
+        app.add_systems(Startup, s_spawn_debug_panel);
+        app.add_systems(
+            Update,
+            (
+                s_toggle_debug_panel,
+                s_drag_tunable_sliders.before(s_movement),
+                s_update_debug_panel
+                    .after(s_toggle_debug_panel)
+                    .after(s_drag_tunable_sliders),
+            ),
+        );
+    }
+}
+
+fn text_bundle(text: impl Into<String>) -> (Text, TextFont, TextColor) {
+    (
+        Text::new(text),
+        TextFont {
+            font_size: PANEL_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(TEXT_COLOR),
+    )
+}
+
+/// Spawns the (hidden) debug panel in the top-left corner of the window
+fn s_spawn_debug_panel(mut commands: Commands) {
+    let panel = commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(PANEL_MARGIN),
+                left: Val::Px(PANEL_MARGIN),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(PANEL_ROW_GAP),
+                padding: UiRect::all(Val::Px(PANEL_PADDING)),
+                ..Default::default()
+            },
+            BackgroundColor(PANEL_COLOR),
+            Visibility::Hidden,
+            DebugPanelRoot,
+        ))
+        .id();
+
+    commands.spawn((text_bundle(""), DebugPanelStats, ChildOf(panel)));
+
+    for tunable in Tunable::ALL {
+        let row = commands
+            .spawn((
+                Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                ChildOf(panel),
+            ))
+            .id();
+
+        commands.spawn((
+            text_bundle(""),
+            Node {
+                width: Val::Px(SLIDER_LABEL_WIDTH),
+                ..Default::default()
+            },
+            TunableLabel(tunable),
+            ChildOf(row),
+        ));
+
+        let track = commands
+            .spawn((
+                Node {
+                    width: Val::Px(SLIDER_WIDTH),
+                    height: Val::Px(SLIDER_HEIGHT),
+                    ..Default::default()
+                },
+                BackgroundColor(TRACK_COLOR),
+                Interaction::default(),
+                RelativeCursorPosition::default(),
+                TunableSlider(tunable),
+                ChildOf(row),
+            ))
+            .id();
+
+        commands.spawn((
+            Node {
+                height: Val::Percent(100.0),
+                ..Default::default()
+            },
+            BackgroundColor(FILL_COLOR),
+            TunableFill(tunable),
+            ChildOf(track),
+        ));
+    }
+}
+
+/// Debug panel toggle system: Shows or hides the panel on request while gizmos are visible
+fn s_toggle_debug_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut panel: ResMut<DebugPanel>,
+    mut root_query: Query<&mut Visibility, With<DebugPanelRoot>>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(DEBUG_PANEL_KEY) {
+        panel.visible = !panel.visible;
+    }
+
+    let shown = gizmos_visible.visible && panel.visible;
+    for mut visibility in root_query.iter_mut() {
+        *visibility = if shown {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Slider system: Sets a tunable from where its slider is pressed (and keeps following the cursor
+/// while the button is held)
+fn s_drag_tunable_sliders(
+    gizmos_visible: Res<GizmosVisible>,
+    panel: Res<DebugPanel>,
+    slider_query: Query<(&Interaction, &RelativeCursorPosition, &TunableSlider)>,
+    mut movement_config: ResMut<MovementConfig>,
+) {
+    if !gizmos_visible.visible || !panel.visible {
+        return;
+    }
+
+    for (interaction, cursor, slider) in slider_query.iter() {
+        let (Interaction::Pressed, Some(normalized)) = (interaction, cursor.normalized) else {
+            continue;
+        };
+
+        // The cursor position runs from -0.5 at the left edge of the track to 0.5 at the right
+        let fraction = (normalized.x + 0.5).clamp(0.0, 1.0);
+        let (min, max) = slider.0.range();
+        *slider.0.value_mut(&mut movement_config) = min + (max - min) * fraction;
+    }
+}
+
+/// Debug panel system: Refreshes the stats, slider labels and slider fills while the panel is
+/// shown
+#[allow(clippy::too_many_arguments)]
+fn s_update_debug_panel(
+    gizmos_visible: Res<GizmosVisible>,
+    panel: Res<DebugPanel>,
+    time: Res<Time<Real>>,
+    movement_config: Res<MovementConfig>,
+    selected: Res<SelectedEntity>,
+    player_query: Query<(&KinematicBody, &Player, &CharacterProfile), With<ActiveCharacter>>,
+    ai_query: Query<(Entity, &PursueAI)>,
+    mut stats_query: Query<&mut Text, (With<DebugPanelStats>, Without<TunableLabel>)>,
+    mut label_query: Query<(&mut Text, &TunableLabel)>,
+    mut fill_query: Query<(&mut Node, &TunableFill)>,
+    // Smoothed frame rate (frames/second)
+    mut fps: Local<f32>,
+) {
+    let dt = time.delta_secs();
+    if dt > 0.0 {
+        let frame_rate = 1.0 / dt;
+        *fps = if *fps > 0.0 {
+            *fps + (frame_rate - *fps) * (FPS_SMOOTHING * dt).min(1.0)
+        } else {
+            frame_rate
+        };
+    }
+
+    if !gizmos_visible.visible || !panel.visible {
+        return;
+    }
+
+    for mut text in stats_query.iter_mut() {
+        text.0 = debug_panel_report(*fps, &player_query, &ai_query, selected.0);
+    }
+
+    for (mut text, label) in label_query.iter_mut() {
+        text.0 = format!("{}: {:.1}", label.0.label(), label.0.value(&movement_config));
+    }
+
+    for (mut node, fill) in fill_query.iter_mut() {
+        let (min, max) = fill.0.range();
+        let fraction = ((fill.0.value(&movement_config) - min) / (max - min)).clamp(0.0, 1.0);
+        node.width = Val::Percent(fraction * 100.0);
+    }
+}
+
+/// Panel stats text: the frame rate, the player's motion and timers, then the agents' states
+/// (the selected agent first)
+fn debug_panel_report(
+    fps: f32,
+    player_query: &Query<(&KinematicBody, &Player, &CharacterProfile), With<ActiveCharacter>>,
+    ai_query: &Query<(Entity, &PursueAI)>,
+    selected: Option<Entity>,
+) -> String {
+    let mut lines = vec![format!("Debug panel ({DEBUG_PANEL_KEY:?} to hide), {fps:.0} fps")];
+
+    for (body, player, profile) in player_query.iter() {
+        lines.push(format!(
+            "Player ({profile:?}) velocity ({:.0}, {:.0}), speed {:.0}",
+            body.velocity.x,
+            body.velocity.y,
+            body.velocity.length()
+        ));
+        lines.push(format!(
+            "On ground {}, coyote {:.0} ms, wall {:.0} ms",
+            body.on_ground(),
+            player.grounded_timer() * 1000.0,
+            player.wall_timer() * 1000.0
+        ));
+    }
+
+    let mut agents: Vec<_> = ai_query.iter().collect();
+    agents.sort_unstable_by_key(|(entity, _)| (Some(*entity) != selected, *entity));
+    for (entity, pursue_ai) in agents.iter().take(MAX_LISTED_AGENTS) {
+        let marker = if Some(*entity) == selected { "*" } else { " " };
+        lines.push(format!("{marker}Agent {entity}: {:?}", pursue_ai.state));
+    }
+    if agents.len() > MAX_LISTED_AGENTS {
+        lines.push(format!(" ...and {} more", agents.len() - MAX_LISTED_AGENTS));
+    }
+
+    lines.join("\n")
+}
@@ -0,0 +1,333 @@
+//! Deterministic simulation mode and input traces.
+//!
+//! `composite --deterministic [--seed N]` runs the game so the same input always plays out the
+//! same way: every frame advances the simulation by the same fixed timestep however long it took,
+//! the random choices behind a level (its colors, agent spawns and the AI's wander goals and
+//! other decisions) are all seeded from `N` (0 by default), levels are built and paths planned on
+//! the main thread, the frame budget guard is off and save files don't move the player.
+//!
+//! On top of that, `--record-trace PATH` records the player's input every frame to an input trace
+//! (`InputTrace`, made of the same frames as replays and filled in by the replay recorder), written
+//! to `PATH` as JSON when the game exits, and `--play-trace PATH` plays one
+//! back in place of the player's input (with the trace's own seed and timestep, on the level it
+//! was recorded on), so a bug in the controller or the AI can be reproduced exactly. Scenario
+//! files can play a trace too, turning it into a regression test (see `scenarios`).
+//!
+//! A trace covers the input since its level was last entered: switching or reloading the level
+//! starts it over.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{
+    app::{App, AppExit, Last, Plugin, PostStartup, PreUpdate},
+    ecs::{
+        message::MessageReader,
+        schedule::{common_conditions::resource_exists, IntoScheduleConfigs},
+        system::{Res, ResMut},
+    },
+    prelude::Resource,
+    state::state::{OnEnter, State},
+    time::TimeUpdateStrategy,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::path_requests::AsyncPathfinding,
+    frame_budget::FrameBudget,
+    game_state::GameState,
+    input::{s_read_input_actions, InputAction, PlayerTwoInput},
+    level_loader::LevelManager,
+    loading::BackgroundLoading,
+    replay::ReplayFrame,
+    s_exit,
+    settings::Settings,
+};
+
+// Command-line flags
+const DETERMINISTIC_FLAG: &str = "--deterministic";
+const SEED_FLAG: &str = "--seed";
+const RECORD_TRACE_FLAG: &str = "--record-trace";
+const PLAY_TRACE_FLAG: &str = "--play-trace";
+
+// Length of every frame in deterministic mode (units: seconds)
+const DETERMINISTIC_TIMESTEP: f64 = 1.0 / 60.0;
+
+/// Deterministic simulation resource: The game is running in deterministic mode
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct DeterministicSim {
+    /// Seed for every random choice in a level
+    pub seed: u64,
+    /// Length of every frame (seconds)
+    pub timestep: f64,
+}
+
+impl DeterministicSim {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            timestep: DETERMINISTIC_TIMESTEP,
+        }
+    }
+
+    /// Random number generator seeded from the seed
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// Adds the resources that make an app deterministic: this one, the fixed timestep, and
+    /// background level builds, async pathfinding and the frame budget guard turned off
+    pub fn insert_into(self, app: &mut App) {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            self.timestep,
+        )))
+        .insert_resource(FrameBudget::disabled())
+        .insert_resource(BackgroundLoading::disabled())
+        .insert_resource(AsyncPathfinding::disabled())
+        .insert_resource(self);
+    }
+}
+
+/// Input trace resource: The player's input on every frame since the level was entered, with
+/// what it takes to play it back the same way
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct InputTrace {
+    /// Version of the game that recorded the trace (others may play it out differently)
+    pub game_version: String,
+    /// Name of the level in the level list (empty if it wasn't picked from the list)
+    pub level: String,
+    /// `DeterministicSim` seed the trace was recorded with
+    pub seed: u64,
+    /// Length of every frame (seconds)
+    pub timestep: f64,
+    /// Whether a second player was playing (see `Settings::two_player`)
+    pub two_player: bool,
+    /// One per frame from the frame the level was entered on
+    pub frames: Vec<ReplayFrame>,
+    /// Next frame to play back
+    #[serde(skip)]
+    next_frame: usize,
+}
+
+impl InputTrace {
+    /// Reads a trace file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let trace: Self = serde_json::from_str(&contents).map_err(|error| error.to_string())?;
+        if trace.timestep <= 0.0 {
+            return Err(format!("invalid timestep {}", trace.timestep));
+        }
+        Ok(trace)
+    }
+
+    /// Writes the trace to a file, one frame per line so traces diff well
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let frames = self
+            .frames
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| error.to_string())?;
+        let level = serde_json::to_string(&self.level).map_err(|error| error.to_string())?;
+        let game_version =
+            serde_json::to_string(&self.game_version).map_err(|error| error.to_string())?;
+
+        let contents = format!(
+            "{{\n\t\"game_version\": {game_version},\n\t\"level\": {level},\n\t\"seed\": {},\n\t\
+             \"timestep\": {},\n\t\"two_player\": {},\n\t\"frames\": [\n\t\t{}\n\t]\n}}\n",
+            self.seed,
+            self.timestep,
+            self.two_player,
+            frames.join(",\n\t\t")
+        );
+        std::fs::write(path, contents).map_err(|error| error.to_string())
+    }
+
+    /// Length of the trace (seconds)
+    pub fn duration(&self) -> f64 {
+        self.frames.len() as f64 * self.timestep
+    }
+}
+
+/// Input trace mode resource: Whether the `InputTrace` is being recorded or played back
+#[derive(Resource, Clone, Debug)]
+pub enum InputTraceMode {
+    /// Recording, to be written to the path when the game exits
+    Record(PathBuf),
+    Play,
+}
+
+/// Deterministic simulation plugin: Turns on deterministic mode and records or plays back an
+/// input trace when asked to on the command line
+pub struct DeterministicSimPlugin;
+
+impl Plugin for DeterministicSimPlugin {
+    fn build(&self, app: &mut App) {
+        let args: Vec<String> = std::env::args().collect();
+        let flag_value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+                .filter(|arg| !arg.starts_with("--"))
+        };
+
+        if let Some(path) = flag_value(PLAY_TRACE_FLAG) {
+            match InputTrace::load(Path::new(path)) {
+                Ok(trace) => {
+                    println!(
+                        "Playing input trace {path} ({:.2} s, seed {})",
+                        trace.duration(),
+                        trace.seed
+                    );
+                    if trace.game_version != env!("CARGO_PKG_VERSION") {
+                        eprintln!(
+                            "The trace was recorded with version {} of the game, this is version \
+                             {}; it may play out differently",
+                            trace.game_version,
+                            env!("CARGO_PKG_VERSION")
+                        );
+                    }
+                    DeterministicSim {
+                        seed: trace.seed,
+                        timestep: trace.timestep,
+                    }
+                    .insert_into(app);
+                    app.insert_resource(trace).insert_resource(InputTraceMode::Play);
+                }
+                Err(error) => eprintln!("Failed to read input trace {path}: {error}"),
+            }
+        } else if args.iter().any(|arg| arg == DETERMINISTIC_FLAG) {
+            let seed = flag_value(SEED_FLAG)
+                .and_then(|seed| seed.parse().ok())
+                .unwrap_or_default();
+            let sim = DeterministicSim::new(seed);
+            println!("Deterministic mode: seed {seed}");
+            sim.insert_into(app);
+
+            if let Some(path) = flag_value(RECORD_TRACE_FLAG) {
+                app.insert_resource(InputTrace {
+                    game_version: env!("CARGO_PKG_VERSION").to_string(),
+                    seed,
+                    timestep: sim.timestep,
+                    ..Default::default()
+                })
+                .insert_resource(InputTraceMode::Record(PathBuf::from(path)));
+            }
+        }
+
+        app.add_systems(
+            PostStartup,
+            s_load_trace_level.run_if(resource_exists::<InputTraceMode>),
+        );
+        app.add_systems(
+            OnEnter(GameState::InGame),
+            s_restart_input_trace.run_if(resource_exists::<InputTraceMode>),
+        );
+        app.add_systems(
+            PreUpdate,
+            s_trace_input
+                .after(s_read_input_actions)
+                .run_if(resource_exists::<InputTraceMode>),
+        );
+        app.add_systems(
+            Last,
+            s_save_input_trace
+                .after(s_exit)
+                .run_if(resource_exists::<InputTraceMode>),
+        );
+    }
+}
+
+/// Trace level system: Switches to the level a trace being played back was recorded on, with a
+/// second player if it was recorded with one
+fn s_load_trace_level(
+    trace: Res<InputTrace>,
+    mode: Res<InputTraceMode>,
+    mut settings: ResMut<Settings>,
+    level_manager: Option<ResMut<LevelManager>>,
+) {
+    if !matches!(*mode, InputTraceMode::Play) {
+        return;
+    }
+    settings.two_player = trace.two_player;
+
+    let Some(mut level_manager) = level_manager else {
+        return;
+    };
+    if !trace.level.is_empty()
+        && trace.level != level_manager.current()
+    {
+        level_manager.load_level(&trace.level);
+    }
+}
+
+/// Trace restart system: Starts the trace over on entering a level. The first frame was recorded
+/// on the frame the level was entered, whose input was read before the level existed, so playback
+/// picks up from the second (as replays taking over the player do)
+fn s_restart_input_trace(
+    mut trace: ResMut<InputTrace>,
+    mode: Res<InputTraceMode>,
+    settings: Res<Settings>,
+    level_manager: Option<Res<LevelManager>>,
+) {
+    trace.next_frame = 1;
+    if let InputTraceMode::Record(_) = *mode {
+        trace.frames.clear();
+        trace.two_player = settings.two_player;
+        trace.level = level_manager.map_or(String::new(), |manager| manager.current().to_string());
+    }
+}
+
+/// Trace input system: Replaces the player's input with the trace being played back's (the trace
+/// being recorded is filled in by `replay::s_record_replay_frame`). Outside a level (and on the
+/// frame one is entered) there is no input, so it can't change how the level starts; once a trace
+/// has been played back the player has control again
+fn s_trace_input(
+    mut trace: ResMut<InputTrace>,
+    mode: Res<InputTraceMode>,
+    state: Res<State<GameState>>,
+    mut input_action: ResMut<InputAction>,
+    mut player_two_input: ResMut<PlayerTwoInput>,
+) {
+    if *state.get() != GameState::InGame {
+        ReplayFrame::default().apply(&mut input_action, &mut player_two_input);
+        return;
+    }
+    if !matches!(*mode, InputTraceMode::Play) {
+        return;
+    }
+
+    if let Some(frame) = trace.frames.get(trace.next_frame).copied() {
+        frame.apply(&mut input_action, &mut player_two_input);
+    } else if trace.next_frame == trace.frames.len() {
+        println!("Input trace finished");
+    }
+    trace.next_frame += 1;
+}
+
+/// Trace save system: Writes the recorded trace when the game exits
+fn s_save_input_trace(
+    mut exit: MessageReader<AppExit>,
+    trace: Res<InputTrace>,
+    mode: Res<InputTraceMode>,
+) {
+    let InputTraceMode::Record(path) = &*mode else {
+        return;
+    };
+    if exit.read().last().is_none() {
+        return;
+    }
+
+    match trace.save(path) {
+        Ok(()) => println!(
+            "Saved a {:.2} s input trace to {}",
+            trace.duration(),
+            path.display()
+        ),
+        Err(error) => eprintln!("Failed to save the input trace to {}: {error}", path.display()),
+    }
+}
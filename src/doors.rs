@@ -0,0 +1,323 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    camera::visibility::Visibility,
+    color::Color,
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        entity::Entity,
+        message::MessageWriter,
+        query::{With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{
+        ops::FloatPow,
+        primitives::{Annulus, Rectangle},
+        Isometry2d, Vec2, Vec3Swizzles,
+    },
+    mesh::{Mesh, Mesh2d},
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    time::Time,
+    transform::components::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{
+        activity::Asleep,
+        pathfinding::{PathfindingGraph, PathfindingGraphChanged},
+    },
+    collisions::{
+        clamp_velocity_into_surface, s_ai_contacts, s_collision, s_player_contacts, s_sensors,
+        Sensor, TOUCH_THRESHOLD,
+    },
+    level::{Aabb, Level},
+    movement_config::MovementConfig,
+    KinematicBody, Player, CEILING_NORMAL_Y_THRESHOLD,
+};
+
+// Switch constants
+const SWITCH_RADIUS: f32 = 10.0;
+const SWITCH_THICKNESS: f32 = 3.0;
+const SWITCH_COLOR: Color = Color::srgb(1.0, 0.9, 0.2);
+const SWITCH_COUNTDOWN_RADIUS: f32 = 16.0;
+
+// Door constants
+const DOOR_COLOR: Color = Color::srgb(0.4, 0.8, 1.0);
+// Seconds before closing during which the door blinks as a warning
+const DOOR_BLINK_TIME: f32 = 2.0;
+// Blinks per second during the warning
+const DOOR_BLINK_RATE: f32 = 6.0;
+const DOOR_Z: f32 = 0.5;
+
+/// A door and the switch that opens it (read from level metadata, positions in world pixels)
+#[derive(Deserialize, Serialize, Clone)]
+pub struct DoorSetting {
+    /// Bottom-left corner of the door
+    pub min: [f32; 2],
+    /// Top-right corner of the door
+    pub max: [f32; 2],
+    /// Position of the switch that opens the door
+    pub switch: [f32; 2],
+    /// Seconds the door stays open after the switch is pressed
+    pub open_time: f32,
+}
+
+/// Door component: Solid rectangle that opens for a limited time when its switch is pressed
+#[derive(Component)]
+pub struct Door {
+    /// Index of the door in the level metadata (used by the pathfinding graph)
+    pub id: usize,
+    pub area: Aabb,
+    /// Seconds the door stays open after the switch is pressed
+    pub open_time: f32,
+    /// Time remaining (seconds) before the door closes
+    pub open_timer: f32,
+    /// Locked doors stay shut and ignore their switch (e.g. during an encounter)
+    pub locked: bool,
+}
+
+impl Door {
+    pub fn is_open(&self) -> bool {
+        self.open_timer > 0.0
+    }
+}
+
+/// Switch component: Opens its door when the player touches it
+#[derive(Component)]
+pub struct Switch {
+    pub door: Entity,
+}
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_switches.after(s_sensors));
+        app.add_systems(Update, s_update_doors.after(s_switches));
+        app.add_systems(
+            Update,
+            s_door_collision
+                .after(s_collision)
+                .before(s_player_contacts)
+                .before(s_ai_contacts)
+                .before(s_sensors),
+        );
+        app.add_systems(Update, s_draw_switch_countdowns.after(s_update_doors));
+    }
+}
+
+/// Spawns every door in the level metadata along with its switch
+pub fn spawn_doors(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    level: &Level,
+) {
+    for (id, setting) in level.metadata.doors.iter().enumerate() {
+        let area = Aabb {
+            min: Vec2::from(setting.min),
+            max: Vec2::from(setting.max),
+        };
+        let center = (area.min + area.max) / 2.0;
+
+        let door = commands
+            .spawn((
+                Transform::from_translation(center.extend(DOOR_Z)),
+                Mesh2d(meshes.add(Rectangle::from_corners(area.min, area.max))),
+                MeshMaterial2d(materials.add(DOOR_COLOR)),
+                Visibility::Visible,
+                Door {
+                    id,
+                    area,
+                    open_time: setting.open_time,
+                    open_timer: 0.0,
+                    locked: false,
+                },
+            ))
+            .id();
+
+        commands.spawn((
+            Transform::from_translation(Vec2::from(setting.switch).extend(DOOR_Z)),
+            Mesh2d(meshes.add(Annulus::new(SWITCH_RADIUS - SWITCH_THICKNESS, SWITCH_RADIUS))),
+            MeshMaterial2d(materials.add(SWITCH_COLOR)),
+            Sensor::new(SWITCH_RADIUS),
+            Switch { door },
+        ));
+    }
+}
+
+/// Switch system: Opens a closed, unlocked door when the player touches its switch
+pub fn s_switches(
+    switch_query: Query<(&Sensor, &Switch)>,
+    player_query: Query<(), With<Player>>,
+    mut door_query: Query<&mut Door>,
+) {
+    for (sensor, switch) in switch_query.iter() {
+        if !sensor
+            .overlapping_entities
+            .iter()
+            .any(|entity| player_query.contains(*entity))
+        {
+            continue;
+        }
+
+        if let Ok(mut door) = door_query.get_mut(switch.door) {
+            if !door.is_open() && !door.locked {
+                door.open_timer = door.open_time;
+            }
+        }
+    }
+}
+
+/// Door system: Counts down open doors, blinks them before they close and keeps the pathfinding
+/// graph's closed doors in sync so AI only uses links through doors that are open
+pub fn s_update_doors(
+    time: Res<Time>,
+    mut door_query: Query<(&mut Door, &mut Visibility)>,
+    mut pathfinding: ResMut<PathfindingGraph>,
+    mut graph_changed: MessageWriter<PathfindingGraphChanged>,
+) {
+    let dt = time.delta_secs();
+
+    for (mut door, mut visibility) in door_query.iter_mut() {
+        if door.open_timer > 0.0 {
+            door.open_timer = (door.open_timer - dt).max(0.0);
+        }
+
+        let open = door.is_open();
+
+        // Only touch the graph when the state changes so AI paths are recalculated once
+        if pathfinding.closed_doors.contains(&door.id) == open {
+            if open {
+                pathfinding.closed_doors.remove(&door.id);
+            } else {
+                pathfinding.closed_doors.insert(door.id);
+            }
+            // A door opening can shorten paths anywhere, not just ones through it
+            graph_changed.write(PathfindingGraphChanged { region: None });
+        }
+
+        // Open doors blink back into view as a warning shortly before they close
+        let blink_on = open
+            && door.open_timer < DOOR_BLINK_TIME
+            && (door.open_timer * DOOR_BLINK_RATE).fract() < 0.5;
+
+        visibility.set_if_neq(if !open || blink_on {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+/// Switch countdown system: Draws the time left before each open door closes around its switch
+pub fn s_draw_switch_countdowns(
+    switch_query: Query<(&Transform, &Switch)>,
+    door_query: Query<&Door>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, switch) in switch_query.iter() {
+        let Ok(door) = door_query.get(switch.door) else {
+            continue;
+        };
+
+        if !door.is_open() {
+            continue;
+        }
+
+        let fraction = door.open_timer / door.open_time.max(f32::EPSILON);
+
+        gizmos.arc_2d(
+            Isometry2d::from_translation(transform.translation.xy()),
+            fraction * TAU,
+            SWITCH_COUNTDOWN_RADIUS,
+            SWITCH_COLOR,
+        );
+    }
+}
+
+/// Door collision system: Pushes bodies out of closed doors and records the doors as contacts
+#[allow(clippy::type_complexity)]
+pub fn s_door_collision(
+    door_query: Query<&Door>,
+    movement_config: Res<MovementConfig>,
+    mut physics_query: Query<
+        (&mut Transform, &mut KinematicBody),
+        (Without<Sensor>, Without<Asleep>),
+    >,
+) {
+    for door in door_query.iter() {
+        if door.is_open() {
+            continue;
+        }
+
+        for (mut transform, mut physics) in physics_query.iter_mut() {
+            let Some((position, normal_dir)) =
+                push_out_of_area(&door.area, transform.translation.xy(), physics.radius)
+            else {
+                continue;
+            };
+
+            transform.translation = position.extend(transform.translation.z);
+
+            // Same rule as level contacts: surfaces above the body aren't contacts
+            if normal_dir.y >= CEILING_NORMAL_Y_THRESHOLD {
+                physics.contacts.push(normal_dir);
+            }
+
+            let mut new_normal = Vec2::ZERO;
+            for contact in &physics.contacts {
+                new_normal -= *contact;
+            }
+            physics.normal = new_normal.normalize_or_zero();
+            physics.velocity = clamp_velocity_into_surface(
+                physics.velocity,
+                -normal_dir,
+                movement_config.landing_restitution_threshold,
+            );
+        }
+    }
+}
+
+/// Pushes a circle out of a rectangle.
+///
+/// Returns the corrected position and the direction away from the rectangle if the circle is
+/// touching it, or `None` if it is clear of it.
+fn push_out_of_area(area: &Aabb, position: Vec2, radius: f32) -> Option<(Vec2, Vec2)> {
+    let closest = position.clamp(area.min, area.max);
+    let offset = position - closest;
+
+    if offset.length_squared() > (radius + TOUCH_THRESHOLD).squared() {
+        return None;
+    }
+
+    if offset.length_squared() > 0.0 {
+        let normal_dir = offset.normalize();
+        let position = if offset.length() < radius {
+            closest + normal_dir * radius
+        } else {
+            position
+        };
+        return Some((position, normal_dir));
+    }
+
+    // The center is inside the rectangle: leave through the nearest side
+    let exits = [
+        (position.x - area.min.x, Vec2::NEG_X),
+        (area.max.x - position.x, Vec2::X),
+        (position.y - area.min.y, Vec2::NEG_Y),
+        (area.max.y - position.y, Vec2::Y),
+    ];
+    let (depth, normal_dir) = exits
+        .into_iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+
+    Some((position + normal_dir * (depth + radius), normal_dir))
+}
@@ -0,0 +1,265 @@
+//! In-game level editor.
+//!
+//! Tab switches between playing and editing the current level. While editing, the game is paused,
+//! WASD pans the camera, the left mouse button paints the selected tile, the right mouse button
+//! erases, `[` and `]` pick the tile to paint and Ctrl+S saves the level as JSON. The level's
+//! polygons, meshes and pathfinding graph are rebuilt whenever a tile changes (see
+//! `Level::mutate_tiles`).
+
+use bevy::{
+    app::{App, Plugin, Update},
+    camera::{Camera, Projection},
+    color::Color,
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    gizmos::gizmos::Gizmos,
+    input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+    math::{URect, UVec2, Vec2},
+    prelude::Resource,
+    state::{
+        app::AppExtStates,
+        condition::in_state,
+        state::{NextState, OnEnter, OnExit, State, StateSet, SubStates},
+        state_scoped::DespawnOnExit,
+    },
+    text::{TextColor, TextFont},
+    time::{Time, Virtual},
+    transform::components::GlobalTransform,
+    ui::{widget::Text, Node, PositionType, Val},
+    window::{PrimaryWindow, Window},
+};
+
+use crate::{
+    camera::{cursor_world_position, CameraControls, GameCamera},
+    game_state::GameState,
+    level::Level,
+    pixel_perfect::CanvasCamera,
+};
+
+// Key that switches between playing and editing
+pub const EDITOR_TOGGLE_KEY: KeyCode = KeyCode::Tab;
+// Keys that pick the previous and next tile to paint
+const PREVIOUS_TILE_KEY: KeyCode = KeyCode::BracketLeft;
+const NEXT_TILE_KEY: KeyCode = KeyCode::BracketRight;
+// Key that saves the level (with Ctrl held)
+const SAVE_KEY: KeyCode = KeyCode::KeyS;
+
+// Where the edited level is saved
+const EDITOR_SAVE_PATH: &str = "assets/edited_level.json";
+
+// Highest tile code (see `tile_polygons`)
+const MAX_TILE_CODE: u32 = 17;
+
+// Overlay constants
+const OVERLAY_FONT_SIZE: f32 = 18.0;
+const OVERLAY_MARGIN: f32 = 12.0;
+const OVERLAY_COLOR: Color = Color::WHITE;
+// Hovered tile outline colors
+const PAINT_CURSOR_COLOR: Color = Color::srgb(0.3, 1.0, 0.5);
+const ERASE_CURSOR_COLOR: Color = Color::srgb(1.0, 0.3, 0.3);
+
+/// Whether the level is being played or edited (only exists in `GameState::InGame`)
+#[derive(SubStates, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[source(GameState = GameState::InGame)]
+pub enum EditorState {
+    #[default]
+    Playing,
+    Editing,
+}
+
+/// Tile the left mouse button paints
+#[derive(Resource)]
+pub struct EditorBrush {
+    pub tile: u32,
+}
+
+/// Editor overlay component: Text listing the controls and the selected tile
+#[derive(Component)]
+pub struct EditorOverlay;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<EditorState>();
+        app.insert_resource(EditorBrush { tile: 1 });
+
+        app.add_systems(Update, s_toggle_editor.run_if(in_state(GameState::InGame)));
+        app.add_systems(OnEnter(EditorState::Editing), s_enter_editor);
+        app.add_systems(OnExit(EditorState::Editing), s_exit_editor);
+        app.add_systems(
+            Update,
+            (
+                s_pick_editor_tile,
+                s_paint_tiles.after(s_pick_editor_tile),
+                s_save_edited_level,
+                s_update_editor_overlay.after(s_pick_editor_tile),
+            )
+                .run_if(in_state(EditorState::Editing)),
+        );
+    }
+}
+
+/// Editor toggle system: Switches between playing and editing the level
+fn s_toggle_editor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    editor_state: Res<State<EditorState>>,
+    mut next_state: ResMut<NextState<EditorState>>,
+) {
+    if keyboard_input.just_pressed(EDITOR_TOGGLE_KEY) {
+        next_state.set(match editor_state.get() {
+            EditorState::Playing => EditorState::Editing,
+            EditorState::Editing => EditorState::Playing,
+        });
+    }
+}
+
+/// Pauses the game, detaches the camera for panning and shows the editor overlay
+fn s_enter_editor(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    mut camera_controls: ResMut<CameraControls>,
+) {
+    time.pause();
+    camera_controls.free_fly = true;
+
+    commands.spawn((
+        DespawnOnExit(EditorState::Editing),
+        Text::new(""),
+        TextFont {
+            font_size: OVERLAY_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(OVERLAY_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(OVERLAY_MARGIN),
+            left: Val::Px(OVERLAY_MARGIN),
+            ..Default::default()
+        },
+        EditorOverlay,
+    ));
+}
+
+/// Resumes the game with the camera back where it was
+fn s_exit_editor(mut time: ResMut<Time<Virtual>>, mut camera_controls: ResMut<CameraControls>) {
+    time.unpause();
+    camera_controls.free_fly = false;
+}
+
+/// Tile pick system: Brackets step through the tile codes
+fn s_pick_editor_tile(keyboard_input: Res<ButtonInput<KeyCode>>, mut brush: ResMut<EditorBrush>) {
+    if keyboard_input.just_pressed(PREVIOUS_TILE_KEY) {
+        brush.tile = if brush.tile <= 1 { MAX_TILE_CODE } else { brush.tile - 1 };
+    }
+    if keyboard_input.just_pressed(NEXT_TILE_KEY) {
+        brush.tile = brush.tile % MAX_TILE_CODE + 1;
+    }
+}
+
+/// Tile paint system: Outlines the tile under the cursor and paints (left button) or erases
+/// (right button) it
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn s_paint_tiles(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    canvas_query: Query<(&Projection, &CanvasCamera)>,
+    brush: Res<EditorBrush>,
+    level: Option<ResMut<Level>>,
+    mut gizmos: Gizmos,
+) {
+    let (Some(mut level), Ok(window), Ok((camera, camera_transform))) =
+        (level, window_query.single(), camera_query.single())
+    else {
+        return;
+    };
+    let Some(cursor) =
+        cursor_world_position(window, camera, camera_transform, canvas_query.single().ok())
+    else {
+        return;
+    };
+
+    let Some(cell) = cursor_cell(&level, cursor) else {
+        return;
+    };
+
+    let erasing = mouse_input.pressed(MouseButton::Right);
+    let grid_size = level.grid_size;
+    let cell_center = Vec2::new(
+        (cell.x as f32 + 0.5) * grid_size - level.half_size.x,
+        level.half_size.y - (cell.y as f32 + 0.5) * grid_size,
+    );
+    let cursor_color = if erasing { ERASE_CURSOR_COLOR } else { PAINT_CURSOR_COLOR };
+    gizmos.rect_2d(cell_center, Vec2::splat(grid_size), cursor_color);
+
+    let tile = if erasing {
+        0
+    } else if mouse_input.pressed(MouseButton::Left) {
+        brush.tile
+    } else {
+        return;
+    };
+
+    // Only edit when the tile changes, so holding the button over a tile doesn't rebuild the
+    // level every frame
+    if level.tiles[cell.y as usize][cell.x as usize] != tile {
+        level.mutate_tiles(URect::from_corners(cell, cell + UVec2::ONE), |_, value| {
+            *value = tile;
+        });
+    }
+}
+
+/// Tile (column, row) under a world position, if it is inside the level
+fn cursor_cell(level: &Level, position: Vec2) -> Option<UVec2> {
+    let column = (position.x + level.half_size.x) / level.grid_size;
+    let row = (level.half_size.y - position.y) / level.grid_size;
+    if column < 0.0 || row < 0.0 {
+        return None;
+    }
+
+    let cell = UVec2::new(column as u32, row as u32);
+    let rows = level.tiles.len() as u32;
+    let columns = level.tiles.first().map_or(0, Vec::len) as u32;
+    (cell.x < columns && cell.y < rows).then_some(cell)
+}
+
+/// Level save system: Ctrl+S writes the edited level to `assets/edited_level.json`
+fn s_save_edited_level(keyboard_input: Res<ButtonInput<KeyCode>>, level: Option<Res<Level>>) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let Some(level) = level.filter(|_| ctrl && keyboard_input.just_pressed(SAVE_KEY)) else {
+        return;
+    };
+
+    let result = level
+        .to_json()
+        .map_err(|error| error.to_string())
+        .and_then(|json| {
+            std::fs::write(EDITOR_SAVE_PATH, json).map_err(|error| error.to_string())
+        });
+    match result {
+        Ok(()) => println!("Saved level to {EDITOR_SAVE_PATH}"),
+        Err(error) => eprintln!("Failed to save {EDITOR_SAVE_PATH}: {error}"),
+    }
+}
+
+/// Editor overlay system: Keeps the overlay text in step with the selected tile
+fn s_update_editor_overlay(
+    brush: Res<EditorBrush>,
+    mut overlay_query: Query<&mut Text, With<EditorOverlay>>,
+) {
+    for mut text in overlay_query.iter_mut() {
+        if brush.is_changed() || text.0.is_empty() {
+            text.0 = format!(
+                "EDITING (Tab to play)\nTile {} ([ / ] to change)\nLeft click: paint, right \
+                 click: erase\nWASD: pan, Ctrl+S: save to {EDITOR_SAVE_PATH}",
+                brush.tile
+            );
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, ResMut},
+    },
+    math::{Vec2, Vec3Swizzles},
+    mesh::Mesh,
+    sprite_render::ColorMaterial,
+    transform::components::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{profile::AIProfilePreset, spawner::AISpawner},
+    characters::PlayerControlled,
+    combo::s_detect_kills,
+    doors::{s_switches, Door},
+    health::{s_respawn, Health, SpawnPoint},
+    level::{Aabb, Level},
+    AIVariant,
+};
+
+/// An encounter placed in the level (read from level metadata, positions in world pixels):
+/// when the player first enters the region its actions run, and it is cleared once every agent
+/// it spawned has been defeated
+#[derive(Deserialize, Serialize, Clone)]
+pub struct EncounterSetting {
+    /// Bottom-left corner of the trigger region
+    pub min: [f32; 2],
+    /// Top-right corner of the trigger region
+    pub max: [f32; 2],
+    pub actions: Vec<EncounterAction>,
+}
+
+/// Something an encounter does when it starts
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EncounterAction {
+    /// Spawns pursuing agents side by side at a spawner position
+    SpawnChasers {
+        at: [f32; 2],
+        count: u32,
+        #[serde(default)]
+        profile: AIProfilePreset,
+    },
+    /// Keeps a door (index into the level's doors) shut until the encounter is cleared
+    LockDoor { door: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncounterState {
+    /// Waiting for the player to enter the region
+    Dormant,
+    /// Agents are still alive
+    Active,
+    Cleared,
+}
+
+/// Encounter component: A scripted combat room
+#[derive(Component)]
+pub struct Encounter {
+    pub region: Aabb,
+    pub actions: Vec<EncounterAction>,
+    pub state: EncounterState,
+    /// Agents spawned by this encounter that are still alive
+    pub remaining: u32,
+}
+
+/// Encounter member component: Agent spawned by an encounter. It doesn't respawn when it dies
+#[derive(Component)]
+pub struct EncounterMember {
+    pub encounter: Entity,
+}
+
+pub struct EncounterPlugin;
+
+impl Plugin for EncounterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_trigger_encounters.before(s_switches));
+        app.add_systems(
+            Update,
+            s_encounter_defeats
+                .after(s_detect_kills)
+                .before(s_respawn),
+        );
+    }
+}
+
+/// Spawns every encounter in the level metadata
+pub fn spawn_encounters(commands: &mut Commands, level: &Level) {
+    for setting in &level.metadata.encounters {
+        commands.spawn(Encounter {
+            region: Aabb {
+                min: Vec2::from(setting.min),
+                max: Vec2::from(setting.max),
+            },
+            actions: setting.actions.clone(),
+            state: EncounterState::Dormant,
+            remaining: 0,
+        });
+    }
+}
+
+/// Encounter trigger system: Starts dormant encounters when either player enters their region
+pub fn s_trigger_encounters(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut encounter_query: Query<(Entity, &mut Encounter)>,
+    player_query: Query<&Transform, PlayerControlled>,
+    mut door_query: Query<&mut Door>,
+) {
+    for (entity, mut encounter) in encounter_query.iter_mut() {
+        let entered = player_query
+            .iter()
+            .any(|transform| encounter.region.contains(transform.translation.xy()));
+        if encounter.state != EncounterState::Dormant || !entered {
+            continue;
+        }
+
+        encounter.state = EncounterState::Active;
+
+        for action in encounter.actions.clone() {
+            match action {
+                EncounterAction::SpawnChasers { at, count, profile } => {
+                    let spawner = AISpawner {
+                        points: vec![Vec2::from(at)],
+                        count: count as usize,
+                        profile: profile.profile(),
+                    };
+                    let agents = spawner.spawn(&mut commands, &mut meshes, &mut materials, || {
+                        AIVariant::Normal
+                    });
+
+                    for agent in agents {
+                        commands
+                            .entity(agent)
+                            .remove::<SpawnPoint>()
+                            .insert(EncounterMember { encounter: entity });
+                    }
+                    encounter.remaining += count;
+                }
+                EncounterAction::LockDoor { door } => {
+                    for mut door_data in door_query.iter_mut() {
+                        if door_data.id == door {
+                            door_data.locked = true;
+                            door_data.open_timer = 0.0;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Nothing to defeat: the encounter is over as soon as it starts
+        if encounter.remaining == 0 {
+            clear_encounter(&mut encounter, &mut door_query);
+        }
+    }
+}
+
+/// Encounter defeat system: Removes dead encounter agents and clears encounters once all of
+/// their agents are gone
+pub fn s_encounter_defeats(
+    mut commands: Commands,
+    member_query: Query<(Entity, &EncounterMember, &Health)>,
+    mut encounter_query: Query<&mut Encounter>,
+    mut door_query: Query<&mut Door>,
+) {
+    for (entity, member, health) in member_query.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+
+        let Ok(mut encounter) = encounter_query.get_mut(member.encounter) else {
+            continue;
+        };
+
+        encounter.remaining = encounter.remaining.saturating_sub(1);
+        if encounter.remaining == 0 && encounter.state == EncounterState::Active {
+            clear_encounter(&mut encounter, &mut door_query);
+        }
+    }
+}
+
+/// Marks an encounter as cleared and unlocks the doors it locked
+fn clear_encounter(encounter: &mut Encounter, door_query: &mut Query<&mut Door>) {
+    encounter.state = EncounterState::Cleared;
+
+    for action in &encounter.actions {
+        if let EncounterAction::LockDoor { door } = action {
+            for mut door_data in door_query.iter_mut() {
+                if door_data.id == *door {
+                    door_data.locked = false;
+                }
+            }
+        }
+    }
+}
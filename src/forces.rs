@@ -0,0 +1,60 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        query::Without,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    math::{Vec2, Vec3Swizzles},
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    ai::{activity::Asleep, platformer_ai::s_platformer_ai_movement},
+    level::Aabb,
+    s_movement, KinematicBody,
+};
+
+/// Force zone: Accelerates every body whose center is inside the area
+#[derive(Component)]
+pub struct ForceZone {
+    /// World-space area affected by the zone
+    pub area: Aabb,
+    /// Acceleration applied to bodies inside the zone (pixels/second²)
+    pub force: Vec2,
+}
+
+pub struct ForceZonePlugin;
+
+impl Plugin for ForceZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            s_apply_force_zones
+                .before(s_movement)
+                .before(s_platformer_ai_movement),
+        );
+    }
+}
+
+/// Force zone system: Adds each zone's force to the velocity of the bodies inside it
+pub fn s_apply_force_zones(
+    zone_query: Query<&ForceZone>,
+    mut physics_query: Query<(&Transform, &mut KinematicBody), Without<Asleep>>,
+    time: Res<Time>,
+) {
+    // Clamp delta time to match the movement systems
+    let dt = time.delta_secs().min(1.0 / 30.0);
+
+    for zone in zone_query.iter() {
+        let force_dt = zone.force * dt;
+
+        for (transform, mut physics) in physics_query.iter_mut() {
+            if zone.area.contains(transform.translation.xy()) {
+                physics.velocity += force_dt;
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::{
+    app::{App, First, Plugin},
+    ecs::system::{Res, ResMut},
+    prelude::Resource,
+    time::{Real, Time},
+};
+
+// Frames slower than this are over budget (units: seconds)
+const FRAME_BUDGET: f32 = 1.0 / 50.0;
+// Consecutive frames over budget before optional work is degraded (so a one-off hitch, like
+// building a level, doesn't count)
+const OVERRUN_FRAMES: u32 = 3;
+// Frames faster than this leave enough headroom to restore the work (units: seconds)
+const HEADROOM_FRAME_TIME: f32 = 1.0 / 75.0;
+// Consecutive frames with headroom before optional work is restored
+const RECOVERY_FRAMES: u32 = 120;
+
+// How much optional work is kept while degraded
+const DEGRADED_AI_TICK_SCALE: f32 = 0.5;
+const DEGRADED_PARTICLE_SCALE: f32 = 0.5;
+
+/// Frame budget resource: Whether optional work is cut back because frames are taking too long.
+///
+/// While degraded, the AI tick rate is halved, debug gizmos aren't drawn and weather particles
+/// are halved, so the controller keeps running at a playable rate on slow machines.
+#[derive(Resource)]
+pub struct FrameBudget {
+    /// Whether the guard reacts to frame times at all (off in headless runs, which must do the
+    /// same work however fast the machine is)
+    pub enabled: bool,
+    pub degraded: bool,
+    /// Consecutive frames over budget
+    slow_frames: u32,
+    /// Consecutive frames with headroom
+    fast_frames: u32,
+}
+
+impl Default for FrameBudget {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            degraded: false,
+            slow_frames: 0,
+            fast_frames: 0,
+        }
+    }
+}
+
+impl FrameBudget {
+    /// Off: optional work always runs in full
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Multiplier for the AI decision rate
+    pub fn ai_tick_scale(&self) -> f32 {
+        if self.degraded {
+            DEGRADED_AI_TICK_SCALE
+        } else {
+            1.0
+        }
+    }
+
+    /// Multiplier for particle counts
+    pub fn particle_scale(&self) -> f32 {
+        if self.degraded {
+            DEGRADED_PARTICLE_SCALE
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether debug gizmos are drawn (when visible)
+    pub fn debug_draws_enabled(&self) -> bool {
+        !self.degraded
+    }
+}
+
+pub struct FrameBudgetPlugin;
+
+impl Plugin for FrameBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FrameBudget>();
+        app.add_systems(First, s_frame_budget);
+    }
+}
+
+/// Frame budget system: Degrades optional work after a run of slow frames and restores it once
+/// frames have had headroom for a while (the decision applies from this frame on)
+pub fn s_frame_budget(time: Res<Time<Real>>, mut frame_budget: ResMut<FrameBudget>) {
+    if !frame_budget.enabled {
+        return;
+    }
+
+    let frame_time = time.delta_secs();
+    frame_budget.slow_frames = if frame_time > FRAME_BUDGET {
+        frame_budget.slow_frames + 1
+    } else {
+        0
+    };
+    frame_budget.fast_frames = if frame_time < HEADROOM_FRAME_TIME {
+        frame_budget.fast_frames + 1
+    } else {
+        0
+    };
+
+    if !frame_budget.degraded && frame_budget.slow_frames >= OVERRUN_FRAMES {
+        frame_budget.degraded = true;
+        frame_budget.fast_frames = 0;
+        println!(
+            "Frame budget exceeded ({:.1} ms > {:.1} ms): halving the AI tick rate and weather \
+             particles, skipping debug draws",
+            frame_time * 1000.0,
+            FRAME_BUDGET * 1000.0
+        );
+    } else if frame_budget.degraded && frame_budget.fast_frames >= RECOVERY_FRAMES {
+        frame_budget.degraded = false;
+        frame_budget.slow_frames = 0;
+        println!(
+            "Frame budget recovered: restoring the AI tick rate, weather particles and debug draws"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::{
+    app::{App, Plugin},
+    ecs::{
+        entity::Entity,
+        query::{Or, With},
+        system::{Commands, Query, ResMut},
+    },
+    state::{
+        app::AppExtStates,
+        state::{OnExit, States},
+    },
+};
+
+#[cfg(feature = "scripting")]
+use crate::scripting::{Dialogue, ScriptTrigger};
+use crate::{
+    ai::{alert::PendingAlerts, pathfinding::PathfindingGraph},
+    collectibles::Collectible,
+    doors::{Door, Switch},
+    encounters::Encounter,
+    hazards::Hazard,
+    level::{Level, LevelMesh},
+    platforms::MovingPlatform,
+    progress::LevelGoal,
+    rest_points::RestPoint,
+    spatial::DynamicSpatialIndex,
+    weather::Wind,
+};
+
+/// Whether a level is being played.
+///
+/// The `Level` resource, the pathfinding graph and every entity spawned for the level only exist
+/// in `InGame`: they are built in `Loading` (from `LevelSource`), spawned on entering `InGame` and
+/// torn down on leaving it.
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GameState {
+    InGame,
+    /// Before the first level and between levels: the old level is gone and the next one is
+    /// being built (see `LoadingPlugin`)
+    #[default]
+    Loading,
+}
+
+/// Everything spawned by `spawn_level`
+type LevelEntityFilter = Or<(
+    With<LevelMesh>,
+    With<Hazard>,
+    With<Door>,
+    With<Switch>,
+    With<RestPoint>,
+    With<Collectible>,
+    With<LevelGoal>,
+    With<Encounter>,
+    With<MovingPlatform>,
+    With<Wind>,
+)>;
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>();
+
+        app.add_systems(OnExit(GameState::InGame), s_tear_down_level);
+    }
+}
+
+/// Level teardown system: Despawns the level and everything placed by its metadata, and clears
+/// the resources built from it so nothing stale carries over into the next level (the player,
+/// AI agents and running scripts are despawned by their `DespawnOnExit` component)
+fn s_tear_down_level(
+    mut commands: Commands,
+    level_entities: Query<Entity, LevelEntityFilter>,
+    #[cfg(feature = "scripting")] script_triggers: Query<Entity, With<ScriptTrigger>>,
+    #[cfg(feature = "scripting")] mut dialogue: ResMut<Dialogue>,
+    mut pathfinding: ResMut<PathfindingGraph>,
+    mut spatial_index: ResMut<DynamicSpatialIndex>,
+    mut pending_alerts: ResMut<PendingAlerts>,
+) {
+    for entity in level_entities.iter() {
+        commands.entity(entity).despawn();
+    }
+    #[cfg(feature = "scripting")]
+    for entity in script_triggers.iter() {
+        commands.entity(entity).despawn();
+    }
+    // A line still showing when the level ended doesn't carry over into the next one
+    #[cfg(feature = "scripting")]
+    {
+        *dialogue = Dialogue::default();
+    }
+
+    commands.remove_resource::<Level>();
+    *pathfinding = PathfindingGraph::default();
+    *spatial_index = DynamicSpatialIndex::default();
+    *pending_alerts = PendingAlerts::default();
+}
@@ -0,0 +1,499 @@
+//! Headless simulation harness.
+//!
+//! `Harness` runs the whole game (movement, collision, timers, AI) in a `MinimalPlugins` app
+//! without a window (see `bench::headless_app`), one fixed frame at a time, with helpers to script
+//! the player's input and to check where the player and agents are. Scenario files run on it (see
+//! `scenarios`), and a check like "the player holding right from the spawn reaches the platform
+//! within 3 seconds" takes a few lines:
+//!
+//! ```ignore
+//! let mut harness = Harness::new(LevelSource::default());
+//! harness
+//!     .replace_agents(Vec::new())
+//!     .script_input(vec![InputSegment::hold(3.0, Vec2::X)])
+//!     .start();
+//! harness.expect_player_reaches(Vec2::new(200.0, -134.0), 40.0, 3.0)?;
+//! ```
+
+use bevy::{
+    app::{App, PreUpdate, Update},
+    asset::Assets,
+    ecs::{
+        entity::Entity,
+        message::MessageReader,
+        query::{Or, With, Without},
+        schedule::{common_conditions::resource_exists, IntoScheduleConfigs},
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{Vec2, Vec3Swizzles},
+    mesh::Mesh,
+    prelude::Resource,
+    sprite_render::ColorMaterial,
+    state::state::OnEnter,
+    time::Time,
+    transform::components::Transform,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::Deserialize;
+
+use crate::{
+    ai::{
+        flying_ai::FlyingAI,
+        patrol::{PatrolAI, PatrolRouteSetting},
+        platformer_ai::PlatformerAI,
+        profile::AIProfilePreset,
+        pursue_ai::AIRng,
+    },
+    bench::{headless_app, BENCH_FRAME_DT},
+    characters::ActiveCharacter,
+    deterministic::{DeterministicSim, InputTrace, InputTraceMode},
+    game_state::GameState,
+    health::{s_respawn, Died, SpawnPoint},
+    input::{s_read_input_actions, InputAction},
+    level::LevelSource,
+    s_enter_game, spawn_ai_agent, spawn_flying_ai_agent, AIVariant, KinematicBody, Player,
+};
+
+/// Where to put an agent in place of the level's own
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AgentPlacement {
+    pub position: [f32; 2],
+    /// Velocity the agent starts with (pixels/second)
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    #[serde(default)]
+    pub variant: Option<AIVariant>,
+    #[serde(default)]
+    pub profile: AIProfilePreset,
+    /// Flying agent instead of a running one (`variant` doesn't apply)
+    #[serde(default)]
+    pub flying: bool,
+    /// Route the agent patrols while it hasn't spotted the player
+    #[serde(default)]
+    pub patrol: Option<PatrolRouteSetting>,
+}
+
+/// Player input held for a while
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputSegment {
+    pub seconds: f32,
+    pub move_dir: [f32; 2],
+    pub jump: bool,
+    pub dash: bool,
+}
+
+impl InputSegment {
+    /// Holds the stick in `move_dir` for `seconds`
+    #[cfg(test)]
+    pub fn hold(seconds: f32, move_dir: Vec2) -> Self {
+        Self {
+            seconds,
+            move_dir: move_dir.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Holds jump as well
+    #[cfg(test)]
+    pub fn with_jump(mut self) -> Self {
+        self.jump = true;
+        self
+    }
+}
+
+/// Harness setup resource: Placements applied once the level is built
+#[derive(Resource, Default)]
+struct HarnessSetup {
+    player: Option<Vec2>,
+    /// Velocity the player starts with (pixels/second)
+    player_velocity: Vec2,
+    agents: Option<Vec<AgentPlacement>>,
+    seed: Option<u64>,
+}
+
+/// Scripted input resource: The player's input, one segment after another
+#[derive(Resource)]
+struct ScriptedInput {
+    segments: Vec<InputSegment>,
+    /// Segment applied last frame (for press and release edges)
+    previous: InputSegment,
+}
+
+/// Player death resource: Whether the player has died since the harness started
+#[derive(Resource, Default)]
+struct PlayerDied(bool);
+
+/// A headless game stepped one fixed frame at a time
+pub struct Harness {
+    app: App,
+    /// Length of every frame (seconds)
+    frame_dt: f64,
+    /// Frames stepped since the level was entered
+    frames: u32,
+}
+
+impl Harness {
+    /// A headless game on the level, not started yet: set it up, then call `start`
+    pub fn new(level_source: LevelSource) -> Self {
+        let mut app = headless_app(level_source);
+        app.init_resource::<HarnessSetup>()
+            .init_resource::<PlayerDied>()
+            .add_systems(OnEnter(GameState::InGame), s_apply_harness_setup.after(s_enter_game))
+            .add_systems(
+                PreUpdate,
+                s_play_scripted_input
+                    .after(s_read_input_actions)
+                    .run_if(resource_exists::<ScriptedInput>),
+            )
+            .add_systems(Update, s_watch_player_death.after(s_respawn));
+
+        Self {
+            app,
+            frame_dt: BENCH_FRAME_DT,
+            frames: 0,
+        }
+    }
+
+    /// Starts the player at `position` instead of the level's spawn (the player is always placed,
+    /// so a save file can't move them somewhere else)
+    pub fn place_player(&mut self, position: Vec2) -> &mut Self {
+        self.setup().player = Some(position);
+        self
+    }
+
+    /// Starts the player moving at `velocity` (pixels/second)
+    pub fn launch_player(&mut self, velocity: Vec2) -> &mut Self {
+        self.setup().player_velocity = velocity;
+        self
+    }
+
+    /// Puts these agents in the level instead of its own
+    pub fn replace_agents(&mut self, agents: Vec<AgentPlacement>) -> &mut Self {
+        self.setup().agents = Some(agents);
+        self
+    }
+
+    /// Seeds the AI's random choices
+    pub fn seed_ai(&mut self, seed: u64) -> &mut Self {
+        self.setup().seed = Some(seed);
+        self
+    }
+
+    /// Plays the segments as the player's input (then no input)
+    pub fn script_input(&mut self, segments: Vec<InputSegment>) -> &mut Self {
+        self.app.insert_resource(ScriptedInput {
+            segments,
+            previous: InputSegment::default(),
+        });
+        self
+    }
+
+    /// Plays an input trace as the player's input, running deterministically with the trace's
+    /// seed and timestep (see `deterministic`)
+    pub fn play_trace(&mut self, trace: InputTrace) -> &mut Self {
+        DeterministicSim {
+            seed: trace.seed,
+            timestep: trace.timestep,
+        }
+        .insert_into(&mut self.app);
+        self.frame_dt = trace.timestep;
+        self.app
+            .insert_resource(trace)
+            .insert_resource(InputTraceMode::Play);
+        self
+    }
+
+    fn setup(&mut self) -> &mut HarnessSetup {
+        self.app
+            .world_mut()
+            .get_resource_mut::<HarnessSetup>()
+            .expect("the harness setup is inserted by `Harness::new`")
+            .into_inner()
+    }
+
+    /// Builds and enters the level
+    pub fn start(&mut self) -> &mut Self {
+        self.app.update();
+        self
+    }
+
+    /// Runs one frame
+    pub fn step(&mut self) {
+        self.app.update();
+        self.frames += 1;
+    }
+
+    /// Simulated seconds since the level was entered
+    pub fn seconds(&self) -> f32 {
+        (self.frames as f64 * self.frame_dt) as f32
+    }
+
+    /// Steps until `condition` holds, returning the seconds it took, or `None` if it still
+    /// doesn't after `timeout` seconds
+    pub fn run_until(
+        &mut self,
+        timeout: f32,
+        mut condition: impl FnMut(&mut Self) -> Result<bool, String>,
+    ) -> Result<Option<f32>, String> {
+        let end = self.frames + (timeout as f64 / self.frame_dt).ceil() as u32;
+        while self.frames < end {
+            self.step();
+            if condition(self)? {
+                return Ok(Some(self.seconds()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Steps until the player is within `distance` of `position`, failing after `timeout` seconds
+    #[cfg(test)]
+    pub fn expect_player_reaches(
+        &mut self,
+        position: Vec2,
+        distance: f32,
+        timeout: f32,
+    ) -> Result<f32, String> {
+        self.run_until(timeout, |harness| {
+            Ok(harness.player_position()?.distance(position) <= distance)
+        })?
+        .ok_or_else(|| {
+            format!(
+                "the player didn't reach {position} in {timeout} s (player at {})",
+                self.player_position().unwrap_or(Vec2::NAN)
+            )
+        })
+    }
+
+    /// Steps until an agent is within `distance` of `position`, failing after `timeout` seconds
+    #[cfg(test)]
+    pub fn expect_agent_reaches(
+        &mut self,
+        position: Vec2,
+        distance: f32,
+        timeout: f32,
+    ) -> Result<f32, String> {
+        self.run_until(timeout, |harness| {
+            Ok(harness
+                .agent_positions()
+                .iter()
+                .any(|agent| agent.distance(position) <= distance))
+        })?
+        .ok_or_else(|| format!("no agent reached {position} in {timeout} s"))
+    }
+
+    pub fn player_position(&mut self) -> Result<Vec2, String> {
+        let world = self.app.world_mut();
+        world
+            .query_filtered::<&Transform, With<ActiveCharacter>>()
+            .single(world)
+            .map(|transform| transform.translation.xy())
+            .map_err(|_| "the player is missing".to_string())
+    }
+
+    /// Where every running and flying agent is
+    pub fn agent_positions(&mut self) -> Vec<Vec2> {
+        let world = self.app.world_mut();
+        world
+            .query_filtered::<&Transform, Or<(With<PlatformerAI>, With<FlyingAI>)>>()
+            .iter(world)
+            .map(|transform| transform.translation.xy())
+            .collect()
+    }
+
+    pub fn player_on_ground(&mut self) -> bool {
+        let world = self.app.world_mut();
+        world
+            .query_filtered::<&KinematicBody, With<ActiveCharacter>>()
+            .single(world)
+            .is_ok_and(KinematicBody::on_ground)
+    }
+
+    /// Whether the player has died since the harness started
+    pub fn player_died(&self) -> bool {
+        self.app.world().resource::<PlayerDied>().0
+    }
+
+    /// The app being stepped, for checks the helpers don't cover
+    #[cfg(test)]
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
+
+/// Harness setup system: Moves and launches the player, replaces (and launches) the agents as set
+/// up, and seeds the AI
+#[allow(clippy::type_complexity)]
+fn s_apply_harness_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    setup: Res<HarnessSetup>,
+    level_source: Res<LevelSource>,
+    mut player_query: Query<
+        (&mut Transform, &mut KinematicBody, &mut SpawnPoint),
+        (With<ActiveCharacter>, Without<PlatformerAI>),
+    >,
+    agent_query: Query<Entity, Or<(With<PlatformerAI>, With<FlyingAI>)>>,
+) {
+    let player_position = setup
+        .player
+        .unwrap_or(level_source.metadata.player_spawn_position());
+    for (mut transform, mut physics, mut spawn_point) in player_query.iter_mut() {
+        transform.translation = player_position.extend(transform.translation.z);
+        physics.prev_position = player_position;
+        physics.velocity = setup.player_velocity;
+        spawn_point.0 = player_position;
+    }
+
+    if let Some(agents) = &setup.agents {
+        for entity in agent_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        for agent in agents {
+            let position = Vec2::from(agent.position);
+            let entity = if agent.flying {
+                spawn_flying_ai_agent(&mut commands, &mut meshes, &mut materials, position)
+            } else {
+                spawn_ai_agent(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    position,
+                    agent.variant.unwrap_or(AIVariant::Normal),
+                )
+            };
+            let velocity = Vec2::from(agent.velocity);
+            commands
+                .entity(entity)
+                .insert(agent.profile.profile())
+                .entry::<KinematicBody>()
+                .and_modify(move |mut physics| physics.velocity = velocity);
+            if let Some(route) = &agent.patrol {
+                commands.entity(entity).insert(PatrolAI::from_setting(route));
+            }
+        }
+    }
+
+    if let Some(seed) = setup.seed {
+        commands.insert_resource(AIRng(StdRng::seed_from_u64(seed)));
+    }
+}
+
+/// Scripted input system: Replaces the player's input with the segment for the current time
+fn s_play_scripted_input(
+    mut scripted_input: ResMut<ScriptedInput>,
+    mut input_action: ResMut<InputAction>,
+    time: Res<Time>,
+) {
+    let mut start = 0.0;
+    let segment = scripted_input
+        .segments
+        .iter()
+        .find(|segment| {
+            start += segment.seconds;
+            time.elapsed_secs() < start
+        })
+        .copied()
+        .unwrap_or_default();
+    let previous = scripted_input.previous;
+
+    input_action.move_dir = Vec2::from(segment.move_dir).clamp_length_max(1.0);
+    input_action.jump_pressed = segment.jump && !previous.jump;
+    input_action.jump_released = !segment.jump && previous.jump;
+    input_action.dash_pressed = segment.dash && !previous.dash;
+
+    scripted_input.previous = segment;
+}
+
+/// Player death system: Remembers the player dying
+fn s_watch_player_death(
+    mut died: MessageReader<Died>,
+    player_query: Query<(), With<Player>>,
+    mut player_died: ResMut<PlayerDied>,
+) {
+    if died.read().any(|died| player_query.contains(died.entity)) {
+        player_died.0 = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai::pursue_ai::PURSUE_AI_AGENT_RADIUS, level_loader::parse_level_file, PLAYER_RADIUS,
+    };
+
+    // Floor top and the thin wall's left face in `thin_wall.json` (pixels)
+    const FLOOR_Y: f32 = -128.0;
+    const WALL_X: f32 = 0.0;
+
+    fn thin_wall_harness() -> Harness {
+        let contents = std::fs::read("assets/scenarios/levels/thin_wall.json").unwrap();
+        Harness::new(parse_level_file(Some("json"), &contents).unwrap())
+    }
+
+    fn agent_at(position: Vec2) -> AgentPlacement {
+        AgentPlacement {
+            position: position.into(),
+            velocity: [0.0, 0.0],
+            variant: None,
+            profile: AIProfilePreset::default(),
+            flying: false,
+            patrol: None,
+        }
+    }
+
+    #[test]
+    fn player_holding_right_runs_up_to_the_wall() {
+        let mut harness = thin_wall_harness();
+        harness
+            .place_player(Vec2::new(-140.0, FLOOR_Y + PLAYER_RADIUS))
+            .replace_agents(Vec::new())
+            .script_input(vec![InputSegment::hold(3.0, Vec2::X)])
+            .start();
+        let against_wall = Vec2::new(WALL_X - PLAYER_RADIUS, FLOOR_Y + PLAYER_RADIUS);
+        harness.expect_player_reaches(against_wall, 4.0, 3.0).unwrap();
+    }
+
+    #[test]
+    fn player_holding_jump_leaves_the_ground() {
+        let mut harness = thin_wall_harness();
+        harness
+            .place_player(Vec2::new(-100.0, FLOOR_Y + PLAYER_RADIUS))
+            .replace_agents(Vec::new())
+            .script_input(vec![InputSegment::hold(0.5, Vec2::ZERO).with_jump()])
+            .start();
+        harness
+            .expect_player_reaches(Vec2::new(-100.0, FLOOR_Y + PLAYER_RADIUS + 60.0), 8.0, 0.5)
+            .unwrap();
+    }
+
+    #[test]
+    fn agent_runs_to_an_idle_player() {
+        let player = Vec2::new(-140.0, FLOOR_Y + PLAYER_RADIUS);
+        let mut harness = thin_wall_harness();
+        harness
+            .place_player(player)
+            .replace_agents(vec![agent_at(Vec2::new(-40.0, FLOOR_Y + PURSUE_AI_AGENT_RADIUS))])
+            .start();
+        harness
+            .expect_agent_reaches(player, PLAYER_RADIUS + PURSUE_AI_AGENT_RADIUS + 4.0, 5.0)
+            .unwrap();
+    }
+
+    #[test]
+    fn replaced_agents_are_the_only_agents() {
+        let mut harness = thin_wall_harness();
+        harness
+            .replace_agents(vec![agent_at(Vec2::new(100.0, FLOOR_Y + PURSUE_AI_AGENT_RADIUS))])
+            .start();
+        let world = harness.app_mut().world_mut();
+        let agents = world
+            .query_filtered::<(), Or<(With<PlatformerAI>, With<FlyingAI>)>>()
+            .iter(world)
+            .count();
+        assert_eq!(agents, 1);
+    }
+}
@@ -0,0 +1,337 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        query::Without,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res},
+    },
+    math::{
+        ops,
+        primitives::{Circle, RegularPolygon},
+        Quat, Vec2, Vec3Swizzles,
+    },
+    mesh::{Mesh, Mesh2d},
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collisions::{resolve_level_penetration, s_sensors, Sensor},
+    game_state::GameState,
+    health::{s_respawn, Health},
+    knockback::{apply_knockback, Mass},
+    level::Level,
+    utils::{closest_point_on_segment, ping_pong_along_path},
+    KinematicBody, AI_MAX_HEALTH,
+};
+
+// Hazard rendering constants
+const CRUSHER_COLOR: Color = Color::srgb(1.0, 0.5, 0.0);
+const SAW_COLOR: Color = Color::srgb(0.7, 0.7, 0.7);
+const SAW_TEETH: u32 = 8;
+const SAW_SPIN_SPEED: f32 = 12.0; // radians/second
+const HAZARD_Z: f32 = 1.0;
+
+// Velocity change (pixels/second) given to a unit mass body when a hazard hits it
+const HAZARD_KNOCKBACK: f32 = 250.0;
+
+// How far (pixels) the level has to push a body back against a crusher before it counts as crushed
+const CRUSH_DEPTH: f32 = 2.0;
+
+fn default_hazard_damage() -> f32 {
+    1.0
+}
+
+/// A hazard placed in the level (read from level metadata, positions in world pixels)
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HazardSetting {
+    /// Piston that moves back and forth between two points and crushes bodies against geometry
+    Crusher {
+        start: [f32; 2],
+        end: [f32; 2],
+        radius: f32,
+        /// Seconds for a full out-and-back cycle
+        period: f32,
+        #[serde(default = "default_hazard_damage")]
+        damage: f32,
+    },
+    /// Saw blade that patrols back and forth along a path (usually the edge of a platform)
+    Saw {
+        points: Vec<[f32; 2]>,
+        radius: f32,
+        /// Patrol speed (pixels/second)
+        speed: f32,
+        #[serde(default = "default_hazard_damage")]
+        damage: f32,
+    },
+}
+
+impl HazardSetting {
+    /// Distance from `position` to anything the hazard can touch over its whole motion (negative
+    /// inside the hazard's reach)
+    pub fn distance_to(&self, position: Vec2) -> f32 {
+        let (points, radius) = match self {
+            HazardSetting::Crusher {
+                start, end, radius, ..
+            } => (vec![Vec2::from(*start), Vec2::from(*end)], *radius),
+            HazardSetting::Saw { points, radius, .. } => (
+                points.iter().map(|point| Vec2::from(*point)).collect(),
+                *radius,
+            ),
+        };
+
+        let closest_distance = match points.as_slice() {
+            [] => f32::MAX,
+            [point] => point.distance(position),
+            _ => points
+                .windows(2)
+                .map(|pair| closest_point_on_segment(pair[0], pair[1], position).distance(position))
+                .fold(f32::MAX, f32::min),
+        };
+
+        closest_distance - radius
+    }
+
+    /// Whether touching the hazard can kill an agent outright (crushers pin bodies against the
+    /// level, anything else only if one hit takes a full health bar)
+    pub fn is_lethal(&self) -> bool {
+        match self {
+            HazardSetting::Crusher { .. } => true,
+            HazardSetting::Saw { damage, .. } => *damage >= AI_MAX_HEALTH,
+        }
+    }
+}
+
+/// How a hazard moves
+pub enum HazardMotion {
+    Piston { start: Vec2, end: Vec2, period: f32 },
+    Patrol { points: Vec<Vec2>, speed: f32 },
+}
+
+/// Hazard component: Damages bodies overlapping its `Sensor`
+#[derive(Component)]
+pub struct Hazard {
+    /// Damage dealt on contact
+    pub damage: f32,
+    pub motion: HazardMotion,
+    /// Whether the hazard pushes bodies out of itself (and so can crush them against geometry)
+    pub solid: bool,
+    /// Seconds since the hazard was spawned (drives the motion)
+    pub elapsed: f32,
+}
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_move_hazards.before(s_sensors));
+        app.add_systems(
+            Update,
+            s_hazard_contacts
+                .after(s_sensors)
+                .before(s_respawn)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Spawns an entity for every hazard in the level metadata
+pub fn spawn_hazards(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    level: &Level,
+) {
+    for setting in &level.metadata.hazards {
+        let (hazard, radius, mesh, color) = match setting {
+            HazardSetting::Crusher {
+                start,
+                end,
+                radius,
+                period,
+                damage,
+            } => (
+                Hazard {
+                    damage: *damage,
+                    motion: HazardMotion::Piston {
+                        start: Vec2::from(*start),
+                        end: Vec2::from(*end),
+                        period: *period,
+                    },
+                    solid: true,
+                    elapsed: 0.0,
+                },
+                *radius,
+                meshes.add(Circle::new(*radius)),
+                CRUSHER_COLOR,
+            ),
+            HazardSetting::Saw {
+                points,
+                radius,
+                speed,
+                damage,
+            } => (
+                Hazard {
+                    damage: *damage,
+                    motion: HazardMotion::Patrol {
+                        points: points.iter().map(|point| Vec2::from(*point)).collect(),
+                        speed: *speed,
+                    },
+                    solid: false,
+                    elapsed: 0.0,
+                },
+                *radius,
+                meshes.add(RegularPolygon::new(*radius, SAW_TEETH)),
+                SAW_COLOR,
+            ),
+        };
+
+        let position = hazard.position();
+
+        commands.spawn((
+            Transform::from_translation(position.extend(HAZARD_Z)),
+            Sensor::new(radius),
+            Mesh2d(mesh),
+            MeshMaterial2d(materials.add(color)),
+            hazard,
+        ));
+    }
+}
+
+impl Hazard {
+    /// Position of the hazard along its motion at the current time
+    pub fn position(&self) -> Vec2 {
+        match &self.motion {
+            HazardMotion::Piston { start, end, period } => {
+                // Ease in and out at both ends of the stroke
+                let t = 0.5 - 0.5 * ops::cos(self.elapsed / period.max(f32::EPSILON) * TAU);
+                start.lerp(*end, t)
+            }
+            HazardMotion::Patrol { points, speed } => {
+                ping_pong_along_path(points, self.elapsed * speed)
+            }
+        }
+    }
+
+    /// Where a piston reached the far end of its stroke during the last `dt` seconds, if it did
+    pub fn slam_position(&self, dt: f32) -> Option<Vec2> {
+        let HazardMotion::Piston { end, period, .. } = &self.motion else {
+            return None;
+        };
+
+        // Counts the strokes completed, ticking over halfway through each cycle
+        let strokes = |elapsed: f32| (elapsed / period.max(f32::EPSILON) - 0.5).floor();
+        (strokes(self.elapsed) > strokes(self.elapsed - dt)).then_some(*end)
+    }
+}
+
+/// Hazard motion system: Animates every hazard along its motion
+pub fn s_move_hazards(time: Res<Time>, mut hazard_query: Query<(&mut Transform, &mut Hazard)>) {
+    let dt = time.delta_secs();
+
+    for (mut transform, mut hazard) in hazard_query.iter_mut() {
+        hazard.elapsed += dt;
+
+        transform.translation = hazard.position().extend(transform.translation.z);
+
+        if let HazardMotion::Patrol { .. } = hazard.motion {
+            transform.rotation = Quat::from_rotation_z(-hazard.elapsed * SAW_SPIN_SPEED);
+        }
+    }
+}
+
+/// Hazard contact system: Damages and knocks back bodies touching a hazard, pushes them out of
+/// solid hazards and kills them if the level pushes back (the body is crushed between the hazard
+/// and the geometry)
+#[allow(clippy::type_complexity)]
+pub fn s_hazard_contacts(
+    hazard_query: Query<(&Transform, &Sensor, &Hazard)>,
+    mut physics_query: Query<
+        (&mut Transform, &mut KinematicBody, &mut Health, Option<&Mass>),
+        Without<Sensor>,
+    >,
+    level: Res<Level>,
+) {
+    for (hazard_transform, sensor, hazard) in hazard_query.iter() {
+        let hazard_pos = hazard_transform.translation.xy();
+
+        for entity in &sensor.overlapping_entities {
+            if let Ok((mut transform, mut physics, mut health, mass)) =
+                physics_query.get_mut(*entity)
+            {
+                if health.damage(hazard.damage) {
+                    let impulse = knockback_impulse(hazard_pos, transform.translation.xy());
+                    apply_knockback(&mut physics.velocity, impulse, mass);
+                }
+
+                if hazard.solid {
+                    let prev_position = physics.prev_position;
+                    let radius = physics.radius;
+                    let (position, crushed) = push_out_of_hazard(
+                        &level,
+                        hazard_pos,
+                        sensor.radius,
+                        transform.translation.xy(),
+                        prev_position,
+                        radius,
+                        &mut physics.velocity,
+                    );
+
+                    transform.translation = position.extend(transform.translation.z);
+                    if crushed {
+                        health.kill();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Knockback away from a hazard's center (straight up for bodies exactly at the center)
+fn knockback_impulse(hazard_pos: Vec2, position: Vec2) -> Vec2 {
+    (position - hazard_pos).try_normalize().unwrap_or(Vec2::Y) * HAZARD_KNOCKBACK
+}
+
+/// Pushes a body out of a solid hazard, then back out of the level.
+///
+/// If the level correction opposes the hazard's push by more than `CRUSH_DEPTH`, the body is
+/// pinned between the two and is reported as crushed. Returns the corrected position.
+fn push_out_of_hazard(
+    level: &Level,
+    hazard_pos: Vec2,
+    hazard_radius: f32,
+    position: Vec2,
+    prev_position: Vec2,
+    radius: f32,
+    velocity: &mut Vec2,
+) -> (Vec2, bool) {
+    let offset = position - hazard_pos;
+    let penetration = hazard_radius + radius - offset.length();
+    if penetration <= 0.0 {
+        return (position, false);
+    }
+
+    // Bodies exactly at the hazard's center get pushed up
+    let push_dir = offset.try_normalize().unwrap_or(Vec2::Y);
+    let pushed = position + push_dir * penetration;
+
+    // Don't let the body keep moving into the hazard
+    let into_hazard = velocity.dot(-push_dir);
+    if into_hazard > 0.0 {
+        *velocity += push_dir * into_hazard;
+    }
+
+    let resolved = resolve_level_penetration(level, pushed, prev_position, radius, velocity);
+    let crushed = (resolved - pushed).dot(push_dir) < -CRUSH_DEPTH;
+
+    (resolved, crushed)
+}
@@ -0,0 +1,113 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        message::{Message, MessageWriter},
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    math::Vec2,
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::KinematicBody;
+
+// Time after taking damage during which further damage is ignored (units: seconds)
+pub const INVULNERABILITY_TIME: f32 = 1.0;
+
+/// Health component: Hit points of an entity that can be damaged or killed
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    /// Time remaining (seconds) before the entity can be damaged again
+    pub invulnerable_timer: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            invulnerable_timer: 0.0,
+        }
+    }
+
+    /// Deals damage unless the entity is still invulnerable from the last hit.
+    ///
+    /// Returns whether the hit landed.
+    pub fn damage(&mut self, amount: f32) -> bool {
+        if self.invulnerable_timer > 0.0 || self.is_dead() {
+            return false;
+        }
+
+        self.current = (self.current - amount).max(0.0);
+        self.invulnerable_timer = INVULNERABILITY_TIME;
+        true
+    }
+
+    /// Kills the entity regardless of invulnerability
+    pub fn kill(&mut self) {
+        self.current = 0.0;
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// Spawn point component: Where an entity is moved back to when it dies
+#[derive(Component)]
+pub struct SpawnPoint(pub Vec2);
+
+/// Sent when an entity dies, just before it is moved back to its spawn point
+#[derive(Message, Clone, Copy)]
+pub struct Died {
+    pub entity: Entity,
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<Died>();
+        app.add_systems(Update, s_health_timers);
+        app.add_systems(Update, s_respawn.after(s_health_timers));
+    }
+}
+
+/// Health timer system: Counts down invulnerability after a hit
+pub fn s_health_timers(time: Res<Time>, mut health_query: Query<&mut Health>) {
+    let dt = time.delta_secs();
+
+    for mut health in health_query.iter_mut() {
+        if health.invulnerable_timer > 0.0 {
+            health.invulnerable_timer = (health.invulnerable_timer - dt).max(0.0);
+        }
+    }
+}
+
+/// Respawn system: Moves dead entities back to their spawn point with full health
+pub fn s_respawn(
+    mut physics_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut KinematicBody,
+        &mut Health,
+        &SpawnPoint,
+    )>,
+    mut died: MessageWriter<Died>,
+) {
+    for (entity, mut transform, mut physics, mut health, spawn_point) in physics_query.iter_mut() {
+        if health.is_dead() {
+            died.write(Died { entity });
+
+            transform.translation = spawn_point.0.extend(transform.translation.z);
+            physics.prev_position = spawn_point.0;
+            physics.velocity = Vec2::ZERO;
+            *health = Health::new(health.max);
+        }
+    }
+}
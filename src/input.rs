@@ -0,0 +1,498 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{
+    app::{App, Plugin, PostUpdate, PreUpdate},
+    ecs::{
+        entity::Entity,
+        message::{Message, MessageReader, MessageWriter},
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res, ResMut},
+    },
+    input::{
+        gamepad::{Gamepad, GamepadButton, GamepadRumbleIntensity, GamepadRumbleRequest},
+        keyboard::KeyCode,
+        ButtonInput, InputSystems,
+    },
+    math::Vec2,
+    prelude::Resource,
+    time::Time,
+};
+use serde::Deserialize;
+
+use crate::{camera::CameraControls, settings::Settings};
+
+const KEY_BINDINGS_PATH: &str = "assets/keybindings.json";
+
+// Stick deflection below this is treated as no input (fraction of full deflection)
+pub const STICK_DEADZONE: f32 = 0.2;
+
+// How often the rumble sent to the gamepads is updated to follow the effects' curves (units:
+// seconds)
+const RUMBLE_UPDATE_INTERVAL: f32 = 0.05;
+
+/// Player intent for the current frame, gathered from the keyboard and every connected gamepad
+/// (only the first gamepad while a second player is playing, see `PlayerTwoInput`)
+#[derive(Resource, Default)]
+pub struct InputAction {
+    /// Movement direction with analog magnitude (length at most 1)
+    pub move_dir: Vec2,
+    pub jump_pressed: bool,
+    pub jump_released: bool,
+    pub dash_pressed: bool,
+    pub switch_character_pressed: bool,
+    pub exit: bool,
+}
+
+/// The second local player's intent for the current frame, gathered from their keys and the
+/// gamepads after the first (only while `Settings::two_player` is on; the switch and exit
+/// actions are left to the first player)
+#[derive(Resource, Default)]
+pub struct PlayerTwoInput(pub InputAction);
+
+/// Actions that keyboard keys can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Dash,
+    PlayerTwoMoveLeft,
+    PlayerTwoMoveRight,
+    PlayerTwoMoveUp,
+    PlayerTwoMoveDown,
+    PlayerTwoJump,
+    PlayerTwoDash,
+    SwitchCharacter,
+    ToggleGizmos,
+    Exit,
+}
+
+/// Keyboard keys bound to each action, loaded from `assets/keybindings.json` at startup
+/// Actions missing from the file keep their default keys
+#[derive(Resource, Clone)]
+pub struct KeyBindings {
+    pub bindings: HashMap<KeyAction, Vec<KeyCode>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: HashMap::from([
+                (KeyAction::MoveLeft, vec![KeyCode::ArrowLeft]),
+                (KeyAction::MoveRight, vec![KeyCode::ArrowRight]),
+                (KeyAction::MoveUp, vec![KeyCode::ArrowUp]),
+                (KeyAction::MoveDown, vec![KeyCode::ArrowDown]),
+                (KeyAction::Jump, vec![KeyCode::Space]),
+                (KeyAction::Dash, vec![KeyCode::ShiftLeft]),
+                (KeyAction::PlayerTwoMoveLeft, vec![KeyCode::KeyA]),
+                (KeyAction::PlayerTwoMoveRight, vec![KeyCode::KeyD]),
+                (KeyAction::PlayerTwoMoveUp, vec![KeyCode::KeyW]),
+                (KeyAction::PlayerTwoMoveDown, vec![KeyCode::KeyS]),
+                (KeyAction::PlayerTwoJump, vec![KeyCode::KeyF]),
+                (KeyAction::PlayerTwoDash, vec![KeyCode::KeyC]),
+                (KeyAction::SwitchCharacter, vec![KeyCode::KeyQ]),
+                (KeyAction::ToggleGizmos, vec![KeyCode::KeyG]),
+                (KeyAction::Exit, vec![KeyCode::Escape]),
+            ]),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn load() -> Self {
+        let mut key_bindings = KeyBindings::default();
+
+        let Ok(contents) = std::fs::read_to_string(KEY_BINDINGS_PATH) else {
+            return key_bindings;
+        };
+
+        match serde_json::from_str::<HashMap<KeyAction, Vec<KeyCode>>>(&contents) {
+            Ok(bindings) => key_bindings.bindings.extend(bindings),
+            Err(error) => eprintln!("Failed to parse {KEY_BINDINGS_PATH}, using defaults: {error}"),
+        }
+
+        key_bindings
+    }
+
+    fn keys(&self, action: KeyAction) -> &[KeyCode] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn pressed(&self, action: KeyAction, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        keyboard_input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_pressed(&self, action: KeyAction, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        keyboard_input.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_released(&self, action: KeyAction, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        keyboard_input.any_just_released(self.keys(action).iter().copied())
+    }
+}
+
+/// How a rumble effect fades over its duration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RumbleCurve {
+    /// Full intensity until the end
+    Constant,
+    /// Fades linearly to nothing
+    Linear,
+    /// Fades quickly at first, then tails off (a sharp hit)
+    EaseOut,
+}
+
+impl RumbleCurve {
+    /// Intensity multiplier at `t` (0 at the start of the effect, 1 at the end)
+    fn falloff(self, t: f32) -> f32 {
+        let remaining = (1.0 - t).clamp(0.0, 1.0);
+        match self {
+            RumbleCurve::Constant => 1.0,
+            RumbleCurve::Linear => remaining,
+            RumbleCurve::EaseOut => remaining * remaining,
+        }
+    }
+}
+
+/// A gamepad rumble: how hard each motor starts, how long it lasts and how it fades
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RumbleEffect {
+    /// Starting intensity of the strong (low-frequency) motor (0 to 1)
+    pub strong: f32,
+    /// Starting intensity of the weak (high-frequency) motor (0 to 1)
+    pub weak: f32,
+    /// Seconds
+    pub duration: f32,
+    pub curve: RumbleCurve,
+}
+
+impl RumbleEffect {
+    /// The same effect with both motors' intensity multiplied by `scale`
+    pub fn scaled(self, scale: f32) -> Self {
+        Self {
+            strong: self.strong * scale,
+            weak: self.weak * scale,
+            ..self
+        }
+    }
+
+    /// Motor intensities `elapsed` seconds into the effect
+    fn intensity_at(&self, elapsed: f32) -> (f32, f32) {
+        let falloff = self.curve.falloff(elapsed / self.duration.max(f32::EPSILON));
+        (self.strong * falloff, self.weak * falloff)
+    }
+}
+
+/// One of the local players
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LocalPlayer {
+    #[default]
+    One,
+    /// The second player (see `PlayerTwoInput`)
+    Two,
+}
+
+impl LocalPlayer {
+    /// The player controlling a character, from whether it is the second player's
+    pub fn of(player_two: bool) -> Self {
+        if player_two {
+            LocalPlayer::Two
+        } else {
+            LocalPlayer::One
+        }
+    }
+}
+
+/// Sent by gameplay systems to rumble the gamepads of one player (every gamepad driving them, see
+/// `s_read_input_actions`)
+#[derive(Message, Clone, Copy, Debug)]
+pub struct Rumble {
+    pub player: LocalPlayer,
+    pub effect: RumbleEffect,
+}
+
+/// Rumble effects currently playing for one player, mixed together and sent to their gamepads by
+/// `s_play_rumble`
+#[derive(Default)]
+struct PlayerRumbles {
+    /// Each effect with the seconds since it started
+    effects: Vec<(RumbleEffect, f32)>,
+    /// Motor intensities last sent to the gamepads
+    sent: (f32, f32),
+    /// Seconds until the intensities are sent again
+    update_timer: f32,
+}
+
+/// Rumble effects currently playing, for each player
+#[derive(Resource, Default)]
+pub struct ActiveRumbles {
+    player_one: PlayerRumbles,
+    player_two: PlayerRumbles,
+}
+
+pub struct InputActionPlugin;
+
+impl Plugin for InputActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(KeyBindings::load());
+        app.init_resource::<InputAction>();
+        app.init_resource::<PlayerTwoInput>();
+        app.init_resource::<ActiveRumbles>();
+        app.add_message::<Rumble>();
+        app.add_systems(PreUpdate, s_read_input_actions.after(InputSystems));
+        app.add_systems(PostUpdate, s_play_rumble);
+    }
+}
+
+/// Applies a radial deadzone and rescales the rest of the stick's range back to 0..1 so small
+/// deflections still give slow movement
+pub fn apply_stick_deadzone(stick: Vec2) -> Vec2 {
+    let magnitude = stick.length();
+
+    if magnitude < STICK_DEADZONE {
+        return Vec2::ZERO;
+    }
+
+    let scaled = ((magnitude - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+    stick / magnitude * scaled
+}
+
+/// The actions one player moves, jumps and dashes with
+struct PlayerKeys {
+    left: KeyAction,
+    right: KeyAction,
+    up: KeyAction,
+    down: KeyAction,
+    jump: KeyAction,
+    dash: KeyAction,
+}
+
+const PLAYER_ONE_KEYS: PlayerKeys = PlayerKeys {
+    left: KeyAction::MoveLeft,
+    right: KeyAction::MoveRight,
+    up: KeyAction::MoveUp,
+    down: KeyAction::MoveDown,
+    jump: KeyAction::Jump,
+    dash: KeyAction::Dash,
+};
+
+const PLAYER_TWO_KEYS: PlayerKeys = PlayerKeys {
+    left: KeyAction::PlayerTwoMoveLeft,
+    right: KeyAction::PlayerTwoMoveRight,
+    up: KeyAction::PlayerTwoMoveUp,
+    down: KeyAction::PlayerTwoMoveDown,
+    jump: KeyAction::PlayerTwoJump,
+    dash: KeyAction::PlayerTwoDash,
+};
+
+/// How many of the connected gamepads (sorted by entity) are the first player's: the first while a
+/// second player is playing (the others go to the second), all of them otherwise
+fn player_one_gamepad_count(gamepads: usize, settings: &Settings) -> usize {
+    if settings.two_player {
+        gamepads.min(1)
+    } else {
+        gamepads
+    }
+}
+
+/// Input action system: Maps bound keyboard keys and gamepad sticks/buttons onto `InputAction`,
+/// and onto `PlayerTwoInput` while a second player is playing (see `player_one_gamepad_count`).
+/// The second player is held still while the free-fly camera is on, since it flies with the same
+/// keys.
+pub fn s_read_input_actions(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    settings: Res<Settings>,
+    camera_controls: Option<Res<CameraControls>>,
+    gamepad_query: Query<(Entity, &Gamepad)>,
+    mut input_action: ResMut<InputAction>,
+    mut player_two_input: ResMut<PlayerTwoInput>,
+) {
+    let mut gamepads: Vec<_> = gamepad_query.iter().collect();
+    gamepads.sort_unstable_by_key(|(entity, _)| *entity);
+    let split = player_one_gamepad_count(gamepads.len(), &settings);
+    let (player_one_gamepads, player_two_gamepads) = gamepads.split_at(split);
+
+    read_player_input(
+        &PLAYER_ONE_KEYS,
+        &keyboard_input,
+        &key_bindings,
+        player_one_gamepads,
+        &mut input_action,
+    );
+    input_action.switch_character_pressed =
+        key_bindings.just_pressed(KeyAction::SwitchCharacter, &keyboard_input)
+            || player_one_gamepads
+                .iter()
+                .any(|(_, gamepad)| gamepad.just_pressed(GamepadButton::North));
+    input_action.exit = key_bindings.just_pressed(KeyAction::Exit, &keyboard_input);
+
+    let free_fly = camera_controls.is_some_and(|camera_controls| camera_controls.free_fly);
+    if settings.two_player && free_fly {
+        player_two_input.0 = InputAction::default();
+    } else if settings.two_player {
+        read_player_input(
+            &PLAYER_TWO_KEYS,
+            &keyboard_input,
+            &key_bindings,
+            player_two_gamepads,
+            &mut player_two_input.0,
+        );
+    }
+}
+
+/// Fills in one player's movement, jump and dash from their keys and gamepads
+fn read_player_input(
+    keys: &PlayerKeys,
+    keyboard_input: &ButtonInput<KeyCode>,
+    key_bindings: &KeyBindings,
+    gamepads: &[(Entity, &Gamepad)],
+    input_action: &mut InputAction,
+) {
+    // Keys and the d-pad are digital, so they always move at full speed
+    let mut digital = Vec2::ZERO;
+    if key_bindings.pressed(keys.up, keyboard_input) {
+        digital.y += 1.0;
+    }
+    if key_bindings.pressed(keys.down, keyboard_input) {
+        digital.y -= 1.0;
+    }
+    if key_bindings.pressed(keys.left, keyboard_input) {
+        digital.x -= 1.0;
+    }
+    if key_bindings.pressed(keys.right, keyboard_input) {
+        digital.x += 1.0;
+    }
+
+    let mut analog = Vec2::ZERO;
+    let mut jump_pressed = key_bindings.just_pressed(keys.jump, keyboard_input);
+    let mut jump_released = key_bindings.just_released(keys.jump, keyboard_input);
+    let mut dash_pressed = key_bindings.just_pressed(keys.dash, keyboard_input);
+
+    for (_, gamepad) in gamepads {
+        digital += gamepad.dpad();
+
+        // Keep the strongest stick if several gamepads are connected
+        let stick = apply_stick_deadzone(gamepad.left_stick());
+        if stick.length_squared() > analog.length_squared() {
+            analog = stick;
+        }
+
+        jump_pressed |= gamepad.just_pressed(GamepadButton::South);
+        jump_released |= gamepad.just_released(GamepadButton::South);
+        dash_pressed |= gamepad.just_pressed(GamepadButton::West);
+    }
+
+    // Digital input wins over the stick when both are held
+    input_action.move_dir = if digital != Vec2::ZERO {
+        digital.normalize()
+    } else {
+        analog
+    };
+    input_action.jump_pressed = jump_pressed;
+    input_action.jump_released = jump_released;
+    input_action.dash_pressed = dash_pressed;
+}
+
+/// Rumble system: Mixes each player's playing rumble effects along their curves and sends the
+/// result to that player's gamepads (nothing rumbles while rumble is turned off in the settings)
+pub fn s_play_rumble(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut rumbles: MessageReader<Rumble>,
+    mut active: ResMut<ActiveRumbles>,
+    gamepad_query: Query<Entity, With<Gamepad>>,
+    mut requests: MessageWriter<GamepadRumbleRequest>,
+) {
+    let dt = time.delta_secs();
+
+    let mut gamepads: Vec<_> = gamepad_query.iter().collect();
+    gamepads.sort_unstable();
+    let split = player_one_gamepad_count(gamepads.len(), &settings);
+    let (player_one_gamepads, player_two_gamepads) = gamepads.split_at(split);
+
+    let mut started: Vec<Rumble> = Vec::new();
+    if settings.rumble {
+        started.extend(rumbles.read().copied());
+    } else {
+        rumbles.clear();
+    }
+
+    let active = &mut *active;
+    for (player, rumbles, gamepads) in [
+        (LocalPlayer::One, &mut active.player_one, player_one_gamepads),
+        (LocalPlayer::Two, &mut active.player_two, player_two_gamepads),
+    ] {
+        let new_effects = started
+            .iter()
+            .filter(|rumble| rumble.player == player)
+            .map(|rumble| rumble.effect);
+        rumbles.play(new_effects, dt, settings.rumble, gamepads, &mut requests);
+    }
+}
+
+impl PlayerRumbles {
+    /// Adds the new effects, advances the playing ones by `dt` and sends the mix to `gamepads`
+    /// when it needs updating
+    fn play(
+        &mut self,
+        new_effects: impl Iterator<Item = RumbleEffect>,
+        dt: f32,
+        enabled: bool,
+        gamepads: &[Entity],
+        requests: &mut MessageWriter<GamepadRumbleRequest>,
+    ) {
+        // New effects start one frame back so they are at zero once this frame's time is added
+        let playing = self.effects.len();
+        if enabled {
+            self.effects.extend(new_effects.map(|effect| (effect, -dt)));
+        } else {
+            self.effects.clear();
+        }
+        let started = self.effects.len() > playing;
+
+        for (_, elapsed) in self.effects.iter_mut() {
+            *elapsed += dt;
+        }
+        self.effects
+            .retain(|(effect, elapsed)| *elapsed < effect.duration);
+
+        // Overlapping effects add up, like they would on the motors themselves
+        let (strong, weak) = self
+            .effects
+            .iter()
+            .map(|(effect, elapsed)| effect.intensity_at(*elapsed))
+            .fold((0.0, 0.0), |(strong, weak), (s, w)| (strong + s, weak + w));
+        let intensity = (strong.min(1.0), weak.min(1.0));
+
+        // New effects and stopping can't wait for the next update
+        let stopping = self.effects.is_empty() && self.sent != (0.0, 0.0);
+        self.update_timer -= dt;
+        let due = !self.effects.is_empty() && self.update_timer <= 0.0;
+        if !(started || stopping || due) {
+            return;
+        }
+        self.update_timer = RUMBLE_UPDATE_INTERVAL;
+        self.sent = intensity;
+
+        for &gamepad in gamepads {
+            // Replace whatever is playing rather than adding to it
+            requests.write(GamepadRumbleRequest::Stop { gamepad });
+            if !self.effects.is_empty() {
+                requests.write(GamepadRumbleRequest::Add {
+                    // Outlasts the next update, so the motors don't drop out between requests
+                    duration: Duration::from_secs_f32(RUMBLE_UPDATE_INTERVAL * 2.0),
+                    intensity: GamepadRumbleIntensity {
+                        strong_motor: intensity.0,
+                        weak_motor: intensity.1,
+                    },
+                    gamepad,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use bevy::math::{ops, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::movement_config::MovementConfig;
+
+// Command-line flags
+pub const COMPARE_INTEGRATORS_FLAG: &str = "--compare-integrators";
+const COMPARISON_OUTPUT_FLAG: &str = "--comparison-output";
+const DEFAULT_COMPARISON_OUTPUT: &str = "integrator_report.json";
+
+// Frame rates (Hz) every integrator is run at in the comparison
+const COMPARISON_FRAME_RATES: [f32; 4] = [30.0, 60.0, 144.0, 240.0];
+// Length of the compared trajectory (units: seconds)
+const COMPARISON_DURATION: f32 = 1.0;
+
+/// How the movement systems advance velocity and position each frame
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Velocity first, then position with the new velocity (first order, the default)
+    #[default]
+    SemiImplicitEuler,
+    /// Position from the start-of-frame acceleration, velocity from the average of the start and
+    /// end accelerations (second order)
+    VelocityVerlet,
+    /// Midpoint method: the whole step uses the state halfway through the frame (second order)
+    Rk2,
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 3] = [
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Rk2,
+    ];
+
+    /// Advances a body by one frame and returns its new position and velocity.
+    ///
+    /// `acceleration` is the acceleration at the start of the frame (computed before impulses
+    /// like gravity and jumps were added to `velocity`) and `acceleration_at` re-evaluates it for
+    /// another velocity, which the higher order methods sample partway through the frame.
+    pub fn step(
+        self,
+        position: Vec2,
+        velocity: Vec2,
+        acceleration: Vec2,
+        acceleration_at: impl Fn(Vec2) -> Vec2,
+        dt: f32,
+    ) -> (Vec2, Vec2) {
+        match self {
+            Integrator::SemiImplicitEuler => {
+                let velocity = velocity + acceleration * dt;
+                (position + velocity * dt, velocity)
+            }
+            Integrator::VelocityVerlet => {
+                let position = position + velocity * dt + acceleration * (0.5 * dt * dt);
+                let end_acceleration = acceleration_at(velocity + acceleration * dt);
+                let velocity = velocity + (acceleration + end_acceleration) * (0.5 * dt);
+                (position, velocity)
+            }
+            Integrator::Rk2 => {
+                let mid_velocity = velocity + acceleration * (0.5 * dt);
+                let mid_acceleration = acceleration_at(mid_velocity);
+                (position + mid_velocity * dt, velocity + mid_acceleration * dt)
+            }
+        }
+    }
+}
+
+/// How far one integrator strays from the exact trajectory at one frame rate (pixels)
+///
+/// Horizontal error comes from the integrator. Vertical error comes from gravity, which the
+/// movement systems add to velocity before integrating, so it is the same for every integrator.
+#[derive(Serialize)]
+struct TrajectoryReport {
+    integrator: Integrator,
+    frame_rate: f32,
+    frames: u32,
+    max_horizontal_error: f32,
+    max_vertical_error: f32,
+    final_position: [f32; 2],
+}
+
+#[derive(Serialize)]
+struct ComparisonReport {
+    version: &'static str,
+    duration: f32,
+    exact_final_position: [f32; 2],
+    trajectories: Vec<TrajectoryReport>,
+}
+
+/// Exact position of the reference jump `time` seconds after take-off
+fn exact_position(config: &MovementConfig, time: f32) -> Vec2 {
+    let rate = config.acceleration_scalers.0;
+    let x = config.max_speed * (time - (1.0 - ops::exp(-rate * time)) / rate);
+    let y = config.jump_velocity * time - 0.5 * config.gravity * time * time;
+    Vec2::new(x, y)
+}
+
+/// Simulates the reference jump (running right from a standstill while jumping) the way
+/// `s_movement` does, comparing every frame against the exact trajectory
+fn simulate(config: &MovementConfig, integrator: Integrator, frame_rate: f32) -> TrajectoryReport {
+    let dt = 1.0 / frame_rate;
+    let frames = (COMPARISON_DURATION * frame_rate).round() as u32;
+
+    // Airborne player holding right: only horizontal acceleration, gravity as a velocity impulse
+    let acceleration_at = |velocity: Vec2| {
+        Vec2::new(
+            (config.max_speed - velocity.x) * config.acceleration_scalers.0,
+            0.0,
+        )
+    };
+
+    let mut position = Vec2::ZERO;
+    let mut velocity = Vec2::new(0.0, config.jump_velocity);
+    let mut max_error = Vec2::ZERO;
+
+    for frame in 1..=frames {
+        let acceleration = acceleration_at(velocity);
+        velocity.y -= config.gravity * dt;
+
+        (position, velocity) =
+            integrator.step(position, velocity, acceleration, acceleration_at, dt);
+
+        max_error = max_error.max((position - exact_position(config, frame as f32 * dt)).abs());
+    }
+
+    TrajectoryReport {
+        integrator,
+        frame_rate,
+        frames,
+        max_horizontal_error: max_error.x,
+        max_vertical_error: max_error.y,
+        final_position: position.to_array(),
+    }
+}
+
+/// Runs the reference jump with every integrator at every frame rate and writes the JSON report
+pub fn run_comparison() {
+    let args: Vec<String> = std::env::args().collect();
+    let output_path = args
+        .iter()
+        .position(|arg| arg == COMPARISON_OUTPUT_FLAG)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_COMPARISON_OUTPUT);
+    let config = MovementConfig::load();
+
+    let trajectories = Integrator::ALL
+        .iter()
+        .flat_map(|integrator| {
+            COMPARISON_FRAME_RATES
+                .iter()
+                .map(|frame_rate| simulate(&config, *integrator, *frame_rate))
+        })
+        .inspect(|report| {
+            println!(
+                "{:?} @ {} Hz: max error {:.4} px horizontal, {:.4} px vertical",
+                report.integrator,
+                report.frame_rate,
+                report.max_horizontal_error,
+                report.max_vertical_error
+            );
+        })
+        .collect();
+
+    let report = ComparisonReport {
+        version: env!("CARGO_PKG_VERSION"),
+        duration: COMPARISON_DURATION,
+        exact_final_position: exact_position(&config, COMPARISON_DURATION).to_array(),
+        trajectories,
+    };
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(contents) => match std::fs::write(output_path, contents) {
+            Ok(()) => println!("Integrator report written to {output_path}"),
+            Err(error) => eprintln!("Failed to write {output_path}: {error}"),
+        },
+        Err(error) => eprintln!("Failed to serialize integrator report: {error}"),
+    }
+}
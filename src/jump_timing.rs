@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    camera::visibility::Visibility,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        message::MessageReader,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    prelude::Resource,
+    text::{TextColor, TextFont},
+    time::Time,
+    ui::{widget::Text, Node, PositionType, Val},
+};
+
+use crate::{
+    characters::ActiveCharacter,
+    collisions::{s_collision, s_player_contacts},
+    debug::JUMP_TIMING_KEY,
+    input::InputAction,
+    movement_config::MovementConfig,
+    s_movement, ControllerAction, ControllerEvent, GizmosVisible, KinematicBody,
+};
+
+// Jumps listed in the overlay, newest first
+const JUMP_HISTORY_LENGTH: usize = 8;
+
+// Overlay layout (units: pixels)
+const OVERLAY_FONT_SIZE: f32 = 14.0;
+const OVERLAY_MARGIN: f32 = 16.0;
+const OVERLAY_COLOR: Color = Color::srgb(1.0, 1.0, 0.6);
+
+/// How one jump was timed
+struct JumpTiming {
+    kind: ControllerAction,
+    /// Frames from the jump press to the frame the jump velocity was applied
+    velocity_frames: u64,
+    /// Seconds from the jump press to the jump velocity being applied (the time the jump buffer
+    /// held the press, if more than a frame)
+    velocity_delay: f32,
+    /// Frames from the jump press to the player leaving the surface (`None` until they do)
+    liftoff_frames: Option<u64>,
+    /// Seconds since the player last touched the ground, for ground jumps made after walking off
+    /// a ledge
+    coyote_time: Option<f32>,
+    /// Frame number of the press
+    pressed_frame: u64,
+}
+
+/// Jump timing resource: Recent jumps and the press waiting to become one
+#[derive(Resource, Default)]
+pub struct JumpTimingLog {
+    /// Whether the overlay is shown (while gizmos are visible)
+    pub visible: bool,
+    /// Frames counted since startup
+    frame: u64,
+    /// Frame number and elapsed time (seconds) of the latest jump press not yet used
+    pending_press: Option<(u64, f32)>,
+    /// Elapsed time (seconds) the player last touched the ground
+    last_grounded_at: f32,
+    jumps: VecDeque<JumpTiming>,
+}
+
+/// Marker for the jump timing overlay text
+#[derive(Component)]
+struct JumpTimingText;
+
+/// Jump timing plugin: Debug tool measuring the delay from a jump press to the jump, and showing
+/// whether recent jumps relied on coyote time or the jump buffer
+pub struct JumpTimingPlugin;
+
+impl Plugin for JumpTimingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<JumpTimingLog>();
+
+        app.add_systems(Startup, s_spawn_jump_timing_text);
+        app.add_systems(
+            Update,
+            (
+                // Contacts are still last frame's between movement and collision, so a jump off
+                // a ledge can be told apart from one off the ground
+                s_measure_jump_timing.after(s_movement).before(s_collision),
+                s_measure_liftoff.after(s_player_contacts),
+                s_update_jump_timing_text.after(s_measure_liftoff),
+            ),
+        );
+    }
+}
+
+/// Spawns the (hidden) jump timing overlay in the top-right corner of the window
+fn s_spawn_jump_timing_text(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: OVERLAY_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(OVERLAY_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(OVERLAY_MARGIN),
+            right: Val::Px(OVERLAY_MARGIN),
+            ..Default::default()
+        },
+        Visibility::Hidden,
+        JumpTimingText,
+    ));
+}
+
+/// Jump timing system: Remembers jump presses and times the jumps they turn into
+fn s_measure_jump_timing(
+    input_action: Res<InputAction>,
+    mut controller_events: MessageReader<ControllerEvent>,
+    player_query: Query<(Entity, &KinematicBody), With<ActiveCharacter>>,
+    mut log: ResMut<JumpTimingLog>,
+    movement_config: Res<MovementConfig>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    log.frame += 1;
+    let frame = log.frame;
+
+    if input_action.jump_pressed {
+        log.pending_press = Some((frame, now));
+    }
+
+    // Presses the jump buffer has given up on
+    if log
+        .pending_press
+        .is_some_and(|(_, pressed_at)| now - pressed_at > movement_config.jump_buffer_time)
+    {
+        log.pending_press = None;
+    }
+
+    let player = player_query.single().ok();
+    let on_ground = player.is_some_and(|(_, body)| body.on_ground());
+
+    for event in controller_events.read() {
+        let by_player = player.is_some_and(|(player, _)| player == event.entity);
+        if !by_player || event.action == ControllerAction::Dash {
+            continue;
+        }
+        let Some((pressed_frame, pressed_at)) = log.pending_press.take() else {
+            continue;
+        };
+
+        let coyote_time = (event.action == ControllerAction::Jump && !on_ground)
+            .then(|| now - log.last_grounded_at);
+        let timing = JumpTiming {
+            kind: event.action,
+            velocity_frames: frame - pressed_frame,
+            velocity_delay: now - pressed_at,
+            liftoff_frames: None,
+            coyote_time,
+            pressed_frame,
+        };
+
+        log.jumps.push_front(timing);
+        log.jumps.truncate(JUMP_HISTORY_LENGTH);
+    }
+
+    if on_ground {
+        log.last_grounded_at = now;
+    }
+}
+
+/// Liftoff system: Counts the frames until the player actually leaves the surface after the
+/// latest jump
+fn s_measure_liftoff(
+    player_query: Query<&KinematicBody, With<ActiveCharacter>>,
+    mut log: ResMut<JumpTimingLog>,
+) {
+    let Ok(physics) = player_query.single() else {
+        return;
+    };
+    // Wall jumps push away from the wall, so any contact left counts as not lifted off
+    if !physics.contacts.is_empty() {
+        return;
+    }
+
+    let frame = log.frame;
+    if let Some(jump) = log.jumps.front_mut() {
+        if jump.liftoff_frames.is_none() {
+            jump.liftoff_frames = Some(frame - jump.pressed_frame);
+        }
+    }
+}
+
+/// Jump timing overlay system: Toggles the overlay and lists the recent jumps in it
+fn s_update_jump_timing_text(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    mut log: ResMut<JumpTimingLog>,
+    movement_config: Res<MovementConfig>,
+    time: Res<Time>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<JumpTimingText>>,
+) {
+    if gizmos_visible.visible && keyboard_input.just_pressed(JUMP_TIMING_KEY) {
+        log.visible = !log.visible;
+    }
+
+    let shown = gizmos_visible.visible && log.visible;
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        *visibility = if shown {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+        if shown {
+            text.0 = jump_timing_report(&log, &movement_config, time.delta_secs());
+        }
+    }
+}
+
+/// Overlay text: one line per recent jump, with the windows it could have used for comparison
+fn jump_timing_report(
+    log: &JumpTimingLog,
+    movement_config: &MovementConfig,
+    frame_time: f32,
+) -> String {
+    let mut lines = vec![format!(
+        "Jump timing ({JUMP_TIMING_KEY:?} to hide), frame {:.1} ms, buffer {:.0} ms, coyote \
+         {:.0} ms",
+        frame_time * 1000.0,
+        movement_config.jump_buffer_time * 1000.0,
+        movement_config.coyote_time * 1000.0,
+    )];
+
+    for jump in &log.jumps {
+        let liftoff = jump
+            .liftoff_frames
+            .map_or("-".to_string(), |frames| frames.to_string());
+
+        // The press only waits in the buffer if the jump couldn't happen on the frame it came in
+        let mut windows = Vec::new();
+        if jump.velocity_frames > 0 {
+            windows.push(format!("buffered {:.0} ms", jump.velocity_delay * 1000.0));
+        }
+        if let Some(coyote_time) = jump.coyote_time {
+            windows.push(format!("coyote {:.0} ms", coyote_time * 1000.0));
+        }
+        if windows.is_empty() {
+            windows.push("on time".to_string());
+        }
+
+        lines.push(format!(
+            "{:?}: velocity +{} f, liftoff +{liftoff} f, {}",
+            jump.kind,
+            jump.velocity_frames,
+            windows.join(", ")
+        ));
+    }
+
+    lines.join("\n")
+}
@@ -0,0 +1,24 @@
+use bevy::{ecs::component::Component, math::Vec2};
+
+/// Mass component: Knockback resistance of a body. Impulses change its velocity by
+/// `impulse / mass`, so heavy bodies barely budge while light ones fly
+#[derive(Component, Clone, Copy)]
+pub struct Mass(pub f32);
+
+impl Default for Mass {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl Mass {
+    /// Mass of a body, treating bodies without a `Mass` component as unit mass
+    pub fn of(mass: Option<&Mass>) -> f32 {
+        mass.copied().unwrap_or_default().0.max(f32::EPSILON)
+    }
+}
+
+/// Applies a knockback impulse (units: pixels/second for a unit mass body) to a velocity
+pub fn apply_knockback(velocity: &mut Vec2, impulse: Vec2, mass: Option<&Mass>) {
+    *velocity += impulse / Mass::of(mass);
+}
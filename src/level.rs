@@ -1,7 +1,59 @@
-use bevy::{color::Color, math::Vec2, prelude::Resource};
-use rand::Rng;
-
-use crate::utils::line_intersect;
+pub mod baked;
+pub mod materials;
+pub mod procgen;
+pub mod tiled;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{Assets, RenderAssetUsages},
+    color::{Color, Luminance},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        message::{Message, MessageWriter},
+        query::With,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{ops, URect, UVec2, Vec2},
+    mesh::{Indices, Mesh, Mesh2d, PrimitiveTopology},
+    prelude::Resource,
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    transform::components::Transform,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::ser::PrettyFormatter;
+
+use crate::{
+    ai::{
+        pathfinding::{PathfindingGraph, PathfindingGraphChanged},
+        patrol::PatrolRouteSetting,
+        profile::AIProfilePreset,
+        territory::TerritorySetting,
+    },
+    camera::CameraZoneSetting,
+    characters::CharacterSetting,
+    doors::DoorSetting,
+    encounters::EncounterSetting,
+    hazards::HazardSetting,
+    lighting::TimeOfDaySetting,
+    movement_config::MovementConfig,
+    platforms::MovingPlatformSetting,
+    utils::{cross_product, line_intersect},
+    weather::WeatherSetting,
+};
+
+#[cfg(feature = "scripting")]
+use crate::scripting::ScriptTriggerSetting;
+
+use materials::SurfaceMaterialRegion;
 
 /// Axis-aligned bounding box for spatial optimization
 #[derive(Clone, Copy)]
@@ -11,6 +63,14 @@ pub struct Aabb {
 }
 
 impl Aabb {
+    /// Create an AABB centered on the origin with the given half extents
+    pub fn from_half_size(half_size: Vec2) -> Self {
+        Self {
+            min: -half_size,
+            max: half_size,
+        }
+    }
+
     /// Create an AABB from a point and radius (for player collision checks)
     pub fn from_point_radius(center: Vec2, radius: f32) -> Self {
         Self {
@@ -27,6 +87,68 @@ impl Aabb {
             && self.max.y >= other.min.y
     }
 
+    /// Smallest AABB containing every point (zero-sized at the origin if there are none)
+    pub fn from_points(points: &[Vec2]) -> Self {
+        let Some(&first) = points.first() else {
+            return Self {
+                min: Vec2::ZERO,
+                max: Vec2::ZERO,
+            };
+        };
+
+        points.iter().skip(1).fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, &point| Self {
+                min: aabb.min.min(point),
+                max: aabb.max.max(point),
+            },
+        )
+    }
+
+    /// Check if a point lies inside this AABB
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    /// Check if the line segment from `start` to `end` passes through this AABB
+    pub fn intersects_segment(&self, start: Vec2, end: Vec2) -> bool {
+        let delta = end - start;
+        let mut t_min: f32 = 0.0;
+        let mut t_max: f32 = 1.0;
+
+        // Clip the segment against each pair of slabs
+        for axis in 0..2 {
+            if delta[axis].abs() < f32::EPSILON {
+                if start[axis] < self.min[axis] || start[axis] > self.max[axis] {
+                    return false;
+                }
+                continue;
+            }
+
+            let t1 = (self.min[axis] - start[axis]) / delta[axis];
+            let t2 = (self.max[axis] - start[axis]) / delta[axis];
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+
+            if t_min > t_max {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn area(&self) -> f32 {
+        let size = self.max - self.min;
+        size.x * size.y
+    }
+
     /// Expand AABB by a given amount in all directions
     pub fn expand(&self, amount: f32) -> Self {
         Self {
@@ -37,6 +159,8 @@ impl Aabb {
 }
 
 pub struct Polygon {
+    /// Index of the polygon in `Level::polygons` (identifies it in collision events)
+    pub id: usize,
     pub points: Vec<Vec2>,
     pub collision_side: f32,
     pub color: Color,
@@ -44,35 +168,522 @@ pub struct Polygon {
     pub aabb: Aabb,
     /// Whether this polygon is a container (boundary polygon that contains the origin)
     pub is_container: bool,
+    /// Ear-clipped triangulation of the outline, as counter-clockwise triangles of indices into
+    /// `points` (see `triangulate_polygon`)
+    pub triangles: Vec<[usize; 3]>,
+    /// Translation applied to the polygon since the level was built (moving platforms)
+    pub offset: Vec2,
+    /// Velocity the polygon moved at this frame (pixels/second, zero for static geometry)
+    pub velocity: Vec2,
+}
+
+impl Polygon {
+    /// Builds a polygon from a closed outline, working out its collision side and bounding box
+    fn new(id: usize, points: Vec<Vec2>, color: Color) -> Self {
+        Self {
+            id,
+            collision_side: calculate_winding_order(&points).signum(),
+            color,
+            aabb: Aabb::from_points(&points),
+            // Containers are the boundary polygons that contain the origin
+            is_container: point_in_polygon(&points, Vec2::ZERO),
+            triangles: triangulate_polygon(&points),
+            points,
+            offset: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+        }
+    }
+
+    /// Whether the point lies inside the outline (or on it), checked against the triangulation
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        self.aabb.contains(point)
+            && self
+                .triangle_points()
+                .any(|[a, b, c]| point_in_triangle(point, a, b, c))
+    }
+
+    /// Corners of each triangle of the triangulation, counter-clockwise
+    pub fn triangle_points(&self) -> impl Iterator<Item = [Vec2; 3]> + '_ {
+        self.triangles
+            .iter()
+            .map(|triangle| triangle.map(|index| self.points[index]))
+    }
+
+    /// Moves the polygon so it sits at `offset` from where the level placed it
+    pub fn set_offset(&mut self, offset: Vec2) {
+        let delta = offset - self.offset;
+
+        for point in &mut self.points {
+            *point += delta;
+        }
+        self.aabb.min += delta;
+        self.aabb.max += delta;
+        self.offset = offset;
+    }
 }
 
 #[derive(Resource)]
 pub struct Level {
     pub polygons: Vec<Polygon>,
     pub grid_size: f32,
+    #[allow(dead_code)]
     pub size: Vec2,
     pub half_size: Vec2,
+    pub metadata: LevelMetadata,
+    /// Uniform grid of polygon indices keyed by cell, for `query_aabb`
+    pub polygon_grid: HashMap<(i32, i32), Vec<usize>>,
+    /// Tile grid the level was built from (kept up to date by `mutate_tiles`)
+    pub tiles: Vec<Vec<u32>>,
+    /// Number of polygons (at the start of `polygons`) built from the tile grid
+    tile_polygon_count: usize,
+    /// Colors polygons built after the level was generated
+    rng: StdRng,
+    /// Geometry changes not yet picked up by `s_apply_level_changes`
+    pending_changes: Vec<LevelChanged>,
+}
+
+/// Sent after the level geometry changed, once the pathfinding graph and level meshes have been
+/// updated to match
+#[derive(Message, Clone, Copy)]
+#[allow(dead_code)]
+pub struct LevelChanged {
+    /// Area of the level whose geometry changed (world pixels)
+    pub region: Aabb,
+    /// Whether polygon ids were reassigned (tile edits rebuild every tile polygon)
+    pub renumbered: bool,
+}
+
+impl Level {
+    /// Level file contents (JSON) for the level as it is now, laid out like the bundled level: one
+    /// tile row per line, then the metadata
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let rows: Vec<String> = self
+            .tiles
+            .iter()
+            .map(|row| {
+                let tiles: Vec<String> = row.iter().map(u32::to_string).collect();
+                format!("[{}]", tiles.join(", "))
+            })
+            .collect();
+
+        let mut metadata = Vec::new();
+        let formatter = PrettyFormatter::with_indent(b"\t");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut metadata, formatter);
+        self.metadata.serialize(&mut serializer)?;
+        let metadata = String::from_utf8_lossy(&metadata).replace('\n', "\n\t");
+
+        Ok(format!(
+            "{{\n\t\"tiles\": [\n\t\t{}\n\t],\n\t\"metadata\": {metadata}\n}}\n",
+            rows.join(",\n\t\t")
+        ))
+    }
+
+    /// Polygons whose bounding boxes overlap the given AABB
+    pub fn query_aabb<'a>(&'a self, aabb: &Aabb) -> impl Iterator<Item = &'a Polygon> + 'a {
+        self.query_aabb_indices(aabb)
+            .into_iter()
+            .map(|polygon_index| &self.polygons[polygon_index])
+    }
+
+    /// Indices (into `polygons`) of the polygons whose bounding boxes overlap the given AABB,
+    /// in ascending order
+    pub fn query_aabb_indices(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut indices = Vec::new();
+
+        for cell in grid_cells(aabb) {
+            if let Some(cell_polygons) = self.polygon_grid.get(&cell) {
+                indices.extend(cell_polygons.iter().copied());
+            }
+        }
+
+        // Polygons spanning several cells are listed once per cell
+        indices.sort_unstable();
+        indices.dedup();
+        indices.retain(|&polygon_index| aabb.overlaps(&self.polygons[polygon_index].aabb));
+
+        indices
+    }
+
+    /// Whether the straight line between two points crosses no level geometry
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        !self
+            .query_aabb(&Aabb::from_points(&[from, to]))
+            .flat_map(|polygon| polygon.points.windows(2))
+            .any(|line| line_intersect(from, to, line[0], line[1]).is_some())
+    }
+
+    /// Moves a polygon to `offset` from where the level placed it, keeping the polygon grid in sync
+    pub fn set_polygon_offset(&mut self, polygon_index: usize, offset: Vec2) {
+        let Some(polygon) = self.polygons.get_mut(polygon_index) else {
+            return;
+        };
+
+        let old_aabb = polygon.aabb;
+        polygon.set_offset(offset);
+        let new_aabb = polygon.aabb;
+
+        for cell in grid_cells(&old_aabb) {
+            if let Some(cell_polygons) = self.polygon_grid.get_mut(&cell) {
+                cell_polygons.retain(|&index| index != polygon_index);
+            }
+        }
+        for cell in grid_cells(&new_aabb) {
+            self.polygon_grid.entry(cell).or_default().push(polygon_index);
+        }
+    }
+
+    /// Indices of the pockets cut into each solid polygon, by polygon index. A polygon inside
+    /// another one that winds the opposite way (see `outline_polygons`) outlines an empty pocket
+    /// in it, unless the outer one is a pocket itself: a polygon inside a pocket is solid again.
+    pub fn polygon_holes(&self) -> HashMap<usize, Vec<usize>> {
+        // Outer polygons first, so each one's parent has been sorted out before it
+        let mut order: Vec<&Polygon> = self
+            .polygons
+            .iter()
+            .filter(|polygon| !polygon.is_container && !polygon.points.is_empty())
+            .collect();
+        order.sort_by(|a, b| b.aabb.area().total_cmp(&a.aabb.area()));
+
+        let mut is_hole = vec![false; self.polygons.len()];
+        let mut holes: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (index, polygon) in order.iter().enumerate() {
+            // The smallest polygon around this one
+            let parent = order[..index].iter().rev().find(|outer| {
+                outer.aabb.contains(polygon.aabb.min)
+                    && outer.aabb.contains(polygon.aabb.max)
+                    && outer.contains_point(polygon.points[0])
+            });
+            let Some(parent) = parent else {
+                continue;
+            };
+
+            let opposite_winding =
+                signed_area(&parent.points).signum() != signed_area(&polygon.points).signum();
+            if !is_hole[parent.id] && opposite_winding {
+                is_hole[polygon.id] = true;
+                holes.entry(parent.id).or_default().push(polygon.id);
+            }
+        }
+
+        holes
+    }
+
+    /// Index of the non-container polygon containing the point
+    pub fn polygon_at(&self, point: Vec2) -> Option<usize> {
+        self.polygons
+            .iter()
+            .position(|polygon| !polygon.is_container && polygon.contains_point(point))
+    }
+
+    /// Adds a polygon to the level and returns its id.
+    ///
+    /// The outline is closed if it isn't already. The pathfinding graph and level meshes are
+    /// updated before the next `LevelChanged` message is sent.
+    #[allow(dead_code)]
+    pub fn add_polygon(&mut self, mut points: Vec<Vec2>, color: Color) -> usize {
+        if points.first() != points.last() {
+            points.push(points[0]);
+        }
+
+        let id = self.polygons.len();
+        let polygon = Polygon::new(id, points, color);
+
+        for cell in grid_cells(&polygon.aabb) {
+            self.polygon_grid.entry(cell).or_default().push(id);
+        }
+        self.pending_changes.push(LevelChanged {
+            region: polygon.aabb,
+            renumbered: false,
+        });
+        self.polygons.push(polygon);
+
+        id
+    }
+
+    /// Removes a polygon from the level.
+    ///
+    /// The polygon keeps its slot (with no points) so the ids of the other polygons don't change.
+    /// Returns false if there is no polygon with that id.
+    #[allow(dead_code)]
+    pub fn remove_polygon(&mut self, polygon_id: usize) -> bool {
+        let Some(polygon) = self.polygons.get_mut(polygon_id) else {
+            return false;
+        };
+        if polygon.points.is_empty() {
+            return false;
+        }
+
+        let old_aabb = polygon.aabb;
+        polygon.points.clear();
+        polygon.triangles.clear();
+        polygon.aabb = Aabb::from_points(&[]);
+        polygon.is_container = false;
+
+        for cell in grid_cells(&old_aabb) {
+            if let Some(cell_polygons) = self.polygon_grid.get_mut(&cell) {
+                cell_polygons.retain(|&index| index != polygon_id);
+            }
+        }
+        self.pending_changes.push(LevelChanged {
+            region: old_aabb,
+            renumbered: false,
+        });
+
+        true
+    }
+
+    /// Calls `edit` with the position and value of every tile in `rect` (tile coordinates,
+    /// clamped to the grid) and rebuilds the tile polygons from the edited grid.
+    ///
+    /// Every tile polygon is rebuilt, so tile polygon ids can change and tile polygons removed
+    /// with `remove_polygon` come back. Polygons added with `add_polygon` are kept.
+    pub fn mutate_tiles(&mut self, rect: URect, mut edit: impl FnMut(UVec2, &mut u32)) {
+        let rows = self.tiles.len() as u32;
+        let columns = self.tiles.first().map_or(0, Vec::len) as u32;
+        let max = rect.max.min(UVec2::new(columns, rows));
+
+        for y in rect.min.y..max.y {
+            for x in rect.min.x..max.x {
+                edit(UVec2::new(x, y), &mut self.tiles[y as usize][x as usize]);
+            }
+        }
+
+        // Rebuild the tile polygons, keeping added polygons after them
+        let added_polygons = self.polygons.split_off(self.tile_polygon_count);
+        self.polygons = tile_polygons(
+            &self.tiles,
+            self.grid_size,
+            self.metadata.arc_segment_count(),
+            &mut self.rng,
+        );
+        self.tile_polygon_count = self.polygons.len();
+        for mut polygon in added_polygons {
+            polygon.id = self.polygons.len();
+            self.polygons.push(polygon);
+        }
+        self.polygon_grid = build_polygon_grid(&self.polygons);
+
+        // Tile (0, 0) sits at the top left corner of the level
+        let top_left = Vec2::new(-self.half_size.x, self.half_size.y);
+        let min_corner = Vec2::new(rect.min.x as f32, -(rect.min.y as f32));
+        let max_corner = Vec2::new(max.x as f32, -(max.y as f32));
+        self.pending_changes.push(LevelChanged {
+            region: Aabb::from_points(&[
+                top_left + min_corner * self.grid_size,
+                top_left + max_corner * self.grid_size,
+            ]),
+            renumbered: true,
+        });
+    }
+}
+
+/// Level file contents: either a bare tile grid or a tile grid with metadata
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LevelFile {
+    Tiles(Vec<Vec<u32>>),
+    Described {
+        tiles: Vec<Vec<u32>>,
+        #[serde(default)]
+        metadata: Box<LevelMetadata>,
+    },
+}
+
+/// Per-level settings that aren't part of the tile grid
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct LevelMetadata {
+    /// Whether the level uses a fixed time of day or a day/night cycle
+    pub time_of_day: TimeOfDaySetting,
+    /// Rain and wind for the level
+    pub weather: WeatherSetting,
+    /// Crushers and saw blades placed in the level
+    pub hazards: Vec<HazardSetting>,
+    /// Timed doors and the switches that open them
+    pub doors: Vec<DoorSetting>,
+    /// Positions of rest points (world pixels)
+    pub rest_points: Vec<[f32; 2]>,
+    /// Positions of collectible gems (world pixels)
+    pub collectibles: Vec<[f32; 2]>,
+    /// Where the level is completed (world pixels); levels without one can't be completed
+    pub exit: Option<[f32; 2]>,
+    /// Scripted combat rooms
+    pub encounters: Vec<EncounterSetting>,
+    /// Level polygons that travel along waypoints
+    pub moving_platforms: Vec<MovingPlatformSetting>,
+    /// Regions that run a script when the player first enters them
+    #[cfg(feature = "scripting")]
+    pub script_triggers: Vec<ScriptTriggerSetting>,
+    /// Where the player starts in this level (world pixels)
+    pub player_spawn: Option<[f32; 2]>,
+    /// Extra characters the player can switch control to, with their movement profiles
+    pub characters: Vec<CharacterSetting>,
+    /// Where AI agents start in this level (world pixels); an empty list spawns none
+    pub ai_spawns: Option<Vec<[f32; 2]>>,
+    /// How many AI agents to spread over the spawn points (one per point by default)
+    pub ai_count: Option<u32>,
+    /// How skilled the level's AI agents are
+    pub ai_profile: AIProfilePreset,
+    /// Where flying AI agents start in this level (world pixels)
+    pub flying_ai_spawns: Vec<[f32; 2]>,
+    /// Routes walked by patrolling AI agents, one agent per route
+    pub patrol_routes: Vec<PatrolRouteSetting>,
+    /// Areas agents keep to, each given to the agents spawning inside it
+    pub ai_territories: Vec<TerritorySetting>,
+    /// What the level's surfaces are made of (stone wherever no region says otherwise)
+    pub surface_materials: Vec<SurfaceMaterialRegion>,
+    /// Straight segments each quarter circle tile is built from
+    pub arc_segments: Option<u32>,
+    /// Points inside level polygons the camera never shows past, like walls hiding a secret area
+    /// (world pixels)
+    pub camera_curtains: Vec<[f32; 2]>,
+    /// Regions that change the camera's zoom or lock its view while the player is in them
+    pub camera_zones: Vec<CameraZoneSetting>,
+}
+
+impl LevelMetadata {
+    /// Player start position, or the default spawn if the level doesn't set one
+    pub fn player_spawn_position(&self) -> Vec2 {
+        self.player_spawn.map_or(DEFAULT_PLAYER_SPAWN, Vec2::from)
+    }
+
+    /// AI agent start positions, or the default agent spawn if the level doesn't set any
+    pub fn ai_spawn_positions(&self) -> Vec<Vec2> {
+        match &self.ai_spawns {
+            Some(spawns) => spawns.iter().copied().map(Vec2::from).collect(),
+            None => vec![DEFAULT_AI_SPAWN],
+        }
+    }
+
+    /// Segments per quarter circle tile, or the default if the level doesn't set it (never fewer
+    /// than the minimum, so agents treat the curve as smooth ground)
+    pub fn arc_segment_count(&self) -> u32 {
+        self.arc_segments
+            .unwrap_or(DEFAULT_ARC_SEGMENTS)
+            .max(MIN_ARC_SEGMENTS)
+    }
 }
 
+// Size of one level tile (units: pixels)
+pub const LEVEL_GRID_SIZE: f32 = 32.0;
+
+// Spawn points used when the level metadata doesn't set them (world pixels)
+const DEFAULT_PLAYER_SPAWN: Vec2 = Vec2::new(0.0, -50.0);
+const DEFAULT_AI_SPAWN: Vec2 = Vec2::new(0.0, -250.0);
+
+// Straight segments a quarter circle tile is built from, by default and at least (fewer segments
+// bend too sharply where they meet for the pathfinding graph to treat the curve as smooth ground)
+const DEFAULT_ARC_SEGMENTS: u32 = 8;
+const MIN_ARC_SEGMENTS: u32 = 4;
+
 // Level generation constants
 const POINT_IN_POLYGON_RAY_DIRECTION: Vec2 = Vec2::new(2.0, 1.0);
 const POINT_IN_POLYGON_RAY_DISTANCE: f32 = 1000.0;
 
-const LEVEL_DATA: &[u8] = include_bytes!("../assets/level.json");
+// Cell size of the level's polygon grid (pixels)
+const POLYGON_GRID_CELL_SIZE: f32 = 128.0;
+
+// Level mesh rendering constants
+const LEVEL_FILL_DARKEN_AMOUNT: f32 = 0.3;
+const LEVEL_FILL_Z: f32 = -2.0;
+const LEVEL_OUTLINE_Z: f32 = -1.0;
+
+/// Mesh entity spawned to render the level geometry
+#[derive(Component)]
+pub struct LevelMesh {
+    /// Unlit material color, before ambient lighting is applied
+    pub base_color: Color,
+    /// Index of the polygon the mesh draws
+    pub polygon: usize,
+}
+
+/// Bundled copy of the level, used until the asset server has loaded `assets/level.json`
+pub const LEVEL_DATA: &[u8] = include_bytes!("../assets/level.json");
+
+/// Tile grid and metadata the level is built from (the bundled level unless replaced)
+#[derive(Resource, Clone)]
+pub struct LevelSource {
+    pub tiles: Vec<Vec<u32>>,
+    pub metadata: LevelMetadata,
+    /// Geometry and pathfinding graph built ahead of time (binary level files), used instead of
+    /// generating them from the tiles
+    pub baked: Option<Arc<baked::BakedLevel>>,
+}
+
+impl Default for LevelSource {
+    fn default() -> Self {
+        let res = std::str::from_utf8(LEVEL_DATA);
+        Self::from_json(res.unwrap()).unwrap()
+    }
+}
+
+impl LevelSource {
+    /// Parses a level file (a bare tile grid or tiles with metadata)
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let (tiles, metadata) = match serde_json::from_str(json)? {
+            LevelFile::Tiles(tiles) => (tiles, LevelMetadata::default()),
+            LevelFile::Described { tiles, metadata } => (tiles, *metadata),
+        };
+
+        Ok(Self {
+            tiles,
+            metadata,
+            baked: None,
+        })
+    }
+}
 
-pub fn generate_level_polygons(grid_size: f32) -> Level {
-    let mut rng = rand::rng();
+pub fn generate_level_polygons(source: &LevelSource, grid_size: f32, rng: &mut impl Rng) -> Level {
+    let polygons = tile_polygons(
+        &source.tiles,
+        grid_size,
+        source.metadata.arc_segment_count(),
+        rng,
+    );
+    level_from_polygons(source, grid_size, polygons, rng)
+}
 
-    let res = std::str::from_utf8(LEVEL_DATA);
-    let json_data: Vec<Vec<u32>> = serde_json::from_str(res.unwrap()).unwrap();
+/// Assembles a level from the polygons built from its tiles
+fn level_from_polygons(
+    source: &LevelSource,
+    grid_size: f32,
+    polygons: Vec<Polygon>,
+    rng: &mut impl Rng,
+) -> Level {
+    let tiles = source.tiles.clone();
+    let metadata = source.metadata.clone();
 
     // Calculate level size
     let size = Vec2::new(
-        json_data[0].len() as f32 * grid_size,
-        json_data.len() as f32 * grid_size,
+        tiles[0].len() as f32 * grid_size,
+        tiles.len() as f32 * grid_size,
     );
     let half_size = size / 2.0;
 
+    let polygon_grid = build_polygon_grid(&polygons);
+
+    Level {
+        tile_polygon_count: polygons.len(),
+        polygons,
+        grid_size,
+        size,
+        half_size,
+        metadata,
+        polygon_grid,
+        tiles,
+        rng: StdRng::seed_from_u64(rng.random()),
+        pending_changes: Vec::new(),
+    }
+}
+
+/// Builds the level polygons from a tile grid, joining the tile edges into closed outlines
+/// (quarter circle tiles are built from `arc_segments` straight segments)
+fn tile_polygons(
+    json_data: &[Vec<u32>],
+    grid_size: f32,
+    arc_segments: u32,
+    rng: &mut impl Rng,
+) -> Vec<Polygon> {
     let offset = Vec2::new(
         json_data[0].len() as f32 * -grid_size / 2.0,
         json_data.len() as f32 * grid_size / 2.0,
@@ -89,13 +700,13 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                     // Squares
 
                     // Left edge
-                    if x == 0 || json_data[y][x - 1] == 0 {
+                    if side_exposed(json_data, x, y, TileSide::Left) {
                         line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
                         line_points
                             .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
                     }
                     // Right edge
-                    if x == json_data[y].len() - 1 || json_data[y][x + 1] == 0 {
+                    if side_exposed(json_data, x, y, TileSide::Right) {
                         line_points
                             .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
                         line_points.push(Vec2::new(
@@ -104,13 +715,13 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                         ));
                     }
                     // Top edge
-                    if y == 0 || json_data[y - 1][x] == 0 {
+                    if side_exposed(json_data, x, y, TileSide::Top) {
                         line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
                         line_points
                             .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
                     }
                     // Bottom edge
-                    if y == json_data.len() - 1 || json_data[y + 1][x] == 0 {
+                    if side_exposed(json_data, x, y, TileSide::Bottom) {
                         line_points
                             .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
                         line_points.push(Vec2::new(
@@ -136,7 +747,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                             ));
 
                             // Bottom edge
-                            if y == json_data.len() - 1 || json_data[y + 1][x] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Bottom) {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_size,
                                     (y + 1) as f32 * grid_size,
@@ -148,7 +759,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                             }
 
                             // Left edge
-                            if x == 0 || json_data[y][x - 1] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Left) {
                                 line_points
                                     .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
                                 line_points.push(Vec2::new(
@@ -167,7 +778,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                                 .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
 
                             // Bottom edge
-                            if y == json_data.len() - 1 || json_data[y + 1][x] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Bottom) {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_size,
                                     (y + 1) as f32 * grid_size,
@@ -179,7 +790,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                             }
 
                             // Right edge
-                            if x == json_data[y].len() - 1 || json_data[y][x + 1] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Right) {
                                 line_points.push(Vec2::new(
                                     (x + 1) as f32 * grid_size,
                                     y as f32 * grid_size,
@@ -200,7 +811,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                                 .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
 
                             // Top edge
-                            if y == 0 || json_data[y - 1][x] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Top) {
                                 line_points
                                     .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
                                 line_points.push(Vec2::new(
@@ -210,7 +821,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                             }
 
                             // Left edge
-                            if x == 0 || json_data[y][x - 1] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Left) {
                                 line_points
                                     .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
                                 line_points.push(Vec2::new(
@@ -230,7 +841,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                             line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
 
                             // Top edge
-                            if y == 0 || json_data[y - 1][x] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Top) {
                                 line_points
                                     .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
                                 line_points.push(Vec2::new(
@@ -240,7 +851,7 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                             }
 
                             // Right edge
-                            if x == json_data[y].len() - 1 || json_data[y][x + 1] == 0 {
+                            if side_exposed(json_data, x, y, TileSide::Right) {
                                 line_points.push(Vec2::new(
                                     (x + 1) as f32 * grid_size,
                                     y as f32 * grid_size,
@@ -255,7 +866,132 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
                     }
                 }
                 6..=9 => {
-                    // Isosceles triangles (not implemented)
+                    // Half-height right triangles (low slopes rising half a tile per tile)
+
+                    let left = x as f32 * grid_size;
+                    let right = (x + 1) as f32 * grid_size;
+                    let top = y as f32 * grid_size;
+                    let middle = (y as f32 + 0.5) * grid_size;
+                    let bottom = (y + 1) as f32 * grid_size;
+
+                    match tile - 6 {
+                        0 => {
+                            // Bottom left
+
+                            // Hypotenuse
+                            line_points.push(Vec2::new(left, middle));
+                            line_points.push(Vec2::new(right, bottom));
+
+                            // Bottom edge
+                            if side_exposed(json_data, x, y, TileSide::Bottom) {
+                                line_points.push(Vec2::new(left, bottom));
+                                line_points.push(Vec2::new(right, bottom));
+                            }
+
+                            // Left edge (lower half)
+                            if side_exposed(json_data, x, y, TileSide::Left) {
+                                line_points.push(Vec2::new(left, middle));
+                                line_points.push(Vec2::new(left, bottom));
+                            }
+                        }
+                        1 => {
+                            // Bottom right
+
+                            // Hypotenuse
+                            line_points.push(Vec2::new(right, middle));
+                            line_points.push(Vec2::new(left, bottom));
+
+                            // Bottom edge
+                            if side_exposed(json_data, x, y, TileSide::Bottom) {
+                                line_points.push(Vec2::new(left, bottom));
+                                line_points.push(Vec2::new(right, bottom));
+                            }
+
+                            // Right edge (lower half)
+                            if side_exposed(json_data, x, y, TileSide::Right) {
+                                line_points.push(Vec2::new(right, middle));
+                                line_points.push(Vec2::new(right, bottom));
+                            }
+                        }
+                        2 => {
+                            // Top left
+
+                            // Hypotenuse
+                            line_points.push(Vec2::new(left, middle));
+                            line_points.push(Vec2::new(right, top));
+
+                            // Top edge
+                            if side_exposed(json_data, x, y, TileSide::Top) {
+                                line_points.push(Vec2::new(left, top));
+                                line_points.push(Vec2::new(right, top));
+                            }
+
+                            // Left edge (upper half)
+                            if side_exposed(json_data, x, y, TileSide::Left) {
+                                line_points.push(Vec2::new(left, top));
+                                line_points.push(Vec2::new(left, middle));
+                            }
+                        }
+                        3 => {
+                            // Top right
+
+                            // Hypotenuse
+                            line_points.push(Vec2::new(right, middle));
+                            line_points.push(Vec2::new(left, top));
+
+                            // Top edge
+                            if side_exposed(json_data, x, y, TileSide::Top) {
+                                line_points.push(Vec2::new(left, top));
+                                line_points.push(Vec2::new(right, top));
+                            }
+
+                            // Right edge (upper half)
+                            if side_exposed(json_data, x, y, TileSide::Right) {
+                                line_points.push(Vec2::new(right, top));
+                                line_points.push(Vec2::new(right, middle));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                10..=17 => {
+                    // Quarter circles: convex quarter discs (hills) and concave fillets (bowls)
+
+                    let left = x as f32 * grid_size;
+                    let right = (x + 1) as f32 * grid_size;
+                    let top = y as f32 * grid_size;
+                    let bottom = (y + 1) as f32 * grid_size;
+
+                    // Solid corner and the tile sides that meet there
+                    let (corner, vertical_side, horizontal_side) = match (tile - 10) % 4 {
+                        0 => (Vec2::new(left, bottom), TileSide::Left, TileSide::Bottom),
+                        1 => (Vec2::new(right, bottom), TileSide::Right, TileSide::Bottom),
+                        2 => (Vec2::new(left, top), TileSide::Left, TileSide::Top),
+                        _ => (Vec2::new(right, top), TileSide::Right, TileSide::Top),
+                    };
+                    let opposite_corner = Vec2::new(left + right, top + bottom) - corner;
+
+                    // The arc joins the far ends of the two solid sides
+                    let vertical_end = Vec2::new(corner.x, opposite_corner.y);
+                    let horizontal_end = Vec2::new(opposite_corner.x, corner.y);
+
+                    // Convex arcs curve around the solid corner, concave ones around the open one
+                    let center = if tile < 14 { corner } else { opposite_corner };
+                    line_points.extend(quarter_arc_lines(
+                        center,
+                        vertical_end,
+                        horizontal_end,
+                        arc_segments,
+                    ));
+
+                    if side_exposed(json_data, x, y, vertical_side) {
+                        line_points.push(corner);
+                        line_points.push(vertical_end);
+                    }
+                    if side_exposed(json_data, x, y, horizontal_side) {
+                        line_points.push(corner);
+                        line_points.push(horizontal_end);
+                    }
                 }
                 _ => {}
             }
@@ -356,8 +1092,8 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
         point.y += offset.y;
     }
 
-    // Separate the lines into polygons
-    let mut polygons: Vec<Polygon> = Vec::new();
+    // Separate the lines into closed outlines
+    let mut outlines: Vec<Vec<Vec2>> = Vec::new();
 
     // While there are lines left
     while line_count > 0 {
@@ -424,38 +1160,175 @@ pub fn generate_level_polygons(grid_size: f32) -> Level {
             }
         }
 
-        let collision_side = calculate_winding_order(&polygon_lines).signum();
+        // Add the outline to the list of outlines
+        outlines.push(polygon_lines);
+    }
 
-        let color = Color::srgb(
-            rng.random_range(0.0..=1.0),
-            rng.random_range(0.0..=1.0),
-            rng.random_range(0.0..=1.0),
-        );
+    outline_polygons(outlines, rng)
+}
 
-        // Compute bounding box for spatial optimization
-        let aabb = compute_polygon_aabb(&polygon_lines);
+/// Builds the polygons for closed tile outlines.
+///
+/// Outlines are traced whichever way their first line points, so each one is turned to wind with
+/// the solid on the right of its edges, the side pathfinding expects: clockwise around solid
+/// ground and counter-clockwise around holes (outlines inside an odd number of others, like the
+/// inside of a closed level border). Holes are empty on the inside, so they collide on the
+/// opposite side to the one their winding gives.
+fn outline_polygons(outlines: Vec<Vec<Vec2>>, rng: &mut impl Rng) -> Vec<Polygon> {
+    let mut polygons: Vec<Polygon> = outlines
+        .into_iter()
+        .enumerate()
+        .map(|(id, mut points)| {
+            if signed_area(&points) > 0.0 {
+                points.reverse();
+            }
+            Polygon::new(id, points, random_polygon_color(rng))
+        })
+        .collect();
+
+    let holes: Vec<usize> = polygons
+        .iter()
+        .filter(|polygon| {
+            // Outlines don't share edges, so the middle of an edge is only on this one
+            let point = polygon.points[0].midpoint(polygon.points[1]);
+            let depth = polygons
+                .iter()
+                .filter(|other| other.id != polygon.id && other.contains_point(point))
+                .count();
+            depth % 2 == 1
+        })
+        .map(|polygon| polygon.id)
+        .collect();
+
+    for id in holes {
+        let mut points = std::mem::take(&mut polygons[id].points);
+        points.reverse();
+
+        let mut hole = Polygon::new(id, points, polygons[id].color);
+        hole.collision_side = -hole.collision_side;
+        polygons[id] = hole;
+    }
 
-        // Check if polygon is a container (contains the origin)
-        let is_container = point_in_polygon(&polygon_lines, Vec2::ZERO);
+    polygons
+}
 
-        // Add the polygon to the list of polygons
-        polygons.push(Polygon {
-            points: polygon_lines,
-            collision_side,
-            color,
-            aabb,
-            is_container,
-        });
+/// Line segments (as pairs of points) approximating the quarter circle around `center` from
+/// `start` to `end`, which must be the same distance from `center` at right angles to each other
+fn quarter_arc_lines(center: Vec2, start: Vec2, end: Vec2, segments: u32) -> Vec<Vec2> {
+    let start_offset = start - center;
+    let end_offset = end - center;
+
+    // The ends are exact so they join up with the neighbouring edges
+    let mut points = vec![start];
+    for segment in 1..segments {
+        let (sin, cos) = ops::sin_cos(segment as f32 / segments as f32 * FRAC_PI_2);
+        points.push(center + start_offset * cos + end_offset * sin);
     }
+    points.push(end);
 
-    Level {
-        polygons,
-        grid_size,
-        size,
-        half_size,
+    points
+        .windows(2)
+        .flat_map(|segment| [segment[0], segment[1]])
+        .collect()
+}
+
+/// Random fill color for a tile polygon
+fn random_polygon_color(rng: &mut impl Rng) -> Color {
+    Color::srgb(
+        rng.random_range(0.0..=1.0),
+        rng.random_range(0.0..=1.0),
+        rng.random_range(0.0..=1.0),
+    )
+}
+
+/// Side of a tile
+#[derive(Clone, Copy)]
+enum TileSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl TileSide {
+    fn opposite(self) -> Self {
+        match self {
+            TileSide::Left => TileSide::Right,
+            TileSide::Right => TileSide::Left,
+            TileSide::Top => TileSide::Bottom,
+            TileSide::Bottom => TileSide::Top,
+        }
     }
 }
 
+/// Solid part of a tile's side, from 0 to 1 (top to bottom along the left and right sides, left
+/// to right along the top and bottom sides), or `None` if the side is open
+fn side_coverage(tile: u32, side: TileSide) -> Option<(f32, f32)> {
+    use TileSide::*;
+
+    match (tile, side) {
+        (1, _) => Some((0.0, 1.0)),
+        // Right triangles
+        (2, Left | Bottom) | (3, Right | Bottom) | (4, Left | Top) | (5, Right | Top) => {
+            Some((0.0, 1.0))
+        }
+        // Half-height right triangles
+        (6 | 7, Bottom) | (8 | 9, Top) => Some((0.0, 1.0)),
+        (6, Left) | (7, Right) => Some((0.5, 1.0)),
+        (8, Left) | (9, Right) => Some((0.0, 0.5)),
+        // Quarter circles
+        (10 | 14, Left | Bottom)
+        | (11 | 15, Right | Bottom)
+        | (12 | 16, Left | Top)
+        | (13 | 17, Right | Top) => Some((0.0, 1.0)),
+        _ => None,
+    }
+}
+
+/// Whether a side of the tile at (`x`, `y`) needs an edge: the neighbouring tile doesn't cover
+/// all of it. Partly covered sides still get an edge; where it overlaps the neighbour's edge
+/// the two are merged away with the superfluous points.
+fn side_exposed(tiles: &[Vec<u32>], x: usize, y: usize, side: TileSide) -> bool {
+    let neighbor = match side {
+        TileSide::Left => x.checked_sub(1).map(|x| tiles[y][x]),
+        TileSide::Right => tiles[y].get(x + 1).copied(),
+        TileSide::Top => y.checked_sub(1).map(|y| tiles[y][x]),
+        TileSide::Bottom => tiles.get(y + 1).map(|row| row[x]),
+    };
+
+    let coverage = side_coverage(tiles[y][x], side);
+    let neighbor_coverage = neighbor.and_then(|tile| side_coverage(tile, side.opposite()));
+
+    match (coverage, neighbor_coverage) {
+        (Some((start, end)), Some((neighbor_start, neighbor_end))) => {
+            neighbor_start > start || neighbor_end < end
+        }
+        _ => true,
+    }
+}
+
+/// Buckets every polygon into the grid cells its bounding box covers
+fn build_polygon_grid(polygons: &[Polygon]) -> HashMap<(i32, i32), Vec<usize>> {
+    let mut polygon_grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (polygon_index, polygon) in polygons.iter().enumerate() {
+        for cell in grid_cells(&polygon.aabb) {
+            polygon_grid.entry(cell).or_default().push(polygon_index);
+        }
+    }
+
+    polygon_grid
+}
+
+/// Polygon grid cells covered by an AABB
+fn grid_cells(aabb: &Aabb) -> impl Iterator<Item = (i32, i32)> {
+    let min_x = (aabb.min.x / POLYGON_GRID_CELL_SIZE).floor() as i32;
+    let min_y = (aabb.min.y / POLYGON_GRID_CELL_SIZE).floor() as i32;
+    let max_x = (aabb.max.x / POLYGON_GRID_CELL_SIZE).floor() as i32;
+    let max_y = (aabb.max.y / POLYGON_GRID_CELL_SIZE).floor() as i32;
+
+    (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+}
+
 /// Check if a point is inside a polygon using ray casting algorithm
 fn point_in_polygon(polygon_lines: &[Vec2], point: Vec2) -> bool {
     let test_line_start = point;
@@ -501,30 +1374,310 @@ fn calculate_winding_order(vertices: &[Vec2]) -> f32 {
     sum
 }
 
-/// Compute axis-aligned bounding box for a polygon
-fn compute_polygon_aabb(points: &[Vec2]) -> Aabb {
-    if points.is_empty() {
-        return Aabb {
-            min: Vec2::ZERO,
-            max: Vec2::ZERO,
+pub struct LevelEditPlugin;
+
+impl Plugin for LevelEditPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<LevelChanged>();
+        app.add_systems(Update, s_apply_level_changes);
+    }
+}
+
+/// Level change system: Updates the pathfinding graph around each edit and rebuilds the level
+/// meshes after the level geometry was edited, then sends the queued `LevelChanged` messages
+#[allow(clippy::too_many_arguments)]
+pub fn s_apply_level_changes(
+    mut commands: Commands,
+    level: Option<ResMut<Level>>,
+    mut pathfinding: ResMut<PathfindingGraph>,
+    level_mesh_query: Query<Entity, With<LevelMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut level_changed: MessageWriter<LevelChanged>,
+    mut graph_changed: MessageWriter<PathfindingGraphChanged>,
+    movement_config: Res<MovementConfig>,
+) {
+    let Some(mut level) = level else {
+        return;
+    };
+    if level.pending_changes.is_empty() {
+        return;
+    }
+
+    for change in &level.pending_changes {
+        pathfinding.update_region(&level, change.region, &movement_config);
+        graph_changed.write(PathfindingGraphChanged {
+            region: Some(change.region),
+        });
+    }
+
+    for entity in level_mesh_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_level_meshes(&mut commands, &mut meshes, &mut materials, &level);
+
+    level_changed.write_batch(level.pending_changes.drain(..));
+}
+
+/// Spawn filled meshes with outlines for every level polygon
+/// Runs once at load time so rendering the level costs nothing per frame
+pub fn spawn_level_meshes(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    level: &Level,
+) {
+    let holes = level.polygon_holes();
+    let pockets: HashSet<usize> = holes.values().flatten().copied().collect();
+
+    for (polygon_index, polygon) in level.polygons.iter().enumerate() {
+        // Removed polygons keep their slot but have nothing to draw
+        if polygon.points.is_empty() {
+            continue;
+        }
+
+        let outline_material = materials.add(ColorMaterial::from_color(polygon.color));
+
+        // Container polygons are solid on the outside, and pockets are cut out of the fill of the
+        // polygon around them, so only their outlines are drawn
+        if !polygon.is_container && !pockets.contains(&polygon_index) {
+            let fill_color = polygon.color.darker(LEVEL_FILL_DARKEN_AMOUNT);
+            let fill_material = materials.add(ColorMaterial::from_color(fill_color));
+
+            let fill_mesh = match holes.get(&polygon_index) {
+                Some(hole_indices) => {
+                    let hole_outlines: Vec<&[Vec2]> = hole_indices
+                        .iter()
+                        .map(|&hole| level.polygons[hole].points.as_slice())
+                        .collect();
+                    let points = bridge_holes(&polygon.points, &hole_outlines);
+                    polygon_fill_mesh(&points, &triangulate_polygon(&points))
+                }
+                None => polygon_fill_mesh(&polygon.points, &polygon.triangles),
+            };
+
+            commands.spawn((
+                LevelMesh {
+                    base_color: fill_color,
+                    polygon: polygon_index,
+                },
+                Mesh2d(meshes.add(fill_mesh)),
+                MeshMaterial2d(fill_material),
+                Transform::from_xyz(0.0, 0.0, LEVEL_FILL_Z),
+            ));
+        }
+
+        commands.spawn((
+            LevelMesh {
+                base_color: polygon.color,
+                polygon: polygon_index,
+            },
+            Mesh2d(meshes.add(polygon_outline_mesh(&polygon.points))),
+            MeshMaterial2d(outline_material),
+            Transform::from_xyz(0.0, 0.0, LEVEL_OUTLINE_Z),
+        ));
+    }
+}
+
+fn polygon_fill_mesh(points: &[Vec2], triangles: &[[usize; 3]]) -> Mesh {
+    let indices: Vec<u32> = triangles
+        .iter()
+        .flat_map(|triangle| triangle.map(|index| index as u32))
+        .collect();
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, mesh_positions(points))
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+fn polygon_outline_mesh(points: &[Vec2]) -> Mesh {
+    Mesh::new(PrimitiveTopology::LineStrip, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, mesh_positions(points))
+}
+
+fn mesh_positions(points: &[Vec2]) -> Vec<[f32; 3]> {
+    points.iter().map(|point| [point.x, point.y, 0.0]).collect()
+}
+
+/// Triangulate a closed polygon outline using ear clipping
+/// Returns counter-clockwise triangles as indices into `points`; a repeated closing point is
+/// ignored
+pub fn triangulate_polygon(points: &[Vec2]) -> Vec<[usize; 3]> {
+    let mut point_count = points.len();
+    if point_count > 1 && points[0] == points[point_count - 1] {
+        point_count -= 1;
+    }
+    if point_count < 3 {
+        return Vec::new();
+    }
+
+    // Work on the vertices in counter-clockwise order
+    let mut remaining: Vec<usize> = (0..point_count).collect();
+    if signed_area(&points[..point_count]) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity(point_count - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+
+        let ear = (0..count).find(|&i| {
+            let a = points[remaining[(i + count - 1) % count]];
+            let b = points[remaining[i]];
+            let c = points[remaining[(i + 1) % count]];
+
+            // Reflex and collinear vertices can't be ears
+            if cross_product(b - a, c - b) <= 0.0 {
+                return false;
+            }
+
+            // No other vertex may lie inside the ear
+            remaining.iter().all(|&other| {
+                let p = points[other];
+                p == a || p == b || p == c || !point_in_triangle(p, a, b, c)
+            })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + count - 1) % count],
+                    remaining[i],
+                    remaining[(i + 1) % count],
+                ]);
+                remaining.remove(i);
+            }
+            None => {
+                // Degenerate outline: drop a collinear vertex if there is one, otherwise give up
+                let collinear = (0..count).find(|&i| {
+                    let a = points[remaining[(i + count - 1) % count]];
+                    let b = points[remaining[i]];
+                    let c = points[remaining[(i + 1) % count]];
+                    cross_product(b - a, c - b).abs() <= f32::EPSILON
+                });
+
+                match collinear {
+                    Some(i) => {
+                        remaining.remove(i);
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+
+    triangles
+}
+
+/// Joins holes into a closed outline so ear clipping can fill the area between them: each hole
+/// (rightmost first) is linked to an outline vertex it can see by a bridge that is walked in and
+/// back out, leaving a single outline (counter-clockwise, holes clockwise) that visits every
+/// vertex. Repeated closing points are ignored; a hole nothing can be bridged to is left out.
+pub fn bridge_holes(outline: &[Vec2], holes: &[&[Vec2]]) -> Vec<Vec2> {
+    let open = |points: &[Vec2], counter_clockwise: bool| {
+        let mut points = points.to_vec();
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if (signed_area(&points) > 0.0) != counter_clockwise {
+            points.reverse();
+        }
+        points
+    };
+
+    let mut merged = open(outline, true);
+    let mut holes: Vec<Vec<Vec2>> = holes
+        .iter()
+        .map(|hole| open(hole, false))
+        .filter(|hole| hole.len() >= 3)
+        .collect();
+    holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+
+    for index in 0..holes.len() {
+        let hole = &holes[index];
+        let Some(start) = (0..hole.len()).max_by(|&a, &b| hole[a].x.total_cmp(&hole[b].x)) else {
+            continue;
         };
+        let from = hole[start];
+
+        // Closest outline vertex whose bridge stays inside the outline and crosses no edge
+        let mut candidates: Vec<usize> = (0..merged.len()).collect();
+        candidates.sort_by(|&a, &b| {
+            merged[a]
+                .distance_squared(from)
+                .total_cmp(&merged[b].distance_squared(from))
+        });
+        let count = merged.len();
+        let target = candidates.into_iter().find(|&candidate| {
+            let to = merged[candidate];
+            let prev = merged[(candidate + count - 1) % count];
+            let next = merged[(candidate + 1) % count];
+            if !in_interior_wedge(prev, to, next, from - to) {
+                return false;
+            }
+
+            let crosses = |points: &[Vec2]| {
+                (0..points.len()).any(|i| {
+                    let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                    a != to
+                        && b != to
+                        && a != from
+                        && b != from
+                        && line_intersect(from, to, a, b).is_some()
+                })
+            };
+            !crosses(&merged) && !holes[index..].iter().any(|hole| crosses(hole))
+        });
+        let Some(target) = target else {
+            continue;
+        };
+
+        // Walk to the hole, all the way around it, and back across the bridge
+        let mut bridged = Vec::with_capacity(merged.len() + hole.len() + 2);
+        bridged.extend_from_slice(&merged[..=target]);
+        bridged.extend(hole[start..].iter().chain(&hole[..=start]));
+        bridged.extend_from_slice(&merged[target..]);
+        merged = bridged;
     }
 
-    let mut min_x = points[0].x;
-    let mut min_y = points[0].y;
-    let mut max_x = points[0].x;
-    let mut max_y = points[0].y;
+    merged
+}
+
+fn max_x(points: &[Vec2]) -> f32 {
+    points.iter().map(|point| point.x).fold(f32::MIN, f32::max)
+}
 
-    for point in points.iter().skip(1) {
-        min_x = min_x.min(point.x);
-        min_y = min_y.min(point.y);
-        max_x = max_x.max(point.x);
-        max_y = max_y.max(point.y);
+/// Whether `direction` points from `vertex` into a counter-clockwise polygon, given the vertices
+/// before and after it
+fn in_interior_wedge(prev: Vec2, vertex: Vec2, next: Vec2, direction: Vec2) -> bool {
+    let to_prev = prev - vertex;
+    let to_next = next - vertex;
+    if cross_product(vertex - prev, next - vertex) > 0.0 {
+        // Convex: between the outgoing edge and the incoming one, turning counter-clockwise
+        cross_product(to_next, direction) > 0.0 && cross_product(direction, to_prev) > 0.0
+    } else {
+        // Reflex: anywhere but the outside wedge
+        !(cross_product(to_prev, direction) >= 0.0 && cross_product(direction, to_next) >= 0.0)
     }
+}
 
-    Aabb {
-        min: Vec2::new(min_x, min_y),
-        max: Vec2::new(max_x, max_y),
+fn signed_area(points: &[Vec2]) -> f32 {
+    let mut sum = 0.0;
+
+    for i in 0..points.len() {
+        sum += cross_product(points[i], points[(i + 1) % points.len()]);
     }
+
+    sum / 2.0
 }
 
+fn point_in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    cross_product(b - a, point - a) >= 0.0
+        && cross_product(c - b, point - b) >= 0.0
+        && cross_product(a - c, point - c) >= 0.0
+}
@@ -0,0 +1,235 @@
+//! Binary level files (`.clvl`) for shipped builds.
+//!
+//! A binary level stores the level's polygons and pathfinding graph along with its tiles, so
+//! loading it skips generating them (and parsing the tile grid as JSON). The file is a short
+//! header followed by the LZ4-compressed postcard encoding of the level. Level files are converted
+//! with `composite --bake-level <level file> [output file]`, and have to be baked again whenever
+//! level generation or the pathfinding graph changes.
+
+use std::{fmt, path::Path, sync::Arc, time::Instant};
+
+use bevy::math::Vec2;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    generate_level_polygons, level_from_polygons, outline_polygons, Level, LevelSource,
+    LEVEL_GRID_SIZE,
+};
+use crate::{
+    ai::pathfinding::{init_pathfinding_graph, PathfindingGraph},
+    level_loader::parse_level_file,
+    movement_config::MovementConfig,
+};
+
+// Command-line flag converting a level file to a binary level
+pub const BAKE_LEVEL_FLAG: &str = "--bake-level";
+
+// File extension of binary levels
+pub const BAKED_LEVEL_EXTENSION: &str = "clvl";
+
+// Start of every binary level file, followed by the format version
+const MAGIC: &[u8; 4] = b"CLVL";
+const FORMAT_VERSION: u8 = 3;
+
+/// Level geometry and pathfinding graph built ahead of time
+#[derive(Serialize, Deserialize)]
+pub struct BakedLevel {
+    grid_size: f32,
+    /// Outlines of the tile polygons (their colors are picked when the level is built)
+    polygons: Vec<Vec<Vec2>>,
+    pathfinding: PathfindingGraph,
+}
+
+/// Contents of a binary level file
+#[derive(Serialize, Deserialize)]
+struct BakedLevelFile {
+    tiles: Vec<Vec<u32>>,
+    /// Level metadata as JSON: it is small, and its settings are internally tagged enums, which
+    /// postcard can't decode
+    metadata: String,
+    level: BakedLevel,
+}
+
+/// A binary level file that couldn't be written or read
+#[derive(Debug)]
+pub enum BakedLevelError {
+    /// The file doesn't start with the binary level header
+    NotBaked,
+    /// The file was written by another version of the format (bake the level again)
+    Version(u8),
+    Decompress(lz4_flex::block::DecompressError),
+    Encoding(postcard::Error),
+    Metadata(serde_json::Error),
+}
+
+impl fmt::Display for BakedLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BakedLevelError::NotBaked => write!(f, "not a binary level"),
+            BakedLevelError::Version(version) => write!(
+                f,
+                "binary level format version {version} (expected {FORMAT_VERSION}), bake it again"
+            ),
+            BakedLevelError::Decompress(error) => write!(f, "corrupt binary level: {error}"),
+            BakedLevelError::Encoding(error) => write!(f, "invalid binary level: {error}"),
+            BakedLevelError::Metadata(error) => write!(f, "invalid level metadata: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BakedLevelError {}
+
+impl BakedLevel {
+    /// Builds the level and fills `pathfinding` with its graph.
+    ///
+    /// The level is the one `generate_level_polygons` would build from the tiles, and `rng` is
+    /// drawn from the same way, so seeded runs play out the same with either kind of level file.
+    pub fn build(
+        &self,
+        source: &LevelSource,
+        rng: &mut impl Rng,
+        pathfinding: &mut PathfindingGraph,
+    ) -> Level {
+        let polygons = outline_polygons(self.polygons.clone(), rng);
+
+        pathfinding.clone_from(&self.pathfinding);
+
+        level_from_polygons(source, self.grid_size, polygons, rng)
+    }
+}
+
+/// Builds the level's geometry and pathfinding graph (with jumps planned for agents moving as
+/// `movement` says) and encodes them, with the level, as a binary level file
+pub fn encode(
+    source: &LevelSource,
+    movement: &MovementConfig,
+) -> Result<Vec<u8>, BakedLevelError> {
+    // Polygon colors aren't stored, so the seed makes no difference to the file
+    let level = generate_level_polygons(source, LEVEL_GRID_SIZE, &mut StdRng::seed_from_u64(0));
+    let mut pathfinding = PathfindingGraph::default();
+    init_pathfinding_graph(&level, &mut pathfinding, movement);
+
+    let file = BakedLevelFile {
+        tiles: source.tiles.clone(),
+        metadata: serde_json::to_string(&source.metadata).map_err(BakedLevelError::Metadata)?,
+        level: BakedLevel {
+            grid_size: level.grid_size,
+            polygons: level.polygons.into_iter().map(|polygon| polygon.points).collect(),
+            pathfinding,
+        },
+    };
+    let encoded = postcard::to_stdvec(&file).map_err(BakedLevelError::Encoding)?;
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(FORMAT_VERSION);
+    bytes.extend(lz4_flex::compress_prepend_size(&encoded));
+
+    Ok(bytes)
+}
+
+/// Reads a binary level file
+pub fn decode(bytes: &[u8]) -> Result<LevelSource, BakedLevelError> {
+    let Some((&version, compressed)) = bytes
+        .strip_prefix(MAGIC.as_slice())
+        .and_then(|body| body.split_first())
+    else {
+        return Err(BakedLevelError::NotBaked);
+    };
+    if version != FORMAT_VERSION {
+        return Err(BakedLevelError::Version(version));
+    }
+
+    let encoded =
+        lz4_flex::decompress_size_prepended(compressed).map_err(BakedLevelError::Decompress)?;
+    let file: BakedLevelFile = postcard::from_bytes(&encoded).map_err(BakedLevelError::Encoding)?;
+
+    Ok(LevelSource {
+        tiles: file.tiles,
+        metadata: serde_json::from_str(&file.metadata).map_err(BakedLevelError::Metadata)?,
+        baked: Some(Arc::new(file.level)),
+    })
+}
+
+/// Converts the level file given on the command line to a binary level, written next to it with
+/// the binary level extension unless an output path follows, and reports how much faster it loads
+pub fn run_bake() {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == BAKE_LEVEL_FLAG);
+    let Some(input) = index.and_then(|index| args.get(index + 1)) else {
+        eprintln!("Usage: composite {BAKE_LEVEL_FLAG} <level file> [output file]");
+        return;
+    };
+    let input = Path::new(input);
+    let output = index
+        .and_then(|index| args.get(index + 2))
+        .map_or_else(|| input.with_extension(BAKED_LEVEL_EXTENSION), Into::into);
+
+    let contents = match std::fs::read(input) {
+        Ok(contents) => contents,
+        Err(error) => {
+            eprintln!("Failed to read {}: {error}", input.display());
+            return;
+        }
+    };
+    let extension = input.extension().and_then(|extension| extension.to_str());
+
+    let start = Instant::now();
+    let source = match parse_level_file(extension, &contents) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Failed to load {}: {error}", input.display());
+            return;
+        }
+    };
+    // The graph is planned for the gravity and AI jump velocity in the movement config the level
+    // is baked with
+    let movement = MovementConfig::load();
+    let mut pathfinding = PathfindingGraph::default();
+    let level = generate_level_polygons(&source, LEVEL_GRID_SIZE, &mut rand::rng());
+    let diagnostics = init_pathfinding_graph(&level, &mut pathfinding, &movement);
+    let generate_time = start.elapsed();
+    if let Some(problem) = diagnostics.problem() {
+        eprintln!(
+            "Warning: agents can't find their way around {} ({problem})",
+            input.display()
+        );
+    }
+
+    let baked = match encode(&source, &movement) {
+        Ok(baked) => baked,
+        Err(error) => {
+            eprintln!("Failed to bake {}: {error}", input.display());
+            return;
+        }
+    };
+
+    // Time loading the binary level the way the game does
+    let start = Instant::now();
+    let baked_source = match decode(&baked) {
+        Ok(baked_source) => baked_source,
+        Err(error) => {
+            eprintln!("Baked level doesn't load back: {error}");
+            return;
+        }
+    };
+    if let Some(baked_level) = &baked_source.baked {
+        baked_level.build(&baked_source, &mut rand::rng(), &mut pathfinding);
+    }
+    let load_time = start.elapsed();
+
+    if let Err(error) = std::fs::write(&output, &baked) {
+        eprintln!("Failed to write {}: {error}", output.display());
+        return;
+    }
+
+    println!(
+        "Baked {} ({} bytes) into {} ({} bytes): loads in {:.2} ms instead of {:.2} ms",
+        input.display(),
+        contents.len(),
+        output.display(),
+        baked.len(),
+        load_time.as_secs_f64() * 1000.0,
+        generate_time.as_secs_f64() * 1000.0
+    );
+}
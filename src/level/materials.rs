@@ -0,0 +1,36 @@
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::{Aabb, Level};
+
+/// What a stretch of level geometry is made of, for effects that depend on the surface (footstep
+/// and landing sounds)
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SurfaceMaterial {
+    #[default]
+    Stone,
+    Metal,
+    Ice,
+}
+
+/// Every surface inside a rectangle of the level is made of one material (read from level
+/// metadata)
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct SurfaceMaterialRegion {
+    pub material: SurfaceMaterial,
+    /// Opposite corners of the rectangle (world pixels)
+    pub corners: [[f32; 2]; 2],
+}
+
+impl Level {
+    /// Material of the surface at `point`: that of the last region containing it, or stone
+    pub fn material_at(&self, point: Vec2) -> SurfaceMaterial {
+        self.metadata
+            .surface_materials
+            .iter()
+            .rev()
+            .find(|region| Aabb::from_points(&region.corners.map(Vec2::from)).contains(point))
+            .map_or(SurfaceMaterial::default(), |region| region.material)
+    }
+}
@@ -0,0 +1,443 @@
+//! Random levels.
+//!
+//! A level is generated from a `u64` seed, so the same seed always builds the same level: either
+//! rooms joined by stepped corridors, left to right, or cellular automaton caves. Start the game
+//! with `composite --procgen [rooms|caves] [seed]` to play endless random levels: reaching the
+//! exit ring generates the next level from the next seed.
+
+use std::collections::VecDeque;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{primitives::Annulus, Vec2},
+    mesh::{Mesh, Mesh2d},
+    prelude::Resource,
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    state::{
+        condition::in_state,
+        state::{NextState, OnEnter},
+        state_scoped::DespawnOnExit,
+    },
+    transform::components::Transform,
+};
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+use serde::Deserialize;
+
+use super::{generate_level_polygons, Level, LevelMetadata, LevelSource, LEVEL_GRID_SIZE};
+use crate::{
+    characters::ActiveCharacter,
+    collisions::{s_sensors, Sensor},
+    game_state::GameState,
+};
+
+// Command-line flag that starts endless random levels
+pub const PROCGEN_FLAG: &str = "--procgen";
+
+// Tile codes used by generated levels
+const SOLID: u32 = 1;
+const EMPTY: u32 = 0;
+
+// Level size (tiles)
+pub const DEFAULT_WIDTH: usize = 80;
+pub const DEFAULT_HEIGHT: usize = 40;
+
+// Rooms: one room per slot across the level, each between the min and max size (tiles)
+const ROOM_SLOT_WIDTH: usize = 16;
+const ROOM_MIN_SIZE: (usize, usize) = (7, 5);
+const ROOM_MAX_SIZE: (usize, usize) = (13, 10);
+// Clear height of corridors (tiles)
+const CORRIDOR_HEIGHT: usize = 3;
+// Corridor floors climb or drop at most one tile every this many columns (single tile steps are
+// an easy jump)
+const CORRIDOR_STEP_LENGTH: usize = 2;
+
+// Caves: starting fraction of solid tiles, smoothing passes, and how many of a tile's eight
+// neighbours have to be solid for a clear tile to fill in, or for a solid tile to stay
+const CAVE_FILL_CHANCE: f64 = 0.45;
+const CAVE_SMOOTHING_PASSES: usize = 5;
+const CAVE_BIRTH_NEIGHBOURS: usize = 5;
+const CAVE_SURVIVAL_NEIGHBOURS: usize = 4;
+
+// Clear tiles needed above a floor tile for something to stand there
+const STANDING_ROOM: usize = 2;
+// Number of AI agents spawned, and how far (tiles) they start from the player at least
+const AI_SPAWN_COUNT: usize = 3;
+const AI_SPAWN_MIN_TILE_DISTANCE: usize = 12;
+// Number of collectible gems scattered over the level's floors
+const COLLECTIBLE_COUNT: usize = 5;
+
+// Exit ring constants
+const EXIT_RADIUS: f32 = 20.0;
+const EXIT_THICKNESS: f32 = 3.0;
+const EXIT_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+const EXIT_Z: f32 = 0.5;
+
+/// How random levels are laid out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcgenStyle {
+    /// Rectangular rooms from left to right, joined by stepped corridors
+    #[default]
+    Rooms,
+    /// One connected cave smoothed out of random noise
+    Caves,
+}
+
+/// A generated level and where its exit is
+pub struct GeneratedLevel {
+    pub source: LevelSource,
+    /// Exit position (world pixels), at the other end of the level from the player spawn
+    pub exit: Vec2,
+}
+
+/// Generates a random level from the seed (tile rows top to bottom, like level files), with the
+/// player and AI agent spawns and collectibles set in its metadata
+pub fn generate_level(
+    seed: u64,
+    style: ProcgenStyle,
+    width: usize,
+    height: usize,
+) -> GeneratedLevel {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut tiles = match style {
+        ProcgenStyle::Rooms => generate_rooms(&mut rng, width, height),
+        ProcgenStyle::Caves => generate_caves(&mut rng, width, height),
+    };
+
+    // Spawns stand on floor tiles: the player at the left end of the level, the exit at the right
+    let mut floors = floor_cells(&tiles);
+    if floors.is_empty() {
+        // Nothing to stand on (only possible in tiny caves): clear a room so the level is playable
+        tiles = generate_rooms(
+            &mut rng,
+            width.max(ROOM_SLOT_WIDTH),
+            height.max(ROOM_MAX_SIZE.1 + 2),
+        );
+        floors = floor_cells(&tiles);
+    }
+    floors.sort_by_key(|&(x, y)| (x, y));
+    let player_cell = floors[0];
+    let exit_cell = floors[floors.len() - 1];
+
+    let far_floors: Vec<(usize, usize)> = floors
+        .iter()
+        .copied()
+        .filter(|&(x, _)| x.abs_diff(player_cell.0) >= AI_SPAWN_MIN_TILE_DISTANCE)
+        .collect();
+    let ai_spawns = (0..AI_SPAWN_COUNT.min(far_floors.len()))
+        .map(|_| far_floors[rng.random_range(0..far_floors.len())])
+        .map(|cell| cell_center(&tiles, cell).to_array())
+        .collect();
+    let collectibles = floors
+        .iter()
+        .filter(|&&cell| cell != player_cell && cell != exit_cell)
+        .choose_multiple(&mut rng, COLLECTIBLE_COUNT)
+        .into_iter()
+        .map(|&cell| cell_center(&tiles, cell).to_array())
+        .collect();
+
+    let metadata = LevelMetadata {
+        player_spawn: Some(cell_center(&tiles, player_cell).to_array()),
+        ai_spawns: Some(ai_spawns),
+        collectibles,
+        ..LevelMetadata::default()
+    };
+    let exit = cell_center(&tiles, exit_cell);
+
+    GeneratedLevel {
+        source: LevelSource {
+            tiles,
+            metadata,
+            baked: None,
+        },
+        exit,
+    }
+}
+
+/// Generates a random level and builds its polygons
+#[allow(dead_code)]
+pub fn generate_random_level(seed: u64, style: ProcgenStyle, grid_size: f32) -> Level {
+    let generated = generate_level(seed, style, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+    generate_level_polygons(&generated.source, grid_size, &mut StdRng::seed_from_u64(seed))
+}
+
+/// Carves rooms out of solid rock, one per slot from left to right, and joins neighbouring rooms
+/// with corridors whose floor steps up or down from one room's floor to the next. Each room's floor
+/// is kept close enough to the previous one's for the steps to make up the difference.
+fn generate_rooms(rng: &mut impl Rng, width: usize, height: usize) -> Vec<Vec<u32>> {
+    let mut tiles = vec![vec![SOLID; width]; height];
+
+    // Rooms as (left, width, floor, height), where the floor is the lowest clear row, keeping a
+    // solid border around the level
+    let slots = ((width - 2) / ROOM_SLOT_WIDTH).max(1);
+    let slot_width = (width - 2) / slots;
+    let mut rooms: Vec<(usize, usize, usize, usize)> = Vec::new();
+    for slot in 0..slots {
+        let room_width = rng
+            .random_range(ROOM_MIN_SIZE.0..=ROOM_MAX_SIZE.0)
+            .min(slot_width.saturating_sub(2).max(1));
+        let mut room_height = rng
+            .random_range(ROOM_MIN_SIZE.1..=ROOM_MAX_SIZE.1)
+            .min(height.saturating_sub(2).max(1));
+        let left = 1 + slot * slot_width + rng.random_range(0..slot_width - room_width);
+
+        let (mut min_floor, mut max_floor) = (room_height, height - 2);
+        if let Some(&(previous_left, previous_width, previous_floor, _)) = rooms.last() {
+            let span = (left + room_width / 2) - (previous_left + previous_width / 2);
+            let max_rise = span / CORRIDOR_STEP_LENGTH;
+            max_floor = max_floor.min(previous_floor + max_rise);
+            room_height = room_height.min(max_floor);
+            min_floor = room_height.max(previous_floor.saturating_sub(max_rise));
+        }
+        let floor = rng.random_range(min_floor..=max_floor);
+
+        for row in &mut tiles[floor + 1 - room_height..=floor] {
+            row[left..left + room_width].fill(EMPTY);
+        }
+        rooms.push((left, room_width, floor, room_height));
+    }
+
+    // Corridors run between room centres, so stairs can start and end inside the rooms
+    for pair in rooms.windows(2) {
+        let (from_left, from_width, mut floor, _) = pair[0];
+        let (to_left, to_width, to_floor, _) = pair[1];
+
+        for (step, x) in (from_left + from_width / 2..=to_left + to_width / 2).enumerate() {
+            if step % CORRIDOR_STEP_LENGTH == 0 && floor != to_floor {
+                floor = if to_floor > floor { floor + 1 } else { floor - 1 };
+            }
+            for row in &mut tiles[floor + 1 - CORRIDOR_HEIGHT..=floor] {
+                row[x] = EMPTY;
+            }
+        }
+    }
+
+    tiles
+}
+
+/// Smooths random noise into caves, then fills in every pocket except the largest so the cave is
+/// one connected space
+fn generate_caves(rng: &mut impl Rng, width: usize, height: usize) -> Vec<Vec<u32>> {
+    let border = |x: usize, y: usize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+
+    let mut tiles: Vec<Vec<u32>> = (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    if border(x, y) || rng.random_bool(CAVE_FILL_CHANCE) {
+                        SOLID
+                    } else {
+                        EMPTY
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    for _ in 0..CAVE_SMOOTHING_PASSES {
+        tiles = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let needed = if tiles[y][x] == EMPTY {
+                            CAVE_BIRTH_NEIGHBOURS
+                        } else {
+                            CAVE_SURVIVAL_NEIGHBOURS
+                        };
+                        if border(x, y) || solid_neighbours(&tiles, x, y) >= needed {
+                            SOLID
+                        } else {
+                            EMPTY
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    // Keep only the largest open region
+    let mut region = vec![vec![usize::MAX; width]; height];
+    let mut region_sizes = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if tiles[y][x] != EMPTY || region[y][x] != usize::MAX {
+                continue;
+            }
+
+            let id = region_sizes.len();
+            let mut size = 0;
+            let mut queue = VecDeque::from([(x, y)]);
+            region[y][x] = id;
+            while let Some((cx, cy)) = queue.pop_front() {
+                size += 1;
+                for (nx, ny) in [(cx - 1, cy), (cx + 1, cy), (cx, cy - 1), (cx, cy + 1)] {
+                    if tiles[ny][nx] == EMPTY && region[ny][nx] == usize::MAX {
+                        region[ny][nx] = id;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+            region_sizes.push(size);
+        }
+    }
+
+    let largest = (0..region_sizes.len()).max_by_key(|&id| region_sizes[id]);
+    for y in 0..height {
+        for x in 0..width {
+            if tiles[y][x] == EMPTY && Some(region[y][x]) != largest {
+                tiles[y][x] = SOLID;
+            }
+        }
+    }
+
+    tiles
+}
+
+/// Number of solid tiles among the eight around a tile
+fn solid_neighbours(tiles: &[Vec<u32>], x: usize, y: usize) -> usize {
+    tiles[y - 1..=y + 1]
+        .iter()
+        .enumerate()
+        .flat_map(|(dy, row)| {
+            row[x - 1..=x + 1]
+                .iter()
+                .enumerate()
+                .filter(move |&(dx, _)| (dx, dy) != (1, 1))
+        })
+        .filter(|&(_, &tile)| tile != EMPTY)
+        .count()
+}
+
+/// Clear tiles with solid ground right below and enough room above to stand in
+fn floor_cells(tiles: &[Vec<u32>]) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    for y in STANDING_ROOM..tiles.len() - 1 {
+        for (x, &below) in tiles[y + 1].iter().enumerate() {
+            let clear = tiles[y + 1 - STANDING_ROOM..=y].iter().all(|row| row[x] == EMPTY);
+            if clear && below != EMPTY {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+/// Centre of a tile in world pixels (the level is centred on the origin)
+fn cell_center(tiles: &[Vec<u32>], (x, y): (usize, usize)) -> Vec2 {
+    let half_size = Vec2::new(tiles[0].len() as f32, tiles.len() as f32) * LEVEL_GRID_SIZE / 2.0;
+    Vec2::new(
+        (x as f32 + 0.5) * LEVEL_GRID_SIZE - half_size.x,
+        half_size.y - (y as f32 + 0.5) * LEVEL_GRID_SIZE,
+    )
+}
+
+/// Endless random levels: the seed and style of the level being played, and where its exit is
+#[derive(Resource)]
+pub struct ProcgenRun {
+    pub seed: u64,
+    pub style: ProcgenStyle,
+    pub exit: Vec2,
+}
+
+impl ProcgenRun {
+    /// Generates the level for the current seed, remembering its exit
+    fn generate(&mut self) -> LevelSource {
+        let generated = generate_level(self.seed, self.style, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        self.exit = generated.exit;
+        generated.source
+    }
+}
+
+/// Level exit component: Moves on to the next random level when the player touches it
+#[derive(Component)]
+pub struct LevelExit;
+
+pub struct ProcgenPlugin;
+
+impl Plugin for ProcgenPlugin {
+    fn build(&self, app: &mut App) {
+        let args: Vec<String> = std::env::args().collect();
+        let Some(index) = args.iter().position(|arg| arg == PROCGEN_FLAG) else {
+            return;
+        };
+
+        // Style and seed can follow the flag in either order
+        let mut run = ProcgenRun {
+            seed: rand::rng().random(),
+            style: ProcgenStyle::default(),
+            exit: Vec2::ZERO,
+        };
+        for arg in args.iter().skip(index + 1).take(2) {
+            match arg.as_str() {
+                "rooms" => run.style = ProcgenStyle::Rooms,
+                "caves" => run.style = ProcgenStyle::Caves,
+                _ => match arg.parse() {
+                    Ok(seed) => run.seed = seed,
+                    Err(_) => break,
+                },
+            }
+        }
+
+        println!("Random level: {:?} seed {}", run.style, run.seed);
+        app.insert_resource(run.generate());
+        app.insert_resource(run);
+
+        app.add_systems(OnEnter(GameState::InGame), s_spawn_level_exit);
+        app.add_systems(
+            Update,
+            s_level_exit.after(s_sensors).run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Spawns the exit ring of the random level
+fn s_spawn_level_exit(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    run: Res<ProcgenRun>,
+) {
+    commands.spawn((
+        DespawnOnExit(GameState::InGame),
+        Transform::from_translation(run.exit.extend(EXIT_Z)),
+        Mesh2d(meshes.add(Annulus::new(EXIT_RADIUS - EXIT_THICKNESS, EXIT_RADIUS))),
+        MeshMaterial2d(materials.add(EXIT_COLOR)),
+        Sensor::new(EXIT_RADIUS),
+        LevelExit,
+    ));
+}
+
+/// Level exit system: Generates the next random level once the player reaches the exit
+fn s_level_exit(
+    mut commands: Commands,
+    exit_query: Query<&Sensor, With<LevelExit>>,
+    player_query: Query<Entity, With<ActiveCharacter>>,
+    mut run: ResMut<ProcgenRun>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(player) = player_query.single() else {
+        return;
+    };
+    if !exit_query
+        .iter()
+        .any(|sensor| sensor.overlapping_entities.contains(&player))
+    {
+        return;
+    }
+
+    run.seed = run.seed.wrapping_add(1);
+    commands.insert_resource(run.generate());
+    next_state.set(GameState::Loading);
+
+    println!("Random level: {:?} seed {}", run.style, run.seed);
+}
@@ -0,0 +1,460 @@
+//! Levels exported from the Tiled map editor (`.tmj` JSON or `.tmx` XML maps).
+//!
+//! Tile layers become the level's tile grid (later layers drawn over earlier ones) and point or
+//! rectangle objects become spawn points. A tile's code comes from its `code` property in the
+//! tileset, or from its position in the tileset otherwise: the first tile is a square (1), the
+//! next four are the right triangles (2 to 5), then the half-height triangles (6 to 9), the convex
+//! quarter circles (10 to 13) and the concave quarter circles (14 to 17), four of each. Flipped or
+//! rotated triangles and quarter circles are turned into the matching code.
+//!
+//! Objects are recognised by name or class: `player_spawn`, `ai_spawn` and `rest_point`. Any
+//! other level metadata can be given as JSON in a map property called `metadata`.
+
+use std::{collections::HashMap, fmt};
+
+use bevy::math::Vec2;
+use serde::Deserialize;
+
+use super::{LevelMetadata, LevelSource, LEVEL_GRID_SIZE};
+
+// Flags Tiled stores in the top bits of a tile id
+const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY_FLAG: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY_FLAG: u32 = 0x2000_0000;
+const TILE_ID_MASK: u32 = 0x0FFF_FFFF;
+
+// Names of the properties and objects read from the map
+const TILE_CODE_PROPERTY: &str = "code";
+const METADATA_PROPERTY: &str = "metadata";
+const PLAYER_SPAWN_OBJECT: &str = "player_spawn";
+const AI_SPAWN_OBJECT: &str = "ai_spawn";
+const REST_POINT_OBJECT: &str = "rest_point";
+
+/// A Tiled map that couldn't be imported
+#[derive(Debug)]
+pub enum TiledError {
+    Json(serde_json::Error),
+    Xml(roxmltree::Error),
+    /// The map is valid but uses something the importer doesn't handle
+    Unsupported(String),
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TiledError::Json(error) => write!(f, "invalid Tiled JSON: {error}"),
+            TiledError::Xml(error) => write!(f, "invalid Tiled XML: {error}"),
+            TiledError::Unsupported(message) => write!(f, "unsupported Tiled map: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+/// The parts of a Tiled map the importer uses, read from either file format
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tile_width: f32,
+    /// Tile ids of every tile layer, row by row
+    tile_layers: Vec<Vec<u32>>,
+    /// First tile id of each tileset
+    first_ids: Vec<u32>,
+    /// Tile codes set by tile properties, by tile id
+    tile_codes: HashMap<u32, u32>,
+    objects: Vec<TiledObject>,
+    /// Level metadata JSON from the map properties
+    metadata: Option<String>,
+}
+
+struct TiledObject {
+    /// Name, or class if the object has no name
+    kind: String,
+    /// Centre of the object (map pixels, y down)
+    position: Vec2,
+}
+
+impl TiledObject {
+    fn new(name: &str, class: &str, x: f32, y: f32, width: f32, height: f32) -> Self {
+        let kind = if name.is_empty() { class } else { name };
+
+        Self {
+            kind: kind.to_string(),
+            position: Vec2::new(x + width / 2.0, y + height / 2.0),
+        }
+    }
+}
+
+/// Reads a Tiled JSON map (`.tmj`)
+pub fn from_tmj(text: &str) -> Result<LevelSource, TiledError> {
+    let map: TmjMap = serde_json::from_str(text).map_err(TiledError::Json)?;
+    if map.infinite {
+        return Err(TiledError::Unsupported("infinite maps".to_string()));
+    }
+
+    let mut tiled_map = TiledMap {
+        width: map.width,
+        height: map.height,
+        tile_width: map.tilewidth,
+        tile_layers: Vec::new(),
+        first_ids: Vec::new(),
+        tile_codes: HashMap::new(),
+        objects: Vec::new(),
+        metadata: string_property(&map.properties, METADATA_PROPERTY),
+    };
+
+    for tileset in &map.tilesets {
+        tiled_map.first_ids.push(tileset.firstgid);
+        for tile in &tileset.tiles {
+            if let Some(code) = code_property(&tile.properties) {
+                tiled_map.tile_codes.insert(tileset.firstgid + tile.id, code);
+            }
+        }
+    }
+
+    read_tmj_layers(&map.layers, &mut tiled_map)?;
+
+    tiled_map.into_level_source()
+}
+
+/// Collects tile and object layers, including those inside groups
+fn read_tmj_layers(layers: &[TmjLayer], tiled_map: &mut TiledMap) -> Result<(), TiledError> {
+    for layer in layers {
+        match layer {
+            TmjLayer::TileLayer { data } => match data {
+                TmjTileData::Ids(ids) => tiled_map.tile_layers.push(ids.clone()),
+                TmjTileData::Encoded(_) => {
+                    return Err(TiledError::Unsupported(
+                        "encoded tile layers (use the CSV layer format)".to_string(),
+                    ));
+                }
+            },
+            TmjLayer::ObjectGroup { objects } => {
+                tiled_map.objects.extend(objects.iter().map(|object| {
+                    let class = object.class.as_deref().unwrap_or(&object.kind);
+                    TiledObject::new(
+                        &object.name,
+                        class,
+                        object.x,
+                        object.y,
+                        object.width,
+                        object.height,
+                    )
+                }));
+            }
+            TmjLayer::Group { layers } => read_tmj_layers(layers, tiled_map)?,
+            TmjLayer::Other => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a Tiled XML map (`.tmx`)
+pub fn from_tmx(text: &str) -> Result<LevelSource, TiledError> {
+    let document = roxmltree::Document::parse(text).map_err(TiledError::Xml)?;
+    let map = document.root_element();
+
+    if map.attribute("infinite") == Some("1") {
+        return Err(TiledError::Unsupported("infinite maps".to_string()));
+    }
+
+    let number = |node: roxmltree::Node, name: &str| -> f32 {
+        node.attribute(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0.0)
+    };
+
+    let mut tiled_map = TiledMap {
+        width: number(map, "width") as usize,
+        height: number(map, "height") as usize,
+        tile_width: number(map, "tilewidth"),
+        tile_layers: Vec::new(),
+        first_ids: Vec::new(),
+        tile_codes: HashMap::new(),
+        objects: Vec::new(),
+        metadata: map
+            .children()
+            .find(|node| node.has_tag_name("properties"))
+            .and_then(|properties| tmx_property(properties, METADATA_PROPERTY))
+            .map(str::to_string),
+    };
+
+    for tileset in map.children().filter(|node| node.has_tag_name("tileset")) {
+        let first_id = number(tileset, "firstgid") as u32;
+        tiled_map.first_ids.push(first_id);
+
+        for tile in tileset.children().filter(|node| node.has_tag_name("tile")) {
+            let code = tile
+                .children()
+                .find(|node| node.has_tag_name("properties"))
+                .and_then(|properties| tmx_property(properties, TILE_CODE_PROPERTY))
+                .and_then(|value| value.parse().ok());
+            if let Some(code) = code {
+                tiled_map
+                    .tile_codes
+                    .insert(first_id + number(tile, "id") as u32, code);
+            }
+        }
+    }
+
+    // Layers and object groups can be nested in groups, so search the whole document
+    for node in map.descendants() {
+        if node.has_tag_name("layer") {
+            let Some(data) = node.children().find(|child| child.has_tag_name("data")) else {
+                continue;
+            };
+
+            let ids = match data.attribute("encoding") {
+                Some("csv") => data
+                    .text()
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|id| id.trim().parse().unwrap_or(0))
+                    .collect(),
+                None => data
+                    .children()
+                    .filter(|child| child.has_tag_name("tile"))
+                    .map(|tile| number(tile, "gid") as u32)
+                    .collect(),
+                Some(encoding) => {
+                    return Err(TiledError::Unsupported(format!(
+                        "{encoding} tile layers (use the CSV layer format)"
+                    )));
+                }
+            };
+            tiled_map.tile_layers.push(ids);
+        } else if node.has_tag_name("object") {
+            let class = node
+                .attribute("class")
+                .or(node.attribute("type"))
+                .unwrap_or_default();
+            tiled_map.objects.push(TiledObject::new(
+                node.attribute("name").unwrap_or_default(),
+                class,
+                number(node, "x"),
+                number(node, "y"),
+                number(node, "width"),
+                number(node, "height"),
+            ));
+        }
+    }
+
+    tiled_map.into_level_source()
+}
+
+/// Value of a property in a `<properties>` element (multi-line values are stored as text)
+fn tmx_property<'a>(properties: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    properties
+        .children()
+        .find(|node| node.has_tag_name("property") && node.attribute("name") == Some(name))
+        .and_then(|property| property.attribute("value").or(property.text()))
+}
+
+impl TiledMap {
+    fn into_level_source(self) -> Result<LevelSource, TiledError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(TiledError::Unsupported("maps without tiles".to_string()));
+        }
+        if self.tile_width <= 0.0 {
+            return Err(TiledError::Unsupported("maps without a tile width".to_string()));
+        }
+
+        // Later layers are drawn over earlier ones
+        let mut tiles = vec![vec![0; self.width]; self.height];
+        for layer in &self.tile_layers {
+            if layer.len() != self.width * self.height {
+                return Err(TiledError::Unsupported(
+                    "tile layers that aren't the size of the map".to_string(),
+                ));
+            }
+
+            for (index, &id) in layer.iter().enumerate() {
+                if id & TILE_ID_MASK != 0 {
+                    tiles[index / self.width][index % self.width] = self.tile_code(id);
+                }
+            }
+        }
+
+        let mut metadata = match &self.metadata {
+            Some(json) => serde_json::from_str(json).map_err(TiledError::Json)?,
+            None => LevelMetadata::default(),
+        };
+
+        // Map pixels (origin at the top left, y down) to world pixels (origin at the centre)
+        let scale = LEVEL_GRID_SIZE / self.tile_width;
+        let half_size = Vec2::new(self.width as f32, self.height as f32) * LEVEL_GRID_SIZE / 2.0;
+        let to_world = |position: Vec2| {
+            Vec2::new(position.x * scale - half_size.x, half_size.y - position.y * scale)
+        };
+
+        let mut ai_spawns = Vec::new();
+        for object in &self.objects {
+            let position = to_world(object.position).to_array();
+            match object.kind.as_str() {
+                PLAYER_SPAWN_OBJECT => metadata.player_spawn = Some(position),
+                AI_SPAWN_OBJECT => ai_spawns.push(position),
+                REST_POINT_OBJECT => metadata.rest_points.push(position),
+                _ => {}
+            }
+        }
+        if !ai_spawns.is_empty() {
+            metadata.ai_spawns = Some(ai_spawns);
+        }
+
+        Ok(LevelSource {
+            tiles,
+            metadata,
+            baked: None,
+        })
+    }
+
+    /// Level tile code for a Tiled tile id (including its flip flags)
+    fn tile_code(&self, id: u32) -> u32 {
+        let tile_id = id & TILE_ID_MASK;
+
+        let code = self.tile_codes.get(&tile_id).copied().unwrap_or_else(|| {
+            // Position in the tileset the tile comes from (the one with the highest first id)
+            let first_id = self
+                .first_ids
+                .iter()
+                .copied()
+                .filter(|&first_id| first_id <= tile_id)
+                .max()
+                .unwrap_or(1);
+            tile_id - first_id + 1
+        });
+
+        orient_corner_tile(code, id)
+    }
+}
+
+/// Turns a triangle or quarter circle code into the one that matches the tile's flips
+fn orient_corner_tile(code: u32, id: u32) -> u32 {
+    // Every set lists its solid corner in the same order
+    let first_code = match code {
+        2..=5 => 2,
+        6..=9 => 6,
+        10..=13 => 10,
+        14..=17 => 14,
+        _ => return code,
+    };
+
+    // Which corner the right angle (or the quarter circle's solid corner) is in
+    let (mut left, mut bottom) = match code - first_code {
+        0 => (true, true),
+        1 => (false, true),
+        2 => (true, false),
+        _ => (false, false),
+    };
+
+    // Tiled applies the diagonal flip (swapping x and y) first. Swapping the axes of a
+    // half-height triangle would make it half-width, so only full-size tiles are turned.
+    if id & FLIPPED_DIAGONALLY_FLAG != 0 && first_code != 6 && left == bottom {
+        left = !left;
+        bottom = !bottom;
+    }
+    if id & FLIPPED_HORIZONTALLY_FLAG != 0 {
+        left = !left;
+    }
+    if id & FLIPPED_VERTICALLY_FLAG != 0 {
+        bottom = !bottom;
+    }
+
+    first_code
+        + match (left, bottom) {
+            (true, true) => 0,
+            (false, true) => 1,
+            (true, false) => 2,
+            (false, false) => 3,
+        }
+}
+
+#[derive(Deserialize)]
+struct TmjMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<TmjLayer>,
+    #[serde(default)]
+    tilesets: Vec<TmjTileset>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TmjLayer {
+    #[serde(rename = "tilelayer")]
+    TileLayer { data: TmjTileData },
+    #[serde(rename = "objectgroup")]
+    ObjectGroup { objects: Vec<TmjObject> },
+    Group { layers: Vec<TmjLayer> },
+    /// Image layers and anything newer
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TmjTileData {
+    Ids(Vec<u32>),
+    /// Base64 data, possibly compressed
+    Encoded(#[allow(dead_code)] String),
+}
+
+#[derive(Deserialize)]
+struct TmjObject {
+    #[serde(default)]
+    name: String,
+    /// Object class (called `type` before Tiled 1.9)
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: Option<String>,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+}
+
+#[derive(Deserialize)]
+struct TmjTileset {
+    firstgid: u32,
+    #[serde(default)]
+    tiles: Vec<TmjTile>,
+}
+
+#[derive(Deserialize)]
+struct TmjTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+fn string_property(properties: &[TmjProperty], name: &str) -> Option<String> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .and_then(|property| property.value.as_str())
+        .map(str::to_string)
+}
+
+fn code_property(properties: &[TmjProperty]) -> Option<u32> {
+    properties
+        .iter()
+        .find(|property| property.name == TILE_CODE_PROPERTY)
+        .and_then(|property| property.value.as_u64())
+        .map(|code| code as u32)
+}
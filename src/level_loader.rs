@@ -0,0 +1,379 @@
+use std::{collections::HashMap, fmt};
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::{
+        io::Reader, Asset, AssetApp, AssetEvent, AssetLoader, AssetServer, Assets, Handle,
+        LoadContext,
+    },
+    ecs::{
+        message::MessageReader,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Res, ResMut},
+    },
+    prelude::Resource,
+    reflect::TypePath,
+    state::state::NextState,
+};
+use serde::Deserialize;
+
+use crate::{
+    game_state::GameState,
+    level::{
+        baked::{self, BakedLevelError, BAKED_LEVEL_EXTENSION},
+        tiled,
+        tiled::TiledError,
+        LevelSource, LEVEL_DATA,
+    },
+    progress::UnlockCondition,
+    save::SaveData,
+};
+
+// Bundled level file (relative to the assets folder) and the name it goes by
+const LEVEL_ASSET_PATH: &str = "level.json";
+const START_LEVEL: &str = "main";
+
+// Level names and the files they load (file paths relative to the assets folder)
+const LEVEL_LIST_PATH: &str = "assets/levels.json";
+
+// Command-line flag picking the level to start in
+const LEVEL_FLAG: &str = "--level";
+
+/// Level file loaded through the asset server, so edits on disk can be picked up while running
+#[derive(Asset, TypePath)]
+pub struct LevelAsset {
+    pub source: LevelSource,
+    /// Contents of the file (compared to tell real edits from reloads of the same level)
+    pub contents: Vec<u8>,
+}
+
+/// A level file that couldn't be loaded
+#[derive(Debug)]
+pub enum LevelLoadError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Tiled(TiledError),
+    Baked(BakedLevelError),
+    /// The tile grid has no tiles
+    EmptyGrid,
+    /// A row of the tile grid (counting from 0 at the top) isn't as long as the first one
+    RaggedGrid {
+        row: usize,
+        length: usize,
+        expected: usize,
+    },
+}
+
+impl fmt::Display for LevelLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelLoadError::Io(error) => write!(f, "failed to read level: {error}"),
+            LevelLoadError::Json(error) => write!(f, "invalid level: {error}"),
+            LevelLoadError::Tiled(error) => write!(f, "{error}"),
+            LevelLoadError::Baked(error) => write!(f, "{error}"),
+            LevelLoadError::EmptyGrid => write!(f, "invalid level: no tiles"),
+            LevelLoadError::RaggedGrid {
+                row,
+                length,
+                expected,
+            } => write!(f, "invalid level: tile row {row} has {length} tiles, not {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for LevelLoadError {}
+
+/// Reads level files: bare tile grids or tiles with metadata (`.json`), Tiled maps (`.tmj`,
+/// `.tmx`) and binary levels (`.clvl`)
+#[derive(Default, TypePath)]
+pub struct LevelAssetLoader;
+
+impl AssetLoader for LevelAssetLoader {
+    type Asset = LevelAsset;
+    type Settings = ();
+    type Error = LevelLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut contents = Vec::new();
+        reader
+            .read_to_end(&mut contents)
+            .await
+            .map_err(LevelLoadError::Io)?;
+
+        let extension = load_context.path().extension().and_then(|extension| extension.to_str());
+        let source = parse_level_file(extension, &contents)?;
+
+        Ok(LevelAsset { source, contents })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json", "tmj", "tmx", BAKED_LEVEL_EXTENSION]
+    }
+}
+
+/// Reads a level file in the format its extension names (level JSON unless it is a Tiled map or
+/// a binary level). Files whose tile grid is empty or ragged are rejected rather than built.
+pub fn parse_level_file(
+    extension: Option<&str>,
+    contents: &[u8],
+) -> Result<LevelSource, LevelLoadError> {
+    if extension == Some(BAKED_LEVEL_EXTENSION) {
+        return baked::decode(contents).map_err(LevelLoadError::Baked);
+    }
+
+    let text = String::from_utf8_lossy(contents);
+    let source = match extension {
+        Some("tmj") => tiled::from_tmj(&text).map_err(LevelLoadError::Tiled)?,
+        Some("tmx") => tiled::from_tmx(&text).map_err(LevelLoadError::Tiled)?,
+        _ => LevelSource::from_json(&text).map_err(LevelLoadError::Json)?,
+    };
+    check_grid(&source.tiles)?;
+
+    Ok(source)
+}
+
+/// Makes sure the tile grid is a rectangle with tiles in it, which building a level relies on
+fn check_grid(tiles: &[Vec<u32>]) -> Result<(), LevelLoadError> {
+    let Some(width) = tiles.first().map(Vec::len).filter(|&width| width > 0) else {
+        return Err(LevelLoadError::EmptyGrid);
+    };
+
+    match tiles.iter().position(|row| row.len() != width) {
+        Some(row) => Err(LevelLoadError::RaggedGrid {
+            row,
+            length: tiles[row].len(),
+            expected: width,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Level files the game can switch between, by name, and which of them is built.
+///
+/// Call `load_level` to switch: once the file has loaded, the game leaves the current level and
+/// enters the new one (see `GameState`).
+#[derive(Resource)]
+pub struct LevelManager {
+    levels: HashMap<String, Handle<LevelAsset>>,
+    /// What it takes to play each locked level (levels not listed are always open)
+    unlocks: HashMap<String, UnlockCondition>,
+    current: String,
+    /// File contents of the level that is currently built
+    built_contents: Vec<u8>,
+    /// Level to switch to once its file has loaded
+    pending: Option<String>,
+}
+
+impl LevelManager {
+    /// Switches to the named level as soon as its file has loaded.
+    ///
+    /// Returns false (and changes nothing) if no level has that name or it is still locked.
+    pub fn load_level(&mut self, name: &str) -> bool {
+        if !self.levels.contains_key(name) {
+            eprintln!("Unknown level {name}");
+            return false;
+        }
+        if let Some(condition) = self.unlocks.get(name) {
+            if !condition.is_met(&SaveData::load().unwrap_or_default()) {
+                eprintln!("Level {name} is locked: {}", condition.describe());
+                return false;
+            }
+        }
+
+        self.pending = Some(name.to_string());
+        true
+    }
+
+    /// Whether the current level's file has finished loading (or failed to), so a level built
+    /// now won't be rebuilt as soon as the file arrives
+    pub fn current_file_settled(&self, asset_server: &AssetServer) -> bool {
+        self.levels
+            .get(&self.current)
+            .is_none_or(|handle| !asset_server.load_state(handle).is_loading())
+    }
+
+    /// Name of the level that is built (or being built)
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Every level's name, in alphabetical order
+    pub fn level_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.levels.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The named level, once its file has loaded
+    pub fn level_source<'a>(
+        &self,
+        name: &str,
+        level_assets: &'a Assets<LevelAsset>,
+    ) -> Option<&'a LevelSource> {
+        let handle = self.levels.get(name)?;
+        level_assets.get(handle).map(|level_asset| &level_asset.source)
+    }
+
+    /// Adds a level that isn't read from a file (such as the daily challenge's) and makes it the
+    /// current level
+    pub fn add_generated_level(
+        &mut self,
+        name: &str,
+        source: LevelSource,
+        level_assets: &mut Assets<LevelAsset>,
+    ) {
+        let handle = level_assets.add(LevelAsset {
+            source,
+            contents: Vec::new(),
+        });
+        self.levels.insert(name.to_string(), handle);
+        self.current = name.to_string();
+        self.built_contents = Vec::new();
+    }
+
+    /// What it takes to play the named level, if it starts out locked
+    pub fn unlock_condition(&self, name: &str) -> Option<&UnlockCondition> {
+        self.unlocks.get(name)
+    }
+}
+
+/// Level list entry: the level file's path, or the path and what it takes to unlock the level
+/// (`{ "path": "...", "unlock": { "collectibles": 10, "completed": ["main"] } }`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LevelListEntry {
+    Path(String),
+    Locked {
+        path: String,
+        unlock: UnlockCondition,
+    },
+}
+
+/// Reads the level list (level name to file), falling back to just the bundled level
+fn load_level_list() -> HashMap<String, LevelListEntry> {
+    let default = HashMap::from([(
+        START_LEVEL.to_string(),
+        LevelListEntry::Path(LEVEL_ASSET_PATH.to_string()),
+    )]);
+
+    match std::fs::read_to_string(LEVEL_LIST_PATH) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Failed to parse {LEVEL_LIST_PATH}, using the bundled level: {error}");
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+pub struct LevelLoaderPlugin;
+
+impl Plugin for LevelLoaderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<LevelAsset>();
+        app.register_asset_loader(LevelAssetLoader);
+
+        app.add_systems(Startup, s_init_level_manager);
+        app.add_systems(Update, (s_reload_level, s_switch_level.after(s_reload_level)));
+    }
+}
+
+/// Starts loading every level file (the bundled copy of the starting level is used until it
+/// arrives) and queues the level picked on the command line, if any
+fn s_init_level_manager(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mut levels = HashMap::new();
+    let mut unlocks = HashMap::new();
+    for (name, entry) in load_level_list() {
+        let path = match entry {
+            LevelListEntry::Path(path) => path,
+            LevelListEntry::Locked { path, unlock } => {
+                unlocks.insert(name.clone(), unlock);
+                path
+            }
+        };
+        levels.insert(name, asset_server.load(path));
+    }
+
+    let mut level_manager = LevelManager {
+        levels,
+        unlocks,
+        current: START_LEVEL.to_string(),
+        built_contents: LEVEL_DATA.to_vec(),
+        pending: None,
+    };
+
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(name) = args
+        .iter()
+        .position(|arg| arg == LEVEL_FLAG)
+        .and_then(|index| args.get(index + 1))
+    {
+        level_manager.load_level(name);
+    }
+
+    commands.insert_resource(level_manager);
+}
+
+/// Level reload system: Rebuilds the current level (through `GameState::Loading`) whenever its
+/// file on disk differs from the level that is built
+fn s_reload_level(
+    mut commands: Commands,
+    mut asset_events: MessageReader<AssetEvent<LevelAsset>>,
+    level_assets: Res<Assets<LevelAsset>>,
+    mut level_manager: ResMut<LevelManager>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(handle) = level_manager.levels.get(&level_manager.current).cloned() else {
+        return;
+    };
+
+    let changed = asset_events.read().any(|event| {
+        event.is_loaded_with_dependencies(&handle) || event.is_modified(&handle)
+    });
+    if !changed {
+        return;
+    }
+
+    let Some(level_asset) = level_assets.get(&handle) else {
+        return;
+    };
+    if level_asset.contents == level_manager.built_contents {
+        return;
+    }
+    level_manager.built_contents = level_asset.contents.clone();
+
+    commands.insert_resource(level_asset.source.clone());
+    next_state.set(GameState::Loading);
+
+    println!("Reloaded level {}", level_manager.current);
+}
+
+/// Level switch system: Once the level passed to `LevelManager::load_level` has loaded, leaves the
+/// current level so the new one is built in its place, with the player and AI agents at its spawn
+/// points
+fn s_switch_level(
+    mut commands: Commands,
+    level_assets: Res<Assets<LevelAsset>>,
+    mut level_manager: ResMut<LevelManager>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(name) = level_manager.pending.clone() else {
+        return;
+    };
+    let Some(level_asset) = level_assets.get(&level_manager.levels[&name]) else {
+        return;
+    };
+
+    level_manager.pending = None;
+    level_manager.current = name;
+    level_manager.built_contents = level_asset.contents.clone();
+
+    commands.insert_resource(level_asset.source.clone());
+    next_state.set(GameState::Loading);
+
+    println!("Switched to level {}", level_manager.current);
+}
@@ -0,0 +1,210 @@
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::Assets,
+    camera::visibility::Visibility,
+    color::Color,
+    ecs::{
+        component::Component,
+        query::With,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    prelude::Resource,
+    text::{TextColor, TextFont},
+    ui::{widget::Text, Node, PositionType, Val},
+};
+
+use crate::{
+    level_loader::{LevelAsset, LevelManager},
+    replay::{
+        PlaybackMode, ReplayPlayback, REPLAY_DIR, REPLAY_GHOST_KEY, REPLAY_TAKEOVER_KEY,
+    },
+    save::SaveData,
+};
+
+// Opens and closes the level select menu
+pub const LEVEL_SELECT_KEY: KeyCode = KeyCode::KeyL;
+// Number keys pick the first nine levels
+const LEVEL_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+// Menu layout (units: pixels)
+const MENU_FONT_SIZE: f32 = 18.0;
+const MENU_MARGIN: f32 = 16.0;
+const MENU_COLOR: Color = Color::WHITE;
+
+/// Whether the level select menu is open
+#[derive(Resource, Default)]
+pub struct LevelSelectMenu {
+    pub open: bool,
+}
+
+/// Marker for the level select menu text
+#[derive(Component)]
+struct LevelSelectText;
+
+pub struct LevelSelectPlugin;
+
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelSelectMenu>();
+
+        app.add_systems(Startup, s_spawn_level_select_text);
+        app.add_systems(Update, s_level_select);
+    }
+}
+
+/// Spawns the (hidden) level select menu text in the top-left corner of the window
+fn s_spawn_level_select_text(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: MENU_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(MENU_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(MENU_MARGIN),
+            left: Val::Px(MENU_MARGIN),
+            ..Default::default()
+        },
+        Visibility::Hidden,
+        LevelSelectText,
+    ));
+}
+
+/// Level select system: Opens the menu, listing every level with the progress saved for it, and
+/// switches to the level picked with the number keys, or imports the newest replay and switches
+/// to its level to play it back
+fn s_level_select(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut menu: ResMut<LevelSelectMenu>,
+    level_manager: Option<ResMut<LevelManager>>,
+    level_assets: Res<Assets<LevelAsset>>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<LevelSelectText>>,
+) {
+    let Some(mut level_manager) = level_manager else {
+        return;
+    };
+
+    let mut toggled = keyboard_input.just_pressed(LEVEL_SELECT_KEY);
+    if menu.open {
+        let names: Vec<String> =
+            level_manager.level_names().into_iter().map(str::to_string).collect();
+        let picked = LEVEL_KEYS
+            .iter()
+            .zip(&names)
+            .find(|(key, _)| keyboard_input.just_pressed(**key));
+
+        // Locked levels leave the menu open
+        if let Some((_, name)) = picked {
+            toggled |= level_manager.load_level(name);
+        }
+
+        let mode = if keyboard_input.just_pressed(REPLAY_GHOST_KEY) {
+            Some(PlaybackMode::Ghost)
+        } else if keyboard_input.just_pressed(REPLAY_TAKEOVER_KEY) {
+            Some(PlaybackMode::Takeover)
+        } else {
+            None
+        };
+        // Replays that can't be played leave the menu open, saying why
+        if let Some(mode) = mode {
+            match ReplayPlayback::import_newest(mode, &mut level_manager, &level_assets) {
+                Ok((playback, path)) => {
+                    println!("Replay: {}", playback.describe(&path));
+                    commands.insert_resource(playback);
+                    toggled = true;
+                }
+                Err(error) => {
+                    eprintln!("Failed to import a replay: {error}");
+                    let menu_text = format!(
+                        "{}\nCan't play the replay: {error}",
+                        level_list(&level_manager, &level_assets)
+                    );
+                    for (mut text, _) in text_query.iter_mut() {
+                        text.0 = menu_text.clone();
+                    }
+                }
+            }
+        }
+    }
+    if !toggled {
+        return;
+    }
+
+    menu.open = !menu.open;
+    let menu_text = if menu.open {
+        level_list(&level_manager, &level_assets)
+    } else {
+        String::new()
+    };
+
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        text.0 = menu_text.clone();
+        *visibility = if menu.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Menu text: one line per level with its completion, best time, collectibles found and deaths,
+/// or what it takes to unlock it
+fn level_list(level_manager: &LevelManager, level_assets: &Assets<LevelAsset>) -> String {
+    let save = SaveData::load().unwrap_or_default();
+    let mut lines = vec![format!("Levels (number to play, {LEVEL_SELECT_KEY:?} to close)")];
+
+    for (number, name) in (1..).zip(level_manager.level_names()) {
+        let current = if name == level_manager.current() {
+            " (playing)"
+        } else {
+            ""
+        };
+
+        if let Some(condition) = level_manager
+            .unlock_condition(name)
+            .filter(|condition| !condition.is_met(&save))
+        {
+            lines.push(format!("{number}. {name}{current}: locked, {}", condition.describe()));
+            continue;
+        }
+
+        let progress = save.levels.get(name).cloned().unwrap_or_default();
+        let completion = match progress.best_time {
+            Some(best_time) if progress.completed => format!("best {best_time:.2} s"),
+            _ if progress.completed => "completed".to_string(),
+            _ => "not completed".to_string(),
+        };
+        let collectibles = match level_manager.level_source(name, level_assets) {
+            Some(source) => format!(
+                "{}/{}",
+                progress.collected.len(),
+                source.metadata.collectibles.len()
+            ),
+            None => progress.collected.len().to_string(),
+        };
+        lines.push(format!(
+            "{number}. {name}{current}: {completion}, gems {collectibles}, deaths {}",
+            progress.deaths
+        ));
+    }
+    lines.push(format!(
+        "Newest replay in {REPLAY_DIR}/: {REPLAY_GHOST_KEY:?} to race its ghost, \
+         {REPLAY_TAKEOVER_KEY:?} to watch it"
+    ));
+
+    lines.join("\n")
+}
@@ -0,0 +1,143 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    camera::ClearColor,
+    color::{Color, ColorToComponents, LinearRgba, Mix},
+    ecs::{
+        change_detection::DetectChanges,
+        query::Changed,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res, ResMut},
+    },
+    math::ops,
+    prelude::Resource,
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    time::Time,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::level::LevelMesh;
+
+// Time of day constants (units: hours)
+const HOURS_PER_DAY: f32 = 24.0;
+const DEFAULT_HOUR: f32 = 12.0;
+
+// Ambient light colors multiplied into the level materials
+const DAY_AMBIENT: Color = Color::WHITE;
+const NIGHT_AMBIENT: Color = Color::srgb(0.25, 0.3, 0.5);
+
+// Background colors
+const DAY_SKY: Color = Color::srgb(0.05, 0.05, 0.08);
+const NIGHT_SKY: Color = Color::srgb(0.0, 0.0, 0.02);
+
+// AI vision range multiplier at midnight (scales up to 1.0 at noon)
+const NIGHT_VISION_MULTIPLIER: f32 = 0.5;
+
+/// How a level's time of day is chosen (read from level metadata)
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum TimeOfDaySetting {
+    /// Time stays fixed at the given hour
+    Static { hour: f32 },
+    /// Time advances from `start_hour`, taking `day_length` seconds for a full day
+    Cycle { start_hour: f32, day_length: f32 },
+}
+
+impl Default for TimeOfDaySetting {
+    fn default() -> Self {
+        TimeOfDaySetting::Static { hour: DEFAULT_HOUR }
+    }
+}
+
+/// Global time of day: Drives ambient lighting and AI vision ranges
+#[derive(Resource)]
+pub struct TimeOfDay {
+    /// Current hour in [0, 24)
+    pub hour: f32,
+    /// Real seconds for a full day, or None if time is static
+    pub day_length: Option<f32>,
+}
+
+impl TimeOfDay {
+    pub fn from_setting(setting: TimeOfDaySetting) -> Self {
+        match setting {
+            TimeOfDaySetting::Static { hour } => Self {
+                hour: hour.rem_euclid(HOURS_PER_DAY),
+                day_length: None,
+            },
+            TimeOfDaySetting::Cycle {
+                start_hour,
+                day_length,
+            } => Self {
+                hour: start_hour.rem_euclid(HOURS_PER_DAY),
+                day_length: Some(day_length),
+            },
+        }
+    }
+
+    /// Amount of daylight: 0.0 at midnight, 1.0 at noon
+    pub fn daylight(&self) -> f32 {
+        0.5 - 0.5 * ops::cos(self.hour / HOURS_PER_DAY * TAU)
+    }
+
+    /// Ambient light color for the current time
+    pub fn ambient_color(&self) -> Color {
+        NIGHT_AMBIENT.mix(&DAY_AMBIENT, self.daylight())
+    }
+
+    /// Multiplier applied to AI vision ranges (shorter at night)
+    pub fn vision_multiplier(&self) -> f32 {
+        NIGHT_VISION_MULTIPLIER + (1.0 - NIGHT_VISION_MULTIPLIER) * self.daylight()
+    }
+}
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TimeOfDay::from_setting(TimeOfDaySetting::default()));
+        app.add_systems(
+            Update,
+            (
+                s_advance_time_of_day,
+                s_apply_ambient_light.after(s_advance_time_of_day),
+            ),
+        );
+    }
+}
+
+/// Time of day system: Advances the clock when the level uses a cycle
+pub fn s_advance_time_of_day(time: Res<Time>, mut time_of_day: ResMut<TimeOfDay>) {
+    if let Some(day_length) = time_of_day.day_length {
+        if day_length > 0.0 {
+            let hours = time.delta_secs() / day_length * HOURS_PER_DAY;
+            time_of_day.hour = (time_of_day.hour + hours).rem_euclid(HOURS_PER_DAY);
+        }
+    }
+}
+
+/// Ambient light system: Tints the level materials and background by the time of day
+pub fn s_apply_ambient_light(
+    time_of_day: Res<TimeOfDay>,
+    mut clear_color: ResMut<ClearColor>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    level_mesh_query: Query<(&LevelMesh, &MeshMaterial2d<ColorMaterial>)>,
+    new_level_mesh_query: Query<(), Changed<LevelMesh>>,
+) {
+    if !time_of_day.is_changed() && new_level_mesh_query.is_empty() {
+        return;
+    }
+
+    let ambient = LinearRgba::from(time_of_day.ambient_color()).to_vec3();
+
+    for (level_mesh, material) in level_mesh_query.iter() {
+        if let Some(material) = materials.get_mut(&material.0) {
+            let base = LinearRgba::from(level_mesh.base_color);
+            material.color = Color::from(LinearRgba::from_vec3(base.to_vec3() * ambient));
+        }
+    }
+
+    clear_color.0 = NIGHT_SKY.mix(&DAY_SKY, time_of_day.daylight());
+}
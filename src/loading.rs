@@ -0,0 +1,285 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::AssetServer,
+    color::Color,
+    ecs::{
+        component::Component,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    prelude::Resource,
+    state::{
+        condition::in_state,
+        state::{NextState, OnEnter},
+        state_scoped::DespawnOnExit,
+    },
+    tasks::{futures::check_ready, AsyncComputeTaskPool, Task},
+    text::{TextColor, TextFont},
+    ui::{widget::Text, AlignItems, BackgroundColor, FlexDirection, JustifyContent, Node, Val},
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    ai::pathfinding::{
+        init_pathfinding_graph_with_progress, GraphBuildProgress, GraphDiagnostics,
+        PathfindingGraph,
+    },
+    daily::DailyChallenge,
+    deterministic::DeterministicSim,
+    game_state::GameState,
+    level::{generate_level_polygons, Level, LevelSource, LEVEL_GRID_SIZE},
+    level_loader::LevelManager,
+    movement_config::MovementConfig,
+};
+
+// Loading screen layout (units: pixels)
+const LOADING_FONT_SIZE: f32 = 24.0;
+const LOADING_BAR_WIDTH: f32 = 320.0;
+const LOADING_BAR_HEIGHT: f32 = 12.0;
+const LOADING_BAR_GAP: f32 = 12.0;
+const LOADING_TEXT_COLOR: Color = Color::WHITE;
+const LOADING_BAR_BACKGROUND: Color = Color::srgb(0.2, 0.2, 0.2);
+const LOADING_BAR_COLOR: Color = Color::WHITE;
+
+/// Whether levels are built on a background task behind a loading screen (off in headless runs,
+/// which step frame by frame and need the level as soon as the game starts)
+#[derive(Resource)]
+pub struct BackgroundLoading {
+    pub enabled: bool,
+}
+
+impl Default for BackgroundLoading {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl BackgroundLoading {
+    /// Off: levels are built on the spot when the game enters `GameState::Loading`
+    pub fn disabled() -> Self {
+        Self { enabled: false }
+    }
+}
+
+/// A level built from `LevelSource`, ready to spawn
+pub struct BuiltLevel {
+    pub level: Level,
+    pub pathfinding: PathfindingGraph,
+    /// What is left of the level's random number generator, for spawning the level's agents
+    pub rng: StdRng,
+}
+
+/// The level built in `GameState::Loading`, handed over to `s_enter_game`
+#[derive(Resource, Default)]
+pub struct PreparedLevel(pub Option<BuiltLevel>);
+
+/// Level build running on the async compute task pool
+#[derive(Resource)]
+struct LevelBuildTask {
+    task: Task<BuiltLevel>,
+    progress: GraphBuildProgress,
+}
+
+/// Marker for the loading screen text
+#[derive(Component)]
+struct LoadingText;
+
+/// Marker for the filled part of the loading bar
+#[derive(Component)]
+struct LoadingBar;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BackgroundLoading>();
+        app.init_resource::<PreparedLevel>();
+
+        app.add_systems(OnEnter(GameState::Loading), s_start_loading);
+        app.add_systems(
+            Update,
+            (s_finish_loading, s_update_loading_screen.after(s_finish_loading))
+                .run_if(in_state(GameState::Loading)),
+        );
+    }
+}
+
+/// Random number generator for building a level and spawning its agents (daily runs are seeded
+/// from the date so everyone gets the same run, and deterministic runs from their seed)
+pub fn level_rng(
+    daily: Option<&DailyChallenge>,
+    deterministic: Option<&DeterministicSim>,
+) -> StdRng {
+    match (daily, deterministic) {
+        (Some(daily), _) => daily.rng(),
+        (None, Some(deterministic)) => deterministic.rng(),
+        (None, None) => StdRng::from_os_rng(),
+    }
+}
+
+/// Builds the level's geometry and pathfinding graph (planning jumps for agents moving as
+/// `movement` says) from its source, reporting the graph build's progress
+pub fn build_level(
+    level_source: &LevelSource,
+    mut rng: StdRng,
+    movement: MovementConfig,
+    progress: &GraphBuildProgress,
+) -> BuiltLevel {
+    let mut pathfinding = PathfindingGraph::default();
+    let (level, diagnostics) = match &level_source.baked {
+        // Binary levels come with their geometry and pathfinding graph already built
+        Some(baked) => {
+            let level = baked.build(level_source, &mut rng, &mut pathfinding);
+            (level, GraphDiagnostics::of(&pathfinding))
+        }
+        None => {
+            let level = generate_level_polygons(level_source, LEVEL_GRID_SIZE, &mut rng);
+
+            // Initialize pathfinding graph
+            let diagnostics =
+                init_pathfinding_graph_with_progress(&level, &mut pathfinding, &movement, progress);
+
+            (level, diagnostics)
+        }
+    };
+
+    // The level is still played, with agents steering straight at their goals
+    if let Some(problem) = diagnostics.problem() {
+        eprintln!(
+            "The level's pathfinding graph can't be used: {problem} ({} nodes, {} links)",
+            diagnostics.nodes, diagnostics.connections
+        );
+    }
+
+    BuiltLevel {
+        level,
+        pathfinding,
+        rng,
+    }
+}
+
+/// Loading start system: Starts building the level in `LevelSource` in the background and shows
+/// the loading screen, or builds it straight away when background loading is off
+#[allow(clippy::too_many_arguments)]
+fn s_start_loading(
+    mut commands: Commands,
+    background_loading: Res<BackgroundLoading>,
+    level_source: Res<LevelSource>,
+    daily: Option<Res<DailyChallenge>>,
+    deterministic: Option<Res<DeterministicSim>>,
+    movement_config: Res<MovementConfig>,
+    mut prepared_level: ResMut<PreparedLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let rng = level_rng(daily.as_deref(), deterministic.as_deref());
+    let movement = *movement_config;
+
+    if !background_loading.enabled {
+        let progress = GraphBuildProgress::default();
+        prepared_level.0 = Some(build_level(&level_source, rng, movement, &progress));
+        next_state.set(GameState::InGame);
+        return;
+    }
+
+    // Replaces (and cancels) the build of a level that changed while it was loading
+    let progress = GraphBuildProgress::default();
+    let task_progress = progress.clone();
+    let level_source = level_source.clone();
+    let task = AsyncComputeTaskPool::get()
+        .spawn(async move { build_level(&level_source, rng, movement, &task_progress) });
+    commands.insert_resource(LevelBuildTask { task, progress });
+
+    spawn_loading_screen(&mut commands);
+}
+
+/// Loading screen: a line of text above a progress bar, centered in the window
+fn spawn_loading_screen(commands: &mut Commands) {
+    commands
+        .spawn((
+            DespawnOnExit(GameState::Loading),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(LOADING_BAR_GAP),
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Loading"),
+                TextFont {
+                    font_size: LOADING_FONT_SIZE,
+                    ..Default::default()
+                },
+                TextColor(LOADING_TEXT_COLOR),
+                LoadingText,
+            ));
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(LOADING_BAR_WIDTH),
+                        height: Val::Px(LOADING_BAR_HEIGHT),
+                        ..Default::default()
+                    },
+                    BackgroundColor(LOADING_BAR_BACKGROUND),
+                ))
+                .with_child((
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..Default::default()
+                    },
+                    BackgroundColor(LOADING_BAR_COLOR),
+                    LoadingBar,
+                ));
+        });
+}
+
+/// Loading finish system: Once the level is built and its file has loaded, hands the level over
+/// and goes in game
+fn s_finish_loading(
+    mut commands: Commands,
+    build_task: Option<ResMut<LevelBuildTask>>,
+    level_manager: Option<Res<LevelManager>>,
+    asset_server: Res<AssetServer>,
+    mut prepared_level: ResMut<PreparedLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(mut build_task) = build_task else {
+        return;
+    };
+
+    // Otherwise a level file that differs from the bundled copy would be built twice
+    let file_settled =
+        level_manager.is_none_or(|manager| manager.current_file_settled(&asset_server));
+    if !file_settled || !build_task.task.is_finished() {
+        return;
+    }
+
+    prepared_level.0 = check_ready(&mut build_task.task);
+    commands.remove_resource::<LevelBuildTask>();
+    next_state.set(GameState::InGame);
+}
+
+/// Loading screen system: Fills the loading bar as the level is built
+fn s_update_loading_screen(
+    build_task: Option<Res<LevelBuildTask>>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+    mut bar_query: Query<&mut Node, With<LoadingBar>>,
+) {
+    let Some(build_task) = build_task else {
+        return;
+    };
+    let fraction = build_task.progress.fraction();
+
+    for mut text in text_query.iter_mut() {
+        text.0 = format!("Loading {:.0}%", fraction * 100.0);
+    }
+    for mut node in bar_query.iter_mut() {
+        node.width = Val::Percent(fraction * 100.0);
+    }
+}
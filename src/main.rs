@@ -1,56 +1,280 @@
 mod ai;
+mod audio;
+mod bench;
+mod camera;
+mod characters;
+mod collectibles;
 mod collisions;
+mod combo;
+mod daily;
+mod debug;
+mod debug_panel;
+mod deterministic;
+mod doors;
+mod editor;
+mod encounters;
+mod forces;
+mod frame_budget;
+mod game_state;
+mod harness;
+mod hazards;
+mod health;
+mod input;
+mod integrators;
+mod jump_timing;
+mod knockback;
 mod level;
+mod level_loader;
+mod level_select;
+mod loading;
+mod lighting;
+mod memory;
+#[cfg(feature = "ml")]
+mod ml;
+mod movement_config;
+mod particles;
+mod pixel_perfect;
+mod platforms;
+mod progress;
+mod replay;
+mod rest_points;
+mod rumble;
+mod save;
+mod scenarios;
+mod screen_shake;
+mod selection;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod settings;
+mod spatial;
+mod sprites;
+mod surface_tilt;
 mod utils;
+mod weather;
 
 use ::bevy::prelude::*;
+use rand::{rngs::StdRng, seq::IndexedRandom, Rng, SeedableRng};
 use bevy::{app::AppExit, input::ButtonInput, window::PresentMode};
 use ai::{
-    pathfinding::{init_pathfinding_graph, PathfindingPlugin},
-    platformer_ai::{AIPhysics, PlatformerAI, PlatformerAIPlugin},
-    pursue_ai::{PursueAI, PursueAIState, PursueAIPlugin, PURSUE_AI_AGENT_RADIUS},
+    activity::AgentActivityPlugin,
+    alert::AIAlertPlugin,
+    brain::{AIGoal, Brain, BrainPlugin},
+    command::AICommandPlugin,
+    flying_ai::{FlyingAI, FlyingAIPlugin},
+    hearing::AIHearingPlugin,
+    metrics::{AIMetrics, AIMetricsPlugin},
+    path_requests::PathRequestPlugin,
+    pathfinding::{GraphBuildProgress, PathfindingPlugin},
+    patrol::{PatrolAI, PatrolAIPlugin},
+    profile::{AIProfile, AIProfilePlugin},
+    platformer_ai::{PlatformerAI, PlatformerAIPlugin},
+    pursue_ai::{AIRng, PursueAI, PursueAIState, PursueAIPlugin, PURSUE_AI_AGENT_RADIUS},
+    spawner::AISpawner,
+    territory::TerritoryPlugin,
+    tick::{AITick, AITickPlugin},
+    vision::{AIVision, AIVisionPlugin},
 };
-use collisions::{s_collision, s_debug_collision, CollisionPlugin};
-use level::{generate_level_polygons, Level};
+use audio::AudioPlugin;
+use camera::{spawn_game_camera, CameraControlsPlugin, CameraFollowPlugin};
+use characters::{ActiveCharacter, CharacterPlugin, CharacterProfile, PlayerTwo};
+use collectibles::{spawn_collectibles, CollectiblePlugin};
+use collisions::{s_player_contacts, sweep_circle, CollisionLayers, CollisionPlugin};
+use combo::ComboPlugin;
+use daily::{DailyChallenge, DailyChallengePlugin};
+use debug::DebugPlugin;
+use debug_panel::DebugPanelPlugin;
+use deterministic::{DeterministicSim, DeterministicSimPlugin};
+use doors::{spawn_doors, DoorPlugin};
+use editor::EditorPlugin;
+use encounters::{spawn_encounters, EncounterPlugin};
+use bench::BENCH_FLAG;
+use level::{
+    baked::BAKE_LEVEL_FLAG, procgen::{ProcgenPlugin, ProcgenRun}, spawn_level_meshes, Level,
+    LevelEditPlugin, LevelSource,
+};
+use level_loader::LevelLoaderPlugin;
+use level_select::LevelSelectPlugin;
+use loading::{build_level, level_rng, BuiltLevel, LoadingPlugin, PreparedLevel};
+use forces::ForceZonePlugin;
+use frame_budget::FrameBudgetPlugin;
+use game_state::{GameState, GameStatePlugin};
+use hazards::{spawn_hazards, HazardPlugin};
+use health::{Health, HealthPlugin, SpawnPoint};
+use input::{InputAction, InputActionPlugin, KeyAction, KeyBindings, PlayerTwoInput};
+use integrators::COMPARE_INTEGRATORS_FLAG;
+use jump_timing::JumpTimingPlugin;
+use knockback::Mass;
+use lighting::{LightingPlugin, TimeOfDay};
+use movement_config::MovementConfig;
+use particles::ParticlePlugin;
+use platforms::{spawn_moving_platforms, MovingPlatformPlugin};
+use progress::{spawn_level_goal, ProgressPlugin};
+use replay::ReplayPlugin;
+use rest_points::{spawn_rest_points, RestPointPlugin};
+use rumble::RumblePlugin;
+use save::SaveData;
+use scenarios::SCENARIOS_FLAG;
+use screen_shake::ScreenShakePlugin;
+use selection::SelectionPlugin;
+#[cfg(feature = "scripting")]
+use scripting::{spawn_script_triggers, ScriptingPlugin};
+use settings::Settings;
+use spatial::SpatialIndexPlugin;
+use sprites::BodySpritePlugin;
+use surface_tilt::{SurfaceTilt, SurfaceTiltPlugin};
+use serde::Deserialize;
+use weather::{spawn_wind, Weather, WeatherPlugin};
 
 // Floating point comparison epsilon
 const EPSILON: f32 = 1e-6;
 
 fn main() {
+    // Headless benchmark runs don't open a window
+    if std::env::args().any(|arg| arg == BENCH_FLAG) {
+        bench::run();
+        return;
+    }
+
+    // Headless integrator comparison runs don't open a window either
+    if std::env::args().any(|arg| arg == COMPARE_INTEGRATORS_FLAG) {
+        integrators::run_comparison();
+        return;
+    }
+
+    // Neither does converting a level file to a binary level
+    if std::env::args().any(|arg| arg == BAKE_LEVEL_FLAG) {
+        level::baked::run_bake();
+        return;
+    }
+
+    // Or running the scenario files
+    if std::env::args().any(|arg| arg == SCENARIOS_FLAG) {
+        scenarios::run();
+        return;
+    }
+
+    // Or the machine-learning environment, which is driven over stdin/stdout
+    #[cfg(feature = "ml")]
+    if std::env::args().any(|arg| arg == ml::ML_ENV_FLAG) {
+        ml::run_env();
+        return;
+    }
+
+    let settings = Settings::load();
+
+    // Pixel-perfect mode samples every texture with nearest-neighbor filtering
+    let image_plugin = if settings.pixel_perfect {
+        ImagePlugin::default_nearest()
+    } else {
+        ImagePlugin::default()
+    };
+
     App::new()
-        .insert_resource(ClearColor(Color::srgb(0.0, 0.0, 0.0)))
-        .insert_resource(InputDir { dir: Vec2::ZERO })
-        .insert_resource(ShouldExit(false))
-        .insert_resource(GizmosVisible { visible: false })
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Advanced Character Controller".to_string(),
-                present_mode: PresentMode::AutoNoVsync,
-                ..default()
-            }),
-            ..default()
-        }))
-        .add_plugins(CollisionPlugin)
-        .add_plugins(PathfindingPlugin)
-        .add_plugins(PlatformerAIPlugin)
-        .add_plugins(PursueAIPlugin)
-        // Startup systems
-        .add_systems(Startup, s_init)
-        // Update systems
-        .add_systems(Update, s_input)
-        .add_systems(Update, s_handle_gizmo_toggle)
-        .add_systems(Update, s_movement.after(s_input))
-        .add_systems(Update, s_timers.after(s_collision))
-        .add_systems(Update, s_debug_collision.after(s_collision))
-        .add_systems(Update, s_render.after(s_timers))
-        // Exit system runs last to ensure clean shutdown
-        .add_systems(Update, s_exit.after(s_render))
+        .insert_resource(settings)
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "Advanced Character Controller".to_string(),
+                        present_mode: PresentMode::AutoNoVsync,
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .set(image_plugin),
+        )
+        .add_plugins(GamePlugin)
         .run();
 }
 
-#[derive(Resource)]
-pub struct InputDir {
-    pub dir: Vec2,
+/// Game plugin: Everything the game adds on top of the engine plugins (shared by the windowed
+/// game and headless benchmark runs)
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClearColor(Color::srgb(0.0, 0.0, 0.0)))
+            .insert_resource(ShouldExit(false))
+            .insert_resource(GizmosVisible { visible: false })
+            .init_resource::<Settings>()
+            .init_resource::<LevelSource>()
+            .insert_resource(MovementConfig::load())
+            .add_message::<ControllerEvent>()
+            .add_plugins(GameStatePlugin)
+            .add_plugins(LoadingPlugin)
+            .add_plugins(FrameBudgetPlugin)
+            .add_plugins(EditorPlugin)
+            .add_plugins(InputActionPlugin)
+            .add_plugins(CharacterPlugin)
+            .add_plugins(RumblePlugin)
+            .add_plugins(ScreenShakePlugin)
+            .add_plugins(AudioPlugin)
+            .add_plugins(SurfaceTiltPlugin)
+            .add_plugins(BodySpritePlugin)
+            .add_plugins(DailyChallengePlugin)
+            .add_plugins(DeterministicSimPlugin)
+            .add_plugins(CollisionPlugin)
+            .add_plugins(PathfindingPlugin)
+            .add_plugins(PlatformerAIPlugin)
+            .add_plugins(FlyingAIPlugin)
+            .add_plugins(PatrolAIPlugin)
+            .add_plugins(TerritoryPlugin)
+            .add_plugins(PursueAIPlugin)
+            .add_plugins(BrainPlugin)
+            .add_plugins(AIAlertPlugin)
+            .add_plugins(AIHearingPlugin)
+            .add_plugins(AITickPlugin)
+            .add_plugins(AIVisionPlugin)
+            .add_plugins(AIProfilePlugin)
+            .add_plugins(AgentActivityPlugin)
+            .add_plugins(SpatialIndexPlugin)
+            .add_plugins(DebugPlugin)
+            .add_plugins(JumpTimingPlugin)
+            .add_plugins(DebugPanelPlugin)
+            .add_plugins(SelectionPlugin)
+            .add_plugins(AICommandPlugin)
+            .add_plugins(AIMetricsPlugin)
+            .add_plugins(PathRequestPlugin)
+            .add_plugins(CameraControlsPlugin)
+            .add_plugins(CameraFollowPlugin)
+            .add_plugins(LightingPlugin)
+            .add_plugins(ForceZonePlugin)
+            .add_plugins(WeatherPlugin)
+            .add_plugins(ParticlePlugin)
+            .add_plugins(HealthPlugin)
+            .add_plugins(HazardPlugin)
+            .add_plugins(DoorPlugin)
+            .add_plugins(RestPointPlugin)
+            .add_plugins(ComboPlugin)
+            .add_plugins(EncounterPlugin)
+            .add_plugins(MovingPlatformPlugin)
+            .add_plugins(LevelEditPlugin)
+            .add_plugins(ProcgenPlugin)
+            .add_plugins(LevelLoaderPlugin)
+            .add_plugins(CollectiblePlugin)
+            .add_plugins(ProgressPlugin)
+            .add_plugins(LevelSelectPlugin)
+            .add_plugins(ReplayPlugin);
+
+        #[cfg(feature = "scripting")]
+        app.add_plugins(ScriptingPlugin);
+
+        #[cfg(feature = "ml")]
+        app.add_plugins(ml::MlPlugin);
+
+        app
+            // Startup systems
+            .add_systems(Startup, s_init)
+            // Level lifecycle (see `GameState`)
+            .add_systems(OnEnter(GameState::InGame), s_enter_game)
+            // Update systems
+            .add_systems(Update, s_input)
+            .add_systems(Update, s_handle_gizmo_toggle)
+            .add_systems(Update, s_movement.after(s_input))
+            .add_systems(Update, s_timers.after(s_player_contacts))
+            // Exit system runs last to ensure clean shutdown
+            .add_systems(Last, s_exit);
+    }
 }
 
 #[derive(Resource)]
@@ -61,40 +285,59 @@ pub struct GizmosVisible {
     pub visible: bool,
 }
 
-// Movement constants (units: pixels/second)
-// Converted from 5.0 pixels/frame at 60fps = 300.0 pixels/second
-pub const PLAYER_MAX_SPEED: f32 = 300.0;
-
-// Acceleration scalers (units: 1/second)
-// These control how quickly velocity approaches target velocity
-// First value: acceleration rate when input is active (1/second)
-// Second value: deceleration rate when input is inactive (1/second)
-// Converted from frame-based: 0.2 per frame at 60fps = 12.0 per second
-pub const PLAYER_ACCELERATION_SCALERS: (f32, f32) = (12.0, 24.0);
-
-// Timer constants (units: seconds)
-// These represent the duration windows for jump buffering, coyote time, and wall contact
-// Originally 10 frames at 60fps = 0.166 seconds
-pub const MAX_JUMP_TIMER: f32 = 0.166;
-pub const MAX_GROUNDED_TIMER: f32 = 0.166;
-pub const MAX_WALLED_TIMER: f32 = 0.166;
-
-// Physics constants
-// Velocity constants (units: pixels/second)
-// Converted from frame-based: multiply by 60 (frames/second)
-pub const JUMP_VELOCITY: f32 = 540.0; // 9.0 pixels/frame * 60
-pub const WALL_JUMP_VELOCITY_Y: f32 = 270.0; // 4.5 pixels/frame * 60
-pub const WALL_JUMP_VELOCITY_X: f32 = 468.0; // 7.8 pixels/frame * 60
-
-// Gravity constant (units: pixels/second²)
-// Converted from frame-based: 0.5 pixels/frame² at 60fps = 1800.0 pixels/second²
-pub const GRAVITY_STRENGTH: f32 = 1800.0;
-
-// Wall jump acceleration reduction (unitless multiplier)
-pub const WALL_JUMP_ACCELERATION_REDUCTION: f32 = 0.5;
-
-// Jump release velocity divisor (unitless)
-pub const JUMP_RELEASE_VELOCITY_DIVISOR: f32 = 3.0;
+// Player collision radius (units: pixels)
+pub const PLAYER_RADIUS: f32 = 12.0;
+
+// Hit points (hazard hits take 1 each)
+pub const PLAYER_MAX_HEALTH: f32 = 3.0;
+pub const AI_MAX_HEALTH: f32 = 3.0;
+
+/// Agent variants that differ in how hard they are to knock around
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AIVariant {
+    Light,
+    Normal,
+    Heavy,
+}
+
+impl AIVariant {
+    pub const ALL: [AIVariant; 3] = [AIVariant::Light, AIVariant::Normal, AIVariant::Heavy];
+
+    pub fn mass(self) -> f32 {
+        match self {
+            AIVariant::Light => 0.5,
+            AIVariant::Normal => 1.0,
+            AIVariant::Heavy => 4.0,
+        }
+    }
+
+    /// Fraction of the ground acceleration the variant can steer with in the air
+    pub fn air_control(self) -> f32 {
+        match self {
+            AIVariant::Light => 0.3,
+            AIVariant::Normal => 0.15,
+            AIVariant::Heavy => 0.05,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            AIVariant::Light => Color::srgb(1.0, 0.5, 0.5),
+            AIVariant::Normal => Color::srgb(1.0, 0.0, 0.0),
+            AIVariant::Heavy => Color::srgb(0.6, 0.0, 0.0),
+        }
+    }
+}
+
+// Thickness of the ring used to render bodies (units: pixels)
+const BODY_OUTLINE_THICKNESS: f32 = 1.5;
+// Color of flying AI agents
+const FLYING_AI_COLOR: Color = Color::srgb(0.8, 0.4, 1.0);
+// Width of the base line drawn inside bodies, and how far below the center it sits (fractions of
+// the body radius)
+const BODY_BASE_WIDTH: f32 = 1.2;
+const BODY_BASE_DEPTH: f32 = 0.5;
 
 // Collision detection thresholds
 // NORMAL_DOT_THRESHOLD: Minimum dot product for considering a surface a "wall" (0.8 ≈ 37°)
@@ -104,11 +347,17 @@ pub const GROUND_NORMAL_Y_THRESHOLD: f32 = 0.01;
 // CEILING_NORMAL_Y_THRESHOLD: Maximum Y component of normal to be considered "ceiling"
 pub const CEILING_NORMAL_Y_THRESHOLD: f32 = -0.01;
 
-/// Player component: Contains gameplay state (timers, jump state, wall contact)
+/// Player component: Contains a controllable character's gameplay state (timers, jump state, wall
+/// contact); the character in control also has `ActiveCharacter`
 #[derive(Component)]
 pub struct Player {
     /// Jump buffer timer: Time remaining (seconds) to execute a buffered jump input
     jump_timer: f32,
+    /// Move direction held this frame, with the analog magnitude from gamepad sticks (zero while
+    /// nobody controls the character)
+    move_dir: Vec2,
+    /// Move direction held when the jump was pressed (picks the wall a buffered jump leaves from)
+    jump_input_dir: Vec2,
     /// Coyote time timer: Time remaining (seconds) player can still jump after leaving ground
     grounded_timer: f32,
     /// Wall contact timer: Time remaining (seconds) player is considered touching a wall
@@ -121,11 +370,61 @@ pub struct Player {
     is_grounded: bool,
     /// Last wall normal vector (for wall jump direction calculation)
     last_wall_normal: Option<Vec2>,
+    /// Whether the player is sliding down a wall they are pressing into
+    wall_sliding: bool,
+    /// Whether the dash button was pressed this frame
+    dash_requested: bool,
+    /// Dash timer: Time remaining (seconds) of the current dash
+    dash_timer: f32,
+    /// Dash cooldown timer: Time remaining (seconds) before the player can dash again
+    dash_cooldown_timer: f32,
+    /// Whether the player has used their air dash (reset on landing)
+    has_air_dashed: bool,
+    /// Air jumps used since the player last touched the ground or a wall
+    air_jumps_used: u32,
 }
 
-/// Physics component: Contains pure physics state (position, velocity, acceleration, collision)
+impl Player {
+    /// Coyote time left (seconds) to jump after leaving the ground
+    pub fn grounded_timer(&self) -> f32 {
+        self.grounded_timer
+    }
+
+    /// Time left (seconds) the player still counts as touching a wall
+    pub fn wall_timer(&self) -> f32 {
+        self.wall_timer
+    }
+
+    /// Whether the player is sliding down a wall they are pressing into
+    pub fn wall_sliding(&self) -> bool {
+        self.wall_sliding
+    }
+}
+
+/// A movement action a character performed this frame, for gameplay systems to react to
+#[derive(Message, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControllerEvent {
+    /// The character that performed it
+    pub entity: Entity,
+    pub action: ControllerAction,
+}
+
+/// Movement actions performed by the player controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControllerAction {
+    Jump,
+    AirJump,
+    WallJump,
+    Dash,
+    /// Started sliding down a wall (`collisions::WallSliding` reports the slide while it lasts)
+    WallSlide,
+}
+
+/// Kinematic body component: Pure physics state shared by the player and AI agents (position,
+/// velocity, acceleration, collision)
+/// Any entity with this component is resolved against the level by `s_collision`
 #[derive(Component)]
-pub struct Physics {
+pub struct KinematicBody {
     /// Previous frame's position (for collision detection)
     pub prev_position: Vec2,
     /// Current velocity vector (pixels/second)
@@ -136,49 +435,274 @@ pub struct Physics {
     pub radius: f32,
     /// Surface normal at current position (zero if not touching surface)
     pub normal: Vec2,
+    /// Directions away from each surface touched this frame (filled in by collision)
+    pub contacts: Vec<Vec2>,
+    /// Velocity of the moving platform the body is standing on (filled in by collision)
+    pub ground_velocity: Vec2,
+    /// Ids of the level polygons touched this frame (filled in by collision)
+    pub touching_polygons: Vec<usize>,
+}
+
+impl KinematicBody {
+    /// Whether a surface with this normal (pointing away from it) counts as ground
+    pub fn is_ground_normal(normal: Vec2) -> bool {
+        normal.y > GROUND_NORMAL_Y_THRESHOLD
+    }
+
+    /// Whether one of this frame's contacts is ground
+    pub fn on_ground(&self) -> bool {
+        self.contacts
+            .iter()
+            .any(|contact| Self::is_ground_normal(*contact))
+    }
 }
 
 /// Initial setup system
-pub fn s_init(mut commands: Commands, pathfinding: ResMut<ai::pathfinding::PathfindingGraph>) {
+pub fn s_init(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    settings: Res<Settings>,
+) {
     // Spawn camera
-    commands.spawn((Camera2d, Transform::default()));
+    spawn_game_camera(&mut commands, &mut images, &settings);
+}
 
-    // Spawn player
-    let initial_position = Vec3::new(0.0, -50.0, 0.0);
-    commands.spawn((
-        Transform::from_translation(initial_position),
-        Physics {
-            prev_position: initial_position.xy(),
-            velocity: Vec2::ZERO,
-            acceleration: Vec2::ZERO,
-            radius: 12.0,
-            normal: Vec2::ZERO,
-        },
-        Player {
-            jump_timer: 0.0,
-            grounded_timer: 0.0,
-            wall_timer: 0.0,
-            wall_direction: 0.0,
-            has_wall_jumped: false,
-            is_grounded: false,
-            last_wall_normal: None,
-        },
-    ));
+/// Level enter system: Spawns the level built in `GameState::Loading` and the player and AI
+/// agents at its spawn points (everything is torn down again when the game leaves
+/// `GameState::InGame`)
+#[allow(clippy::too_many_arguments)]
+pub fn s_enter_game(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut pathfinding: ResMut<ai::pathfinding::PathfindingGraph>,
+    mut prepared_level: ResMut<PreparedLevel>,
+    settings: Res<Settings>,
+    level_source: Res<LevelSource>,
+    daily: Option<Res<DailyChallenge>>,
+    procgen: Option<Res<ProcgenRun>>,
+    deterministic: Option<Res<DeterministicSim>>,
+    movement_config: Res<MovementConfig>,
+    mut entered_before: Local<bool>,
+) {
+    // Levels are built while loading; anything entering the game directly builds it here
+    let BuiltLevel {
+        level,
+        pathfinding: level_pathfinding,
+        mut rng,
+    } = prepared_level.0.take().unwrap_or_else(|| {
+        let rng = level_rng(daily.as_deref(), deterministic.as_deref());
+        let progress = GraphBuildProgress::default();
+        build_level(&level_source, rng, *movement_config, &progress)
+    });
+    *pathfinding = level_pathfinding;
+
+    // Spawn player (at the last rest point if there is a save, except in daily runs, random
+    // levels and deterministic runs; the save only applies to the level the game starts in)
+    let initial_position = SaveData::load()
+        .filter(|_| {
+            daily.is_none() && procgen.is_none() && deterministic.is_none() && !*entered_before
+        })
+        .and_then(|save| save.respawn_position())
+        .unwrap_or(level_source.metadata.player_spawn_position())
+        .extend(0.0);
+    *entered_before = true;
+    let player = spawn_character(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        initial_position.xy(),
+        CharacterProfile::Standard,
+    );
+    commands.entity(player).insert(ActiveCharacter);
+
+    // The second player starts in the same spot (characters don't collide with each other)
+    if settings.two_player {
+        let player_two = spawn_character(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            initial_position.xy(),
+            CharacterProfile::Standard,
+        );
+        commands.entity(player_two).insert(PlayerTwo);
+    }
+
+    // The level's other characters wait for the player to switch to them
+    for character in &level_source.metadata.characters {
+        spawn_character(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            Vec2::from(character.position),
+            character.profile,
+        );
+    }
+
+    // Init level
+    spawn_level(&mut commands, &mut meshes, &mut materials, &settings, level);
+
+    // Spawn AI agents (randomly placed on the graph with random variants in daily runs)
+    let ai_spawner = match &daily {
+        Some(daily) => AISpawner::at_points(daily.ai_spawn_positions(
+            &pathfinding,
+            &mut rng,
+            initial_position.xy(),
+            PURSUE_AI_AGENT_RADIUS,
+        )),
+        None => AISpawner::from_metadata(&level_source.metadata),
+    };
+    ai_spawner.spawn(&mut commands, &mut meshes, &mut materials, || match &daily {
+        Some(_) => *AIVariant::ALL.choose(&mut rng).unwrap(),
+        None => AIVariant::Normal,
+    });
+
+    // Flying agents are only placed by the level metadata
+    for &position in &level_source.metadata.flying_ai_spawns {
+        let agent =
+            spawn_flying_ai_agent(&mut commands, &mut meshes, &mut materials, Vec2::from(position));
+        commands
+            .entity(agent)
+            .insert(level_source.metadata.ai_profile.profile());
+    }
 
-    // Spawn AI agent
-    let ai_initial_position = Vec3::new(0.0, -250.0, 0.0);
+    // Each patrol route gets an agent of its own, starting at the route's first waypoint
+    for route in &level_source.metadata.patrol_routes {
+        let Some(&start) = route.waypoints.first() else {
+            continue;
+        };
+        let agent = spawn_ai_agent(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            Vec2::from(start),
+            AIVariant::Normal,
+        );
+        commands.entity(agent).insert((
+            level_source.metadata.ai_profile.profile(),
+            PatrolAI::from_setting(route),
+        ));
+    }
+
+    // AI decisions draw from the same seed so daily runs play out the same way
+    commands.insert_resource(AIRng(StdRng::seed_from_u64(rng.random())));
+}
+
+/// Spawns a controllable character moving with `profile` (not yet the one in control)
+pub fn spawn_character(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec2,
+    profile: CharacterProfile,
+) -> Entity {
+    commands
+        .spawn((
+            DespawnOnExit(GameState::InGame),
+            Transform::from_translation(position.extend(0.0)),
+            KinematicBody {
+                prev_position: position,
+                velocity: Vec2::ZERO,
+                acceleration: Vec2::ZERO,
+                radius: PLAYER_RADIUS,
+                normal: Vec2::ZERO,
+                contacts: Vec::new(),
+                ground_velocity: Vec2::ZERO,
+                touching_polygons: Vec::new(),
+            },
+            Mesh2d(meshes.add(body_mesh(PLAYER_RADIUS))),
+            MeshMaterial2d(materials.add(profile.color())),
+            SurfaceTilt::default(),
+            Health::new(PLAYER_MAX_HEALTH),
+            Mass(profile.mass()),
+            CollisionLayers::new(CollisionLayers::PLAYER, CollisionLayers::AGENT),
+            SpawnPoint(position),
+            profile,
+            Player {
+                jump_timer: 0.0,
+                move_dir: Vec2::ZERO,
+                jump_input_dir: Vec2::ZERO,
+                grounded_timer: 0.0,
+                wall_timer: 0.0,
+                wall_direction: 0.0,
+                has_wall_jumped: false,
+                is_grounded: false,
+                last_wall_normal: None,
+                wall_sliding: false,
+                dash_requested: false,
+                dash_timer: 0.0,
+                dash_cooldown_timer: 0.0,
+                has_air_dashed: false,
+                air_jumps_used: 0,
+            },
+        ))
+        .id()
+}
+
+/// Spawns a built level: the level meshes and everything placed by the level metadata, then
+/// inserts the `Level` resource
+pub fn spawn_level(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    settings: &Settings,
+    level: Level,
+) {
+    // Spawn the level meshes once; gizmo linestrips are only drawn for debugging
+    spawn_level_meshes(commands, meshes, materials, &level);
+
+    // Hazards are placed by the level metadata
+    spawn_hazards(commands, meshes, materials, &level);
+    spawn_doors(commands, meshes, materials, &level);
+    spawn_rest_points(commands, meshes, materials, &level);
+    spawn_collectibles(commands, meshes, materials, &level);
+    spawn_level_goal(commands, meshes, materials, &level);
+    spawn_encounters(commands, &level);
+    spawn_moving_platforms(commands, &level);
+    #[cfg(feature = "scripting")]
+    spawn_script_triggers(commands, &level);
+
+    // Time of day comes from the level metadata
+    commands.insert_resource(TimeOfDay::from_setting(level.metadata.time_of_day));
+
+    // Weather comes from the level metadata, unless disabled in the settings
+    commands.insert_resource(Weather::new(level.metadata.weather, settings));
+    spawn_wind(commands, &level);
+
+    commands.insert_resource(level);
+}
+
+/// Spawns a pursuing AI agent
+pub fn spawn_ai_agent(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec2,
+    variant: AIVariant,
+) -> Entity {
     commands.spawn((
-        Transform::from_translation(ai_initial_position),
-        AIPhysics {
-            prev_position: ai_initial_position.xy(),
+        DespawnOnExit(GameState::InGame),
+        Transform::from_translation(position.extend(0.0)),
+        KinematicBody {
+            prev_position: position,
             velocity: Vec2::ZERO,
             acceleration: Vec2::ZERO,
             radius: PURSUE_AI_AGENT_RADIUS,
             normal: Vec2::ZERO,
-            grounded: false,
-            walled: 0,
-            has_wall_jumped: false,
+            contacts: Vec::new(),
+            ground_velocity: Vec2::ZERO,
+            touching_polygons: Vec::new(),
         },
+        Mesh2d(meshes.add(body_mesh(PURSUE_AI_AGENT_RADIUS))),
+        MeshMaterial2d(materials.add(variant.color())), // Shades of red for AI
+        SurfaceTilt::default(),
+        Health::new(AI_MAX_HEALTH),
+        Mass(variant.mass()),
+        CollisionLayers::new(
+            CollisionLayers::AGENT,
+            CollisionLayers::PLAYER | CollisionLayers::AGENT,
+        ),
+        SpawnPoint(position),
         PlatformerAI {
             current_target_node: None,
             jump_from_pos: None,
@@ -186,236 +710,394 @@ pub fn s_init(mut commands: Commands, pathfinding: ResMut<ai::pathfinding::Pathf
             cached_path: None,
             last_goal_position: None,
             current_path_index: 0,
+            move_dir: Vec2::ZERO,
+            corridor: None,
+            grounded: false,
+            walled: 0,
+            has_wall_jumped: false,
+            air_control: variant.air_control(),
+            replan_timer: 0.0,
         },
-        PursueAI {
-            state: PursueAIState::Pursue,  // Start in Pursue mode
-            current_wander_goal: None,
-        },
-    ));
-
-    // Init level
-    {
-        let grid_size = 32.0;
-
-        let level = generate_level_polygons(grid_size);
-
-        // Initialize pathfinding graph
-        init_pathfinding_graph(&level, pathfinding);
+        PursueAI::new(PursueAIState::Pursue), // Start in Pursue mode
+        AIVision::default(),
+        (
+            Brain::default(),
+            AIGoal::default(),
+            AITick::default(),
+            AIProfile::default(),
+            AIMetrics::default(),
+        ),
+    ))
+    .id()
+}
 
-        commands.insert_resource(level);
-    }
+/// Spawns a flying AI agent: chases like a pursuing agent, but flies through open space instead
+/// of running and jumping along surfaces
+pub fn spawn_flying_ai_agent(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec2,
+) -> Entity {
+    commands.spawn((
+        DespawnOnExit(GameState::InGame),
+        Transform::from_translation(position.extend(0.0)),
+        KinematicBody {
+            prev_position: position,
+            velocity: Vec2::ZERO,
+            acceleration: Vec2::ZERO,
+            radius: PURSUE_AI_AGENT_RADIUS,
+            normal: Vec2::ZERO,
+            contacts: Vec::new(),
+            ground_velocity: Vec2::ZERO,
+            touching_polygons: Vec::new(),
+        },
+        Mesh2d(meshes.add(body_mesh(PURSUE_AI_AGENT_RADIUS))),
+        MeshMaterial2d(materials.add(FLYING_AI_COLOR)),
+        Health::new(AI_MAX_HEALTH),
+        Mass(AIVariant::Light.mass()),
+        CollisionLayers::new(
+            CollisionLayers::AGENT,
+            CollisionLayers::PLAYER | CollisionLayers::AGENT,
+        ),
+        SpawnPoint(position),
+        FlyingAI::default(),
+        PursueAI::new(PursueAIState::Pursue), // Start in Pursue mode
+        AIVision::default(),
+        (
+            Brain::default(),
+            AIGoal::default(),
+            AITick::default(),
+            AIProfile::default(),
+            AIMetrics::default(),
+        ),
+    ))
+    .id()
 }
 
-/// Input system
+/// Input system: Hands the frame's input to the characters in control (the first player's input
+/// to the active character, the second player's to theirs)
+#[allow(clippy::type_complexity)]
 pub fn s_input(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_action: Res<InputAction>,
+    player_two_input: Res<PlayerTwoInput>,
     mut should_exit: ResMut<ShouldExit>,
-    mut input_dir: ResMut<InputDir>,
-    mut player_query: Query<(&mut Player, &mut Physics)>,
+    movement_config: Res<MovementConfig>,
+    mut player_query: Query<(
+        &mut Player,
+        &mut KinematicBody,
+        &CharacterProfile,
+        Has<ActiveCharacter>,
+        Has<PlayerTwo>,
+    )>,
 ) {
-    // Escape to exit - set flag for dedicated exit system to handle
-    if keyboard_input.just_pressed(KeyCode::Escape) {
+    // Escape (by default) to exit - set flag for dedicated exit system to handle
+    if input_action.exit {
         should_exit.0 = true;
         return;
     }
 
-    if let Ok((mut player_data, mut player_physics)) = player_query.single_mut() {
-        let mut direction = Vec2::ZERO;
+    for (mut player_data, mut player_physics, profile, active, player_two) in
+        player_query.iter_mut()
+    {
+        let input_action = if active {
+            &*input_action
+        } else if player_two {
+            &player_two_input.0
+        } else {
+            player_data.move_dir = Vec2::ZERO;
+            continue;
+        };
+        let config = profile.movement_config(&movement_config);
 
-        // Arrow keys to move
-        if keyboard_input.pressed(KeyCode::ArrowUp) {
-            direction.y += 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowDown) {
-            direction.y -= 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowLeft) {
-            direction.x -= 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowRight) {
-            direction.x += 1.0;
+        // Jump button pressed
+        if input_action.jump_pressed {
+            player_data.jump_timer = config.jump_buffer_time;
+            player_data.jump_input_dir = input_action.move_dir;
         }
 
-        // Space to jump
-        if keyboard_input.just_pressed(KeyCode::Space) {
-            player_data.jump_timer = MAX_JUMP_TIMER;
+        // Dash button pressed (executed in s_movement)
+        if input_action.dash_pressed {
+            player_data.dash_requested = true;
         }
 
-        // Variable jump height: reduce velocity if jump key released early
-        if keyboard_input.just_released(KeyCode::Space) && player_physics.velocity.y > EPSILON {
-            player_physics.velocity.y /= JUMP_RELEASE_VELOCITY_DIVISOR;
+        // Variable jump height: reduce velocity if jump button released early
+        if input_action.jump_released && player_physics.velocity.y > EPSILON {
+            player_physics.velocity.y /= config.jump_release_velocity_divisor;
         }
 
-        // Normalize direction
-        direction = direction.normalize_or_zero();
-
-        // Set direction resource
-        input_dir.dir = direction;
+        // Keeps the analog magnitude from gamepad sticks
+        player_data.move_dir = input_action.move_dir;
     }
 }
 
-/// Movement system
-/// Implements frame-rate independent physics using delta time and semi-implicit Euler integration
+/// Movement system: Moves every character, steering each with its player's input (characters
+/// nobody controls get none)
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn s_movement(
-    mut player_query: Query<(&mut Transform, &mut Physics, &mut Player)>,
-    input_dir: Res<InputDir>,
+    mut player_query: Query<(
+        &mut Transform,
+        &mut KinematicBody,
+        &mut Player,
+        &CharacterProfile,
+        Entity,
+    )>,
+    level: Res<Level>,
+    weather: Res<Weather>,
+    movement_config: Res<MovementConfig>,
+    settings: Res<Settings>,
     time: Res<Time>,
+    mut controller_events: MessageWriter<ControllerEvent>,
 ) {
-    if let Ok((mut player_transform, mut player_physics, mut player_data)) =
-        player_query.single_mut()
+    for (mut player_transform, mut player_physics, mut player_data, profile, entity) in
+        player_query.iter_mut()
     {
         // Clamp delta time to prevent huge jumps on first frame or frame skips
         // Maximum delta time of 1/30th second (30 FPS minimum)
         let dt = time.delta_secs().min(1.0 / 30.0);
+        let config = profile.movement_config(&movement_config);
+        let move_dir = player_data.move_dir;
+        let mut controller_event = |action| {
+            controller_events.write(ControllerEvent { entity, action });
+        };
 
         // Use epsilon comparison for floating point values
         let player_falling = player_physics.normal.length_squared() < EPSILON;
-        let no_input = input_dir.dir.length_squared() < EPSILON;
+        let no_input = move_dir.length_squared() < EPSILON;
 
         // Rotate input according to the normal (compute locally, don't mutate resource)
-        let mut effective_input_dir = input_dir.dir;
+        let mut effective_input_dir = move_dir;
         if !no_input
             && !player_falling
-            && input_dir.dir.dot(player_physics.normal).abs() < NORMAL_DOT_THRESHOLD
+            && move_dir.dot(player_physics.normal).abs() < NORMAL_DOT_THRESHOLD
         {
             let mut new_input_dir = Vec2::new(player_physics.normal.y, -player_physics.normal.x);
 
-            if new_input_dir.dot(input_dir.dir) < 0.0 {
+            if new_input_dir.dot(move_dir) < 0.0 {
                 new_input_dir *= -1.0;
             }
 
-            effective_input_dir = new_input_dir;
+            // Keep the analog magnitude so a half-tilted stick still moves at half speed
+            effective_input_dir = new_input_dir * move_dir.length();
+        }
+
+        // Dashing
+        {
+            let can_dash = player_data.dash_cooldown_timer <= 0.0
+                && (player_data.grounded_timer > 0.0 || !player_data.has_air_dashed);
+
+            if player_data.dash_requested && can_dash {
+                // Dash in the input direction, or horizontally the way the player is moving
+                let dash_dir = if no_input {
+                    Vec2::new(
+                        if player_physics.velocity.x < 0.0 { -1.0 } else { 1.0 },
+                        0.0,
+                    )
+                } else {
+                    move_dir.normalize()
+                };
+
+                player_physics.velocity = dash_dir * config.dash_velocity;
+                player_data.dash_timer = config.dash_duration;
+                player_data.dash_cooldown_timer = config.dash_cooldown;
+                if player_data.grounded_timer <= 0.0 {
+                    player_data.has_air_dashed = true;
+                }
+                controller_event(ControllerAction::Dash);
+            }
+            player_data.dash_requested = false;
         }
+        let dashing = player_data.dash_timer > 0.0;
 
         // If the player is on a wall and is trying to move away from it
         let player_move_off_wall = player_physics.normal.x.abs() >= NORMAL_DOT_THRESHOLD
             && effective_input_dir.x.abs() >= NORMAL_DOT_THRESHOLD
             && player_physics.normal.x.signum() != effective_input_dir.x.signum();
 
+        // Wall sliding: pressing into the wall the player is on while airborne slides them down it
+        // (no faster than the slide speed) instead of clinging to it
+        let wall_sliding = !dashing
+            && player_data.grounded_timer <= 0.0
+            && player_physics.normal.x.abs() >= NORMAL_DOT_THRESHOLD
+            && move_dir.x.abs() >= NORMAL_DOT_THRESHOLD
+            && player_data
+                .last_wall_normal
+                .is_some_and(|wall_normal| wall_normal.x.signum() != move_dir.x.signum());
+        if wall_sliding {
+            if !player_data.wall_sliding {
+                controller_event(ControllerAction::WallSlide);
+            }
+
+            // Sliding keeps the player able to jump off the wall
+            player_data.wall_timer = config.wall_coyote_time;
+            player_data.has_wall_jumped = false;
+        }
+        player_data.wall_sliding = wall_sliding;
+
         // Calculate acceleration (units: pixels/second²)
-        {
+        // A function of velocity so higher order integrators can sample it mid-frame
+        let normal = player_physics.normal;
+        let has_wall_jumped = player_data.has_wall_jumped;
+        let surface_friction = weather.surface_friction();
+        let acceleration_at = |velocity: Vec2| {
             // Apply acceleration towards target velocity
             // This creates smooth acceleration/deceleration
-            player_physics.acceleration = (effective_input_dir * PLAYER_MAX_SPEED
-                - player_physics.velocity)
+            let mut acceleration = (effective_input_dir * config.max_speed - velocity)
                 * if no_input {
                     // Deceleration
-                    PLAYER_ACCELERATION_SCALERS.1
+                    config.acceleration_scalers.1
                 } else {
                     // Acceleration
-                    PLAYER_ACCELERATION_SCALERS.0
+                    config.acceleration_scalers.0
                 };
 
+            // Wet surfaces reduce grip while on the ground
+            if !player_falling {
+                acceleration *= surface_friction;
+            }
+
             // Wall jump physics - reduce acceleration after wall jump
-            player_physics.acceleration *= if player_data.has_wall_jumped {
-                WALL_JUMP_ACCELERATION_REDUCTION
+            acceleration *= if has_wall_jumped {
+                config.wall_jump_acceleration_reduction
             } else {
                 1.0
             };
 
-            // If the player is falling
-            if player_falling {
+            // Keep the dash burst intact until the dash ends
+            if dashing {
+                acceleration = Vec2::ZERO;
+            }
+
+            // If the player is falling (or sliding down a wall)
+            if player_falling || wall_sliding {
                 // Ignore any other acceleration in the y direction
-                player_physics.acceleration.y = 0.0;
+                acceleration.y = 0.0;
             }
-            // Unless the player is on a wall and is trying to move away from it
-            if !player_move_off_wall {
+            // Unless the player is on a wall and is trying to move away from it (or pressing into
+            // it to keep sliding down it)
+            if !player_move_off_wall && !wall_sliding {
                 // Remove the acceleration in the direction of the normal
                 // This prevents acceleration into walls
-                let acceleration_adjustment =
-                    player_physics.normal * player_physics.acceleration.dot(player_physics.normal);
-                player_physics.acceleration -= acceleration_adjustment;
+                acceleration -= normal * acceleration.dot(normal);
             }
-        }
+
+            acceleration
+        };
+        // The closure is handed to the integrator below as well
+        #[allow(clippy::redundant_closure_call)]
+        let acceleration = acceleration_at(player_physics.velocity);
+        player_physics.acceleration = acceleration;
 
         // Apply gravity directly to velocity (not additive to acceleration)
         // Gravity is a force that should be applied consistently each frame
         {
-            if player_move_off_wall || player_falling {
+            if dashing {
+                // Gravity is suppressed for the duration of the dash
+            } else if player_move_off_wall || player_falling || wall_sliding {
                 // Gravity goes down (negative Y)
-                player_physics.velocity.y -= GRAVITY_STRENGTH * dt;
+                player_physics.velocity.y -= config.gravity * dt;
             } else {
                 // Gravity goes towards the normal (for wall/ceiling walking)
-                let gravity_normal_dir = player_physics.normal * GRAVITY_STRENGTH * dt;
+                let gravity_normal_dir = player_physics.normal * config.gravity * dt;
                 player_physics.velocity += gravity_normal_dir;
             }
+
+            // The wall's friction caps how fast the player slides down it
+            if wall_sliding {
+                player_physics.velocity.y = player_physics.velocity.y.max(-config.wall_slide_speed);
+            }
         }
 
         // Jumping
         {
             // If the player is trying to jump
             if player_data.jump_timer > 0.0 {
+                // The way the player meant to go when they pressed jump (or is going, if they
+                // weren't steering), and whether they reach a wall that way before the buffered
+                // press runs out
+                let heading = if player_data.jump_input_dir.x.abs() > EPSILON {
+                    player_data.jump_input_dir.x.signum()
+                } else {
+                    player_physics.velocity.x.signum()
+                };
+                let buffer_left = (player_data.jump_timer - dt).max(0.0);
+                let position = player_transform.translation.xy();
+                let reach = heading * player_physics.velocity.x.abs() * buffer_left;
+                let wall_ahead = reach != 0.0
+                    && sweep_circle(
+                        &level,
+                        position,
+                        position + Vec2::new(reach, 0.0),
+                        player_physics.radius,
+                    )
+                    .is_some_and(|(_, normal)| normal.x.abs() >= NORMAL_DOT_THRESHOLD);
+                // Heading for a new wall away from the one just left: jump off the new one
+                let wall_jump_ready = player_data.wall_timer > 0.0
+                    && !(wall_ahead && heading == player_data.wall_direction);
+
                 // If on the ground
                 if player_data.grounded_timer > 0.0 {
                     // Jump
-                    player_physics.velocity.y = JUMP_VELOCITY;
+                    player_physics.velocity.y = config.jump_velocity;
                     player_data.jump_timer = 0.0;
                     player_data.grounded_timer = 0.0;
+                    controller_event(ControllerAction::Jump);
                 }
                 // If on a wall
-                else if player_data.wall_timer > 0.0 {
+                else if wall_jump_ready {
                     // Wall jump
-                    player_physics.velocity.y = WALL_JUMP_VELOCITY_Y;
-                    player_physics.velocity.x = player_data.wall_direction * WALL_JUMP_VELOCITY_X;
+                    let (wall_jump_x, wall_jump_y) = config.wall_jump_velocity;
+                    player_physics.velocity.y = wall_jump_y;
+                    player_physics.velocity.x = player_data.wall_direction * wall_jump_x;
                     player_data.jump_timer = 0.0;
                     player_data.wall_timer = 0.0;
                     player_data.wall_direction = 0.0;
                     player_data.has_wall_jumped = true;
+                    controller_event(ControllerAction::WallJump);
+                }
+                // If in the air with air jumps left (unless a wall jump is coming up, which keeps
+                // the press buffered)
+                else if !wall_ahead && player_data.air_jumps_used < config.max_air_jumps {
+                    // Air jump
+                    player_physics.velocity.y = config.jump_velocity;
+                    player_data.jump_timer = 0.0;
+                    player_data.air_jumps_used += 1;
+                    controller_event(ControllerAction::AirJump);
                 }
             }
         }
 
-        // Update physics using semi-implicit Euler integration
-        // 1. Update velocity: v(t+dt) = v(t) + a(t) * dt
-        // 2. Update position: x(t+dt) = x(t) + v(t+dt) * dt
-        // This is more stable than explicit Euler and preserves energy better
+        // Update physics with the configured integrator (semi-implicit Euler by default:
+        // v(t+dt) = v(t) + a(t) * dt, then x(t+dt) = x(t) + v(t+dt) * dt, which is more stable
+        // than explicit Euler and preserves energy better)
         player_physics.prev_position = player_transform.translation.xy();
 
-        // Apply acceleration to velocity (scaled by delta time)
-        let acceleration_dt = player_physics.acceleration * dt;
-        player_physics.velocity += acceleration_dt;
-
-        // Update position using new velocity (scaled by delta time)
-        let velocity_dt = player_physics.velocity * dt;
-        player_transform.translation.x += velocity_dt.x;
-        player_transform.translation.y += velocity_dt.y;
-    }
-}
-
-/// Render system
-pub fn s_render(
-    mut gizmos: Gizmos,
-    player_query: Query<(&Transform, &Physics), With<Player>>,
-    ai_query: Query<(&Transform, &AIPhysics), With<PursueAI>>,
-    level: Res<Level>,
-) {
-    // Draw level
-    for polygon in &level.polygons {
-        gizmos.linestrip_2d(polygon.points.iter().copied(), polygon.color);
-    }
-
-    // Draw player
-    if let Ok((player_transform, player_physics)) = player_query.single() {
-        gizmos.circle_2d(
-            player_transform.translation.xy(),
-            player_physics.radius,
-            Color::WHITE,
+        let (position, velocity) = settings.integrator.step(
+            player_physics.prev_position,
+            player_physics.velocity,
+            player_physics.acceleration,
+            acceleration_at,
+            dt,
         );
+        player_physics.velocity = velocity;
+        player_transform.translation.x = position.x;
+        player_transform.translation.y = position.y;
     }
+}
 
-    // Draw AI agents
-    for (ai_transform, ai_physics) in ai_query.iter() {
-        gizmos.circle_2d(
-            ai_transform.translation.xy(),
-            ai_physics.radius,
-            Color::srgb(1.0, 0.0, 0.0), // Red for AI
-        );
-    }
+/// Body mesh: a ring with a flat base line across its lower half, so leaning into slopes shows
+fn body_mesh(radius: f32) -> Mesh {
+    let mut mesh = Mesh::from(Annulus::new(radius - BODY_OUTLINE_THICKNESS, radius));
+    let base = Mesh::from(Rectangle::new(radius * BODY_BASE_WIDTH, BODY_OUTLINE_THICKNESS))
+        .translated_by(Vec3::new(0.0, -radius * BODY_BASE_DEPTH, 0.0));
+    mesh.merge(&base)
+        .expect("Ring and rectangle meshes should have the same vertex attributes");
+    mesh
 }
 
-/// Timer system: Decrements all timers by delta time
+/// Timer system: Decrements every character's timers by delta time
 pub fn s_timers(time: Res<Time>, mut player_query: Query<&mut Player>) {
-    if let Ok(mut player_data) = player_query.single_mut() {
+    for mut player_data in player_query.iter_mut() {
         let dt = time.delta_secs();
 
         if player_data.jump_timer > 0.0 {
@@ -444,16 +1126,25 @@ pub fn s_timers(time: Res<Time>, mut player_query: Query<&mut Player>) {
                 player_data.wall_direction = 0.0;
             }
         }
+
+        if player_data.dash_timer > 0.0 {
+            player_data.dash_timer = (player_data.dash_timer - dt).max(0.0);
+        }
+
+        if player_data.dash_cooldown_timer > 0.0 {
+            player_data.dash_cooldown_timer = (player_data.dash_cooldown_timer - dt).max(0.0);
+        }
     }
 }
 
 /// Gizmo toggle system: Toggles debug gizmo visibility with G key
 pub fn s_handle_gizmo_toggle(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
     mut gizmos_visible: ResMut<GizmosVisible>,
 ) {
-    // G to toggle gizmos
-    if keyboard_input.just_pressed(KeyCode::KeyG) {
+    // G (by default) to toggle gizmos
+    if key_bindings.just_pressed(KeyAction::ToggleGizmos, &keyboard_input) {
         gizmos_visible.visible = !gizmos_visible.visible;
     }
 }
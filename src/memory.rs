@@ -0,0 +1,124 @@
+use std::{collections::HashMap, fmt, mem::size_of};
+
+use crate::{
+    ai::pathfinding::{PathfindingGraph, PathfindingGraphConnection, PathfindingGraphNode},
+    level::Level,
+};
+
+const BYTES_PER_KIB: f64 = 1024.0;
+
+/// Approximate heap and inline memory used by the level and pathfinding data, with element counts.
+/// Sizes are estimated from container capacities, so they include reserved but unused space.
+pub struct MemoryReport {
+    pub polygon_count: usize,
+    pub polygon_point_count: usize,
+    pub polygon_bytes: usize,
+    pub node_count: usize,
+    pub walkable_edge_count: usize,
+    pub jumpable_edge_count: usize,
+    pub droppable_edge_count: usize,
+    pub graph_bytes: usize,
+    pub spatial_cell_count: usize,
+    pub spatial_entry_count: usize,
+    pub spatial_index_bytes: usize,
+}
+
+impl MemoryReport {
+    pub fn collect(level: &Level, pathfinding: &PathfindingGraph) -> Self {
+        let polygon_bytes = vec_bytes(&level.polygons)
+            + level
+                .polygons
+                .iter()
+                .map(|polygon| vec_bytes(&polygon.points))
+                .sum::<usize>()
+            + map_bytes(&level.polygon_grid)
+            + level.polygon_grid.values().map(vec_bytes).sum::<usize>();
+
+        let graph_bytes = vec_bytes(&pathfinding.nodes)
+            + pathfinding
+                .nodes
+                .iter()
+                .map(|node| {
+                    vec_bytes(&node.line_indicies)
+                        + vec_bytes(&node.walkable_connections)
+                        + vec_bytes(&node.jumpable_connections)
+                        + vec_bytes(&node.droppable_connections)
+                })
+                .sum::<usize>();
+
+        let spatial_index_bytes = map_bytes(&pathfinding.spatial_grid)
+            + pathfinding
+                .spatial_grid
+                .values()
+                .map(vec_bytes)
+                .sum::<usize>();
+
+        Self {
+            polygon_count: level.polygons.len(),
+            polygon_point_count: level.polygons.iter().map(|polygon| polygon.points.len()).sum(),
+            polygon_bytes,
+            node_count: pathfinding.nodes.len(),
+            walkable_edge_count: count_edges(pathfinding, |node| &node.walkable_connections),
+            jumpable_edge_count: count_edges(pathfinding, |node| &node.jumpable_connections),
+            droppable_edge_count: count_edges(pathfinding, |node| &node.droppable_connections),
+            graph_bytes,
+            spatial_cell_count: pathfinding.spatial_grid.len(),
+            spatial_entry_count: pathfinding.spatial_grid.values().map(Vec::len).sum(),
+            spatial_index_bytes,
+        }
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.polygon_bytes + self.graph_bytes + self.spatial_index_bytes
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Memory report")?;
+        writeln!(
+            f,
+            "  Level polygons:    {:>8.1} KiB  ({} polygons, {} points)",
+            kib(self.polygon_bytes),
+            self.polygon_count,
+            self.polygon_point_count
+        )?;
+        writeln!(
+            f,
+            "  Pathfinding graph: {:>8.1} KiB  ({} nodes, {} walkable / {} jumpable / {} droppable edges)",
+            kib(self.graph_bytes),
+            self.node_count,
+            self.walkable_edge_count,
+            self.jumpable_edge_count,
+            self.droppable_edge_count
+        )?;
+        writeln!(
+            f,
+            "  Spatial index:     {:>8.1} KiB  ({} cells, {} entries)",
+            kib(self.spatial_index_bytes),
+            self.spatial_cell_count,
+            self.spatial_entry_count
+        )?;
+        write!(f, "  Total:             {:>8.1} KiB", kib(self.total_bytes()))
+    }
+}
+
+fn count_edges(
+    pathfinding: &PathfindingGraph,
+    connections: impl Fn(&PathfindingGraphNode) -> &Vec<PathfindingGraphConnection>,
+) -> usize {
+    pathfinding.nodes.iter().map(|node| connections(node).len()).sum()
+}
+
+fn vec_bytes<T>(vec: &Vec<T>) -> usize {
+    vec.capacity() * size_of::<T>()
+}
+
+/// Buckets only; values that own heap memory have to be added separately
+fn map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (size_of::<K>() + size_of::<V>())
+}
+
+fn kib(bytes: usize) -> f64 {
+    bytes as f64 / BYTES_PER_KIB
+}
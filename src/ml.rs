@@ -0,0 +1,326 @@
+//! Observations and external actions for machine-learning experiments (`ml` feature).
+//!
+//! Every frame, `LatestObservation` is filled with what a learning agent can see: the player's
+//! and AI agents' bodies, distances to level geometry around the player and where everyone is on
+//! the pathfinding graph. An `ExternalAction` in `ExternalInput` replaces the player's input for
+//! the frame, going through `InputAction` like the keyboard and gamepads do, so the controller
+//! can't tell a policy from a person.
+//!
+//! `composite --ml-env` runs the environment headlessly over stdin/stdout: every line read is an
+//! action as JSON (e.g. `{"move_dir": [1.0, 0.0], "jump": true, "dash": false}`) or `reset`, and
+//! every line written is the observation after stepping one frame (or after resetting).
+//! `--record-observations <file>` writes every frame's observation as a line of JSON, in the
+//! windowed game as well.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+};
+
+use bevy::{
+    app::{App, Plugin, PostUpdate, PreUpdate},
+    ecs::{
+        query::{With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res, ResMut},
+    },
+    state::state::NextState,
+    math::{ops, Vec2, Vec3Swizzles},
+    prelude::Resource,
+    transform::components::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{pathfinding::PathfindingGraph, platformer_ai::PlatformerAI},
+    bench::headless_app,
+    characters::ActiveCharacter,
+    game_state::GameState,
+    health::Health,
+    input::{s_read_input_actions, InputAction},
+    level::{Aabb, Level, LevelSource},
+    utils::line_intersect,
+    KinematicBody, Player,
+};
+
+// Command-line flags
+pub const ML_ENV_FLAG: &str = "--ml-env";
+const RECORD_OBSERVATIONS_FLAG: &str = "--record-observations";
+
+// Line that restarts the headless environment
+const RESET_COMMAND: &str = "reset";
+// Frames a reset takes to tear the level down and build it again
+const RESET_FRAMES: usize = 2;
+
+// Geometry samples: rays cast evenly around the player, starting to the right and going
+// counterclockwise, each reporting the distance to the first level surface it hits (pixels)
+const GEOMETRY_RAY_COUNT: usize = 8;
+const GEOMETRY_RAY_LENGTH: f32 = 256.0;
+
+/// Position and motion of a body (world pixels, pixels/second)
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct BodyObservation {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    /// Whether the body is touching a surface
+    pub grounded: bool,
+    pub health: f32,
+}
+
+/// Where bodies are on the pathfinding graph
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct GraphObservation {
+    pub node_count: usize,
+    /// Graph node nearest the player
+    pub player_node: Option<usize>,
+    /// Graph node nearest each agent (in the order of `Observation::agents`)
+    pub agent_nodes: Vec<Option<usize>>,
+}
+
+/// Everything a learning agent sees in one frame
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Observation {
+    /// Frames since the game started
+    pub tick: u64,
+    /// `None` while the player doesn't exist (between levels)
+    pub player: Option<BodyObservation>,
+    pub agents: Vec<BodyObservation>,
+    /// Distance along each geometry ray to the level (`GEOMETRY_RAY_LENGTH` if it hits nothing)
+    pub geometry: Vec<f32>,
+    pub graph: GraphObservation,
+}
+
+/// Latest observation resource: Refilled at the end of every frame
+#[derive(Resource, Default)]
+pub struct LatestObservation(pub Observation);
+
+/// Player input from outside the game
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct ExternalAction {
+    /// Movement direction (clamped to length 1)
+    pub move_dir: [f32; 2],
+    /// Whether jump is held (pressing and releasing are worked out from the previous action)
+    pub jump: bool,
+    /// Whether dash is held
+    pub dash: bool,
+}
+
+/// External input resource: When an action is set, it replaces the player's input every frame
+#[derive(Resource, Default)]
+pub struct ExternalInput {
+    pub action: Option<ExternalAction>,
+    /// Action applied last frame (for press and release edges)
+    previous: ExternalAction,
+}
+
+/// Observation recorder resource: File every frame's observation is written to
+#[derive(Resource)]
+struct ObservationRecorder(BufWriter<File>);
+
+pub struct MlPlugin;
+
+impl Plugin for MlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LatestObservation>();
+        app.init_resource::<ExternalInput>();
+
+        app.add_systems(PreUpdate, s_apply_external_action.after(s_read_input_actions));
+        app.add_systems(PostUpdate, s_observe);
+
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(path) = args
+            .iter()
+            .position(|arg| arg == RECORD_OBSERVATIONS_FLAG)
+            .and_then(|index| args.get(index + 1))
+        {
+            match File::create(path) {
+                Ok(file) => {
+                    app.insert_resource(ObservationRecorder(BufWriter::new(file)));
+                    app.add_systems(PostUpdate, s_record_observation.after(s_observe));
+                }
+                Err(error) => eprintln!("Failed to create {path}: {error}"),
+            }
+        }
+    }
+}
+
+/// External action system: Replaces the player's input with the external action, if there is one
+fn s_apply_external_action(
+    mut external_input: ResMut<ExternalInput>,
+    mut input_action: ResMut<InputAction>,
+) {
+    let Some(action) = external_input.action else {
+        return;
+    };
+    let previous = external_input.previous;
+
+    input_action.move_dir = Vec2::from(action.move_dir).clamp_length_max(1.0);
+    input_action.jump_pressed = action.jump && !previous.jump;
+    input_action.jump_released = !action.jump && previous.jump;
+    input_action.dash_pressed = action.dash && !previous.dash;
+
+    external_input.previous = action;
+}
+
+/// Observation system: Fills `LatestObservation` from this frame's state
+#[allow(clippy::type_complexity)]
+fn s_observe(
+    player_query: Query<(&Transform, &KinematicBody, &Health), With<ActiveCharacter>>,
+    agent_query: Query<
+        (&Transform, &KinematicBody, &Health),
+        (With<PlatformerAI>, Without<Player>),
+    >,
+    level: Option<Res<Level>>,
+    pathfinding: Res<PathfindingGraph>,
+    mut latest: ResMut<LatestObservation>,
+) {
+    let player = player_query.single().ok().map(body_observation);
+    let agents: Vec<BodyObservation> = agent_query.iter().map(body_observation).collect();
+
+    let geometry = match (&player, &level) {
+        (Some(player), Some(level)) => {
+            geometry_samples(level, Vec2::from(player.position)).collect()
+        }
+        _ => vec![GEOMETRY_RAY_LENGTH; GEOMETRY_RAY_COUNT],
+    };
+
+    let graph = GraphObservation {
+        node_count: pathfinding.nodes.len(),
+        player_node: player.and_then(|player| nearest_node(&pathfinding, player.position)),
+        agent_nodes: agents
+            .iter()
+            .map(|agent| nearest_node(&pathfinding, agent.position))
+            .collect(),
+    };
+
+    latest.0 = Observation {
+        tick: latest.0.tick + 1,
+        player,
+        agents,
+        geometry,
+        graph,
+    };
+}
+
+/// Observation recording system: Writes the latest observation to the recording as a JSON line
+fn s_record_observation(latest: Res<LatestObservation>, mut recorder: ResMut<ObservationRecorder>) {
+    let result = serde_json::to_writer(&mut recorder.0, &latest.0)
+        .map_err(std::io::Error::from)
+        .and_then(|()| writeln!(recorder.0));
+    if let Err(error) = result {
+        eprintln!("Failed to record observation: {error}");
+    }
+}
+
+fn body_observation(
+    (transform, physics, health): (&Transform, &KinematicBody, &Health),
+) -> BodyObservation {
+    BodyObservation {
+        position: transform.translation.xy().to_array(),
+        velocity: physics.velocity.to_array(),
+        grounded: physics.normal.length_squared() > 0.0,
+        health: health.current,
+    }
+}
+
+/// Distances from `origin` to the level along each geometry ray
+fn geometry_samples(level: &Level, origin: Vec2) -> impl Iterator<Item = f32> + '_ {
+    (0..GEOMETRY_RAY_COUNT).map(move |index| {
+        let angle = index as f32 / GEOMETRY_RAY_COUNT as f32 * std::f32::consts::TAU;
+        let (sin, cos) = ops::sin_cos(angle);
+        let end = origin + Vec2::new(cos, sin) * GEOMETRY_RAY_LENGTH;
+
+        level
+            .query_aabb(&Aabb::from_points(&[origin, end]))
+            .flat_map(|polygon| polygon.points.windows(2))
+            .filter_map(|line| line_intersect(origin, end, line[0], line[1]))
+            .map(|hit| origin.distance(hit))
+            .fold(GEOMETRY_RAY_LENGTH, f32::min)
+    })
+}
+
+/// Graph node nearest a position
+fn nearest_node(pathfinding: &PathfindingGraph, position: [f32; 2]) -> Option<usize> {
+    let position = Vec2::from(position);
+    pathfinding
+        .nodes
+        .iter()
+        .min_by(|a, b| {
+            a.position
+                .distance_squared(position)
+                .total_cmp(&b.position.distance_squared(position))
+        })
+        .map(|node| node.id)
+}
+
+/// Headless environment stepped one frame at a time
+pub struct MlEnv {
+    app: App,
+}
+
+impl MlEnv {
+    /// Starts the game on the level and builds it
+    pub fn new(level_source: LevelSource) -> Self {
+        let mut app = headless_app(level_source);
+        app.update();
+        Self { app }
+    }
+
+    /// Applies the action for one frame and returns what the frame ended with
+    pub fn step(&mut self, action: ExternalAction) -> &Observation {
+        self.app.world_mut().resource_mut::<ExternalInput>().action = Some(action);
+        self.app.update();
+        self.observation()
+    }
+
+    /// Rebuilds the level from scratch (through `GameState::Loading`) and returns the first
+    /// observation of the new run
+    pub fn reset(&mut self) -> &Observation {
+        self.app.world_mut().resource_mut::<ExternalInput>().action = None;
+        self.app.world_mut().resource_mut::<NextState<GameState>>().set(GameState::Loading);
+        // One frame to leave the level and build the next one, one to spawn it
+        for _ in 0..RESET_FRAMES {
+            self.app.update();
+        }
+        self.observation()
+    }
+
+    pub fn observation(&self) -> &Observation {
+        &self.app.world().resource::<LatestObservation>().0
+    }
+}
+
+/// Runs the headless environment over stdin/stdout (see the module docs)
+pub fn run_env() {
+    let mut env = MlEnv::new(LevelSource::default());
+    // Not locked for the whole run: the game prints from worker threads too
+    let write_observation = |observation: &Observation| {
+        let mut stdout = std::io::stdout().lock();
+        let result = serde_json::to_writer(&mut stdout, observation)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(stdout))
+            .and_then(|()| stdout.flush());
+        if let Err(error) = result {
+            eprintln!("Failed to write observation: {error}");
+        }
+    };
+    write_observation(env.observation());
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let line = line.trim();
+
+        if line == RESET_COMMAND {
+            write_observation(env.reset());
+            continue;
+        }
+
+        match serde_json::from_str::<ExternalAction>(line) {
+            Ok(action) => write_observation(env.step(action)),
+            Err(error) => eprintln!("Invalid action {line:?}: {error}"),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::Resource;
+use serde::Deserialize;
+
+const MOVEMENT_CONFIG_PATH: &str = "assets/movement.ron";
+
+/// How characters move, loaded from `assets/movement.ron` at startup (and changeable at runtime
+/// with the debug panel)
+/// Missing files or fields fall back to the defaults. Agents fall with the same gravity, and
+/// their pathfinding graph plans jumps with it and the AI jump velocity.
+#[derive(Resource, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct MovementConfig {
+    /// Top running speed (pixels/second)
+    /// Converted from 5.0 pixels/frame at 60fps
+    pub max_speed: f32,
+    /// How quickly velocity approaches the target velocity, with and without input (1/second)
+    /// Converted from 0.2 per frame at 60fps
+    pub acceleration_scalers: (f32, f32),
+    /// Gravity (pixels/second²)
+    /// Converted from 0.5 pixels/frame² at 60fps
+    pub gravity: f32,
+    /// Upward velocity of ground and air jumps (pixels/second)
+    pub jump_velocity: f32,
+    /// Velocity of wall jumps away from the wall and up (pixels/second)
+    pub wall_jump_velocity: (f32, f32),
+    /// Fastest a character slides down a wall it presses into while airborne (pixels/second)
+    pub wall_slide_speed: f32,
+    /// Multiplier on acceleration after a wall jump, so the jump carries the player off the wall
+    pub wall_jump_acceleration_reduction: f32,
+    /// Upward velocity is divided by this when jump is released early, for short hops
+    pub jump_release_velocity_divisor: f32,
+    /// Extra jumps allowed while airborne (0 = single jump, 1 = double jump, 2 = triple jump)
+    pub max_air_jumps: u32,
+    /// How long (seconds) a jump press is buffered before landing
+    /// Originally 10 frames at 60fps
+    pub jump_buffer_time: f32,
+    /// How long (seconds) the player can still jump after walking off a ledge
+    pub coyote_time: f32,
+    /// How long (seconds) the player can still wall jump after leaving a wall
+    pub wall_coyote_time: f32,
+    /// Speed of the dash burst (pixels/second)
+    pub dash_velocity: f32,
+    /// How long (seconds) the dash lasts, during which gravity and steering are suppressed
+    pub dash_duration: f32,
+    /// How long (seconds) after a dash starts before the player can dash again
+    pub dash_cooldown: f32,
+    /// Speed into a surface (pixels/second) below which contact keeps velocity
+    pub landing_restitution_threshold: f32,
+    /// How quickly platformer agents' velocity approaches their target velocity, with and without
+    /// a direction to move in (1/second)
+    pub ai_acceleration_scalers: (f32, f32),
+    /// Fastest launch of a platformer agent's jump (pixels/second); its pathfinding graph only
+    /// plans jumps within it
+    /// Converted from 8.0 pixels/frame at 60fps
+    pub ai_jump_velocity: f32,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        Self {
+            max_speed: 300.0,
+            acceleration_scalers: (12.0, 24.0),
+            gravity: 1800.0,
+            jump_velocity: 540.0,
+            wall_jump_velocity: (468.0, 270.0),
+            wall_slide_speed: 150.0,
+            wall_jump_acceleration_reduction: 0.5,
+            jump_release_velocity_divisor: 3.0,
+            max_air_jumps: 0,
+            jump_buffer_time: 0.166,
+            coyote_time: 0.166,
+            wall_coyote_time: 0.166,
+            dash_velocity: 720.0,
+            dash_duration: 0.15,
+            dash_cooldown: 0.6,
+            landing_restitution_threshold: 1.0,
+            ai_acceleration_scalers: (12.0, 24.0),
+            ai_jump_velocity: 480.0,
+        }
+    }
+}
+
+impl MovementConfig {
+    pub fn load() -> Self {
+        match std::fs::read_to_string(MOVEMENT_CONFIG_PATH) {
+            Ok(contents) => ron::from_str(&contents).unwrap_or_else(|error| {
+                eprintln!("Failed to parse {MOVEMENT_CONFIG_PATH}, using defaults: {error}");
+                MovementConfig::default()
+            }),
+            Err(_) => MovementConfig::default(),
+        }
+    }
+}
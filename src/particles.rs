@@ -0,0 +1,323 @@
+//! Movement feedback particles: dust kicked up by landings (more the harder the landing), streaks
+//! scraped off walls while sliding down them, and bursts on wall jumps and dashes.
+//!
+//! Particles are plain data in a resource drawn with gizmos, like the rain in `weather`, and are
+//! emitted from collision and movement messages rather than from the movement code itself.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    color::{Alpha, Color},
+    ecs::{
+        message::MessageReader,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Local, Query, Res, ResMut},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{ops, Vec2, Vec3Swizzles},
+    prelude::Resource,
+    state::{condition::in_state, state::OnExit},
+    time::Time,
+    transform::components::Transform,
+};
+use rand::Rng;
+
+use crate::{
+    collisions::{s_collision, s_wall_slides, CollisionStarted, WallSliding},
+    frame_budget::FrameBudget,
+    game_state::GameState,
+    level::materials::SurfaceMaterial,
+    settings::Settings,
+    ControllerAction, ControllerEvent, KinematicBody, Player,
+};
+
+// Most particles alive at once; emitting past this drops the oldest (unitless)
+const MAX_PARTICLES: usize = 512;
+// Downward pull on particles, and the fraction of their speed they keep each second (units:
+// pixels/second², unitless)
+const PARTICLE_GRAVITY: f32 = 300.0;
+const PARTICLE_DRAG: f32 = 0.05;
+// Seconds of motion drawn per streak
+const STREAK_DURATION: f32 = 0.03;
+
+// Landings softer than this raise no dust, and landings this hard raise the most (units:
+// pixels/second)
+const DUST_MIN_SPEED: f32 = 150.0;
+const DUST_MAX_SPEED: f32 = 900.0;
+// Dust puffs from the hardest landing (unitless)
+const DUST_MAX_COUNT: f32 = 24.0;
+// Sideways speed of dust at full strength, and its size and life (units: pixels/second, pixels,
+// seconds)
+const DUST_SPEED: f32 = 160.0;
+const DUST_RADIUS: f32 = 3.0;
+const DUST_LIFETIME: f32 = 0.45;
+
+// Streaks per pixel slid down a wall, and how fast they fly off it (units: 1/pixel, pixels/second)
+const STREAKS_PER_PIXEL: f32 = 0.08;
+const STREAK_SPEED: f32 = 120.0;
+const STREAK_LIFETIME: f32 = 0.25;
+
+// Particles in a wall jump or dash burst, their speed and life (units: pixels/second, seconds)
+const BURST_COUNT: usize = 14;
+const BURST_SPEED: f32 = 260.0;
+const BURST_LIFETIME: f32 = 0.3;
+const WALL_JUMP_COLOR: Color = Color::srgb(0.9, 0.9, 1.0);
+const DASH_COLOR: Color = Color::srgb(0.5, 0.9, 1.0);
+const WALL_SLIDE_COLOR: Color = Color::srgb(0.75, 0.75, 0.75);
+
+/// How a particle is drawn
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ParticleShape {
+    /// A circle that shrinks as it ages
+    Puff,
+    /// A line along its motion
+    Streak,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    /// Seconds alive, and seconds it lives for
+    age: f32,
+    lifetime: f32,
+    /// Starting radius of puffs (pixels)
+    radius: f32,
+    color: Color,
+    shape: ParticleShape,
+    /// Whether gravity pulls on it
+    falls: bool,
+}
+
+/// Particles resource: Every live particle
+#[derive(Resource, Default)]
+pub struct Particles {
+    particles: Vec<Particle>,
+}
+
+impl Particles {
+    fn emit(&mut self, particle: Particle) {
+        if self.particles.len() >= MAX_PARTICLES {
+            self.particles.remove(0);
+        }
+        self.particles.push(particle);
+    }
+}
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Particles>();
+        app.add_systems(OnExit(GameState::InGame), s_clear_particles);
+        app.add_systems(
+            Update,
+            (
+                (
+                    s_emit_landing_dust.after(s_collision),
+                    s_emit_wall_slide_streaks.after(s_wall_slides),
+                    s_emit_movement_bursts,
+                )
+                    .run_if(|settings: Res<Settings>| settings.particles),
+                s_update_particles,
+                s_draw_particles,
+            )
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Colour of dust and streaks kicked off a surface
+fn surface_color(material: SurfaceMaterial) -> Color {
+    match material {
+        SurfaceMaterial::Stone => Color::srgb(0.75, 0.7, 0.6),
+        SurfaceMaterial::Metal => Color::srgb(1.0, 0.75, 0.35),
+        SurfaceMaterial::Ice => Color::srgb(0.8, 0.95, 1.0),
+    }
+}
+
+/// Particle counts are scaled down while the frame budget is degraded
+fn scaled_count(count: f32, frame_budget: &FrameBudget) -> usize {
+    (count * frame_budget.particle_scale()).round() as usize
+}
+
+/// Landing dust system: Kicks up dust where bodies land, more and faster the harder they land
+fn s_emit_landing_dust(
+    mut collisions: MessageReader<CollisionStarted>,
+    body_query: Query<(&Transform, &KinematicBody)>,
+    frame_budget: Res<FrameBudget>,
+    mut particles: ResMut<Particles>,
+) {
+    let mut rng = rand::rng();
+
+    for collision in collisions.read() {
+        if !KinematicBody::is_ground_normal(collision.normal)
+            || collision.impact_speed < DUST_MIN_SPEED
+        {
+            continue;
+        }
+        let Ok((transform, body)) = body_query.get(collision.entity) else {
+            continue;
+        };
+
+        let strength = ((collision.impact_speed - DUST_MIN_SPEED)
+            / (DUST_MAX_SPEED - DUST_MIN_SPEED))
+            .clamp(0.1, 1.0);
+        let contact = transform.translation.xy() - collision.normal * body.radius;
+        let along_surface = collision.normal.perp();
+        let color = surface_color(collision.material);
+
+        for _ in 0..scaled_count(DUST_MAX_COUNT * strength, &frame_budget) {
+            // Dust spreads out along the ground to both sides, lifting a little off it
+            let spread = rng.random_range(-1.0..=1.0_f32);
+            let lift = rng.random_range(0.1..=0.4);
+            particles.emit(Particle {
+                position: contact + along_surface * spread * body.radius,
+                velocity: (along_surface * spread + collision.normal * lift)
+                    * DUST_SPEED
+                    * strength,
+                age: 0.0,
+                lifetime: DUST_LIFETIME * rng.random_range(0.6..=1.0),
+                radius: DUST_RADIUS * rng.random_range(0.6..=1.0),
+                color,
+                shape: ParticleShape::Puff,
+                falls: false,
+            });
+        }
+    }
+}
+
+/// Wall slide streak system: Scrapes streaks off walls bodies slide down, one every few pixels
+fn s_emit_wall_slide_streaks(
+    time: Res<Time>,
+    mut wall_slides: MessageReader<WallSliding>,
+    body_query: Query<(&Transform, &KinematicBody)>,
+    frame_budget: Res<FrameBudget>,
+    mut particles: ResMut<Particles>,
+    // Fraction of a streak carried over to the next frame
+    mut carried_streaks: Local<f32>,
+) {
+    let mut rng = rand::rng();
+    let mut sliding = false;
+
+    for slide in wall_slides.read() {
+        let Ok((transform, body)) = body_query.get(slide.entity) else {
+            continue;
+        };
+        sliding = true;
+
+        let streaks = slide.speed * time.delta_secs() * STREAKS_PER_PIXEL
+            * frame_budget.particle_scale()
+            + *carried_streaks;
+        *carried_streaks = streaks.fract();
+
+        let contact = transform.translation.xy() - slide.normal * body.radius;
+        let color = surface_color(slide.material);
+        for _ in 0..streaks as usize {
+            // Streaks fly up and away from the wall, against the slide
+            let away = slide.normal * rng.random_range(0.3..=1.0);
+            let up = Vec2::Y * rng.random_range(0.5..=1.0);
+            particles.emit(Particle {
+                position: contact,
+                velocity: (away + up) * STREAK_SPEED,
+                age: 0.0,
+                lifetime: STREAK_LIFETIME * rng.random_range(0.6..=1.0),
+                radius: 0.0,
+                color,
+                shape: ParticleShape::Streak,
+                falls: true,
+            });
+        }
+    }
+
+    if !sliding {
+        *carried_streaks = 0.0;
+    }
+}
+
+/// Burst system: Bursts particles out of a character when they wall jump, dash or catch onto a
+/// wall (wall jumps ring out all around, dashes spray backwards and wall slides off the wall)
+fn s_emit_movement_bursts(
+    mut controller_events: MessageReader<ControllerEvent>,
+    player_query: Query<(&Transform, &KinematicBody), With<Player>>,
+    frame_budget: Res<FrameBudget>,
+    mut particles: ResMut<Particles>,
+) {
+    let mut rng = rand::rng();
+
+    for event in controller_events.read() {
+        let Ok((transform, body)) = player_query.get(event.entity) else {
+            continue;
+        };
+        let position = transform.translation.xy();
+        let heading = body.velocity.normalize_or(Vec2::X);
+
+        let (color, spray) = match event.action {
+            ControllerAction::WallJump => (WALL_JUMP_COLOR, None),
+            ControllerAction::Dash => (DASH_COLOR, Some(-heading)),
+            // The body's normal points into the wall
+            ControllerAction::WallSlide => (WALL_SLIDE_COLOR, Some(-body.normal)),
+            ControllerAction::Jump | ControllerAction::AirJump => continue,
+        };
+
+        for _ in 0..scaled_count(BURST_COUNT as f32, &frame_budget) {
+            let direction = match spray {
+                Some(backwards) => Vec2::from_angle(rng.random_range(-0.5..=0.5)).rotate(backwards),
+                None => Vec2::from_angle(rng.random_range(0.0..TAU)),
+            };
+            particles.emit(Particle {
+                position: position + direction * body.radius,
+                velocity: direction * BURST_SPEED * rng.random_range(0.5..=1.0),
+                age: 0.0,
+                lifetime: BURST_LIFETIME * rng.random_range(0.6..=1.0),
+                radius: 0.0,
+                color,
+                shape: ParticleShape::Streak,
+                falls: false,
+            });
+        }
+    }
+}
+
+/// Particle system: Moves the particles and removes the ones that have lived out their lifetime
+fn s_update_particles(time: Res<Time>, mut particles: ResMut<Particles>) {
+    let dt = time.delta_secs();
+    let drag = ops::powf(PARTICLE_DRAG, dt);
+
+    particles.particles.retain_mut(|particle| {
+        particle.age += dt;
+        if particle.falls {
+            particle.velocity.y -= PARTICLE_GRAVITY * dt;
+        }
+        particle.velocity *= drag;
+        particle.position += particle.velocity * dt;
+        particle.age < particle.lifetime
+    });
+}
+
+/// Particle rendering system: Draws puffs as shrinking circles and streaks as lines along their
+/// motion, both fading out as they age
+fn s_draw_particles(particles: Res<Particles>, mut gizmos: Gizmos) {
+    for particle in &particles.particles {
+        let remaining = 1.0 - particle.age / particle.lifetime;
+        let color = particle.color.with_alpha(remaining);
+
+        match particle.shape {
+            ParticleShape::Puff => {
+                gizmos.circle_2d(particle.position, particle.radius * remaining, color);
+            }
+            ParticleShape::Streak => {
+                let tail = particle.position - particle.velocity * STREAK_DURATION;
+                gizmos.line_2d(particle.position, tail, color);
+            }
+        }
+    }
+}
+
+/// Particle cleanup system: Removes every particle when leaving a level
+fn s_clear_particles(mut particles: ResMut<Particles>) {
+    particles.particles.clear();
+}
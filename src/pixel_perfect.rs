@@ -0,0 +1,112 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    camera::{
+        visibility::RenderLayers, Camera, Camera2d, ClearColorConfig, ImageRenderTarget,
+        Projection, RenderTarget,
+    },
+    color::Color,
+    ecs::{
+        component::Component,
+        query::With,
+        system::{Commands, Query},
+    },
+    image::{Image, ImageSampler},
+    math::UVec2,
+    render::{render_resource::TextureFormat, view::Msaa},
+    sprite::Sprite,
+    window::{PrimaryWindow, Window},
+};
+
+use crate::camera::GameCamera;
+
+// The world renders on the default layer; the upscaled canvas gets its own so the outer camera
+// only sees the canvas
+const CANVAS_LAYER: usize = 1;
+
+/// Sprite showing the low-res render of the world
+#[derive(Component)]
+pub struct PixelPerfectCanvas;
+
+/// Camera that draws the canvas to the window at an integer scale
+#[derive(Component)]
+pub struct CanvasCamera {
+    /// Virtual resolution the world is rendered at (pixels)
+    pub resolution: UVec2,
+}
+
+pub struct PixelPerfectPlugin;
+
+impl Plugin for PixelPerfectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_fit_canvas);
+    }
+}
+
+/// Spawns the world camera rendering into a low-res texture, a sprite showing that texture and the
+/// camera that draws the sprite to the window
+pub fn spawn_pixel_perfect_cameras(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    resolution: UVec2,
+) {
+    let mut canvas = Image::new_target_texture(
+        resolution.x,
+        resolution.y,
+        TextureFormat::Bgra8UnormSrgb,
+    );
+    // Nearest-neighbor upscaling keeps pixels sharp
+    canvas.sampler = ImageSampler::nearest();
+    let canvas = images.add(canvas);
+
+    commands.spawn((
+        Camera2d,
+        Camera {
+            // Render the world before the canvas is drawn
+            order: -1,
+            target: RenderTarget::Image(ImageRenderTarget::from(canvas.clone())),
+            ..Default::default()
+        },
+        Msaa::Off,
+        GameCamera,
+    ));
+
+    commands.spawn((
+        Sprite::from_image(canvas),
+        PixelPerfectCanvas,
+        RenderLayers::layer(CANVAS_LAYER),
+    ));
+
+    commands.spawn((
+        Camera2d,
+        Camera {
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..Default::default()
+        },
+        Msaa::Off,
+        CanvasCamera { resolution },
+        RenderLayers::layer(CANVAS_LAYER),
+    ));
+}
+
+/// Canvas fit system: Scales the canvas by the largest whole number that fits the window
+pub fn s_fit_canvas(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut canvas_camera_query: Query<(&mut Projection, &CanvasCamera)>,
+) {
+    let Ok(window) = window_query.single() else {
+        return;
+    };
+
+    for (mut projection, canvas_camera) in canvas_camera_query.iter_mut() {
+        let horizontal_scale = window.width() / canvas_camera.resolution.x as f32;
+        let vertical_scale = window.height() / canvas_camera.resolution.y as f32;
+        let scale = horizontal_scale.min(vertical_scale).floor().max(1.0);
+
+        if let Projection::Orthographic(orthographic) = projection.as_mut() {
+            if orthographic.scale != 1.0 / scale {
+                orthographic.scale = 1.0 / scale;
+            }
+        }
+    }
+}
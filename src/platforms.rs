@@ -0,0 +1,113 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::Vec2,
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::platformer_ai::s_platformer_ai_movement,
+    game_state::GameState,
+    level::{Level, LevelMesh},
+    s_movement,
+    utils::ping_pong_along_path,
+};
+
+/// A level polygon that moves along waypoints (read from level metadata, positions in world
+/// pixels)
+#[derive(Deserialize, Serialize, Clone)]
+pub struct MovingPlatformSetting {
+    /// A point inside the level polygon to move
+    pub polygon_at: [f32; 2],
+    /// Offsets from the polygon's starting position, travelled in order and back again
+    pub waypoints: Vec<[f32; 2]>,
+    /// Travel speed (pixels/second)
+    pub speed: f32,
+}
+
+/// Moving platform component: Moves a level polygon back and forth along its waypoints
+#[derive(Component)]
+pub struct MovingPlatform {
+    /// Index of the polygon in `Level::polygons`
+    pub polygon: usize,
+    pub waypoints: Vec<Vec2>,
+    /// Travel speed (pixels/second)
+    pub speed: f32,
+    /// Time (seconds) since the platform started moving
+    pub elapsed: f32,
+}
+
+pub struct MovingPlatformPlugin;
+
+impl Plugin for MovingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            s_move_platforms
+                .before(s_movement)
+                .before(s_platformer_ai_movement)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Spawns every moving platform in the level metadata
+pub fn spawn_moving_platforms(commands: &mut Commands, level: &Level) {
+    for setting in &level.metadata.moving_platforms {
+        let Some(polygon) = level.polygon_at(Vec2::from(setting.polygon_at)) else {
+            eprintln!(
+                "No level polygon at {:?} for a moving platform",
+                setting.polygon_at
+            );
+            continue;
+        };
+
+        commands.spawn(MovingPlatform {
+            polygon,
+            waypoints: setting.waypoints.iter().copied().map(Vec2::from).collect(),
+            speed: setting.speed,
+            elapsed: 0.0,
+        });
+    }
+}
+
+/// Moving platform system: Moves each platform's polygon and mesh along its waypoints and records
+/// the polygon's velocity so bodies standing on it are carried along
+pub fn s_move_platforms(
+    time: Res<Time>,
+    mut platform_query: Query<&mut MovingPlatform>,
+    mut level: ResMut<Level>,
+    mut mesh_query: Query<(&LevelMesh, &mut Transform)>,
+) {
+    let dt = time.delta_secs();
+
+    for mut platform in platform_query.iter_mut() {
+        platform.elapsed += dt;
+
+        let Some(polygon) = level.polygons.get_mut(platform.polygon) else {
+            continue;
+        };
+
+        let offset = ping_pong_along_path(&platform.waypoints, platform.elapsed * platform.speed);
+        polygon.velocity = if dt > 0.0 {
+            (offset - polygon.offset) / dt
+        } else {
+            Vec2::ZERO
+        };
+        level.set_polygon_offset(platform.polygon, offset);
+
+        for (level_mesh, mut transform) in mesh_query.iter_mut() {
+            if level_mesh.polygon == platform.polygon {
+                transform.translation.x = offset.x;
+                transform.translation.y = offset.y;
+            }
+        }
+    }
+}
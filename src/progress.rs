@@ -0,0 +1,246 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        message::{Message, MessageReader, MessageWriter},
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{primitives::Annulus, Vec2},
+    mesh::{Mesh, Mesh2d},
+    prelude::Resource,
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    state::{
+        condition::in_state,
+        state::{NextState, OnEnter},
+    },
+    time::Time,
+    transform::components::Transform,
+};
+use serde::Deserialize;
+
+use crate::{
+    characters::PlayerControlled,
+    collectibles::{s_collect, Collectible, Collected},
+    collisions::{s_sensors, Sensor},
+    game_state::GameState,
+    health::{s_respawn, Died},
+    level::{procgen::ProcgenRun, Level},
+    level_loader::LevelManager,
+    s_enter_game,
+    save::{LevelProgress, SaveData},
+    Player,
+};
+
+// Level exit ring constants
+const GOAL_RADIUS: f32 = 20.0;
+const GOAL_THICKNESS: f32 = 3.0;
+const GOAL_COLOR: Color = Color::srgb(0.3, 1.0, 0.8);
+const GOAL_Z: f32 = 0.5;
+
+/// Level goal component: The ring that completes the level when the player reaches it
+#[derive(Component)]
+pub struct LevelGoal;
+
+/// Sent when the player reaches the level's exit
+#[derive(Message, Clone, Copy)]
+pub struct LevelCompleted;
+
+/// What it takes to unlock a level (every part has to be met)
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnlockCondition {
+    /// Collectibles found across every level
+    pub collectibles: usize,
+    /// Levels that have to be completed first
+    pub completed: Vec<String>,
+}
+
+impl UnlockCondition {
+    pub fn is_met(&self, save: &SaveData) -> bool {
+        save.total_collected() >= self.collectibles
+            && self.completed.iter().all(|level| save.is_completed(level))
+    }
+
+    /// What is left to do, for the level select menu and messages
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.collectibles > 0 {
+            parts.push(format!("collect {} gems", self.collectibles));
+        }
+        if !self.completed.is_empty() {
+            parts.push(format!("complete {}", self.completed.join(", ")));
+        }
+        parts.join(" and ")
+    }
+}
+
+/// Level progress tracker resource: Which level's progress is being recorded in the save file,
+/// and when the current run of it started
+#[derive(Resource)]
+pub struct LevelProgressTracker {
+    /// Whether progress is saved at all (off in headless runs, which mustn't touch the save file)
+    pub enabled: bool,
+    /// Name of the level being played (`None` in random levels, which aren't tracked)
+    level: Option<String>,
+    /// Elapsed time (seconds) when the level was entered
+    started_at: f32,
+}
+
+impl Default for LevelProgressTracker {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            level: None,
+            started_at: 0.0,
+        }
+    }
+}
+
+impl LevelProgressTracker {
+    /// Off: nothing is written to the save file
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Changes the current level's saved progress
+    fn update(&self, change: impl FnOnce(&mut LevelProgress)) {
+        let Some(level) = self.level.as_ref().filter(|_| self.enabled) else {
+            return;
+        };
+        SaveData::update(|save| change(save.levels.entry(level.clone()).or_default()));
+    }
+}
+
+pub struct ProgressPlugin;
+
+impl Plugin for ProgressPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelProgressTracker>();
+        app.add_message::<LevelCompleted>();
+
+        app.add_systems(OnEnter(GameState::InGame), s_start_level_run.after(s_enter_game));
+        app.add_systems(
+            Update,
+            (
+                s_track_collectibles.after(s_collect),
+                s_track_deaths.after(s_respawn),
+                s_level_goal.after(s_sensors).run_if(in_state(GameState::InGame)),
+            ),
+        );
+    }
+}
+
+/// Spawns the level's exit ring, if it has one
+pub fn spawn_level_goal(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    level: &Level,
+) {
+    let Some(exit) = level.metadata.exit else {
+        return;
+    };
+
+    commands.spawn((
+        Transform::from_translation(Vec2::from(exit).extend(GOAL_Z)),
+        Mesh2d(meshes.add(Annulus::new(GOAL_RADIUS - GOAL_THICKNESS, GOAL_RADIUS))),
+        MeshMaterial2d(materials.add(GOAL_COLOR)),
+        Sensor::new(GOAL_RADIUS),
+        LevelGoal,
+    ));
+}
+
+/// Level run start system: Starts timing the level and removes the collectibles already found in
+/// it
+fn s_start_level_run(
+    mut commands: Commands,
+    mut tracker: ResMut<LevelProgressTracker>,
+    level_manager: Option<Res<LevelManager>>,
+    procgen: Option<Res<ProcgenRun>>,
+    collectible_query: Query<(Entity, &Collectible)>,
+    time: Res<Time>,
+) {
+    tracker.started_at = time.elapsed_secs();
+    tracker.level = level_manager
+        .filter(|_| procgen.is_none())
+        .map(|level_manager| level_manager.current().to_string());
+
+    let Some(level) = tracker.level.as_ref().filter(|_| tracker.enabled) else {
+        return;
+    };
+    let Some(progress) = SaveData::load().and_then(|mut save| save.levels.remove(level)) else {
+        return;
+    };
+
+    for (entity, collectible) in collectible_query.iter() {
+        if progress.collected.binary_search(&collectible.index).is_ok() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Collectible tracking system: Saves each collectible the player finds
+fn s_track_collectibles(
+    mut collected: MessageReader<Collected>,
+    tracker: Res<LevelProgressTracker>,
+) {
+    for collected in collected.read() {
+        tracker.update(|progress| {
+            if let Err(position) = progress.collected.binary_search(&collected.index) {
+                progress.collected.insert(position, collected.index);
+            }
+        });
+    }
+}
+
+/// Death tracking system: Counts the player's deaths in the level
+fn s_track_deaths(
+    mut died: MessageReader<Died>,
+    tracker: Res<LevelProgressTracker>,
+    player_query: Query<(), With<Player>>,
+) {
+    for died in died.read() {
+        if player_query.contains(died.entity) {
+            tracker.update(|progress| progress.deaths += 1);
+        }
+    }
+}
+
+/// Level goal system: Completes the level when either player reaches its exit, saving the time if
+/// it is the best yet, and starts it again
+pub fn s_level_goal(
+    goal_query: Query<&Sensor, With<LevelGoal>>,
+    player_query: Query<(), PlayerControlled>,
+    tracker: Res<LevelProgressTracker>,
+    time: Res<Time>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut level_completed: MessageWriter<LevelCompleted>,
+) {
+    let reached = goal_query.iter().any(|sensor| {
+        sensor
+            .overlapping_entities
+            .iter()
+            .any(|&overlapping| player_query.contains(overlapping))
+    });
+    if !reached {
+        return;
+    }
+
+    let run_time = time.elapsed_secs() - tracker.started_at;
+    tracker.update(|progress| {
+        progress.completed = true;
+        progress.best_time = Some(progress.best_time.map_or(run_time, |best| best.min(run_time)));
+    });
+    next_state.set(GameState::Loading);
+    level_completed.write(LevelCompleted);
+
+    println!("Level complete in {run_time:.2} s");
+}
@@ -0,0 +1,677 @@
+//! Run replays (`.crep` files).
+//!
+//! Every run of a level from the level list is recorded: the player's input and position each
+//! frame, along with how long the frame took. The last finished run (completed or left for
+//! another level) can be exported with `REPLAY_EXPORT_KEY` to a small file in `replays/` to share,
+//! and the level select menu imports the newest file there, either as a ghost to race or taking
+//! over the player to watch the run play out again.
+//!
+//! Input traces recorded in deterministic mode (see `deterministic`) are made of the same frames,
+//! filled in by the same recorder.
+//!
+//! A replay file is a short header followed by the LZ4-compressed postcard encoding of the run
+//! (like binary levels, see `level::baked`). Replays only play back on the level they were recorded
+//! on, unchanged, and in the game version that recorded them.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    app::{App, Plugin, PostUpdate, PreUpdate, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        message::MessageReader,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    math::{Vec2, Vec3Swizzles},
+    mesh::{Mesh, Mesh2d},
+    prelude::Resource,
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    state::{
+        condition::in_state,
+        state::{OnEnter, OnExit},
+        state_scoped::DespawnOnExit,
+    },
+    time::{Time, TimeUpdateStrategy},
+    transform::components::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body_mesh,
+    characters::ActiveCharacter,
+    deterministic::{InputTrace, InputTraceMode},
+    game_state::GameState,
+    input::{s_read_input_actions, InputAction, PlayerTwoInput},
+    level::{procgen::ProcgenRun, LevelSource},
+    level_loader::{LevelAsset, LevelManager},
+    progress::{s_level_goal, LevelCompleted},
+    s_enter_game,
+    settings::Settings,
+    PLAYER_RADIUS,
+};
+
+// Exports the last finished run while playing
+pub const REPLAY_EXPORT_KEY: KeyCode = KeyCode::KeyE;
+// Level select menu keys that import the newest replay, as a ghost or taking over the player
+pub const REPLAY_GHOST_KEY: KeyCode = KeyCode::KeyR;
+pub const REPLAY_TAKEOVER_KEY: KeyCode = KeyCode::KeyP;
+
+// Folder replays are exported to and imported from
+pub const REPLAY_DIR: &str = "replays";
+// File extension of replays
+pub const REPLAY_EXTENSION: &str = "crep";
+
+// Start of every replay file, followed by the format version
+const MAGIC: &[u8; 4] = b"CREP";
+const FORMAT_VERSION: u8 = 2;
+
+// Ghost look
+const GHOST_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.35);
+const GHOST_Z: f32 = -0.1;
+
+/// One recorded frame of a run (of a replay or an input trace)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct ReplayFrame {
+    /// Length of the frame (seconds)
+    #[serde(default)]
+    pub dt: f32,
+    /// Where the player ended the frame
+    #[serde(default)]
+    pub position: Vec2,
+    pub move_dir: Vec2,
+    pub jump_pressed: bool,
+    pub jump_released: bool,
+    pub dash_pressed: bool,
+    /// Runs recorded before characters could be switched never switch
+    #[serde(default)]
+    pub switch_character_pressed: bool,
+    /// The second player's input (`None` when nobody was playing second)
+    #[serde(default)]
+    pub player_two: Option<PlayerTwoFrame>,
+}
+
+/// The second player's input on one recorded frame
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct PlayerTwoFrame {
+    pub move_dir: Vec2,
+    pub jump_pressed: bool,
+    pub jump_released: bool,
+    pub dash_pressed: bool,
+}
+
+impl ReplayFrame {
+    fn record(
+        dt: f32,
+        position: Vec2,
+        input_action: &InputAction,
+        player_two_input: Option<&PlayerTwoInput>,
+    ) -> Self {
+        Self {
+            dt,
+            position,
+            move_dir: input_action.move_dir,
+            jump_pressed: input_action.jump_pressed,
+            jump_released: input_action.jump_released,
+            dash_pressed: input_action.dash_pressed,
+            switch_character_pressed: input_action.switch_character_pressed,
+            player_two: player_two_input.map(|PlayerTwoInput(input)| PlayerTwoFrame {
+                move_dir: input.move_dir,
+                jump_pressed: input.jump_pressed,
+                jump_released: input.jump_released,
+                dash_pressed: input.dash_pressed,
+            }),
+        }
+    }
+
+    /// Replaces both players' input with this frame's (leaving the exit action alone)
+    pub fn apply(&self, input_action: &mut InputAction, player_two_input: &mut PlayerTwoInput) {
+        input_action.move_dir = self.move_dir;
+        input_action.jump_pressed = self.jump_pressed;
+        input_action.jump_released = self.jump_released;
+        input_action.dash_pressed = self.dash_pressed;
+        input_action.switch_character_pressed = self.switch_character_pressed;
+
+        let player_two = self.player_two.unwrap_or_default();
+        let PlayerTwoInput(input) = player_two_input;
+        input.move_dir = player_two.move_dir;
+        input.jump_pressed = player_two.jump_pressed;
+        input.jump_released = player_two.jump_released;
+        input.dash_pressed = player_two.dash_pressed;
+    }
+}
+
+/// A recorded run of a level
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Replay {
+    /// Version of the game that recorded the run (movement may play out differently in others)
+    game_version: String,
+    /// Name of the level in the level list
+    level: String,
+    /// `level_hash` of the level when it was recorded
+    level_hash: u64,
+    /// Whether the run reached the level's exit
+    completed: bool,
+    frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    /// Length of the run (seconds)
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.dt).sum()
+    }
+}
+
+/// A replay that couldn't be written, read or played back
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    /// No replay files in `REPLAY_DIR`
+    NoReplays,
+    /// The file doesn't start with the replay header
+    NotReplay,
+    /// The file was written by another version of the replay format
+    Version(u8),
+    Decompress(lz4_flex::block::DecompressError),
+    Encoding(postcard::Error),
+    /// The run was recorded by another version of the game
+    GameVersion(String),
+    /// The run was recorded on a level that isn't in the level list
+    UnknownLevel(String),
+    /// The run's level hasn't finished loading yet
+    LevelNotLoaded(String),
+    /// The run's level has been edited since it was recorded
+    LevelChanged(String),
+    /// The run's level hasn't been unlocked yet
+    Locked(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "{error}"),
+            ReplayError::NoReplays => write!(f, "no .{REPLAY_EXTENSION} files in {REPLAY_DIR}/"),
+            ReplayError::NotReplay => write!(f, "not a replay"),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay format version {version} (expected {FORMAT_VERSION})"
+            ),
+            ReplayError::Decompress(error) => write!(f, "corrupt replay: {error}"),
+            ReplayError::Encoding(error) => write!(f, "invalid replay: {error}"),
+            ReplayError::GameVersion(version) => write!(
+                f,
+                "recorded with version {version} of the game, this is version {}",
+                env!("CARGO_PKG_VERSION")
+            ),
+            ReplayError::UnknownLevel(level) => {
+                write!(f, "recorded on level {level}, which isn't in the level list")
+            }
+            ReplayError::LevelNotLoaded(level) => write!(f, "level {level} hasn't loaded yet"),
+            ReplayError::LevelChanged(level) => {
+                write!(f, "level {level} has changed since the replay was recorded")
+            }
+            ReplayError::Locked(level) => write!(f, "level {level} is still locked"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Encodes a replay as a replay file
+pub fn encode(replay: &Replay) -> Result<Vec<u8>, ReplayError> {
+    let encoded = postcard::to_stdvec(replay).map_err(ReplayError::Encoding)?;
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(FORMAT_VERSION);
+    bytes.extend(lz4_flex::compress_prepend_size(&encoded));
+
+    Ok(bytes)
+}
+
+/// Reads a replay file
+pub fn decode(bytes: &[u8]) -> Result<Replay, ReplayError> {
+    let Some((&version, compressed)) = bytes
+        .strip_prefix(MAGIC.as_slice())
+        .and_then(|body| body.split_first())
+    else {
+        return Err(ReplayError::NotReplay);
+    };
+    if version != FORMAT_VERSION {
+        return Err(ReplayError::Version(version));
+    }
+
+    let encoded =
+        lz4_flex::decompress_size_prepended(compressed).map_err(ReplayError::Decompress)?;
+    postcard::from_bytes(&encoded).map_err(ReplayError::Encoding)
+}
+
+/// Fingerprint of a level's tiles and metadata (FNV-1a, so it is the same on every machine and
+/// build), telling whether a replay was recorded on the level as it is now
+pub fn level_hash(source: &LevelSource) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let metadata = serde_json::to_string(&source.metadata).unwrap_or_default();
+    let tiles = source.tiles.iter().flat_map(|row| {
+        // Row lengths keep differently shaped grids with the same tiles apart
+        (row.len() as u32)
+            .to_le_bytes()
+            .into_iter()
+            .chain(row.iter().flat_map(|tile| tile.to_le_bytes()))
+    });
+
+    tiles
+        .chain(metadata.bytes())
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// The most recently modified replay file in `REPLAY_DIR`
+fn newest_replay_file() -> Result<PathBuf, ReplayError> {
+    let entries = std::fs::read_dir(REPLAY_DIR).map_err(|_| ReplayError::NoReplays)?;
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.path().extension().is_some_and(|extension| extension == REPLAY_EXTENSION)
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+        .ok_or(ReplayError::NoReplays)
+}
+
+/// How an imported replay is played back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// A see-through body retraces the run while the player plays
+    Ghost,
+    /// The recorded input drives the player, frame by frame
+    Takeover,
+}
+
+/// Replay playback resource: The imported replay being played back (from the next time the game
+/// enters its level)
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    mode: PlaybackMode,
+    /// Whether the level has been entered and playback is under way
+    started: bool,
+    /// Next frame to play back
+    frame: usize,
+    /// Time (seconds) played back so far, and by the end of `frame` (ghosts only)
+    elapsed: f32,
+    frame_end: f32,
+}
+
+impl ReplayPlayback {
+    /// Reads the newest replay in `REPLAY_DIR` and checks it can be played in this game, then
+    /// switches to its level to play it back from the start
+    pub fn import_newest(
+        mode: PlaybackMode,
+        level_manager: &mut LevelManager,
+        level_assets: &Assets<LevelAsset>,
+    ) -> Result<(Self, PathBuf), ReplayError> {
+        let path = newest_replay_file()?;
+        let playback = Self::import(&path, mode, level_manager, level_assets)?;
+        Ok((playback, path))
+    }
+
+    fn import(
+        path: &Path,
+        mode: PlaybackMode,
+        level_manager: &mut LevelManager,
+        level_assets: &Assets<LevelAsset>,
+    ) -> Result<Self, ReplayError> {
+        let replay = decode(&std::fs::read(path).map_err(ReplayError::Io)?)?;
+
+        if replay.game_version != env!("CARGO_PKG_VERSION") {
+            return Err(ReplayError::GameVersion(replay.game_version));
+        }
+        if !level_manager.level_names().contains(&replay.level.as_str()) {
+            return Err(ReplayError::UnknownLevel(replay.level));
+        }
+        let Some(source) = level_manager.level_source(&replay.level, level_assets) else {
+            return Err(ReplayError::LevelNotLoaded(replay.level));
+        };
+        if level_hash(source) != replay.level_hash {
+            return Err(ReplayError::LevelChanged(replay.level));
+        }
+        if !level_manager.load_level(&replay.level) {
+            return Err(ReplayError::Locked(replay.level));
+        }
+
+        Ok(Self {
+            replay,
+            mode,
+            started: false,
+            frame: 0,
+            elapsed: 0.0,
+            frame_end: 0.0,
+        })
+    }
+
+    /// One line for the level select menu
+    pub fn describe(&self, path: &Path) -> String {
+        let mode = match self.mode {
+            PlaybackMode::Ghost => "racing",
+            PlaybackMode::Takeover => "watching",
+        };
+        let outcome = if self.replay.completed {
+            "completed"
+        } else {
+            "not completed"
+        };
+        format!(
+            "{mode} {} ({}, {:.2} s, {outcome})",
+            path.display(),
+            self.replay.level,
+            self.replay.duration()
+        )
+    }
+}
+
+/// Replay recorder resource: The run being recorded and the last one that finished
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    /// Whether runs are recorded at all (off in headless runs)
+    pub enabled: bool,
+    /// Run being recorded (`None` in random levels and replays taking over the player, which
+    /// can't be exported)
+    current: Option<Replay>,
+    /// Last run that finished, for `REPLAY_EXPORT_KEY`
+    last_run: Option<Replay>,
+}
+
+impl Default for ReplayRecorder {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            current: None,
+            last_run: None,
+        }
+    }
+}
+
+impl ReplayRecorder {
+    /// Off: nothing is recorded
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Ends the run being recorded, keeping it for export if anything was recorded
+    fn finish_run(&mut self, completed: bool) {
+        let Some(mut run) = self.current.take() else {
+            return;
+        };
+        if run.frames.is_empty() {
+            return;
+        }
+        run.completed = completed;
+        self.last_run = Some(run);
+    }
+}
+
+/// Marker for the ghost retracing a replay
+#[derive(Component)]
+struct ReplayGhost;
+
+/// Replay plugin: Records runs, exports them to replay files and plays imported ones back
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>();
+
+        app.add_systems(
+            OnEnter(GameState::InGame),
+            (
+                s_start_replay_recording.after(s_enter_game),
+                s_start_replay_playback.after(s_enter_game),
+            ),
+        );
+        app.add_systems(OnExit(GameState::InGame), s_stop_replays);
+        app.add_systems(
+            PreUpdate,
+            s_take_over_player_input
+                .after(s_read_input_actions)
+                .run_if(in_state(GameState::InGame)),
+        );
+        app.add_systems(
+            Update,
+            (
+                s_move_replay_ghost,
+                s_finish_replay_recording.after(s_level_goal),
+                s_export_replay,
+            )
+                .run_if(in_state(GameState::InGame)),
+        );
+        app.add_systems(
+            PostUpdate,
+            (s_record_replay_frame, s_advance_takeover).run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Replay recording start system: Starts recording a run of the level just entered
+fn s_start_replay_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    level_manager: Option<Res<LevelManager>>,
+    level_source: Res<LevelSource>,
+    procgen: Option<Res<ProcgenRun>>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    let taking_over = playback.is_some_and(|playback| playback.mode == PlaybackMode::Takeover);
+    let level = level_manager
+        .filter(|_| recorder.enabled && procgen.is_none() && !taking_over)
+        .map(|level_manager| level_manager.current().to_string());
+
+    recorder.current = level.map(|level| Replay {
+        game_version: env!("CARGO_PKG_VERSION").to_string(),
+        level,
+        level_hash: level_hash(&level_source),
+        completed: false,
+        frames: Vec::new(),
+    });
+}
+
+/// Replay recording system: Adds the frame's input and the player's position to the run, and to
+/// the input trace being recorded
+#[allow(clippy::too_many_arguments)]
+fn s_record_replay_frame(
+    mut recorder: ResMut<ReplayRecorder>,
+    trace: Option<ResMut<InputTrace>>,
+    trace_mode: Option<Res<InputTraceMode>>,
+    input_action: Res<InputAction>,
+    player_two_input: Res<PlayerTwoInput>,
+    settings: Res<Settings>,
+    player_query: Query<&Transform, With<ActiveCharacter>>,
+    time: Res<Time>,
+) {
+    let Ok(transform) = player_query.single() else {
+        return;
+    };
+    let frame = ReplayFrame::record(
+        time.delta_secs(),
+        transform.translation.xy(),
+        &input_action,
+        Some(&*player_two_input).filter(|_| settings.two_player),
+    );
+
+    if let Some(run) = recorder.current.as_mut() {
+        run.frames.push(frame);
+    }
+    if let (Some(mut trace), Some(InputTraceMode::Record(_))) = (trace, trace_mode.as_deref()) {
+        trace.frames.push(frame);
+    }
+}
+
+/// Replay finish system: Keeps the run for export once the player completes the level
+fn s_finish_replay_recording(
+    mut level_completed: MessageReader<LevelCompleted>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if level_completed.read().last().is_some() {
+        recorder.finish_run(true);
+    }
+}
+
+/// Replay export system: Writes the last finished run to a new file in `REPLAY_DIR`
+fn s_export_replay(keyboard_input: Res<ButtonInput<KeyCode>>, recorder: Res<ReplayRecorder>) {
+    if !keyboard_input.just_pressed(REPLAY_EXPORT_KEY) {
+        return;
+    }
+    let Some(run) = &recorder.last_run else {
+        println!("No finished run to export yet");
+        return;
+    };
+
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let path = Path::new(REPLAY_DIR).join(format!("{}-{seconds}.{REPLAY_EXTENSION}", run.level));
+
+    let result = encode(run).and_then(|bytes| {
+        std::fs::create_dir_all(REPLAY_DIR)
+            .and_then(|()| std::fs::write(&path, &bytes))
+            .map_err(ReplayError::Io)
+            .map(|()| bytes.len())
+    });
+    match result {
+        Ok(size) => println!(
+            "Exported a {:.2} s run of {} to {} ({size} bytes)",
+            run.duration(),
+            run.level,
+            path.display()
+        ),
+        Err(error) => eprintln!("Failed to export the replay to {}: {error}", path.display()),
+    }
+}
+
+/// Replay playback start system: Starts the imported replay once its level is entered, spawning
+/// its ghost if it has one
+fn s_start_replay_playback(
+    mut commands: Commands,
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let Some(mut playback) = playback else {
+        return;
+    };
+    playback.started = true;
+    if playback.mode != PlaybackMode::Ghost {
+        return;
+    }
+
+    let start = playback
+        .replay
+        .frames
+        .first()
+        .map_or(Vec2::ZERO, |frame| frame.position);
+    commands.spawn((
+        DespawnOnExit(GameState::InGame),
+        Transform::from_translation(start.extend(GHOST_Z)),
+        Mesh2d(meshes.add(body_mesh(PLAYER_RADIUS))),
+        MeshMaterial2d(materials.add(GHOST_COLOR)),
+        ReplayGhost,
+    ));
+}
+
+/// Replay takeover system: Replaces the players' input with the frame being played back (the
+/// exit key still works; the frame the level is entered on keeps the live input, as it was
+/// already read)
+fn s_take_over_player_input(
+    playback: Option<Res<ReplayPlayback>>,
+    mut input_action: ResMut<InputAction>,
+    mut player_two_input: ResMut<PlayerTwoInput>,
+) {
+    let Some(frame) = playback
+        .filter(|playback| playback.started && playback.mode == PlaybackMode::Takeover)
+        .and_then(|playback| playback.replay.frames.get(playback.frame).copied())
+    else {
+        return;
+    };
+
+    frame.apply(&mut input_action, &mut player_two_input);
+}
+
+/// Replay takeover advance system: Steps to the next frame and makes it last as long as it did
+/// when it was recorded, ending playback after the last one
+fn s_advance_takeover(mut commands: Commands, playback: Option<ResMut<ReplayPlayback>>) {
+    let Some(mut playback) = playback
+        .filter(|playback| playback.started && playback.mode == PlaybackMode::Takeover)
+    else {
+        return;
+    };
+
+    playback.frame += 1;
+    match playback.replay.frames.get(playback.frame) {
+        Some(frame) => commands.insert_resource(TimeUpdateStrategy::ManualDuration(
+            Duration::from_secs_f32(frame.dt),
+        )),
+        None => {
+            commands.insert_resource(TimeUpdateStrategy::Automatic);
+            commands.remove_resource::<ReplayPlayback>();
+            println!("Replay finished");
+        }
+    }
+}
+
+/// Replay ghost system: Moves the ghost to where the recorded player was at this point of the run
+fn s_move_replay_ghost(
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut ghost_query: Query<&mut Transform, With<ReplayGhost>>,
+    time: Res<Time>,
+) {
+    let Some(mut playback) =
+        playback.filter(|playback| playback.started && playback.mode == PlaybackMode::Ghost)
+    else {
+        return;
+    };
+
+    playback.elapsed += time.delta_secs();
+    while playback.elapsed > playback.frame_end {
+        let Some(dt) = playback.replay.frames.get(playback.frame + 1).map(|frame| frame.dt) else {
+            break;
+        };
+        playback.frame += 1;
+        playback.frame_end += dt;
+    }
+
+    // The ghost stays where the run ended
+    let Some(frame) = playback.replay.frames.get(playback.frame) else {
+        return;
+    };
+    for mut transform in ghost_query.iter_mut() {
+        transform.translation = frame.position.extend(GHOST_Z);
+    }
+}
+
+/// Replay stop system: Ends the recorded run and the replay being played back when the level is
+/// left (replays imported while playing wait for their level to be entered)
+fn s_stop_replays(
+    mut commands: Commands,
+    mut recorder: ResMut<ReplayRecorder>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    recorder.finish_run(false);
+
+    if playback.is_some_and(|playback| playback.started) {
+        commands.insert_resource(TimeUpdateStrategy::Automatic);
+        commands.remove_resource::<ReplayPlayback>();
+    }
+}
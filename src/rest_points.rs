@@ -0,0 +1,150 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Has, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{ops::FloatPow, primitives::Annulus, Isometry2d, Vec2, Vec3Swizzles},
+    mesh::{Mesh, Mesh2d},
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    characters::{PlayerControlled, PlayerTwo},
+    collisions::{s_sensors, Sensor},
+    health::{s_respawn, Health, SpawnPoint},
+    level::Level,
+    save::SaveData,
+    KinematicBody, EPSILON,
+};
+
+// Rest point constants
+const REST_POINT_RADIUS: f32 = 16.0;
+const REST_POINT_THICKNESS: f32 = 2.0;
+const REST_POINT_COLOR: Color = Color::srgb(0.3, 1.0, 0.5);
+const REST_POINT_Z: f32 = 0.5;
+// Seconds the player has to stand still at a rest point before resting
+const REST_CHANNEL_TIME: f32 = 1.0;
+// Speed (pixels/second) below which the player counts as standing still
+const REST_MAX_SPEED: f32 = 20.0;
+// Radius of the channel progress ring (pixels)
+const REST_CHANNEL_RING_RADIUS: f32 = 22.0;
+
+/// Rest point component: Restores health, sets the respawn point and saves once channeled
+#[derive(Component)]
+pub struct RestPoint {
+    /// Time (seconds) the player has been channeling at this rest point
+    pub channel_timer: f32,
+    /// Whether the player has already rested during this visit (cleared when they leave)
+    pub rested: bool,
+}
+
+pub struct RestPointPlugin;
+
+impl Plugin for RestPointPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_rest_points.after(s_sensors).before(s_respawn));
+        app.add_systems(Update, s_draw_rest_channel.after(s_rest_points));
+    }
+}
+
+/// Spawns a rest point for every position in the level metadata
+pub fn spawn_rest_points(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    level: &Level,
+) {
+    for position in &level.metadata.rest_points {
+        commands.spawn((
+            Transform::from_translation(Vec2::from(*position).extend(REST_POINT_Z)),
+            Mesh2d(meshes.add(Annulus::new(
+                REST_POINT_RADIUS - REST_POINT_THICKNESS,
+                REST_POINT_RADIUS,
+            ))),
+            MeshMaterial2d(materials.add(REST_POINT_COLOR)),
+            Sensor::new(REST_POINT_RADIUS),
+            RestPoint {
+                channel_timer: 0.0,
+                rested: false,
+            },
+        ));
+    }
+}
+
+/// Rest point system: Channels while a player stands still at a rest point (the first of them,
+/// if both are there), then restores their health, moves their respawn point there and saves the
+/// game (only the first player's respawn point is saved)
+#[allow(clippy::type_complexity)]
+pub fn s_rest_points(
+    time: Res<Time>,
+    mut rest_point_query: Query<(&Transform, &Sensor, &mut RestPoint)>,
+    mut player_query: Query<
+        (Entity, &KinematicBody, &mut Health, &mut SpawnPoint, Has<PlayerTwo>),
+        (PlayerControlled, Without<Sensor>),
+    >,
+) {
+    for (transform, sensor, mut rest_point) in rest_point_query.iter_mut() {
+        let mut resting = player_query
+            .iter_mut()
+            .filter(|(entity, ..)| sensor.overlapping_entities.contains(entity));
+        let Some((_, player_physics, mut health, mut spawn_point, player_two)) = resting.next()
+        else {
+            rest_point.channel_timer = 0.0;
+            rest_point.rested = false;
+            continue;
+        };
+
+        if rest_point.rested {
+            continue;
+        }
+
+        // Moving interrupts the channel
+        let player_still = player_physics.velocity.length_squared() < REST_MAX_SPEED.squared();
+        let player_grounded = player_physics.normal.length_squared() > EPSILON;
+        if !player_still || !player_grounded {
+            rest_point.channel_timer = 0.0;
+            continue;
+        }
+
+        rest_point.channel_timer += time.delta_secs();
+
+        if rest_point.channel_timer >= REST_CHANNEL_TIME {
+            rest_point.channel_timer = 0.0;
+            rest_point.rested = true;
+
+            health.current = health.max;
+            spawn_point.0 = transform.translation.xy();
+
+            if !player_two {
+                SaveData::update(|save| save.respawn = Some(spawn_point.0.to_array()));
+            }
+        }
+    }
+}
+
+/// Rest channel system: Draws the channel progress as a ring filling up around the rest point
+pub fn s_draw_rest_channel(rest_point_query: Query<(&Transform, &RestPoint)>, mut gizmos: Gizmos) {
+    for (transform, rest_point) in rest_point_query.iter() {
+        if rest_point.channel_timer <= 0.0 {
+            continue;
+        }
+
+        gizmos.arc_2d(
+            Isometry2d::from_translation(transform.translation.xy()),
+            rest_point.channel_timer / REST_CHANNEL_TIME * TAU,
+            REST_CHANNEL_RING_RADIUS,
+            REST_POINT_COLOR,
+        );
+    }
+}
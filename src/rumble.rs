@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        entity::Entity,
+        message::{MessageReader, MessageWriter},
+        query::Has,
+        schedule::IntoScheduleConfigs,
+        system::{Local, Query, Res},
+    },
+    math::{Vec2, Vec3Swizzles},
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    characters::{PlayerControlled, PlayerTwo},
+    collisions::s_player_contacts,
+    hazards::{s_move_hazards, Hazard},
+    health::Health,
+    input::{LocalPlayer, Rumble, RumbleCurve, RumbleEffect},
+    s_movement, ControllerAction, ControllerEvent, KinematicBody,
+};
+
+// Rumble presets (intensities are fractions of full motor strength, durations in seconds)
+const LANDING_RUMBLE: RumbleEffect = RumbleEffect {
+    strong: 0.8,
+    weak: 0.3,
+    duration: 0.25,
+    curve: RumbleCurve::EaseOut,
+};
+const DAMAGE_RUMBLE: RumbleEffect = RumbleEffect {
+    strong: 1.0,
+    weak: 0.8,
+    duration: 0.4,
+    curve: RumbleCurve::Linear,
+};
+const DASH_RUMBLE: RumbleEffect = RumbleEffect {
+    strong: 0.0,
+    weak: 0.5,
+    duration: 0.12,
+    curve: RumbleCurve::Constant,
+};
+const SLAM_RUMBLE: RumbleEffect = RumbleEffect {
+    strong: 1.0,
+    weak: 0.2,
+    duration: 0.35,
+    curve: RumbleCurve::EaseOut,
+};
+
+// Fall speed above which a landing rumbles, and the speed at which it rumbles at full strength
+// (units: pixels/second; a normal jump lands at about 540)
+pub const HEAVY_LANDING_SPEED: f32 = 700.0;
+pub const MAX_LANDING_SPEED: f32 = 1200.0;
+// Distance within which a crusher slam is felt, fading out with distance (units: pixels)
+const SLAM_RUMBLE_RANGE: f32 = 240.0;
+
+pub struct RumblePlugin;
+
+impl Plugin for RumblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                s_rumble_on_landing.after(s_player_contacts),
+                s_rumble_on_damage,
+                s_rumble_on_dash.after(s_movement),
+                s_rumble_on_slam.after(s_move_hazards),
+            ),
+        );
+    }
+}
+
+/// Landing rumble system: Rumbles a player's gamepads when their character hits the ground
+/// falling fast, harder the faster the fall
+fn s_rumble_on_landing(
+    player_query: Query<(Entity, &KinematicBody, Has<PlayerTwo>), PlayerControlled>,
+    // Whether each character was on the ground last frame, and how fast they were moving
+    mut last_frame: Local<HashMap<Entity, (bool, Vec2)>>,
+    mut rumble: MessageWriter<Rumble>,
+) {
+    for (entity, physics, player_two) in player_query.iter() {
+        let on_ground = physics.on_ground();
+        let Some((was_on_ground, last_velocity)) =
+            last_frame.insert(entity, (on_ground, physics.velocity))
+        else {
+            continue;
+        };
+
+        // Collision has already stopped the fall, so the impact speed is last frame's
+        let fall_speed = -last_velocity.y;
+        if on_ground && !was_on_ground && fall_speed > HEAVY_LANDING_SPEED {
+            let strength = ((fall_speed - HEAVY_LANDING_SPEED)
+                / (MAX_LANDING_SPEED - HEAVY_LANDING_SPEED))
+                .clamp(0.0, 1.0);
+            rumble.write(Rumble {
+                player: LocalPlayer::of(player_two),
+                // Even the lightest heavy landing is felt
+                effect: LANDING_RUMBLE.scaled(0.4 + 0.6 * strength),
+            });
+        }
+    }
+    last_frame.retain(|entity, _| player_query.contains(*entity));
+}
+
+/// Damage rumble system: Rumbles a player's gamepads whenever their character loses health
+fn s_rumble_on_damage(
+    player_query: Query<(Entity, &Health, Has<PlayerTwo>), PlayerControlled>,
+    mut last_health: Local<HashMap<Entity, f32>>,
+    mut rumble: MessageWriter<Rumble>,
+) {
+    for (entity, health, player_two) in player_query.iter() {
+        let last = last_health.insert(entity, health.current);
+        if last.is_some_and(|last| health.current < last) {
+            rumble.write(Rumble {
+                player: LocalPlayer::of(player_two),
+                effect: DAMAGE_RUMBLE,
+            });
+        }
+    }
+    last_health.retain(|entity, _| player_query.contains(*entity));
+}
+
+/// Dash rumble system: Gives each dash a short buzz on the dashing player's gamepads
+fn s_rumble_on_dash(
+    mut controller_events: MessageReader<ControllerEvent>,
+    player_query: Query<Has<PlayerTwo>, PlayerControlled>,
+    mut rumble: MessageWriter<Rumble>,
+) {
+    for event in controller_events.read() {
+        if event.action != ControllerAction::Dash {
+            continue;
+        }
+        if let Ok(player_two) = player_query.get(event.entity) {
+            rumble.write(Rumble {
+                player: LocalPlayer::of(player_two),
+                effect: DASH_RUMBLE,
+            });
+        }
+    }
+}
+
+/// Slam rumble system: Rumbles the gamepads of players near a crusher when it slams into the end
+/// of its stroke
+fn s_rumble_on_slam(
+    time: Res<Time>,
+    hazard_query: Query<&Hazard>,
+    player_query: Query<(&Transform, Has<PlayerTwo>), PlayerControlled>,
+    mut rumble: MessageWriter<Rumble>,
+) {
+    let dt = time.delta_secs();
+
+    for hazard in hazard_query.iter() {
+        let Some(slam_pos) = hazard.slam_position(dt) else {
+            continue;
+        };
+
+        for (player_transform, player_two) in player_query.iter() {
+            let distance = slam_pos.distance(player_transform.translation.xy());
+            let strength = 1.0 - distance / SLAM_RUMBLE_RANGE;
+            if strength > 0.0 {
+                rumble.write(Rumble {
+                    player: LocalPlayer::of(player_two),
+                    effect: SLAM_RUMBLE.scaled(strength),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+const SAVE_PATH: &str = "save.json";
+
+/// Progress saved at rest points and as levels are played
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SaveData {
+    /// Where the player respawns (the last rest point used)
+    pub respawn: Option<[f32; 2]>,
+    /// Completion and collectibles for each level played, by level name
+    pub levels: HashMap<String, LevelProgress>,
+}
+
+/// How far the player has got in one level
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LevelProgress {
+    /// Indices (into the level's collectibles) of the collectibles found, in ascending order
+    pub collected: Vec<usize>,
+    /// Whether the player has reached the level's exit
+    pub completed: bool,
+    /// Fastest run from entering the level to reaching its exit (seconds)
+    pub best_time: Option<f32>,
+    /// Times the player has died in the level
+    pub deaths: u32,
+}
+
+impl SaveData {
+    /// Loads the save file, or `None` if there is no usable save yet
+    pub fn load() -> Option<Self> {
+        let contents = std::fs::read_to_string(SAVE_PATH).ok()?;
+
+        serde_json::from_str(&contents)
+            .inspect_err(|error| eprintln!("Failed to parse {SAVE_PATH}, ignoring it: {error}"))
+            .ok()
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                std::fs::write(SAVE_PATH, contents).map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = result {
+            eprintln!("Failed to write {SAVE_PATH}: {error}");
+        }
+    }
+
+    /// Loads the save file, changes it and writes it back (starting a new save if there is none)
+    pub fn update(change: impl FnOnce(&mut SaveData)) {
+        let mut save = SaveData::load().unwrap_or_default();
+        change(&mut save);
+        save.save();
+    }
+
+    pub fn respawn_position(&self) -> Option<Vec2> {
+        self.respawn.map(Vec2::from)
+    }
+
+    /// Collectibles found across every level
+    pub fn total_collected(&self) -> usize {
+        self.levels.values().map(|level| level.collected.len()).sum()
+    }
+
+    pub fn is_completed(&self, level: &str) -> bool {
+        self.levels.get(level).is_some_and(|progress| progress.completed)
+    }
+}
@@ -0,0 +1,377 @@
+//! Scenario files: AI behavior expectations as reproducible headless tests.
+//!
+//! `composite --scenarios [directory]` runs every `.json` file in the directory
+//! (`assets/scenarios` by default) headlessly, prints whether each passed and exits with a failure
+//! status if any didn't. A scenario places the player and agents on a level, plays scripted
+//! player input and checks an expectation until it is met or the timeout runs out:
+//!
+//! ```json
+//! {
+//!     "level": "assets/level.json",
+//!     "player": [0.0, -50.0],
+//!     "player_velocity": [0.0, 0.0],
+//!     "agents": [
+//!         { "position": [0.0, -250.0], "variant": "heavy", "profile": "ruthless" },
+//!         { "position": [100.0, -150.0], "velocity": [600.0, 0.0], "flying": true },
+//!         {
+//!             "position": [-120.0, -280.0],
+//!             "patrol": { "waypoints": [[-120.0, -280.0], [120.0, -280.0]], "ping_pong": true }
+//!         }
+//!     ],
+//!     "inputs": [{ "seconds": 2.0, "move_dir": [1.0, 0.0], "jump": true }],
+//!     "seed": 7,
+//!     "timeout": 20.0,
+//!     "expect": { "type": "agent_reaches_player" }
+//! }
+//! ```
+//!
+//! Instead of a `level` file, a scenario can play a random level (`"generated": { "seed": 3,
+//! "style": "caves" }`, with `rooms` the default style, see `level::procgen`).
+//!
+//! Everything but `timeout` and `expect` is optional: the level defaults to the bundled one, the
+//! player and agents to the level's spawns, the player's velocity and input to standing still and
+//! the seed (for the AI's random choices) to 0. Expectations are `agent_reaches_player`,
+//! `agent_reaches` and `player_reaches` (both with a `position`), which take an optional
+//! `distance` (pixels), `player_survives`, `player_stays_grounded`, `player_stays_within` (with
+//! the `min` and `max` corners of a box), `agents_stay_within` (likewise) and `player_rests_at`
+//! (with a `position`, an optional `distance` and the seconds to settle in `after`).
+//!
+//! Instead of `inputs`, a scenario can play an input trace recorded in deterministic mode
+//! (`"trace": "assets/traces/run.json"`, see `deterministic`), with the trace's seed and timestep
+//! in place of the scenario's, so a reproduced bug becomes a regression test.
+//!
+//! Scenarios run on the headless simulation harness (`harness::Harness`), which tests in code can
+//! drive directly. `cargo test` runs the scenarios in `assets/scenarios` too.
+
+use std::path::{Path, PathBuf};
+
+use bevy::math::Vec2;
+use serde::Deserialize;
+
+use crate::{
+    ai::pursue_ai::PURSUE_AI_AGENT_RADIUS,
+    deterministic::InputTrace,
+    harness::{AgentPlacement, Harness, InputSegment},
+    level::{
+        procgen::{generate_level, ProcgenStyle, DEFAULT_HEIGHT, DEFAULT_WIDTH},
+        LevelSource,
+    },
+    level_loader::parse_level_file,
+    PLAYER_RADIUS,
+};
+
+// Command-line flag
+pub const SCENARIOS_FLAG: &str = "--scenarios";
+const DEFAULT_SCENARIO_DIR: &str = "assets/scenarios";
+
+// How close bodies must be for a reach expectation without a `distance` (pixels)
+const DEFAULT_REACH_DISTANCE: f32 = PLAYER_RADIUS + PURSUE_AI_AGENT_RADIUS + 4.0;
+// How far a resting player can drift without a `distance` (pixels)
+const DEFAULT_REST_DISTANCE: f32 = 0.5;
+
+/// A scenario file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    /// Level file to play (any format the level loader reads), or the bundled level
+    #[serde(default)]
+    level: Option<PathBuf>,
+    /// Random level to play instead of a level file
+    #[serde(default)]
+    generated: Option<GeneratedScenarioLevel>,
+    /// Where the player starts, or the level's player spawn
+    #[serde(default)]
+    player: Option<[f32; 2]>,
+    /// Velocity the player starts with (pixels/second)
+    #[serde(default)]
+    player_velocity: [f32; 2],
+    /// Agents replacing the level's, or the level's own agents
+    #[serde(default)]
+    agents: Option<Vec<AgentPlacement>>,
+    /// Player input, one segment after another (then no input)
+    #[serde(default)]
+    inputs: Vec<InputSegment>,
+    /// Input trace played instead of `inputs`
+    #[serde(default)]
+    trace: Option<PathBuf>,
+    /// Seed for the AI's random choices
+    #[serde(default)]
+    seed: u64,
+    /// Simulated seconds the expectation has to be met in
+    timeout: f32,
+    expect: Expectation,
+}
+
+/// A random level (see `level::procgen`) a scenario plays, at the default size
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GeneratedScenarioLevel {
+    seed: u64,
+    #[serde(default)]
+    style: ProcgenStyle,
+}
+
+/// What has to happen for a scenario to pass
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum Expectation {
+    /// An agent gets within `distance` of the player before the timeout
+    AgentReachesPlayer { distance: Option<f32> },
+    /// An agent gets within `distance` of `position` before the timeout
+    AgentReaches {
+        position: [f32; 2],
+        distance: Option<f32>,
+    },
+    /// The player gets within `distance` of `position` before the timeout
+    PlayerReaches {
+        position: [f32; 2],
+        distance: Option<f32>,
+    },
+    /// The player doesn't die before the timeout
+    PlayerSurvives,
+    /// The player doesn't leave the ground (once it has landed) before the timeout
+    PlayerStaysGrounded,
+    /// The player doesn't leave the box from `min` to `max` before the timeout
+    PlayerStaysWithin { min: [f32; 2], max: [f32; 2] },
+    /// No agent leaves the box from `min` to `max` before the timeout
+    AgentsStayWithin { min: [f32; 2], max: [f32; 2] },
+    /// From `after` seconds on, the player stays within `distance` of `position` until the
+    /// timeout (so it neither jitters nor slips through the level there)
+    PlayerRestsAt {
+        position: [f32; 2],
+        distance: Option<f32>,
+        after: f32,
+    },
+}
+
+/// How one scenario went
+struct ScenarioOutcome {
+    passed: bool,
+    /// Simulated seconds until the scenario was decided
+    seconds: f32,
+    reason: String,
+}
+
+/// Runs every scenario in the directory and reports which passed
+pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+    let directory = args
+        .iter()
+        .position(|arg| arg == SCENARIOS_FLAG)
+        .and_then(|index| args.get(index + 1))
+        .filter(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_SCENARIO_DIR);
+
+    match run_directory(directory) {
+        Ok(failed) if failed.is_empty() => {}
+        Ok(_) => std::process::exit(1),
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs every scenario in the directory, printing how each went, and returns the names of the
+/// ones that failed
+fn run_directory(directory: &str) -> Result<Vec<String>, String> {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .collect(),
+        Err(error) => return Err(format!("Failed to read {directory}: {error}")),
+    };
+    paths.sort();
+
+    let mut failed = Vec::new();
+    for path in &paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        match run_scenario(path) {
+            Ok(outcome) if outcome.passed => {
+                println!("PASS {name} ({:.2} s): {}", outcome.seconds, outcome.reason);
+            }
+            Ok(outcome) => {
+                println!("FAIL {name} ({:.2} s): {}", outcome.seconds, outcome.reason);
+                failed.push(name.into_owned());
+            }
+            Err(error) => {
+                println!("FAIL {name}: {error}");
+                failed.push(name.into_owned());
+            }
+        }
+    }
+
+    println!("{}/{} scenarios passed", paths.len() - failed.len(), paths.len());
+    Ok(failed)
+}
+
+/// Reads a scenario file and plays it until its expectation is decided
+fn run_scenario(path: &Path) -> Result<ScenarioOutcome, String> {
+    let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let scenario: Scenario = serde_json::from_str(&contents).map_err(|error| error.to_string())?;
+
+    let level_source = match (&scenario.level, &scenario.generated) {
+        (Some(_), Some(_)) => {
+            return Err("a scenario plays either a `level` file or a `generated` level".to_string());
+        }
+        (Some(level_path), None) => {
+            let contents = std::fs::read(level_path)
+                .map_err(|error| format!("{}: {error}", level_path.display()))?;
+            let extension = level_path.extension().and_then(|extension| extension.to_str());
+            parse_level_file(extension, &contents)
+                .map_err(|error| format!("{}: {error}", level_path.display()))?
+        }
+        (None, Some(generated)) => {
+            generate_level(generated.seed, generated.style, DEFAULT_WIDTH, DEFAULT_HEIGHT).source
+        }
+        (None, None) => LevelSource::default(),
+    };
+
+    let trace = match &scenario.trace {
+        Some(_) if !scenario.inputs.is_empty() => {
+            return Err("a scenario plays either `inputs` or a `trace`".to_string());
+        }
+        Some(trace_path) => Some(
+            InputTrace::load(trace_path)
+                .map_err(|error| format!("{}: {error}", trace_path.display()))?,
+        ),
+        None => None,
+    };
+
+    let mut harness = Harness::new(level_source);
+    if let Some(player) = scenario.player {
+        harness.place_player(Vec2::from(player));
+    }
+    harness.launch_player(Vec2::from(scenario.player_velocity));
+    if let Some(agents) = scenario.agents {
+        harness.replace_agents(agents);
+    }
+    match trace {
+        Some(trace) => {
+            harness.play_trace(trace);
+        }
+        None => {
+            harness.seed_ai(scenario.seed).script_input(scenario.inputs);
+        }
+    }
+    harness.start();
+
+    let mut player = Vec2::ZERO;
+    // Whether the player has touched the ground yet (for `PlayerStaysGrounded`)
+    let mut landed = false;
+    let mut decided = None;
+    harness.run_until(scenario.timeout, |harness| {
+        player = harness.player_position()?;
+        decided = match scenario.expect {
+            Expectation::AgentReachesPlayer { distance } => {
+                let distance = distance.unwrap_or(DEFAULT_REACH_DISTANCE);
+                let reached = harness
+                    .agent_positions()
+                    .iter()
+                    .any(|agent| agent.distance(player) <= distance);
+                reached.then(|| (true, "an agent reached the player".to_string()))
+            }
+            Expectation::AgentReaches { position, distance } => {
+                let distance = distance.unwrap_or(DEFAULT_REACH_DISTANCE);
+                let reached = harness
+                    .agent_positions()
+                    .iter()
+                    .any(|agent| agent.distance(Vec2::from(position)) <= distance);
+                reached.then(|| (true, format!("an agent reached {position:?}")))
+            }
+            Expectation::PlayerReaches { position, distance } => {
+                let distance = distance.unwrap_or(DEFAULT_REACH_DISTANCE);
+                (player.distance(Vec2::from(position)) <= distance)
+                    .then(|| (true, format!("the player reached {position:?}")))
+            }
+            Expectation::PlayerSurvives => harness
+                .player_died()
+                .then(|| (false, "the player died".to_string())),
+            Expectation::PlayerStaysGrounded => {
+                if harness.player_on_ground() {
+                    landed = true;
+                    None
+                } else {
+                    landed.then(|| (false, format!("the player left the ground at {player}")))
+                }
+            }
+            Expectation::PlayerStaysWithin { min, max } => {
+                let inside =
+                    player.cmpge(Vec2::from(min)).all() && player.cmple(Vec2::from(max)).all();
+                (!inside).then(|| (false, format!("the player left the box at {player}")))
+            }
+            Expectation::AgentsStayWithin { min, max } => harness
+                .agent_positions()
+                .into_iter()
+                .find(|agent| {
+                    !(agent.cmpge(Vec2::from(min)).all() && agent.cmple(Vec2::from(max)).all())
+                })
+                .map(|agent| (false, format!("an agent left the box at {agent}"))),
+            Expectation::PlayerRestsAt {
+                position,
+                distance,
+                after,
+            } => {
+                let distance = distance.unwrap_or(DEFAULT_REST_DISTANCE);
+                let away = player.distance(Vec2::from(position));
+                (harness.seconds() >= after && away > distance)
+                    .then(|| (false, format!("the player was {away:.2} px away, at {player}")))
+            }
+        };
+        Ok(decided.is_some())
+    })?;
+
+    let seconds = harness.seconds();
+    if let Some((passed, reason)) = decided {
+        return Ok(ScenarioOutcome {
+            passed,
+            seconds,
+            reason,
+        });
+    }
+    Ok(match scenario.expect {
+        Expectation::PlayerSurvives => ScenarioOutcome {
+            passed: true,
+            seconds,
+            reason: "the player survived".to_string(),
+        },
+        Expectation::PlayerStaysGrounded if landed => ScenarioOutcome {
+            passed: true,
+            seconds,
+            reason: "the player stayed on the ground".to_string(),
+        },
+        Expectation::PlayerStaysWithin { .. } => ScenarioOutcome {
+            passed: true,
+            seconds,
+            reason: "the player stayed inside the box".to_string(),
+        },
+        Expectation::AgentsStayWithin { .. } => ScenarioOutcome {
+            passed: true,
+            seconds,
+            reason: "the agents stayed inside the box".to_string(),
+        },
+        Expectation::PlayerRestsAt { position, .. } => ScenarioOutcome {
+            passed: true,
+            seconds,
+            reason: format!("the player rested at {position:?}"),
+        },
+        expectation => ScenarioOutcome {
+            passed: false,
+            seconds,
+            reason: format!("timed out waiting for {expectation:?} (player at {player})"),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_scenarios_pass() {
+        let failed = run_directory(DEFAULT_SCENARIO_DIR).unwrap();
+        assert!(failed.is_empty(), "scenarios failed: {}", failed.join(", "));
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate, PreUpdate, Update},
+    ecs::{
+        message::{Message, MessageReader, MessageWriter},
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Local, Query, Res, ResMut},
+    },
+    math::{ops, Quat, Vec2},
+    prelude::Resource,
+    time::Time,
+    transform::{components::Transform, TransformSystems},
+};
+
+use crate::{
+    camera::{s_camera_collision, GameCamera},
+    characters::ActiveCharacter,
+    collisions::s_player_contacts,
+    rumble::{HEAVY_LANDING_SPEED, MAX_LANDING_SPEED},
+    s_movement,
+    settings::Settings,
+    ControllerAction, ControllerEvent, KinematicBody,
+};
+
+// Shake at full trauma (units: pixels, radians)
+const MAX_SHAKE_OFFSET: f32 = 12.0;
+const MAX_SHAKE_ANGLE: f32 = 0.04;
+// Trauma worn off per second (full trauma settles in 2/3 of a second)
+const TRAUMA_DECAY: f32 = 1.5;
+// How fast the shake wobbles (units: radians/second of its slowest wave)
+const SHAKE_SPEED: f32 = 40.0;
+
+// Trauma added by each source (0 to 1)
+const LANDING_TRAUMA: f32 = 0.5;
+const WALL_JUMP_TRAUMA: f32 = 0.2;
+
+/// Sent by gameplay systems to shake the screen: adds trauma (0 to 1). The shake grows with the
+/// square of the total trauma, which wears off over time, so small knocks barely register and big
+/// ones stack up.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct ScreenShake(pub f32);
+
+/// Camera shake resource: Trauma built up by `ScreenShake` messages and the shake it gave the
+/// camera this frame
+#[derive(Resource, Default)]
+pub struct CameraShake {
+    /// Current trauma (0 to 1)
+    pub trauma: f32,
+    /// Seconds the shake has been running, for its wobble
+    elapsed: f32,
+    /// Offset added to the camera this frame (taken back off before anything moves the camera)
+    offset: Vec2,
+}
+
+pub struct ScreenShakePlugin;
+
+impl Plugin for ScreenShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<ScreenShake>();
+        app.init_resource::<CameraShake>();
+        app.add_systems(PreUpdate, s_unshake_camera);
+        app.add_systems(
+            Update,
+            (
+                s_shake_on_landing.after(s_player_contacts),
+                s_shake_on_wall_jump.after(s_movement),
+            ),
+        );
+        // After everything that moves the camera, before the move reaches the renderer
+        app.add_systems(
+            PostUpdate,
+            s_shake_camera
+                .after(s_camera_collision)
+                .before(TransformSystems::Propagate),
+        );
+    }
+}
+
+/// Camera unshake system: Takes last frame's shake back off the camera, so following, free-fly
+/// and collision work from where the camera really is
+fn s_unshake_camera(
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<GameCamera>>,
+) {
+    if shake.offset == Vec2::ZERO {
+        return;
+    }
+
+    for mut transform in camera_query.iter_mut() {
+        transform.translation -= shake.offset.extend(0.0);
+        transform.rotation = Quat::IDENTITY;
+    }
+    shake.offset = Vec2::ZERO;
+}
+
+/// Camera shake system: Adds up the frame's trauma, wears it off and wobbles the camera by it
+/// (unless screen shake is turned off in the settings)
+fn s_shake_camera(
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut screen_shake: MessageReader<ScreenShake>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<GameCamera>>,
+) {
+    let dt = time.delta_secs();
+    let added: f32 = screen_shake.read().map(|shake| shake.0).sum();
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * dt + added).clamp(0.0, 1.0);
+    if shake.trauma <= 0.0 || !settings.screen_shake {
+        shake.elapsed = 0.0;
+        return;
+    }
+    shake.elapsed += dt;
+
+    // Two out-of-step sine waves per axis make a smooth, irregular wobble
+    let wobble = |phase: f32| {
+        let t = shake.elapsed * SHAKE_SPEED + phase;
+        (ops::sin(t) + 0.5 * ops::sin(t * 2.3 + 1.7)) / 1.5
+    };
+    let strength = shake.trauma * shake.trauma;
+    let offset = Vec2::new(wobble(0.0), wobble(3.1)) * MAX_SHAKE_OFFSET * strength;
+    let angle = wobble(5.3) * MAX_SHAKE_ANGLE * strength;
+
+    for mut transform in camera_query.iter_mut() {
+        transform.translation += offset.extend(0.0);
+        transform.rotation = Quat::from_rotation_z(angle);
+    }
+    shake.offset = offset;
+}
+
+/// Landing shake system: Shakes the screen when the player hits the ground falling fast, harder
+/// the faster the fall
+fn s_shake_on_landing(
+    player_query: Query<&KinematicBody, With<ActiveCharacter>>,
+    // Whether the player was on the ground last frame, and how fast they were moving
+    mut last_frame: Local<(bool, Vec2)>,
+    mut screen_shake: MessageWriter<ScreenShake>,
+) {
+    let Ok(physics) = player_query.single() else {
+        return;
+    };
+
+    let on_ground = physics.on_ground();
+    let (was_on_ground, last_velocity) = *last_frame;
+    *last_frame = (on_ground, physics.velocity);
+
+    // Collision has already stopped the fall, so the impact speed is last frame's
+    let fall_speed = -last_velocity.y;
+    if on_ground && !was_on_ground && fall_speed > HEAVY_LANDING_SPEED {
+        let strength = ((fall_speed - HEAVY_LANDING_SPEED)
+            / (MAX_LANDING_SPEED - HEAVY_LANDING_SPEED))
+            .clamp(0.0, 1.0);
+        screen_shake.write(ScreenShake(LANDING_TRAUMA * (0.4 + 0.6 * strength)));
+    }
+}
+
+/// Wall jump shake system: Gives each of the active character's wall jumps a small kick
+fn s_shake_on_wall_jump(
+    mut controller_events: MessageReader<ControllerEvent>,
+    active_query: Query<(), With<ActiveCharacter>>,
+    mut screen_shake: MessageWriter<ScreenShake>,
+) {
+    for event in controller_events.read() {
+        if event.action == ControllerAction::WallJump && active_query.contains(event.entity) {
+            screen_shake.write(ScreenShake(WALL_JUMP_TRAUMA));
+        }
+    }
+}
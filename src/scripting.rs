@@ -0,0 +1,448 @@
+//! A small line-based scripting language for level triggers and cutscenes.
+//!
+//! Scripts live in `assets/scripts` and are compiled into a list of ops when they are run, so
+//! content can be changed without recompiling. One command per line, `#` starts a comment:
+//!
+//! ```text
+//! say "The door is sealed." 3
+//! wait 1.5
+//! spawn chaser 176 150
+//! open_door 0
+//! close_door 0
+//! set_ai_state wander
+//! move_camera 0 100
+//! ```
+
+use std::fmt;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{Vec2, Vec3Swizzles},
+    mesh::Mesh,
+    prelude::Resource,
+    sprite_render::ColorMaterial,
+    state::state_scoped::DespawnOnExit,
+    text::{TextColor, TextFont},
+    time::Time,
+    transform::components::Transform,
+    ui::{widget::Text, Node, PositionType, Val},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::pursue_ai::{PursueAI, PursueAIState},
+    camera::{CameraOverride, GameCamera},
+    characters::PlayerControlled,
+    doors::{s_switches, Door},
+    game_state::GameState,
+    level::{Aabb, Level},
+    spawn_ai_agent, AIVariant,
+};
+
+const SCRIPTS_DIRECTORY: &str = "assets/scripts";
+
+// Dialogue box constants
+const DIALOGUE_FONT_SIZE: f32 = 18.0;
+const DIALOGUE_MARGIN: f32 = 24.0;
+const DIALOGUE_COLOR: Color = Color::WHITE;
+// Seconds a line of dialogue stays up when the script doesn't say
+const DEFAULT_DIALOGUE_TIME: f32 = 3.0;
+
+/// A script trigger placed in the level (read from level metadata, positions in world pixels)
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ScriptTriggerSetting {
+    /// Bottom-left corner of the trigger region
+    pub min: [f32; 2],
+    /// Top-right corner of the trigger region
+    pub max: [f32; 2],
+    /// Script file name, relative to `assets/scripts`
+    pub script: String,
+}
+
+/// Things scripts are allowed to spawn
+#[derive(Clone, Copy, Debug)]
+pub enum Prefab {
+    Chaser(AIVariant),
+}
+
+/// AI states scripts are allowed to set
+#[derive(Clone, Copy, Debug)]
+pub enum ScriptAIState {
+    Wander,
+    Pursue,
+}
+
+/// A compiled script command
+#[derive(Clone, Debug)]
+pub enum ScriptOp {
+    Spawn { prefab: Prefab, position: Vec2 },
+    OpenDoor { door: usize },
+    CloseDoor { door: usize },
+    SetAIState { state: ScriptAIState },
+    /// Holds the camera at `position` until the script finishes
+    MoveCamera { position: Vec2 },
+    Say { text: String, duration: f32 },
+    Wait { seconds: f32 },
+}
+
+/// A script that failed to compile
+#[derive(Debug)]
+pub struct ScriptError {
+    /// 1-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Splits a line into words, keeping double-quoted strings together
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut token = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Compiles script source into ops
+pub fn compile_script(source: &str) -> Result<Vec<ScriptOp>, ScriptError> {
+    let mut ops = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |message: String| ScriptError {
+            line: index + 1,
+            message,
+        };
+
+        let line = line.split_once('#').map_or(line, |(code, _)| code);
+        let tokens = tokenize(line).map_err(error)?;
+        let Some((command, args)) = tokens.split_first() else {
+            continue;
+        };
+
+        let number = |i: usize| -> Result<f32, ScriptError> {
+            args.get(i)
+                .ok_or_else(|| error(format!("`{command}` is missing argument {}", i + 1)))?
+                .parse::<f32>()
+                .map_err(|_| error(format!("`{command}` argument {} must be a number", i + 1)))
+        };
+        let door = || -> Result<usize, ScriptError> {
+            args.first()
+                .and_then(|arg| arg.parse().ok())
+                .ok_or_else(|| error(format!("`{command}` needs a door index")))
+        };
+
+        let op = match command.as_str() {
+            "spawn" => {
+                let prefab = match args.first().map(String::as_str) {
+                    Some("chaser") => Prefab::Chaser(AIVariant::Normal),
+                    Some("light_chaser") => Prefab::Chaser(AIVariant::Light),
+                    Some("heavy_chaser") => Prefab::Chaser(AIVariant::Heavy),
+                    other => return Err(error(format!("unknown prefab {other:?}"))),
+                };
+                ScriptOp::Spawn {
+                    prefab,
+                    position: Vec2::new(number(1)?, number(2)?),
+                }
+            }
+            "open_door" => ScriptOp::OpenDoor { door: door()? },
+            "close_door" => ScriptOp::CloseDoor { door: door()? },
+            "set_ai_state" => {
+                let state = match args.first().map(String::as_str) {
+                    Some("wander") => ScriptAIState::Wander,
+                    Some("pursue") => ScriptAIState::Pursue,
+                    other => return Err(error(format!("unknown AI state {other:?}"))),
+                };
+                ScriptOp::SetAIState { state }
+            }
+            "move_camera" => ScriptOp::MoveCamera {
+                position: Vec2::new(number(0)?, number(1)?),
+            },
+            "say" => ScriptOp::Say {
+                text: args
+                    .first()
+                    .cloned()
+                    .ok_or_else(|| error("`say` needs some text".to_string()))?,
+                duration: if args.len() > 1 {
+                    number(1)?
+                } else {
+                    DEFAULT_DIALOGUE_TIME
+                },
+            },
+            "wait" => ScriptOp::Wait {
+                seconds: number(0)?,
+            },
+            _ => return Err(error(format!("unknown command `{command}`"))),
+        };
+
+        ops.push(op);
+    }
+
+    Ok(ops)
+}
+
+/// Script runner component: A script being executed, one op after another
+#[derive(Component)]
+pub struct ScriptRunner {
+    pub ops: Vec<ScriptOp>,
+    /// Index of the next op to run
+    pub next: usize,
+    /// Time remaining (seconds) before the script continues after a `wait`
+    pub wait_timer: f32,
+    /// Whether the script has moved the camera (the camera follows the player again once the
+    /// script finishes)
+    pub moved_camera: bool,
+}
+
+/// Script trigger component: Runs a script the first time the player enters the region
+#[derive(Component)]
+pub struct ScriptTrigger {
+    pub region: Aabb,
+    pub script: String,
+    pub fired: bool,
+}
+
+/// Line of dialogue currently shown by a script
+#[derive(Resource, Default)]
+pub struct Dialogue {
+    pub text: String,
+    /// Time remaining (seconds) before the line disappears
+    pub timer: f32,
+}
+
+/// Marker for the dialogue text
+#[derive(Component)]
+pub struct DialogueText;
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dialogue>();
+        app.add_systems(Startup, s_spawn_dialogue_text);
+        app.add_systems(Update, s_script_triggers);
+        app.add_systems(
+            Update,
+            s_run_scripts.after(s_script_triggers).before(s_switches),
+        );
+        app.add_systems(Update, s_update_dialogue.after(s_run_scripts));
+    }
+}
+
+/// Loads and compiles a script from `assets/scripts` and starts running it.
+///
+/// Scripts that are missing or fail to compile are reported and skipped.
+pub fn run_script(commands: &mut Commands, name: &str) {
+    let path = format!("{SCRIPTS_DIRECTORY}/{name}");
+
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Failed to read script {path}: {error}");
+            return;
+        }
+    };
+
+    match compile_script(&source) {
+        Ok(ops) => {
+            // Scripts stop with the level they were started in
+            commands.spawn((
+                ScriptRunner {
+                    ops,
+                    next: 0,
+                    wait_timer: 0.0,
+                    moved_camera: false,
+                },
+                DespawnOnExit(GameState::InGame),
+            ));
+        }
+        Err(error) => eprintln!("Failed to compile script {path}: {error}"),
+    }
+}
+
+/// Spawns every script trigger in the level metadata
+pub fn spawn_script_triggers(commands: &mut Commands, level: &Level) {
+    for setting in &level.metadata.script_triggers {
+        commands.spawn(ScriptTrigger {
+            region: Aabb {
+                min: Vec2::from(setting.min),
+                max: Vec2::from(setting.max),
+            },
+            script: setting.script.clone(),
+            fired: false,
+        });
+    }
+}
+
+fn s_spawn_dialogue_text(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: DIALOGUE_FONT_SIZE,
+            ..Default::default()
+        },
+        TextColor(DIALOGUE_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(DIALOGUE_MARGIN),
+            left: Val::Px(DIALOGUE_MARGIN),
+            ..Default::default()
+        },
+        DialogueText,
+    ));
+}
+
+/// Script trigger system: Starts a trigger's script the first time either player enters it
+pub fn s_script_triggers(
+    mut commands: Commands,
+    mut trigger_query: Query<&mut ScriptTrigger>,
+    player_query: Query<&Transform, PlayerControlled>,
+) {
+    for mut trigger in trigger_query.iter_mut() {
+        let entered = player_query
+            .iter()
+            .any(|transform| trigger.region.contains(transform.translation.xy()));
+        if !trigger.fired && entered {
+            trigger.fired = true;
+            run_script(&mut commands, &trigger.script);
+        }
+    }
+}
+
+/// Script system: Runs each script's ops until it hits a `wait` or finishes
+#[allow(clippy::too_many_arguments)]
+pub fn s_run_scripts(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+    mut runner_query: Query<(Entity, &mut ScriptRunner)>,
+    mut door_query: Query<&mut Door>,
+    mut pursue_query: Query<&mut PursueAI>,
+    mut camera_query: Query<&mut Transform, With<GameCamera>>,
+    mut camera_override: ResMut<CameraOverride>,
+    mut dialogue: ResMut<Dialogue>,
+) {
+    for (entity, mut runner) in runner_query.iter_mut() {
+        if runner.wait_timer > 0.0 {
+            runner.wait_timer -= time.delta_secs();
+            if runner.wait_timer > 0.0 {
+                continue;
+            }
+        }
+
+        while let Some(op) = runner.ops.get(runner.next).cloned() {
+            runner.next += 1;
+
+            match op {
+                ScriptOp::Spawn {
+                    prefab: Prefab::Chaser(variant),
+                    position,
+                } => {
+                    spawn_ai_agent(&mut commands, &mut meshes, &mut materials, position, variant);
+                }
+                ScriptOp::OpenDoor { door } => {
+                    for mut door_data in door_query.iter_mut().filter(|d| d.id == door) {
+                        door_data.locked = false;
+                        door_data.open_timer = door_data.open_time;
+                    }
+                }
+                ScriptOp::CloseDoor { door } => {
+                    for mut door_data in door_query.iter_mut().filter(|d| d.id == door) {
+                        door_data.open_timer = 0.0;
+                    }
+                }
+                ScriptOp::SetAIState { state } => {
+                    for mut pursue_ai in pursue_query.iter_mut() {
+                        pursue_ai.state = match state {
+                            ScriptAIState::Wander => PursueAIState::Wander,
+                            ScriptAIState::Pursue => PursueAIState::Pursue,
+                        };
+                    }
+                }
+                ScriptOp::MoveCamera { position } => {
+                    camera_override.0 = Some(position);
+                    runner.moved_camera = true;
+                    for mut camera_transform in camera_query.iter_mut() {
+                        camera_transform.translation =
+                            position.extend(camera_transform.translation.z);
+                    }
+                }
+                ScriptOp::Say { text, duration } => {
+                    dialogue.text = text;
+                    dialogue.timer = duration;
+                }
+                ScriptOp::Wait { seconds } => {
+                    runner.wait_timer = seconds;
+                    break;
+                }
+            }
+        }
+
+        if runner.next >= runner.ops.len() && runner.wait_timer <= 0.0 {
+            if runner.moved_camera {
+                camera_override.0 = None;
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Dialogue system: Shows the current line of dialogue until its time runs out
+pub fn s_update_dialogue(
+    time: Res<Time>,
+    mut dialogue: ResMut<Dialogue>,
+    mut text_query: Query<&mut Text, With<DialogueText>>,
+) {
+    if dialogue.timer > 0.0 {
+        dialogue.timer = (dialogue.timer - time.delta_secs()).max(0.0);
+        if dialogue.timer == 0.0 {
+            dialogue.text.clear();
+        }
+    }
+
+    for mut text in text_query.iter_mut() {
+        if text.0 != dialogue.text {
+            text.0 = dialogue.text.clone();
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    camera::{Camera, Projection},
+    color::Color,
+    ecs::{
+        entity::Entity,
+        query::{Or, With},
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res, ResMut},
+    },
+    gizmos::gizmos::Gizmos,
+    input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+    math::{Vec2, Vec3Swizzles},
+    prelude::Resource,
+    state::condition::in_state,
+    transform::components::{GlobalTransform, Transform},
+    window::{PrimaryWindow, Window},
+};
+
+use crate::{
+    ai::{flying_ai::FlyingAI, platformer_ai::PlatformerAI},
+    camera::{cursor_world_position, GameCamera},
+    debug::{SELECT_NEXT_KEY, SELECT_PREVIOUS_KEY},
+    editor::EditorState,
+    pixel_perfect::CanvasCamera,
+    GizmosVisible, KinematicBody,
+};
+
+// How far outside a body a click still selects it (units: pixels)
+const SELECT_MARGIN: f32 = 8.0;
+// Gap between a selected body and its outline (units: pixels)
+const OUTLINE_MARGIN: f32 = 4.0;
+const OUTLINE_COLOR: Color = Color::srgb(1.0, 1.0, 0.3);
+
+/// Selected entity resource: The body debug tools act on, picked by clicking near it or cycling
+/// through the agents while gizmos are visible
+#[derive(Resource, Default)]
+pub struct SelectedEntity(pub Option<Entity>);
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedEntity>();
+
+        app.add_systems(
+            Update,
+            (
+                s_forget_missing_selection,
+                s_click_select
+                    .after(s_forget_missing_selection)
+                    .run_if(in_state(EditorState::Playing)),
+                s_cycle_selection.after(s_forget_missing_selection),
+                s_draw_selection_outline
+                    .after(s_click_select)
+                    .after(s_cycle_selection),
+            ),
+        );
+    }
+}
+
+/// Body under `point` (within its radius plus a margin), picking the closest if several are
+pub fn body_at<'a>(
+    bodies: impl Iterator<Item = (Entity, &'a Transform, &'a KinematicBody)>,
+    point: Vec2,
+) -> Option<Entity> {
+    bodies
+        .map(|(entity, transform, physics)| {
+            let distance = transform.translation.xy().distance(point) - physics.radius;
+            (entity, distance)
+        })
+        .filter(|(_, distance)| *distance <= SELECT_MARGIN)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+/// Selection cleanup system: Drops the selection once its entity is gone
+fn s_forget_missing_selection(
+    mut selected: ResMut<SelectedEntity>,
+    body_query: Query<(), With<KinematicBody>>,
+) {
+    if selected.0.is_some_and(|entity| !body_query.contains(entity)) {
+        selected.0 = None;
+    }
+}
+
+/// Click select system: Left clicking near a body selects it while gizmos are visible (clicks
+/// anywhere else are left to the tool using the selection)
+pub fn s_click_select(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    gizmos_visible: Res<GizmosVisible>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    canvas_query: Query<(&Projection, &CanvasCamera)>,
+    body_query: Query<(Entity, &Transform, &KinematicBody)>,
+    mut selected: ResMut<SelectedEntity>,
+) {
+    if !gizmos_visible.visible || !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.single(), camera_query.single())
+    else {
+        return;
+    };
+    let Some(cursor) =
+        cursor_world_position(window, camera, camera_transform, canvas_query.single().ok())
+    else {
+        return;
+    };
+
+    if let Some(entity) = body_at(body_query.iter(), cursor) {
+        selected.0 = Some(entity);
+    }
+}
+
+/// Selection cycle system: Steps the selection through the agents in spawn order while gizmos
+/// are visible, going through no selection after the last one
+#[allow(clippy::type_complexity)]
+fn s_cycle_selection(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmos_visible: Res<GizmosVisible>,
+    agent_query: Query<Entity, Or<(With<PlatformerAI>, With<FlyingAI>)>>,
+    mut selected: ResMut<SelectedEntity>,
+) {
+    if !gizmos_visible.visible {
+        return;
+    }
+    let step: isize = if keyboard_input.just_pressed(SELECT_NEXT_KEY) {
+        1
+    } else if keyboard_input.just_pressed(SELECT_PREVIOUS_KEY) {
+        -1
+    } else {
+        return;
+    };
+
+    let mut agents: Vec<Entity> = agent_query.iter().collect();
+    agents.sort_unstable();
+
+    // Position `agents.len()` stands for no selection
+    let slots = agents.len() as isize + 1;
+    let current = selected
+        .0
+        .and_then(|entity| agents.iter().position(|&agent| agent == entity))
+        .unwrap_or(agents.len()) as isize;
+    let next = (current + step).rem_euclid(slots) as usize;
+
+    selected.0 = agents.get(next).copied();
+}
+
+/// Selection outline system: Circles the selected body while gizmos are visible
+fn s_draw_selection_outline(
+    gizmos_visible: Res<GizmosVisible>,
+    selected: Res<SelectedEntity>,
+    body_query: Query<(&Transform, &KinematicBody)>,
+    mut gizmos: Gizmos,
+) {
+    if !gizmos_visible.visible {
+        return;
+    }
+    let Some((transform, physics)) = selected.0.and_then(|entity| body_query.get(entity).ok())
+    else {
+        return;
+    };
+
+    gizmos.circle_2d(
+        transform.translation.xy(),
+        physics.radius + OUTLINE_MARGIN,
+        OUTLINE_COLOR,
+    );
+}
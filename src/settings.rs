@@ -0,0 +1,89 @@
+use bevy::prelude::Resource;
+use serde::Deserialize;
+
+use crate::integrators::Integrator;
+
+const SETTINGS_PATH: &str = "assets/settings.json";
+
+/// User settings loaded from `assets/settings.json` at startup
+/// Missing files or fields fall back to the defaults
+#[derive(Resource, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// Simulate and draw weather (rain, wind, wet surfaces); disable for performance
+    pub weather_effects: bool,
+    /// Draw the player and running agents as animated sprites instead of flat circles
+    pub sprites: bool,
+    /// Kick up dust, wall streaks and bursts from movement; disable for performance
+    pub particles: bool,
+    /// Render the world at `virtual_resolution` and upscale it by whole pixels
+    pub pixel_perfect: bool,
+    /// Resolution (pixels) the world is rendered at in pixel-perfect mode
+    pub virtual_resolution: [u32; 2],
+    /// Rate (Hz) at which AI agents make decisions; 0 makes them decide every frame
+    pub ai_tick_rate: f32,
+    /// How the movement systems advance velocity and position each frame
+    pub integrator: Integrator,
+    /// Add a second local player, on WASD or the second gamepad, alongside the first
+    pub two_player: bool,
+    /// Rumble the gamepad on heavy landings, hits, dashes and nearby slams
+    pub rumble: bool,
+    /// Shake the screen on heavy landings, wall jumps and hits
+    pub screen_shake: bool,
+    /// Volume of sound effects (0 turns them off, 1 is full volume)
+    pub sound_volume: f32,
+    /// Largest angle (degrees) bodies lean to match the slope they stand on; 0 keeps them upright
+    pub tilt_max_angle: f32,
+    /// Rate (1/second) at which bodies lean into slopes and straighten up again
+    pub tilt_smoothing: f32,
+    /// Keep the camera from showing anything outside the level or behind its curtain polygons
+    pub camera_collision: bool,
+    /// Keep the camera on the player (otherwise it stays where it is)
+    pub camera_follow: bool,
+    /// Rate (1/second) at which the camera catches up with the player
+    pub camera_damping: f32,
+    /// How far ahead of the player the camera looks (seconds of horizontal velocity)
+    pub camera_look_ahead: f32,
+    /// Largest look-ahead (pixels)
+    pub camera_max_look_ahead: f32,
+    /// How far (pixels) the player can rise or fall in the air before the camera follows
+    pub camera_vertical_deadzone: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            weather_effects: true,
+            sprites: false,
+            particles: true,
+            pixel_perfect: false,
+            virtual_resolution: [640, 360],
+            ai_tick_rate: 15.0,
+            integrator: Integrator::default(),
+            two_player: false,
+            rumble: true,
+            screen_shake: true,
+            sound_volume: 0.8,
+            tilt_max_angle: 30.0,
+            tilt_smoothing: 12.0,
+            camera_collision: true,
+            camera_follow: true,
+            camera_damping: 6.0,
+            camera_look_ahead: 0.25,
+            camera_max_look_ahead: 96.0,
+            camera_vertical_deadzone: 64.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        match std::fs::read_to_string(SETTINGS_PATH) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+                eprintln!("Failed to parse {SETTINGS_PATH}, using defaults: {error}");
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use bevy::{
+    app::{App, Plugin, PreUpdate},
+    ecs::{
+        entity::Entity,
+        query::{Has, With},
+        system::{Query, ResMut},
+    },
+    math::{Vec2, Vec3Swizzles},
+    prelude::Resource,
+    transform::components::Transform,
+};
+
+use crate::{
+    ai::{flying_ai::FlyingAI, platformer_ai::PlatformerAI},
+    KinematicBody, Player,
+};
+
+// Width and height of a spatial index cell (pixels)
+const SPATIAL_INDEX_CELL_SIZE: f32 = 128.0;
+
+/// What kind of dynamic entity an index entry is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DynamicKind {
+    Player,
+    Agent,
+}
+
+#[derive(Clone, Copy)]
+pub struct IndexedEntity {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub kind: DynamicKind,
+}
+
+/// Uniform grid of every dynamic entity's position, rebuilt at the start of each frame so any
+/// system can ask for the entities within a radius without scanning them all
+#[derive(Resource, Default)]
+pub struct DynamicSpatialIndex {
+    cells: HashMap<(i32, i32), Vec<IndexedEntity>>,
+}
+
+impl DynamicSpatialIndex {
+    fn position_to_cell(position: Vec2) -> (i32, i32) {
+        (
+            (position.x / SPATIAL_INDEX_CELL_SIZE).floor() as i32,
+            (position.y / SPATIAL_INDEX_CELL_SIZE).floor() as i32,
+        )
+    }
+
+    /// Empties every cell but keeps their allocations for the next rebuild
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entry: IndexedEntity) {
+        self.cells
+            .entry(Self::position_to_cell(entry.position))
+            .or_default()
+            .push(entry);
+    }
+
+    /// Entities whose center lies within `radius` of `center`
+    pub fn within_radius(
+        &self,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = &IndexedEntity> + '_ {
+        let (min_x, min_y) = Self::position_to_cell(center - Vec2::splat(radius));
+        let (max_x, max_y) = Self::position_to_cell(center + Vec2::splat(radius));
+        let radius_sq = radius * radius;
+
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |entry| entry.position.distance_squared(center) <= radius_sq)
+    }
+
+    /// Every entity of the given kind, wherever it is
+    pub fn within_kind(&self, kind: DynamicKind) -> impl Iterator<Item = &IndexedEntity> + '_ {
+        self.cells
+            .values()
+            .flatten()
+            .filter(move |entry| entry.kind == kind)
+    }
+}
+
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DynamicSpatialIndex>();
+        // Rebuilt before gameplay so every Update system sees the same snapshot
+        app.add_systems(PreUpdate, s_update_spatial_index);
+    }
+}
+
+/// Spatial index system: Rebuilds the dynamic entity index from current positions
+#[allow(clippy::type_complexity)]
+pub fn s_update_spatial_index(
+    mut index: ResMut<DynamicSpatialIndex>,
+    body_query: Query<
+        (Entity, &Transform, Has<Player>, Has<PlatformerAI>, Has<FlyingAI>),
+        With<KinematicBody>,
+    >,
+) {
+    index.clear();
+
+    for (entity, transform, is_player, is_walker, is_flier) in body_query.iter() {
+        // Other bodies (e.g. projectiles) get their own kinds as they are added
+        let kind = if is_player {
+            DynamicKind::Player
+        } else if is_walker || is_flier {
+            DynamicKind::Agent
+        } else {
+            continue;
+        };
+
+        index.insert(IndexedEntity {
+            entity,
+            position: transform.translation.xy(),
+            kind,
+        });
+    }
+}
@@ -0,0 +1,346 @@
+//! Sprite render layer: draws the player and running agents as animated sprites instead of flat
+//! circles, when turned on in the settings (`"sprites": true`). The debug gizmos still draw on
+//! top.
+//!
+//! Each body gets a `SpriteAnimation` that picks an animation (idle, run, jump, fall or wall
+//! slide) from its physics state every frame and steps through that animation's frames. The art
+//! is a placeholder sheet drawn at startup (`BodySpriteSheet`): one row of
+//! `FRAMES_PER_ANIMATION` frames per animation, in `BodyAnimation` order, facing right and tinted
+//! with the body's color.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::{Assets, Handle, RenderAssetUsages},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Added, Or, With},
+        schedule::{common_conditions::resource_exists, IntoScheduleConfigs},
+        system::{Commands, Query, Res, ResMut},
+    },
+    image::{Image, ImageSampler, TextureAtlas, TextureAtlasLayout},
+    math::{ops, UVec2, Vec2},
+    mesh::Mesh2d,
+    prelude::Resource,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    sprite::Sprite,
+    sprite_render::{ColorMaterial, MeshMaterial2d},
+    time::Time,
+};
+
+use crate::{
+    ai::platformer_ai::PlatformerAI, collisions::s_player_contacts, settings::Settings,
+    KinematicBody, Player,
+};
+
+// Sheet layout (units: pixels)
+const FRAME_SIZE: u32 = 32;
+const FRAMES_PER_ANIMATION: usize = 4;
+// Radius of the body drawn in each frame, leaving room to stretch (units: pixels)
+const BODY_FRAME_RADIUS: f32 = 11.0;
+const OUTLINE_WIDTH: f32 = 1.5;
+const OUTLINE_SHADE: u8 = 170;
+const EYE_SHADE: u8 = 40;
+
+// Frame rates (units: frames/second); running speeds up with the body's speed
+const IDLE_FPS: f32 = 4.0;
+const AIR_FPS: f32 = 8.0;
+const WALL_SLIDE_FPS: f32 = 6.0;
+const MIN_RUN_FPS: f32 = 6.0;
+const RUN_FPS_PER_SPEED: f32 = 0.04;
+
+// Speed below which a grounded body counts as standing still, and above which it turns to face
+// the way it is moving (units: pixels/second)
+const RUN_SPEED: f32 = 20.0;
+const TURN_SPEED: f32 = 10.0;
+// Smallest horizontal part of a contact normal that counts as a wall (unitless)
+const WALL_NORMAL_X: f32 = 0.7;
+
+/// The animations of a body, in sprite sheet row order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyAnimation {
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    WallSlide,
+}
+
+impl BodyAnimation {
+    const ALL: [BodyAnimation; 5] = [
+        BodyAnimation::Idle,
+        BodyAnimation::Run,
+        BodyAnimation::Jump,
+        BodyAnimation::Fall,
+        BodyAnimation::WallSlide,
+    ];
+
+    /// The animation for a body's physics state (the player's own ground check stands in for the
+    /// contacts, so coyote time doesn't flicker into a fall)
+    fn of(physics: &KinematicBody, player: Option<&Player>) -> Self {
+        let grounded = player.map_or(physics.on_ground(), |player| player.is_grounded);
+        let walled = physics
+            .contacts
+            .iter()
+            .any(|contact| contact.x.abs() >= WALL_NORMAL_X);
+
+        if grounded {
+            if physics.velocity.x.abs() > RUN_SPEED {
+                BodyAnimation::Run
+            } else {
+                BodyAnimation::Idle
+            }
+        } else if (walled && physics.velocity.y < 0.0)
+            || player.is_some_and(|player| player.wall_sliding())
+        {
+            BodyAnimation::WallSlide
+        } else if physics.velocity.y > 0.0 {
+            BodyAnimation::Jump
+        } else {
+            BodyAnimation::Fall
+        }
+    }
+
+    /// Frame rate at a horizontal speed (frames/second)
+    fn fps(&self, speed: f32) -> f32 {
+        match self {
+            BodyAnimation::Idle => IDLE_FPS,
+            BodyAnimation::Run => MIN_RUN_FPS + speed * RUN_FPS_PER_SPEED,
+            BodyAnimation::Jump | BodyAnimation::Fall => AIR_FPS,
+            BodyAnimation::WallSlide => WALL_SLIDE_FPS,
+        }
+    }
+
+    /// Stretch of the body, offset of its center and offset of its eyes (pixels, facing right)
+    /// on a frame of the animation
+    fn pose(&self, frame: usize) -> (Vec2, Vec2, Vec2) {
+        let phase = frame as f32 / FRAMES_PER_ANIMATION as f32 * TAU;
+        match self {
+            BodyAnimation::Idle => {
+                let breath = 0.03 * ops::sin(phase);
+                (Vec2::new(1.0 + breath, 1.0 - breath), Vec2::ZERO, Vec2::ZERO)
+            }
+            BodyAnimation::Run => {
+                let bounce = ops::sin(phase).abs();
+                (
+                    Vec2::new(1.08 - 0.12 * bounce, 0.92 + 0.12 * bounce),
+                    Vec2::new(0.0, 2.0 * bounce),
+                    Vec2::new(2.0, 0.0),
+                )
+            }
+            BodyAnimation::Jump => (
+                Vec2::new(0.85, 1.18 - 0.03 * frame as f32),
+                Vec2::ZERO,
+                Vec2::new(1.0, 2.0),
+            ),
+            BodyAnimation::Fall => {
+                let flutter = 0.03 * ops::sin(phase);
+                (
+                    Vec2::new(1.08 + flutter, 0.94 - flutter),
+                    Vec2::ZERO,
+                    Vec2::new(1.0, -1.0),
+                )
+            }
+            // Pressed against a wall on the right, looking away from it
+            BodyAnimation::WallSlide => (
+                Vec2::new(0.88, 1.05),
+                Vec2::new(2.0, 0.5 * ops::sin(phase)),
+                Vec2::new(-2.0, 1.0),
+            ),
+        }
+    }
+}
+
+/// Sprite animation component: Which animation a body's sprite is playing, and where it is in it
+#[derive(Component)]
+pub struct SpriteAnimation {
+    pub animation: BodyAnimation,
+    pub frame: usize,
+    /// Seconds until the next frame
+    pub frame_timer: f32,
+    /// Whether the sprite is flipped to face left
+    pub facing_left: bool,
+}
+
+/// Body sprite sheet resource: The placeholder sheet every body sprite is cut from
+#[derive(Resource)]
+pub struct BodySpriteSheet {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+}
+
+pub struct BodySpritePlugin;
+
+impl Plugin for BodySpritePlugin {
+    fn build(&self, app: &mut App) {
+        let sprites_enabled = |settings: Res<Settings>| settings.sprites;
+        app.add_systems(Startup, s_build_body_sprite_sheet.run_if(sprites_enabled));
+        app.add_systems(
+            Update,
+            (s_attach_body_sprites, s_animate_body_sprites.after(s_player_contacts))
+                .chain()
+                .run_if(resource_exists::<BodySpriteSheet>),
+        );
+    }
+}
+
+/// Sprite sheet system: Draws the placeholder body sprite sheet
+fn s_build_body_sprite_sheet(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let columns = FRAMES_PER_ANIMATION as u32;
+    let rows = BodyAnimation::ALL.len() as u32;
+    let size = UVec2::new(columns, rows) * FRAME_SIZE;
+    let mut pixels = vec![0; (size.x * size.y * 4) as usize];
+
+    for (row, animation) in BodyAnimation::ALL.iter().enumerate() {
+        for frame in 0..FRAMES_PER_ANIMATION {
+            let origin = UVec2::new(frame as u32, row as u32) * FRAME_SIZE;
+            draw_body_frame(&mut pixels, size.x, origin, animation.pose(frame));
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+
+    commands.insert_resource(BodySpriteSheet {
+        image: images.add(image),
+        layout: layouts.add(TextureAtlasLayout::from_grid(
+            UVec2::splat(FRAME_SIZE),
+            columns,
+            rows,
+            None,
+            None,
+        )),
+    });
+}
+
+/// Draws one frame of the sheet: a white body with a grey outline and two eyes, facing right
+fn draw_body_frame(pixels: &mut [u8], width: u32, origin: UVec2, pose: (Vec2, Vec2, Vec2)) {
+    let (stretch, offset, eye_offset) = pose;
+    let radii = stretch * BODY_FRAME_RADIUS;
+    let center = Vec2::splat(FRAME_SIZE as f32 / 2.0) + offset;
+    let eyes = [Vec2::new(2.0, 3.0), Vec2::new(6.0, 3.0)].map(|eye| center + eye + eye_offset);
+
+    for y in 0..FRAME_SIZE {
+        for x in 0..FRAME_SIZE {
+            // Pixel center, with y up like the world
+            let point = Vec2::new(x as f32 + 0.5, (FRAME_SIZE - y) as f32 - 0.5);
+            let distance = ((point - center) / radii).length();
+            if distance > 1.0 {
+                continue;
+            }
+
+            let shade = if eyes.iter().any(|eye| eye.distance(point) < 1.5) {
+                EYE_SHADE
+            } else if distance > 1.0 - OUTLINE_WIDTH / radii.min_element() {
+                OUTLINE_SHADE
+            } else {
+                u8::MAX
+            };
+            let index = (((origin.y + y) * width + origin.x + x) * 4) as usize;
+            pixels[index..index + 4].copy_from_slice(&[shade, shade, shade, u8::MAX]);
+        }
+    }
+}
+
+/// Body sprite system: Swaps the flat circle of newly spawned players and running agents for an
+/// animated sprite in the same color
+#[allow(clippy::type_complexity)]
+fn s_attach_body_sprites(
+    mut commands: Commands,
+    sheet: Res<BodySpriteSheet>,
+    body_query: Query<
+        (Entity, &KinematicBody),
+        (Added<KinematicBody>, Or<(With<Player>, With<PlatformerAI>)>, With<Mesh2d>),
+    >,
+) {
+    for (entity, physics) in body_query.iter() {
+        let sprite = Sprite {
+            image: sheet.image.clone(),
+            texture_atlas: Some(TextureAtlas {
+                layout: sheet.layout.clone(),
+                index: 0,
+            }),
+            custom_size: Some(Vec2::splat(
+                FRAME_SIZE as f32 * physics.radius / BODY_FRAME_RADIUS,
+            )),
+            ..Default::default()
+        };
+
+        commands.entity(entity).remove::<Mesh2d>().insert((
+            sprite,
+            SpriteAnimation {
+                animation: BodyAnimation::Idle,
+                frame: 0,
+                frame_timer: 0.0,
+                facing_left: false,
+            },
+        ));
+    }
+}
+
+/// Sprite animation system: Picks each body's animation from its physics state, steps through
+/// its frames, turns it to face the way it is moving and keeps its tint in step with its material
+#[allow(clippy::type_complexity)]
+fn s_animate_body_sprites(
+    time: Res<Time>,
+    materials: Res<Assets<ColorMaterial>>,
+    mut body_query: Query<(
+        &KinematicBody,
+        Option<&Player>,
+        &MeshMaterial2d<ColorMaterial>,
+        &mut Sprite,
+        &mut SpriteAnimation,
+    )>,
+) {
+    for (physics, player, material, mut sprite, mut animation) in body_query.iter_mut() {
+        let speed = physics.velocity.x.abs();
+        let next = BodyAnimation::of(physics, player);
+        if next != animation.animation {
+            animation.animation = next;
+            animation.frame = 0;
+            animation.frame_timer = 1.0 / next.fps(speed);
+        } else {
+            animation.frame_timer -= time.delta_secs();
+            if animation.frame_timer <= 0.0 {
+                animation.frame = (animation.frame + 1) % FRAMES_PER_ANIMATION;
+                animation.frame_timer = 1.0 / next.fps(speed);
+            }
+        }
+
+        // Sliding bodies face the wall they are on
+        let wall_x = physics
+            .contacts
+            .iter()
+            .find(|contact| contact.x.abs() >= WALL_NORMAL_X)
+            .map(|contact| contact.x);
+        match (animation.animation, wall_x) {
+            (BodyAnimation::WallSlide, Some(normal_x)) => animation.facing_left = normal_x > 0.0,
+            _ if speed > TURN_SPEED => animation.facing_left = physics.velocity.x < 0.0,
+            _ => {}
+        }
+
+        if let Some(atlas) = sprite.texture_atlas.as_mut() {
+            atlas.index = animation.animation as usize * FRAMES_PER_ANIMATION + animation.frame;
+        }
+        sprite.flip_x = animation.facing_left;
+        if let Some(material) = materials.get(&material.0) {
+            sprite.color = material.color;
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    math::{ops, Quat},
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{collisions::s_collision, settings::Settings, KinematicBody};
+
+/// Surface tilt component: Leans a body's mesh to match the slope it is standing on. Only the
+/// rendered rotation changes; collision still treats the body as an upright circle.
+#[derive(Component, Default)]
+pub struct SurfaceTilt {
+    /// Current lean (radians, counterclockwise)
+    pub angle: f32,
+}
+
+pub struct SurfaceTiltPlugin;
+
+impl Plugin for SurfaceTiltPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_surface_tilt.after(s_collision));
+    }
+}
+
+/// Surface tilt system: Eases each grounded body towards the angle of the ground normal (clamped
+/// to the maximum tilt in the settings), and back upright while it is in the air
+pub fn s_surface_tilt(
+    mut body_query: Query<(&mut Transform, &KinematicBody, &mut SurfaceTilt)>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let max_tilt = settings.tilt_max_angle.to_radians();
+    // Fraction of the remaining angle covered this frame (framerate independent)
+    let blend = 1.0 - ops::exp(-settings.tilt_smoothing * time.delta_secs());
+
+    for (mut transform, physics, mut tilt) in body_query.iter_mut() {
+        let target = if physics.on_ground() {
+            // Angle that turns straight up onto the normal
+            ops::atan2(-physics.normal.x, physics.normal.y).clamp(-max_tilt, max_tilt)
+        } else {
+            0.0
+        };
+
+        tilt.angle += (target - tilt.angle) * blend;
+        transform.rotation = Quat::from_rotation_z(tilt.angle);
+    }
+}
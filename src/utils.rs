@@ -34,3 +34,58 @@ pub fn side_of_line_detection(line_start: Vec2, line_end: Vec2, point: Vec2) ->
 
     determinant.signum()
 }
+
+/// Closest point to `point` on the segment from `start` to `end`
+pub fn closest_point_on_segment(start: Vec2, end: Vec2, point: Vec2) -> Vec2 {
+    let segment = end - start;
+    let length_sq = segment.length_squared();
+    if length_sq <= f32::EPSILON {
+        return start;
+    }
+
+    let t = ((point - start).dot(segment) / length_sq).clamp(0.0, 1.0);
+    start + segment * t
+}
+
+/// Position after travelling `distance` along a path of points, bouncing back and forth between
+/// its ends
+pub fn ping_pong_along_path(points: &[Vec2], distance: f32) -> Vec2 {
+    let Some(first) = points.first() else {
+        return Vec2::ZERO;
+    };
+
+    let length: f32 = points.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+    if length <= 0.0 {
+        return *first;
+    }
+
+    let mut distance = distance.rem_euclid(length * 2.0);
+    if distance > length {
+        distance = length * 2.0 - distance;
+    }
+
+    for pair in points.windows(2) {
+        let segment_length = pair[0].distance(pair[1]);
+        if distance <= segment_length {
+            return pair[0].lerp(pair[1], distance / segment_length.max(f32::EPSILON));
+        }
+        distance -= segment_length;
+    }
+
+    *points.last().unwrap()
+}
+
+/// Whether `point` is inside the closed polygon through `points` (crossing number test)
+pub fn polygon_contains(points: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (index, &start) in points.iter().enumerate() {
+        let end = points[(index + 1) % points.len()];
+        if (start.y > point.y) != (end.y > point.y) {
+            let crossing_x = start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x);
+            if point.x < crossing_x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
@@ -0,0 +1,200 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    ecs::{
+        component::Component,
+        query::With,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{ops, Vec2},
+    prelude::Resource,
+    state::condition::in_state,
+    time::Time,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    forces::{s_apply_force_zones, ForceZone},
+    frame_budget::FrameBudget,
+    game_state::GameState,
+    level::{Aabb, Level},
+    settings::Settings,
+};
+
+// Rain constants
+const RAIN_MAX_DROPS: usize = 400;
+const RAIN_FALL_SPEED: f32 = 900.0; // pixels/second
+const RAIN_STREAK_DURATION: f32 = 0.02; // seconds of motion drawn per streak
+const RAIN_WIND_DRIFT: f32 = 0.5; // fraction of wind force applied to drops as drift speed
+const RAIN_COLOR: Color = Color::srgba(0.6, 0.7, 1.0, 0.5);
+
+// Surface friction multiplier on fully wet ground (unitless)
+const WET_SURFACE_FRICTION: f32 = 0.5;
+
+/// Weather for a level (read from level metadata)
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct WeatherSetting {
+    /// Rain intensity in [0, 1] (0 = no rain)
+    pub rain: f32,
+    /// Peak wind acceleration (pixels/second²); positive blows right
+    pub wind: f32,
+    /// Seconds between wind gust peaks
+    pub gust_period: f32,
+}
+
+impl Default for WeatherSetting {
+    fn default() -> Self {
+        Self {
+            rain: 0.0,
+            wind: 0.0,
+            gust_period: 4.0,
+        }
+    }
+}
+
+/// Current weather state
+#[derive(Resource, Default)]
+pub struct Weather {
+    pub setting: WeatherSetting,
+    /// Whether weather effects are active (from the settings file)
+    pub enabled: bool,
+    /// Seconds since the weather started (drives the gust cycle)
+    pub elapsed: f32,
+    /// Current wind acceleration (pixels/second²)
+    pub wind_force: Vec2,
+    /// Rain drop positions
+    pub drops: Vec<Vec2>,
+}
+
+impl Weather {
+    pub fn new(setting: WeatherSetting, settings: &Settings) -> Self {
+        Self {
+            setting,
+            enabled: settings.weather_effects,
+            ..Default::default()
+        }
+    }
+
+    /// Multiplier applied to ground acceleration (wet surfaces are slippery)
+    pub fn surface_friction(&self) -> f32 {
+        if self.enabled {
+            1.0 + (WET_SURFACE_FRICTION - 1.0) * self.setting.rain.clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Marker for the force zone that carries the level's wind
+#[derive(Component)]
+pub struct Wind;
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Weather::default());
+        app.add_systems(
+            Update,
+            (
+                s_wind_gusts.before(s_apply_force_zones),
+                s_update_rain.after(s_wind_gusts).run_if(in_state(GameState::InGame)),
+                s_draw_rain.after(s_update_rain),
+            ),
+        );
+    }
+}
+
+/// Spawn a level-wide force zone for the wind (it stays still until the weather enables it)
+pub fn spawn_wind(commands: &mut Commands, level: &Level) {
+    commands.spawn((
+        Wind,
+        ForceZone {
+            area: Aabb::from_half_size(level.half_size),
+            force: Vec2::ZERO,
+        },
+    ));
+}
+
+/// Wind system: Gusts the wind force periodically between calm and the level's peak wind
+pub fn s_wind_gusts(
+    time: Res<Time>,
+    mut weather: ResMut<Weather>,
+    mut wind_query: Query<&mut ForceZone, With<Wind>>,
+) {
+    weather.elapsed += time.delta_secs();
+
+    let gust = if weather.enabled && weather.setting.gust_period > 0.0 {
+        0.5 - 0.5 * ops::cos(weather.elapsed / weather.setting.gust_period * TAU)
+    } else {
+        0.0
+    };
+
+    weather.wind_force = Vec2::new(weather.setting.wind * gust, 0.0);
+
+    for mut zone in wind_query.iter_mut() {
+        zone.force = weather.wind_force;
+    }
+}
+
+/// Rain system: Moves the drops and respawns them at the top of the level (with fewer drops while
+/// the frame budget is degraded)
+pub fn s_update_rain(
+    time: Res<Time>,
+    level: Res<Level>,
+    frame_budget: Res<FrameBudget>,
+    mut weather: ResMut<Weather>,
+) {
+    let target_drop_count = if weather.enabled {
+        let intensity = weather.setting.rain.clamp(0.0, 1.0) * frame_budget.particle_scale();
+        (RAIN_MAX_DROPS as f32 * intensity) as usize
+    } else {
+        0
+    };
+
+    let mut rng = rand::rng();
+    let half_size = level.half_size;
+    let velocity = Vec2::new(weather.wind_force.x * RAIN_WIND_DRIFT, -RAIN_FALL_SPEED);
+    let velocity_dt = velocity * time.delta_secs();
+
+    let drops = &mut weather.drops;
+    drops.truncate(target_drop_count);
+
+    // Fill up to the target count, scattered over the whole level so rain doesn't start as a sheet
+    while drops.len() < target_drop_count {
+        drops.push(Vec2::new(
+            rng.random_range(-half_size.x..=half_size.x),
+            rng.random_range(-half_size.y..=half_size.y),
+        ));
+    }
+
+    for drop in drops.iter_mut() {
+        *drop += velocity_dt;
+
+        if drop.y < -half_size.y {
+            drop.y += half_size.y * 2.0;
+            drop.x = rng.random_range(-half_size.x..=half_size.x);
+        }
+        if drop.x.abs() > half_size.x {
+            drop.x -= drop.x.signum() * half_size.x * 2.0;
+        }
+    }
+}
+
+/// Rain rendering system: Draws each drop as a short streak along its motion
+pub fn s_draw_rain(weather: Res<Weather>, mut gizmos: Gizmos) {
+    let streak = Vec2::new(
+        weather.wind_force.x * RAIN_WIND_DRIFT,
+        -RAIN_FALL_SPEED,
+    ) * RAIN_STREAK_DURATION;
+
+    for drop in &weather.drops {
+        gizmos.line_2d(*drop, *drop - streak, RAIN_COLOR);
+    }
+}